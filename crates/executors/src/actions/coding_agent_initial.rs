@@ -4,15 +4,18 @@ use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use ts_rs::TS;
 
-#[cfg(not(feature = "qa-mode"))]
-use crate::profile::ExecutorConfigs;
 use crate::{
     actions::Executable,
     approvals::ExecutorApprovalService,
     env::ExecutionEnv,
-    executors::{BaseCodingAgent, ExecutorError, SpawnedChild, StandardCodingAgentExecutor},
+    executors::{
+        BaseCodingAgent, ExecutorError, SpawnedChild, StandardCodingAgentExecutor,
+        check_agent_config,
+    },
     profile::ExecutorProfileId,
 };
+#[cfg(not(feature = "qa-mode"))]
+use crate::{mcp_config::persisted_mcp_servers, profile::ExecutorConfigs};
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, TS)]
 pub struct CodingAgentInitialRequest {
@@ -69,7 +72,22 @@ impl Executable for CodingAgentInitialRequest {
 
             agent.use_approvals(approvals.clone());
 
-            agent.spawn(&effective_dir, &self.prompt, env).await
+            check_agent_config(&agent, env).await?;
+
+            // The saved MCP servers are read for the prompt instructions in their meta
+            let env = &match persisted_mcp_servers(&agent).await {
+                Some(servers) => env.clone().with_persisted_mcp_servers(servers),
+                None => env.clone(),
+            };
+
+            let max_runtime = agent.max_runtime();
+            let prompt = agent
+                .prompt_with_git_status(&effective_dir, &self.prompt)
+                .await;
+            agent
+                .spawn(&effective_dir, &prompt, env)
+                .await
+                .map(|spawned| spawned.with_max_runtime(max_runtime))
         }
     }
 }
@@ -0,0 +1,71 @@
+use std::{path::Path, sync::Arc};
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use crate::{
+    actions::Executable,
+    approvals::ExecutorApprovalService,
+    env::ExecutionEnv,
+    executor_registry::ExecutorRegistry,
+    executors::{ExecutorError, SpawnedChild},
+};
+
+/// Run an executor registered at runtime in the [`ExecutorRegistry`] rather than a built-in
+/// agent.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, TS)]
+pub struct ExternalAgentRequest {
+    /// Id the executor was registered under
+    pub executor_id: String,
+    pub prompt: String,
+    /// Session to continue; a new session is started when None
+    #[serde(default)]
+    pub session_id: Option<String>,
+    /// Optional relative path to execute the agent in (relative to container_ref).
+    /// If None, uses the container_ref directory directly.
+    #[serde(default)]
+    pub working_dir: Option<String>,
+}
+
+impl ExternalAgentRequest {
+    pub fn effective_dir(&self, current_dir: &Path) -> std::path::PathBuf {
+        match &self.working_dir {
+            Some(rel_path) => current_dir.join(rel_path),
+            None => current_dir.to_path_buf(),
+        }
+    }
+}
+
+#[async_trait]
+impl Executable for ExternalAgentRequest {
+    /// Registered executors are shared, so they handle approvals themselves and `approvals` is
+    /// not passed on.
+    async fn spawn(
+        &self,
+        current_dir: &Path,
+        _approvals: Arc<dyn ExecutorApprovalService>,
+        env: &ExecutionEnv,
+    ) -> Result<SpawnedChild, ExecutorError> {
+        let effective_dir = self.effective_dir(current_dir);
+        let registry = ExecutorRegistry::shared();
+        match &self.session_id {
+            Some(session_id) => {
+                registry
+                    .spawn_follow_up(
+                        &self.executor_id,
+                        &effective_dir,
+                        &self.prompt,
+                        session_id,
+                        env,
+                    )
+                    .await
+            }
+            None => {
+                registry
+                    .spawn(&self.executor_id, &effective_dir, &self.prompt, env)
+                    .await
+            }
+        }
+    }
+}
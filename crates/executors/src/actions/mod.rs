@@ -1,4 +1,4 @@
-use std::{path::Path, sync::Arc};
+use std::{path::Path, sync::Arc, time::Duration};
 
 use async_trait::async_trait;
 use enum_dispatch::enum_dispatch;
@@ -8,15 +8,17 @@ use ts_rs::TS;
 use crate::{
     actions::{
         coding_agent_follow_up::CodingAgentFollowUpRequest,
-        coding_agent_initial::CodingAgentInitialRequest, review::ReviewRequest,
-        script::ScriptRequest,
+        coding_agent_initial::CodingAgentInitialRequest, external_agent::ExternalAgentRequest,
+        review::ReviewRequest, script::ScriptRequest,
     },
     approvals::ExecutorApprovalService,
     env::ExecutionEnv,
     executors::{BaseCodingAgent, ExecutorError, SpawnedChild},
+    profile::ExecutorConfigs,
 };
 pub mod coding_agent_follow_up;
 pub mod coding_agent_initial;
+pub mod external_agent;
 pub mod review;
 pub mod script;
 
@@ -30,6 +32,7 @@ pub enum ExecutorActionType {
     CodingAgentFollowUpRequest,
     ScriptRequest,
     ReviewRequest,
+    ExternalAgentRequest,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, TS)]
@@ -66,9 +69,28 @@ impl ExecutorAction {
                 Some(request.base_executor())
             }
             ExecutorActionType::ReviewRequest(request) => Some(request.base_executor()),
-            ExecutorActionType::ScriptRequest(_) => None,
+            ExecutorActionType::ScriptRequest(_) | ExecutorActionType::ExternalAgentRequest(_) => {
+                None
+            }
         }
     }
+
+    /// How long spawning this action's coding agent may wait for it to become ready, per
+    /// [`CodingAgent::spawn_timeout`](crate::executors::CodingAgent::spawn_timeout). `None` for
+    /// scripts and external agents.
+    pub fn spawn_timeout(&self) -> Option<Duration> {
+        let executor_profile_id = match self.typ() {
+            ExecutorActionType::CodingAgentInitialRequest(request) => &request.executor_profile_id,
+            ExecutorActionType::CodingAgentFollowUpRequest(request) => &request.executor_profile_id,
+            ExecutorActionType::ReviewRequest(request) => &request.executor_profile_id,
+            ExecutorActionType::ScriptRequest(_) | ExecutorActionType::ExternalAgentRequest(_) => {
+                return None;
+            }
+        };
+        ExecutorConfigs::get_cached()
+            .get_coding_agent(executor_profile_id)?
+            .spawn_timeout()
+    }
 }
 
 #[async_trait]
@@ -9,7 +9,10 @@ use crate::{
     actions::Executable,
     approvals::ExecutorApprovalService,
     env::ExecutionEnv,
-    executors::{BaseCodingAgent, ExecutorError, SpawnedChild, StandardCodingAgentExecutor},
+    executors::{
+        BaseCodingAgent, ExecutorError, SpawnedChild, StandardCodingAgentExecutor,
+        check_agent_config,
+    },
     profile::{ExecutorConfigs, ExecutorProfileId},
 };
 
@@ -18,6 +21,19 @@ pub struct RepoReviewContext {
     pub repo_id: Uuid,
     pub repo_name: String,
     pub base_commit: String,
+    /// Ask the reviewer to read and summarize the commit messages in the reviewed range
+    #[serde(default)]
+    pub include_commit_log: bool,
+}
+
+/// What the reviewer should look at.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, TS)]
+pub enum ReviewScope {
+    /// Committed changes, as described by the prompt's repository context.
+    #[default]
+    Commits,
+    /// Staged and unstaged changes in the working tree.
+    UncommittedChanges,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, TS)]
@@ -25,6 +41,8 @@ pub struct ReviewRequest {
     pub executor_profile_id: ExecutorProfileId,
     pub context: Option<Vec<RepoReviewContext>>,
     pub prompt: String,
+    #[serde(default)]
+    pub scope: ReviewScope,
     /// Optional session ID to resume an existing session
     #[serde(default)]
     pub session_id: Option<String>,
@@ -69,13 +87,18 @@ impl Executable for ReviewRequest {
 
         agent.use_approvals(approvals.clone());
 
+        check_agent_config(&agent, env).await?;
+
+        let max_runtime = agent.max_runtime();
         agent
             .spawn_review(
                 &effective_dir,
                 &self.prompt,
                 self.session_id.as_deref(),
+                self.scope,
                 env,
             )
             .await
+            .map(|spawned| spawned.with_max_runtime(max_runtime))
     }
 }
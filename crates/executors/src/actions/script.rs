@@ -64,7 +64,7 @@ impl Executable for ScriptRequest {
             .current_dir(&effective_dir);
 
         // Apply environment variables
-        env.apply_to_command(&mut command);
+        env.apply_to_command(&mut command).await;
 
         let child = command.group_spawn()?;
 
@@ -1,9 +1,17 @@
-use std::fmt;
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    fs::File,
+    io::{self, BufRead, BufReader, Write},
+    path::{Component, Path, PathBuf},
+    sync::{Arc, Mutex},
+};
 
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use thiserror::Error;
+use tokio::sync::{mpsc, oneshot};
 use workspace_utils::approvals::ApprovalStatus;
 
 /// Errors emitted by executor approval services.
@@ -50,7 +58,486 @@ impl ExecutorApprovalService for NoopExecutorApprovalService {
     }
 }
 
+/// Approval service driven by decisions pushed over a channel as `(tool_call_id, status)`
+/// pairs, for UIs that decide proactively (e.g. "approve everything queued").
+///
+/// A decision that arrives before its request is kept and answers the request as soon as it
+/// is made; at most [`MAX_EARLY_DECISIONS`] are kept. A request whose caller stops waiting is
+/// no longer pending. Once the sender side is dropped, outstanding and new requests fail with
+/// [`ExecutorApprovalError::ServiceUnavailable`].
+#[derive(Debug, Clone)]
+pub struct ChannelApprovalService {
+    state: Arc<Mutex<ChannelApprovalState>>,
+}
+
+/// Decisions kept for requests not made yet. Beyond this the oldest is dropped, so decisions
+/// for tool calls that never happen can't pile up.
+pub const MAX_EARLY_DECISIONS: usize = 256;
+
+#[derive(Debug, Default)]
+struct ChannelApprovalState {
+    waiting: HashMap<String, oneshot::Sender<ApprovalStatus>>,
+    /// Decisions that arrived before their request, oldest first
+    decided: VecDeque<(String, ApprovalStatus)>,
+    closed: bool,
+}
+
+impl ChannelApprovalState {
+    fn decide_early(&mut self, tool_call_id: String, status: ApprovalStatus) {
+        self.decided.retain(|(id, _)| *id != tool_call_id);
+        if self.decided.len() == MAX_EARLY_DECISIONS
+            && let Some((dropped, _)) = self.decided.pop_front()
+        {
+            tracing::debug!("Dropping approval decision for {dropped}, it was never requested");
+        }
+        self.decided.push_back((tool_call_id, status));
+    }
+
+    fn take_early_decision(&mut self, tool_call_id: &str) -> Option<ApprovalStatus> {
+        let index = self.decided.iter().position(|(id, _)| id == tool_call_id)?;
+        self.decided.remove(index).map(|(_, status)| status)
+    }
+}
+
+/// Removes a request from `waiting` once its caller stops waiting, e.g. because the request
+/// future was dropped, unless the router already answered it.
+struct WaitingRequest<'a> {
+    state: &'a Mutex<ChannelApprovalState>,
+    tool_call_id: &'a str,
+}
+
+impl Drop for WaitingRequest<'_> {
+    fn drop(&mut self) {
+        let mut state = self.state.lock().unwrap();
+        if state
+            .waiting
+            .get(self.tool_call_id)
+            .is_some_and(oneshot::Sender::is_closed)
+        {
+            state.waiting.remove(self.tool_call_id);
+        }
+    }
+}
+
+impl ChannelApprovalService {
+    pub fn new(mut decisions: mpsc::UnboundedReceiver<(String, ApprovalStatus)>) -> Self {
+        let state = Arc::new(Mutex::new(ChannelApprovalState::default()));
+        let router_state = state.clone();
+        tokio::spawn(async move {
+            while let Some((tool_call_id, status)) = decisions.recv().await {
+                let mut state = router_state.lock().unwrap();
+                match state.waiting.remove(&tool_call_id) {
+                    Some(waiter) => {
+                        let _ = waiter.send(status);
+                    }
+                    None => state.decide_early(tool_call_id, status),
+                }
+            }
+            let mut state = router_state.lock().unwrap();
+            state.closed = true;
+            state.waiting.clear();
+        });
+        Self { state }
+    }
+
+    /// Tool call ids currently waiting for a decision.
+    pub fn pending_requests(&self) -> Vec<String> {
+        let mut pending: Vec<String> = self.state.lock().unwrap().waiting.keys().cloned().collect();
+        pending.sort();
+        pending
+    }
+}
+
+#[async_trait]
+impl ExecutorApprovalService for ChannelApprovalService {
+    async fn request_tool_approval(
+        &self,
+        _tool_name: &str,
+        _tool_input: Value,
+        tool_call_id: &str,
+    ) -> Result<ApprovalStatus, ExecutorApprovalError> {
+        // Declared before the receiver so it is dropped after it
+        let _waiting = WaitingRequest {
+            state: &self.state,
+            tool_call_id,
+        };
+        let decision = {
+            let mut state = self.state.lock().unwrap();
+            if let Some(status) = state.take_early_decision(tool_call_id) {
+                return Ok(status);
+            }
+            if state.closed {
+                return Err(ExecutorApprovalError::ServiceUnavailable);
+            }
+            let (tx, rx) = oneshot::channel();
+            state.waiting.insert(tool_call_id.to_string(), tx);
+            rx
+        };
+        decision
+            .await
+            .map_err(|_| ExecutorApprovalError::ServiceUnavailable)
+    }
+}
+
+/// One approval request and the decision it got, as stored by [`RecordingApprovalService`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApprovalRecord {
+    pub tool_name: String,
+    pub tool_input: Value,
+    pub tool_call_id: String,
+    pub decision: ApprovalStatus,
+}
+
+/// Wraps another approval service and appends every decision it makes to a JSONL file, so a
+/// later run can replay them with [`ReplayApprovalService`]. Failed requests are not recorded.
+pub struct RecordingApprovalService {
+    inner: Arc<dyn ExecutorApprovalService>,
+    file: Mutex<File>,
+}
+
+impl RecordingApprovalService {
+    /// Record into `path`, replacing any earlier recording there.
+    pub fn new(
+        inner: Arc<dyn ExecutorApprovalService>,
+        path: impl AsRef<Path>,
+    ) -> io::Result<Self> {
+        Ok(Self {
+            inner,
+            file: Mutex::new(File::create(path)?),
+        })
+    }
+}
+
+#[async_trait]
+impl ExecutorApprovalService for RecordingApprovalService {
+    async fn request_tool_approval(
+        &self,
+        tool_name: &str,
+        tool_input: Value,
+        tool_call_id: &str,
+    ) -> Result<ApprovalStatus, ExecutorApprovalError> {
+        let decision = self
+            .inner
+            .request_tool_approval(tool_name, tool_input.clone(), tool_call_id)
+            .await?;
+        let record = ApprovalRecord {
+            tool_name: tool_name.to_string(),
+            tool_input,
+            tool_call_id: tool_call_id.to_string(),
+            decision: decision.clone(),
+        };
+        let mut line =
+            serde_json::to_string(&record).map_err(ExecutorApprovalError::request_failed)?;
+        line.push('\n');
+        let mut file = self.file.lock().unwrap();
+        file.write_all(line.as_bytes())
+            .map_err(ExecutorApprovalError::request_failed)?;
+        Ok(decision)
+    }
+}
+
+/// Answers approval requests from a recording made by [`RecordingApprovalService`], in the
+/// order they were recorded. Tool call ids usually differ between runs, so requests are
+/// matched by position and checked against the recorded tool name; a request for a different
+/// tool, or one past the end of the recording, fails instead of guessing.
+#[derive(Debug)]
+pub struct ReplayApprovalService {
+    records: Mutex<VecDeque<ApprovalRecord>>,
+}
+
+impl ReplayApprovalService {
+    pub fn new(records: impl IntoIterator<Item = ApprovalRecord>) -> Self {
+        Self {
+            records: Mutex::new(records.into_iter().collect()),
+        }
+    }
+
+    /// Load a recording written by [`RecordingApprovalService`]. Blank lines are skipped.
+    pub fn from_file(path: impl AsRef<Path>) -> io::Result<Self> {
+        let mut records = Vec::new();
+        for line in BufReader::new(File::open(path)?).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            records.push(serde_json::from_str(&line)?);
+        }
+        Ok(Self::new(records))
+    }
+
+    /// Recorded decisions that have not been replayed yet.
+    pub fn remaining(&self) -> usize {
+        self.records.lock().unwrap().len()
+    }
+}
+
+#[async_trait]
+impl ExecutorApprovalService for ReplayApprovalService {
+    async fn request_tool_approval(
+        &self,
+        tool_name: &str,
+        _tool_input: Value,
+        tool_call_id: &str,
+    ) -> Result<ApprovalStatus, ExecutorApprovalError> {
+        let mut records = self.records.lock().unwrap();
+        let Some(record) = records.front() else {
+            return Err(ExecutorApprovalError::RequestFailed(format!(
+                "no recorded decision left for `{tool_name}` ({tool_call_id})"
+            )));
+        };
+        if record.tool_name != tool_name {
+            return Err(ExecutorApprovalError::RequestFailed(format!(
+                "expected a request for `{}` but got `{tool_name}` ({tool_call_id})",
+                record.tool_name
+            )));
+        }
+        Ok(records.pop_front().unwrap().decision)
+    }
+}
+
+/// Tool input keys that name a file the tool is about to touch.
+const PATH_KEYS: &[&str] = &["path", "file_path", "filePath", "filepath", "absolute_path"];
+
+/// Flags edits that land outside an agent's working directory, so auto-approve can hand
+/// them back to the user. Meant for agents that run without a filesystem sandbox.
+///
+/// Paths are compared lexically (`..` is resolved, symlinks are not followed).
+#[derive(Debug, Clone)]
+pub struct ExternalPathGuard {
+    root: PathBuf,
+}
+
+impl ExternalPathGuard {
+    pub fn new(root: impl AsRef<Path>) -> Self {
+        Self {
+            root: normalize_lexically(root.as_ref()),
+        }
+    }
+
+    /// Paths referenced by a tool's input that resolve outside the guarded root. Relative
+    /// paths are resolved against the root.
+    pub fn external_paths(&self, tool_input: &Value) -> Vec<PathBuf> {
+        let mut paths = Vec::new();
+        collect_paths(tool_input, &mut paths);
+        paths
+            .into_iter()
+            .map(|path| normalize_lexically(&self.root.join(path)))
+            .filter(|path| !path.starts_with(&self.root))
+            .collect()
+    }
+}
+
+fn collect_paths<'a>(value: &'a Value, out: &mut Vec<&'a str>) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                match value {
+                    Value::String(path) if PATH_KEYS.contains(&key.as_str()) => out.push(path),
+                    _ => collect_paths(value, out),
+                }
+            }
+        }
+        Value::Array(items) => items.iter().for_each(|item| collect_paths(item, out)),
+        _ => {}
+    }
+}
+
+fn normalize_lexically(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct ToolCallMetadata {
     pub tool_call_id: String,
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn in_tree_edits_are_not_external() {
+        let guard = ExternalPathGuard::new("/work/repo");
+        let input = json!({
+            "metadata": { "filepath": "/work/repo/src/main.rs" },
+            "changes": [{ "path": "src/lib.rs" }, { "path": "./docs/../README.md" }],
+        });
+
+        assert!(guard.external_paths(&input).is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn out_of_tree_edits_are_reported() {
+        let guard = ExternalPathGuard::new("/work/repo");
+        let input = json!({
+            "changes": [
+                { "path": "/etc/hosts" },
+                { "file_path": "../repo-sibling/secrets.env" },
+                { "path": "src/ok.rs" },
+            ],
+        });
+
+        assert_eq!(
+            guard.external_paths(&input),
+            vec![
+                PathBuf::from("/etc/hosts"),
+                PathBuf::from("/work/repo-sibling/secrets.env"),
+            ]
+        );
+    }
+
+    /// Await an approval that must not need anything else to happen first.
+    async fn answered(
+        request: impl Future<Output = Result<ApprovalStatus, ExecutorApprovalError>>,
+    ) -> Result<ApprovalStatus, ExecutorApprovalError> {
+        tokio::time::timeout(std::time::Duration::from_secs(5), request)
+            .await
+            .expect("approval request was never answered")
+    }
+
+    #[tokio::test]
+    async fn channel_decisions_answer_queued_and_future_requests() {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let service = ChannelApprovalService::new(rx);
+
+        let mut queued = std::pin::pin!(service.request_tool_approval(
+            "bash",
+            json!({ "command": "ls" }),
+            "call_1"
+        ));
+        assert!(futures::poll!(&mut queued).is_pending());
+        assert_eq!(service.pending_requests(), vec!["call_1".to_string()]);
+
+        // "Approve everything queued", plus a pre-approval for a call not made yet
+        for tool_call_id in service.pending_requests() {
+            tx.send((tool_call_id, ApprovalStatus::Approved)).unwrap();
+        }
+        tx.send(("call_2".to_string(), ApprovalStatus::Approved))
+            .unwrap();
+
+        assert!(matches!(
+            answered(queued).await,
+            Ok(ApprovalStatus::Approved)
+        ));
+        assert!(matches!(
+            answered(service.request_tool_approval("edit", json!({}), "call_2")).await,
+            Ok(ApprovalStatus::Approved)
+        ));
+        assert!(service.pending_requests().is_empty());
+
+        drop(tx);
+        assert!(matches!(
+            answered(service.request_tool_approval("edit", json!({}), "call_3")).await,
+            Err(ExecutorApprovalError::ServiceUnavailable)
+        ));
+    }
+
+    #[tokio::test]
+    async fn abandoned_requests_and_unrequested_decisions_are_not_kept() {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let service = ChannelApprovalService::new(rx);
+
+        let mut abandoned = Box::pin(service.request_tool_approval("bash", json!({}), "call_1"));
+        assert!(futures::poll!(&mut abandoned).is_pending());
+        drop(abandoned);
+        assert!(service.pending_requests().is_empty());
+
+        // One decision too many is kept for later. Decisions are routed in order, so once the
+        // one sent after them has answered its request, the first has been dropped
+        for n in 0..=MAX_EARLY_DECISIONS {
+            tx.send((format!("early_{n}"), ApprovalStatus::Approved))
+                .unwrap();
+        }
+        tx.send(("call_2".to_string(), ApprovalStatus::Approved))
+            .unwrap();
+        assert!(matches!(
+            answered(service.request_tool_approval("edit", json!({}), "call_2")).await,
+            Ok(ApprovalStatus::Approved)
+        ));
+        let mut first = std::pin::pin!(service.request_tool_approval("edit", json!({}), "early_0"));
+        assert!(futures::poll!(&mut first).is_pending());
+        assert!(matches!(
+            answered(service.request_tool_approval("edit", json!({}), "early_1")).await,
+            Ok(ApprovalStatus::Approved)
+        ));
+    }
+
+    /// Approves every tool except `bash`.
+    struct DenyBash;
+
+    #[async_trait]
+    impl ExecutorApprovalService for DenyBash {
+        async fn request_tool_approval(
+            &self,
+            tool_name: &str,
+            _tool_input: Value,
+            _tool_call_id: &str,
+        ) -> Result<ApprovalStatus, ExecutorApprovalError> {
+            Ok(match tool_name {
+                "bash" => ApprovalStatus::Denied {
+                    reason: Some("no shell".to_string()),
+                },
+                _ => ApprovalStatus::Approved,
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn recorded_decisions_replay_in_order() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let recorder = RecordingApprovalService::new(Arc::new(DenyBash), file.path()).unwrap();
+        for (tool, call) in [("edit", "call_1"), ("bash", "call_2"), ("edit", "call_3")] {
+            recorder
+                .request_tool_approval(tool, json!({ "path": "src/lib.rs" }), call)
+                .await
+                .unwrap();
+        }
+        drop(recorder);
+
+        // A later run gets new tool call ids but the same decisions
+        let replay = ReplayApprovalService::from_file(file.path()).unwrap();
+        assert_eq!(replay.remaining(), 3);
+        assert!(matches!(
+            replay
+                .request_tool_approval("edit", json!({}), "run2_1")
+                .await,
+            Ok(ApprovalStatus::Approved)
+        ));
+        assert!(matches!(
+            replay
+                .request_tool_approval("edit", json!({}), "run2_2")
+                .await,
+            Err(ExecutorApprovalError::RequestFailed(_))
+        ));
+        assert!(matches!(
+            replay.request_tool_approval("bash", json!({}), "run2_2").await,
+            Ok(ApprovalStatus::Denied { reason: Some(reason) }) if reason == "no shell"
+        ));
+        assert!(matches!(
+            replay
+                .request_tool_approval("edit", json!({}), "run2_3")
+                .await,
+            Ok(ApprovalStatus::Approved)
+        ));
+        assert!(matches!(
+            replay
+                .request_tool_approval("edit", json!({}), "run2_4")
+                .await,
+            Err(ExecutorApprovalError::RequestFailed(_))
+        ));
+    }
+}
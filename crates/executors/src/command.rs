@@ -1,4 +1,8 @@
-use std::{collections::HashMap, path::PathBuf};
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+    time::Duration,
+};
 
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
@@ -18,6 +22,8 @@ pub enum CommandBuildError {
     QuoteError(#[from] shlex::QuoteError),
     #[error("invalide shell parameters: {0}")]
     InvalidShellParams(String),
+    #[error("invalid command override: {0}")]
+    InvalidOverride(String),
 }
 
 #[derive(Debug, Clone)]
@@ -31,6 +37,14 @@ impl CommandParts {
         Self { program, args }
     }
 
+    pub fn program(&self) -> &str {
+        &self.program
+    }
+
+    pub fn args(&self) -> &[String] {
+        &self.args
+    }
+
     pub async fn into_resolved(self) -> Result<(PathBuf, Vec<String>), ExecutorError> {
         let CommandParts { program, args } = self;
         let executable = resolve_executable_path(&program)
@@ -54,12 +68,137 @@ pub struct CmdOverrides {
     )]
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub additional_params: Option<Vec<String>>,
+    #[schemars(
+        title = "Prepended Parameters",
+        description = "Parameters to insert right after the base command, before any subcommand \
+                       (e.g. global flags like `--verbose`)"
+    )]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prepend_args: Option<Vec<String>>,
     #[schemars(
         title = "Environment Variables",
         description = "Environment variables to set when running the executor"
     )]
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub env: Option<HashMap<String, String>>,
+    #[schemars(
+        title = "Isolated Home",
+        description = "Absolute path of a directory to run the executor with as its home \
+                       (`HOME`, `CODEX_HOME` and the XDG directories), keeping its config, auth \
+                       and caches apart from yours"
+    )]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub isolated_home: Option<String>,
+    #[schemars(
+        title = "Maximum Runtime (seconds)",
+        description = "Interrupt and then kill the executor once it has run for this many seconds"
+    )]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_runtime_secs: Option<u32>,
+    #[schemars(
+        title = "Spawn Timeout (seconds)",
+        description = "Kill the executor if it is not ready for work within this many seconds"
+    )]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub spawn_timeout_secs: Option<u32>,
+    #[schemars(
+        title = "Include Git Status",
+        description = "Prepend `git status --porcelain` of the working directory to the prompt"
+    )]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub include_git_status: Option<bool>,
+    #[schemars(
+        title = "Include Git Diff Summary",
+        description = "With Include Git Status, also prepend `git diff --stat HEAD`"
+    )]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub include_git_diff: Option<bool>,
+    #[schemars(
+        title = "Resource Limits",
+        description = "Memory and CPU time caps applied to the executor process (unix only)"
+    )]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resource_limits: Option<ResourceLimits>,
+}
+
+impl CmdOverrides {
+    /// `key` from the profile's `env`, then the process environment, as the agent will see it.
+    pub fn lookup_env(&self, key: &str) -> Option<String> {
+        self.env
+            .as_ref()
+            .and_then(|env| env.get(key).cloned())
+            .or_else(|| std::env::var(key).ok())
+    }
+
+    /// Whether any of `keys` is set to a non-blank value for the agent.
+    pub fn has_env(&self, keys: &[&str]) -> bool {
+        keys.iter().any(|key| {
+            self.lookup_env(key)
+                .is_some_and(|value| !value.trim().is_empty())
+        })
+    }
+
+    /// How long the executor may take to become ready, from `spawn_timeout_secs`.
+    pub fn spawn_timeout(&self) -> Option<Duration> {
+        self.spawn_timeout_secs
+            .map(|secs| Duration::from_secs(u64::from(secs)))
+    }
+
+    /// Apply [`CmdOverrides::resource_limits`], if any, to the agent's command before it is
+    /// spawned.
+    pub fn apply_resource_limits(&self, command: &mut tokio::process::Command) {
+        if let Some(limits) = &self.resource_limits {
+            limits.apply_to_command(command);
+        }
+    }
+}
+
+/// Caps set with `setrlimit` on the spawned agent, and inherited by the processes it starts.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, TS, JsonSchema, Default)]
+pub struct ResourceLimits {
+    /// Maximum virtual memory in megabytes (`RLIMIT_AS`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory_mb: Option<u32>,
+    /// Maximum CPU time in seconds (`RLIMIT_CPU`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpu_time_secs: Option<u32>,
+}
+
+impl ResourceLimits {
+    /// Set the limits in the child between fork and exec. Other platforms have no
+    /// `setrlimit`, so the limits are ignored there with a warning.
+    pub fn apply_to_command(&self, command: &mut tokio::process::Command) {
+        #[cfg(unix)]
+        {
+            let limits = self.clone();
+            // SAFETY: the closure only calls setrlimit, which is async-signal-safe.
+            unsafe {
+                command.pre_exec(move || limits.set_for_current_process());
+            }
+        }
+        #[cfg(not(unix))]
+        {
+            let _ = command;
+            tracing::warn!("Resource limits are only supported on unix; ignoring {self:?}");
+        }
+    }
+
+    #[cfg(unix)]
+    fn set_for_current_process(&self) -> std::io::Result<()> {
+        use nix::{
+            libc::rlim_t,
+            sys::resource::{Resource, setrlimit},
+        };
+
+        if let Some(memory_mb) = self.memory_mb {
+            let bytes = u64::from(memory_mb) * 1024 * 1024;
+            setrlimit(Resource::RLIMIT_AS, bytes as rlim_t, bytes as rlim_t)?;
+        }
+        if let Some(secs) = self.cpu_time_secs {
+            setrlimit(Resource::RLIMIT_CPU, secs as rlim_t, secs as rlim_t)?;
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, TS, JsonSchema)]
@@ -68,6 +207,22 @@ pub struct CommandBuilder {
     pub base: String,
     /// Optional parameters to append to the base command
     pub params: Option<Vec<String>>,
+    /// Arguments placed right after the base command, ahead of `subcommand` and `params`
+    #[serde(skip)]
+    #[ts(skip)]
+    #[schemars(skip)]
+    prepend: Vec<String>,
+    /// The agent subcommand run by the base command (e.g. `exec`); see
+    /// [`CommandBuilder::subcommand`]
+    #[serde(skip)]
+    #[ts(skip)]
+    #[schemars(skip)]
+    subcommand: Option<String>,
+    /// Drop repeated flags from the params when building; see [`CommandBuilder::dedupe_params`]
+    #[serde(skip)]
+    #[ts(skip)]
+    #[schemars(skip)]
+    dedupe: bool,
 }
 
 impl CommandBuilder {
@@ -75,9 +230,21 @@ impl CommandBuilder {
         Self {
             base: base.into(),
             params: None,
+            prepend: Vec::new(),
+            subcommand: None,
+            dedupe: false,
         }
     }
 
+    /// Collapse repeated flags in the params (including user overrides) when building: the last
+    /// value wins for valued flags and boolean flags are kept once. A flag followed by a token
+    /// that doesn't start with `-` is treated as valued, so this is opt-in for CLIs whose
+    /// positional arguments could be mistaken for flag values. Nothing after `--` is touched.
+    pub fn dedupe_params(mut self) -> Self {
+        self.dedupe = true;
+        self
+    }
+
     pub fn params<I>(mut self, params: I) -> Self
     where
         I: IntoIterator,
@@ -87,8 +254,18 @@ impl CommandBuilder {
         self
     }
 
+    /// Run `subcommand` after the base command, with any prepended args in between. A base that
+    /// already ends with it (e.g. a `VK_*` command written with the subcommand) keeps one copy.
+    pub fn subcommand<S: Into<String>>(mut self, subcommand: S) -> Self {
+        self.subcommand = Some(subcommand.into());
+        self
+    }
+
+    /// Replace the base command. The override stands for the whole command up to the params,
+    /// so it drops the subcommand too.
     pub fn override_base<S: Into<String>>(mut self, base: S) -> Self {
         self.base = base.into();
+        self.subcommand = None;
         self
     }
 
@@ -113,6 +290,25 @@ impl CommandBuilder {
         Ok(self)
     }
 
+    /// Insert shell-split `args` right after the base command, before the subcommand and
+    /// params, for CLIs that only accept global flags in that position.
+    fn prepend_shell_params<I>(mut self, args: I) -> Result<Self, CommandBuildError>
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        let joined = args
+            .into_iter()
+            .map(|p| p.into())
+            .collect::<Vec<String>>()
+            .join(" ");
+
+        let extra: Vec<String> = split_command_line(&joined)
+            .map_err(|err| CommandBuildError::InvalidShellParams(format!("{joined}: {err}")))?;
+        self.prepend.extend(extra);
+        Ok(self)
+    }
+
     pub fn extend_params<I>(mut self, more: I) -> Self
     where
         I: IntoIterator,
@@ -139,10 +335,19 @@ impl CommandBuilder {
 
     fn build(&self, additional_args: &[String]) -> Result<CommandParts, CommandBuildError> {
         let mut parts = vec![];
-        let base_parts = split_command_line(&self.base)?;
+        let mut base_parts = split_command_line(&self.base)?;
+        if self.subcommand.is_some() && base_parts.last() == self.subcommand.as_ref() {
+            base_parts.pop();
+        }
         parts.extend(base_parts);
+        parts.extend(self.prepend.iter().cloned());
+        parts.extend(self.subcommand.iter().cloned());
         if let Some(ref params) = self.params {
-            parts.extend(params.clone());
+            if self.dedupe {
+                parts.extend(dedupe_flags(params));
+            } else {
+                parts.extend(params.clone());
+            }
         }
         parts.extend(additional_args.iter().cloned());
 
@@ -155,7 +360,83 @@ impl CommandBuilder {
     }
 }
 
-fn split_command_line(input: &str) -> Result<Vec<String>, CommandBuildError> {
+/// A flag with any value it carries, or a standalone argument.
+enum ParamGroup<'a> {
+    Flag {
+        name: &'a str,
+        valued: bool,
+        tokens: &'a [String],
+    },
+    Other(&'a [String]),
+}
+
+fn group_params(params: &[String]) -> Vec<ParamGroup<'_>> {
+    let mut groups = Vec::new();
+    let mut i = 0;
+    while i < params.len() {
+        let token = &params[i];
+        if token == "--" {
+            groups.push(ParamGroup::Other(&params[i..]));
+            break;
+        }
+        if !token.starts_with('-') || token == "-" {
+            groups.push(ParamGroup::Other(&params[i..=i]));
+            i += 1;
+            continue;
+        }
+
+        let (name, valued, len) = if let Some((name, _)) = token.split_once('=') {
+            (name, true, 1)
+        } else if params.get(i + 1).is_some_and(|next| !next.starts_with('-')) {
+            (token.as_str(), true, 2)
+        } else {
+            (token.as_str(), false, 1)
+        };
+        groups.push(ParamGroup::Flag {
+            name,
+            valued,
+            tokens: &params[i..i + len],
+        });
+        i += len;
+    }
+    groups
+}
+
+fn dedupe_flags(params: &[String]) -> Vec<String> {
+    let groups = group_params(params);
+    let mut last_valued: HashMap<&str, usize> = HashMap::new();
+    for (index, group) in groups.iter().enumerate() {
+        if let ParamGroup::Flag {
+            name, valued: true, ..
+        } = group
+        {
+            last_valued.insert(*name, index);
+        }
+    }
+
+    let mut seen_boolean = HashSet::new();
+    let mut deduped = Vec::with_capacity(params.len());
+    for (index, group) in groups.iter().enumerate() {
+        let keep = match group {
+            ParamGroup::Flag {
+                name, valued: true, ..
+            } => last_valued.get(name) == Some(&index),
+            ParamGroup::Flag {
+                name,
+                valued: false,
+                ..
+            } => !last_valued.contains_key(name) && seen_boolean.insert(*name),
+            ParamGroup::Other(_) => true,
+        };
+        if keep {
+            let (ParamGroup::Flag { tokens, .. } | ParamGroup::Other(tokens)) = group;
+            deduped.extend(tokens.iter().cloned());
+        }
+    }
+    deduped
+}
+
+pub(crate) fn split_command_line(input: &str) -> Result<Vec<String>, CommandBuildError> {
     #[cfg(windows)]
     {
         let parts = winsplit::split(input);
@@ -185,9 +466,91 @@ pub fn apply_overrides(
     } else {
         builder
     };
+    let builder = if let Some(ref args) = overrides.prepend_args {
+        builder.prepend_shell_params(args.clone())?
+    } else {
+        builder
+    };
     if let Some(ref extra) = overrides.additional_params {
         builder.extend_shell_params(extra.clone())
     } else {
         Ok(builder)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn built_args(builder: CommandBuilder) -> Vec<String> {
+        let parts = builder.build_initial().unwrap();
+        parts.args().to_vec()
+    }
+
+    #[test]
+    fn dedupe_keeps_last_value_for_valued_flags() {
+        let builder = CommandBuilder::new("agent")
+            .params(["--model", "a", "--verbose", "--model=b"])
+            .extend_params(["--model", "c"])
+            .dedupe_params();
+        assert_eq!(built_args(builder), ["--verbose", "--model", "c"]);
+    }
+
+    #[test]
+    fn dedupe_keeps_boolean_flags_once() {
+        let builder = CommandBuilder::new("agent")
+            .params(["--yolo", "-p", "--yolo"])
+            .extend_params(["--yolo", "--", "--yolo"])
+            .dedupe_params();
+        assert_eq!(built_args(builder), ["--yolo", "-p", "--", "--yolo"]);
+    }
+
+    #[test]
+    fn duplicates_are_kept_without_opt_in() {
+        let builder = CommandBuilder::new("agent").params(["--yolo", "--yolo"]);
+        assert_eq!(built_args(builder), ["--yolo", "--yolo"]);
+    }
+
+    #[test]
+    fn prepend_args_go_between_the_program_and_its_subcommand() {
+        let overrides = CmdOverrides {
+            prepend_args: Some(vec!["--verbose".to_string()]),
+            ..Default::default()
+        };
+        let build = |builder: CommandBuilder| {
+            apply_overrides(builder.params(["-p"]), &overrides)
+                .unwrap()
+                .build_initial()
+                .unwrap()
+        };
+
+        let parts = build(CommandBuilder::new("droid").subcommand("exec"));
+        assert_eq!(parts.program(), "droid");
+        assert_eq!(parts.args(), ["--verbose", "exec", "-p"]);
+
+        // A base that already names the subcommand, like the claude-code-router commands
+        let parts = build(CommandBuilder::new("npx -y ccr@1.0.0 code").subcommand("code"));
+        assert_eq!(parts.program(), "npx");
+        assert_eq!(parts.args(), ["-y", "ccr@1.0.0", "--verbose", "code", "-p"]);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn resource_limits_are_applied_to_the_child() {
+        let overrides = CmdOverrides {
+            resource_limits: Some(ResourceLimits {
+                memory_mb: Some(512),
+                cpu_time_secs: Some(30),
+            }),
+            ..Default::default()
+        };
+        let mut command = tokio::process::Command::new("sh");
+        command.args(["-c", "ulimit -v; ulimit -t"]);
+        overrides.apply_resource_limits(&mut command);
+
+        let output = command.output().await.unwrap();
+        assert!(output.status.success(), "{output:?}");
+        // `ulimit -v` reports kilobytes
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "524288\n30\n");
+    }
+}
@@ -1,8 +1,20 @@
-use std::{collections::HashMap, path::PathBuf};
+use std::{
+    collections::HashMap,
+    io,
+    path::{Path, PathBuf},
+};
 
-use tokio::process::Command;
+use tokio::{process::Command, sync::mpsc};
 
-use crate::command::CmdOverrides;
+use crate::{
+    command::CmdOverrides,
+    executors::ExecutorError,
+    spawn_timing::{SpawnPhase, SpawnTimer},
+};
+
+/// Receives the final prompt an executor sends to its agent (after `AppendPrompt` and any
+/// executor-side additions). Nothing is redacted, so captured prompts may contain secrets.
+pub type PromptCaptureSender = mpsc::UnboundedSender<String>;
 
 /// Repository context for executor operations
 #[derive(Debug, Clone, Default)]
@@ -34,6 +46,19 @@ pub struct ExecutionEnv {
     pub vars: HashMap<String, String>,
     pub repo_context: RepoContext,
     pub commit_reminder: bool,
+    /// Optional sink for the exact prompt sent to the agent, for audit/repro.
+    pub prompt_capture: Option<PromptCaptureSender>,
+    /// The MCP servers (canonical `name -> server` map) saved in the agent's config, read for
+    /// the prompt instructions in their `meta`.
+    pub persisted_mcp_servers: Option<serde_json::Value>,
+    /// MCP servers (canonical `name -> server` map) added for this spawn only.
+    /// Never written to the agent's on-disk config.
+    pub ephemeral_mcp_servers: Option<serde_json::Value>,
+    /// Scratch directory used as the child's home, so the agent's config, auth and caches
+    /// stay out of the real user's. Only affects the spawned process's environment.
+    pub isolated_home: Option<PathBuf>,
+    /// Optional recorder for how long each phase of the spawn takes.
+    pub spawn_timer: Option<SpawnTimer>,
 }
 
 impl ExecutionEnv {
@@ -42,6 +67,83 @@ impl ExecutionEnv {
             vars: HashMap::new(),
             repo_context,
             commit_reminder,
+            prompt_capture: None,
+            persisted_mcp_servers: None,
+            ephemeral_mcp_servers: None,
+            isolated_home: None,
+            spawn_timer: None,
+        }
+    }
+
+    /// Load the variables of a dotenv file, to be merged into a spawn's env. Supports
+    /// `# comments`, an optional `export ` prefix, and single- or double-quoted values
+    /// (double quotes understand `\n`, `\t`, `\"` and `\\`). Values must fit on one line.
+    pub fn from_dotenv(path: &Path) -> Result<ExecutionEnv, ExecutorError> {
+        let content = std::fs::read_to_string(path).map_err(|err| {
+            ExecutorError::Io(io::Error::new(
+                err.kind(),
+                format!("Failed to read env file {}: {err}", path.display()),
+            ))
+        })?;
+
+        let mut env = ExecutionEnv::new(RepoContext::default(), false);
+        for (index, line) in content.lines().enumerate() {
+            let parsed = parse_dotenv_line(line).map_err(|reason| {
+                ExecutorError::Io(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{}:{}: {reason}", path.display(), index + 1),
+                ))
+            })?;
+            if let Some((key, value)) = parsed {
+                env.insert(key, value);
+            }
+        }
+        Ok(env)
+    }
+
+    /// Return a new env that records `servers` as the MCP servers saved in the agent's config.
+    pub fn with_persisted_mcp_servers(mut self, servers: serde_json::Value) -> Self {
+        self.persisted_mcp_servers = Some(servers);
+        self
+    }
+
+    /// Return a new env that adds `servers` to the agent's MCP servers for this spawn only.
+    pub fn with_ephemeral_mcp_servers(mut self, servers: serde_json::Value) -> Self {
+        self.ephemeral_mcp_servers = Some(servers);
+        self
+    }
+
+    /// Return a new env that points the child's `HOME`, `CODEX_HOME` and XDG base
+    /// directories into `home`.
+    pub fn with_isolated_home(mut self, home: PathBuf) -> Self {
+        self.isolated_home = Some(home);
+        self
+    }
+
+    /// Return a new env that reports the final prompt to `sender` when it is sent.
+    pub fn with_prompt_capture(mut self, sender: PromptCaptureSender) -> Self {
+        self.prompt_capture = Some(sender);
+        self
+    }
+
+    /// Report the final prompt to the capture sink, if one is configured.
+    pub fn capture_prompt(&self, prompt: &str) {
+        if let Some(sender) = &self.prompt_capture {
+            // Best-effort; the receiver may have been dropped
+            let _ = sender.send(prompt.to_string());
+        }
+    }
+
+    /// Return a new env that records the spawn's phases in `timer`.
+    pub fn with_spawn_timer(mut self, timer: SpawnTimer) -> Self {
+        self.spawn_timer = Some(timer);
+        self
+    }
+
+    /// Record that the spawn reached `phase`, if a timer is configured.
+    pub fn mark_spawn_phase(&self, phase: SpawnPhase) {
+        if let Some(timer) = &self.spawn_timer {
+            timer.mark(phase);
         }
     }
 
@@ -62,20 +164,44 @@ impl ExecutionEnv {
         self
     }
 
-    /// Return a new env with profile env from CmdOverrides merged in.
+    /// Return a new env with profile env and isolated home from CmdOverrides merged in.
     pub fn with_profile(self, cmd: &CmdOverrides) -> Self {
-        if let Some(ref profile_env) = cmd.env {
+        let env = if let Some(ref profile_env) = cmd.env {
             self.with_overrides(profile_env)
         } else {
             self
+        };
+        match &cmd.isolated_home {
+            Some(home) => env.with_isolated_home(PathBuf::from(home)),
+            None => env,
         }
     }
 
-    /// Apply all environment variables to a Command
-    pub fn apply_to_command(&self, command: &mut Command) {
+    /// The `CODEX_HOME` of a child spawned with this env and `cmd`'s profile, when it runs in
+    /// an isolated home.
+    pub fn isolated_codex_home(&self, cmd: &CmdOverrides) -> Option<PathBuf> {
+        cmd.isolated_home
+            .as_deref()
+            .map(Path::new)
+            .or(self.isolated_home.as_deref())
+            .map(codex_home_in)
+    }
+
+    /// Apply all environment variables to a Command. An isolated home takes precedence over
+    /// variables that point elsewhere.
+    pub async fn apply_to_command(&self, command: &mut Command) {
         for (key, value) in &self.vars {
             command.env(key, value);
         }
+        if let Some(home) = &self.isolated_home {
+            for (key, dir) in isolated_home_dirs(home) {
+                // Codex refuses to start when CODEX_HOME does not exist
+                if let Err(err) = tokio::fs::create_dir_all(&dir).await {
+                    tracing::warn!("Failed to create {} for {key}: {err}", dir.display());
+                }
+                command.env(key, dir);
+            }
+        }
     }
 
     pub fn contains_key(&self, key: &str) -> bool {
@@ -83,6 +209,87 @@ impl ExecutionEnv {
     }
 }
 
+/// The variables an isolated home sets, and the directories they point to.
+fn isolated_home_dirs(home: &Path) -> [(&'static str, PathBuf); 7] {
+    [
+        ("HOME", home.to_path_buf()),
+        ("USERPROFILE", home.to_path_buf()),
+        ("CODEX_HOME", codex_home_in(home)),
+        ("XDG_CONFIG_HOME", home.join(".config")),
+        ("XDG_DATA_HOME", home.join(".local/share")),
+        ("XDG_STATE_HOME", home.join(".local/state")),
+        ("XDG_CACHE_HOME", home.join(".cache")),
+    ]
+}
+
+fn codex_home_in(home: &Path) -> PathBuf {
+    home.join(".codex")
+}
+
+/// Parse one dotenv line into a key/value pair; blank and comment lines yield `None`.
+fn parse_dotenv_line(line: &str) -> Result<Option<(String, String)>, String> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return Ok(None);
+    }
+    let line = line.strip_prefix("export ").unwrap_or(line);
+    let Some((key, raw_value)) = line.split_once('=') else {
+        return Err(format!("expected KEY=VALUE, found `{line}`"));
+    };
+
+    let key = key.trim();
+    let valid_key = key
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.');
+    if !valid_key {
+        return Err(format!("invalid variable name `{key}`"));
+    }
+
+    let raw_value = raw_value.trim_start();
+    let (value, rest) = match raw_value.chars().next() {
+        Some(quote @ ('"' | '\'')) => {
+            let mut value = String::new();
+            let mut chars = raw_value[1..].char_indices();
+            let mut end = None;
+            while let Some((i, c)) = chars.next() {
+                match c {
+                    c if c == quote => {
+                        end = Some(i + 2);
+                        break;
+                    }
+                    '\\' if quote == '"' => match chars.next() {
+                        Some((_, 'n')) => value.push('\n'),
+                        Some((_, 't')) => value.push('\t'),
+                        Some((_, escaped)) => value.push(escaped),
+                        None => value.push('\\'),
+                    },
+                    c => value.push(c),
+                }
+            }
+            let Some(end) = end else {
+                return Err(format!("unterminated {quote}-quoted value for `{key}`"));
+            };
+            (value, raw_value[end..].trim())
+        }
+        _ => {
+            // An unquoted value ends at a ` #` inline comment
+            let value = match raw_value.find(" #") {
+                Some(comment) => &raw_value[..comment],
+                None => raw_value,
+            };
+            (value.trim_end().to_string(), "")
+        }
+    };
+    if !rest.is_empty() && !rest.starts_with('#') {
+        return Err(format!("unexpected text after quoted value for `{key}`"));
+    }
+    Ok(Some((key.to_string(), value)))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -103,4 +310,110 @@ mod tests {
         assert_eq!(merged.vars.get("FOO").unwrap(), "profile"); // overrides
         assert_eq!(merged.vars.get("BAR").unwrap(), "profile");
     }
+
+    #[test]
+    fn prompt_capture_survives_profile_merge() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let env = ExecutionEnv::new(RepoContext::default(), false)
+            .with_prompt_capture(tx)
+            .with_overrides(&HashMap::new());
+
+        env.capture_prompt("hello");
+
+        assert_eq!(rx.try_recv().unwrap(), "hello");
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn dotenv_parses_quotes_and_comments() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(
+            file.path(),
+            r#"# Agent credentials
+OPENAI_API_KEY=sk-test # inline comment
+export ANTHROPIC_API_KEY="sk-ant-#1"
+
+GREETING="hello\nworld"  # trailing comment
+LITERAL='no $expansion \n here'
+EMPTY=
+"#,
+        )
+        .unwrap();
+
+        let env = ExecutionEnv::from_dotenv(file.path()).unwrap();
+
+        assert_eq!(env.vars.len(), 5);
+        assert_eq!(env.vars["OPENAI_API_KEY"], "sk-test");
+        assert_eq!(env.vars["ANTHROPIC_API_KEY"], "sk-ant-#1");
+        assert_eq!(env.vars["GREETING"], "hello\nworld");
+        assert_eq!(env.vars["LITERAL"], "no $expansion \\n here");
+        assert_eq!(env.vars["EMPTY"], "");
+    }
+
+    #[test]
+    fn dotenv_reports_missing_files_and_malformed_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("missing.env");
+        let ExecutorError::Io(err) = ExecutionEnv::from_dotenv(&missing).unwrap_err() else {
+            panic!("expected an I/O error");
+        };
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+
+        let path = dir.path().join("malformed.env");
+        std::fs::write(&path, "GOOD=1\nnot a pair\n").unwrap();
+        let err = ExecutionEnv::from_dotenv(&path).unwrap_err();
+        assert!(err.to_string().contains(":2: expected KEY=VALUE"), "{err}");
+
+        assert!(parse_dotenv_line("KEY=\"unterminated").is_err());
+        assert!(parse_dotenv_line("1KEY=value").is_err());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn isolated_home_replaces_the_childs_home() {
+        let dir = tempfile::tempdir().unwrap();
+        let home = dir.path().join("home");
+        let mut env =
+            ExecutionEnv::new(RepoContext::default(), false).with_isolated_home(home.clone());
+        env.insert("CODEX_HOME", "/home/real/.codex");
+
+        let mut command = Command::new("sh");
+        command
+            .arg("-c")
+            .arg(r#"printf '%s\n%s\n%s' "$HOME" "$CODEX_HOME" "$XDG_CONFIG_HOME""#);
+        env.apply_to_command(&mut command).await;
+        let output = command.output().await.unwrap();
+
+        let expected = format!(
+            "{}\n{}\n{}",
+            home.display(),
+            home.join(".codex").display(),
+            home.join(".config").display()
+        );
+        assert_eq!(String::from_utf8_lossy(&output.stdout), expected);
+        assert!(home.join(".codex").is_dir());
+    }
+
+    #[test]
+    fn profile_isolated_home_wins_over_the_runtime_one() {
+        let env = ExecutionEnv::new(RepoContext::default(), false)
+            .with_isolated_home(PathBuf::from("/runtime"));
+        let cmd = CmdOverrides {
+            isolated_home: Some("/profile".to_string()),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            env.isolated_codex_home(&cmd),
+            Some(PathBuf::from("/profile/.codex"))
+        );
+        assert_eq!(
+            env.isolated_codex_home(&CmdOverrides::default()),
+            Some(PathBuf::from("/runtime/.codex"))
+        );
+        assert_eq!(
+            env.with_profile(&cmd).isolated_home,
+            Some(PathBuf::from("/profile"))
+        );
+    }
 }
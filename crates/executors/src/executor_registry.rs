@@ -0,0 +1,238 @@
+//! Runtime registry for executors that are not built in, so advanced users can plug in their own
+//! agent without adding a [`CodingAgent`](crate::executors::CodingAgent) variant.
+//!
+//! Registered executors are keyed by a string id. [`ExecutorRegistry::resolve`] and the spawn
+//! helpers dispatch on that id: built-in agent names (`CODEX`, `CLAUDE_CODE`, ...) go to the
+//! configured built-in agent, anything else to the executor registered under it. Executions are
+//! started through an [`ExternalAgentRequest`](crate::actions::external_agent::ExternalAgentRequest)
+//! action, which spawns from the [shared](ExecutorRegistry::shared) registry.
+
+use std::{
+    collections::HashMap,
+    path::Path,
+    str::FromStr,
+    sync::{Arc, LazyLock, RwLock},
+};
+
+use thiserror::Error;
+
+use crate::{
+    env::ExecutionEnv,
+    executors::{BaseCodingAgent, ExecutorError, SpawnedChild, StandardCodingAgentExecutor},
+    profile::{ExecutorConfigs, ExecutorProfileId},
+};
+
+/// An executor provided at runtime rather than compiled into [`BaseCodingAgent`].
+pub type ExternalExecutor = Arc<dyn StandardCodingAgentExecutor + Send + Sync>;
+
+static SHARED: LazyLock<ExecutorRegistry> = LazyLock::new(ExecutorRegistry::new);
+
+#[derive(Debug, Error)]
+pub enum ExecutorRegistryError {
+    #[error("`{0}` is the id of a built-in executor")]
+    BuiltIn(String),
+    #[error("An executor is already registered as `{0}`")]
+    AlreadyRegistered(String),
+}
+
+#[derive(Default)]
+pub struct ExecutorRegistry {
+    executors: RwLock<HashMap<String, ExternalExecutor>>,
+}
+
+impl ExecutorRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The process-wide registry.
+    pub fn shared() -> &'static ExecutorRegistry {
+        &SHARED
+    }
+
+    /// Make `executor` available under `id`. Built-in agent names and ids that are already
+    /// taken are rejected.
+    pub fn register(
+        &self,
+        id: impl Into<String>,
+        executor: ExternalExecutor,
+    ) -> Result<(), ExecutorRegistryError> {
+        let id = id.into();
+        if BaseCodingAgent::from_str(&id).is_ok() {
+            return Err(ExecutorRegistryError::BuiltIn(id));
+        }
+        let mut executors = self.executors.write().unwrap();
+        if executors.contains_key(&id) {
+            return Err(ExecutorRegistryError::AlreadyRegistered(id));
+        }
+        executors.insert(id, executor);
+        Ok(())
+    }
+
+    pub fn unregister(&self, id: &str) -> Option<ExternalExecutor> {
+        self.executors.write().unwrap().remove(id)
+    }
+
+    /// Ids of the registered executors, sorted.
+    pub fn ids(&self) -> Vec<String> {
+        let mut ids: Vec<String> = self.executors.read().unwrap().keys().cloned().collect();
+        ids.sort();
+        ids
+    }
+
+    /// The executor for `id`: the default configuration of a built-in agent, or a registered
+    /// executor.
+    pub fn resolve(&self, id: &str) -> Result<ExternalExecutor, ExecutorError> {
+        if let Ok(base) = BaseCodingAgent::from_str(id) {
+            let agent = ExecutorConfigs::get_cached()
+                .get_coding_agent(&ExecutorProfileId::new(base))
+                .ok_or_else(|| ExecutorError::UnknownExecutorType(id.to_string()))?;
+            return Ok(Arc::new(agent));
+        }
+        self.executors
+            .read()
+            .unwrap()
+            .get(id)
+            .cloned()
+            .ok_or_else(|| ExecutorError::UnknownExecutorType(id.to_string()))
+    }
+
+    pub async fn spawn(
+        &self,
+        id: &str,
+        current_dir: &Path,
+        prompt: &str,
+        env: &ExecutionEnv,
+    ) -> Result<SpawnedChild, ExecutorError> {
+        self.resolve(id)?.spawn(current_dir, prompt, env).await
+    }
+
+    pub async fn spawn_follow_up(
+        &self,
+        id: &str,
+        current_dir: &Path,
+        prompt: &str,
+        session_id: &str,
+        env: &ExecutionEnv,
+    ) -> Result<SpawnedChild, ExecutorError> {
+        self.resolve(id)?
+            .spawn_follow_up(current_dir, prompt, session_id, env)
+            .await
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use async_trait::async_trait;
+    use command_group::AsyncCommandGroup;
+    use tokio::io::AsyncReadExt;
+    use workspace_utils::msg_store::MsgStore;
+
+    use super::*;
+    use crate::{
+        actions::{
+            Executable, ExecutorAction, ExecutorActionType, external_agent::ExternalAgentRequest,
+        },
+        approvals::NoopExecutorApprovalService,
+        env::RepoContext,
+    };
+
+    /// An "agent" that prints its prompt and exits.
+    struct EchoExecutor;
+
+    #[async_trait]
+    impl StandardCodingAgentExecutor for EchoExecutor {
+        async fn spawn(
+            &self,
+            current_dir: &Path,
+            prompt: &str,
+            _env: &ExecutionEnv,
+        ) -> Result<SpawnedChild, ExecutorError> {
+            let child = tokio::process::Command::new("sh")
+                .args(["-c", "printf '%s' \"$1\"", "sh", prompt])
+                .current_dir(current_dir)
+                .stdout(std::process::Stdio::piped())
+                .kill_on_drop(true)
+                .group_spawn()?;
+            Ok(child.into())
+        }
+
+        async fn spawn_follow_up(
+            &self,
+            current_dir: &Path,
+            prompt: &str,
+            session_id: &str,
+            env: &ExecutionEnv,
+        ) -> Result<SpawnedChild, ExecutorError> {
+            let prompt = format!("{session_id}: {prompt}");
+            self.spawn(current_dir, &prompt, env).await
+        }
+
+        fn normalize_logs(&self, _msg_store: Arc<MsgStore>, _worktree_path: &Path) {}
+
+        fn default_mcp_config_path(&self) -> Option<std::path::PathBuf> {
+            None
+        }
+    }
+
+    #[tokio::test]
+    async fn registered_executor_is_spawned_by_id() {
+        let registry = ExecutorRegistry::new();
+        registry.register("echo", Arc::new(EchoExecutor)).unwrap();
+        assert!(matches!(
+            registry.register("echo", Arc::new(EchoExecutor)),
+            Err(ExecutorRegistryError::AlreadyRegistered(_))
+        ));
+        assert!(matches!(
+            registry.register("CODEX", Arc::new(EchoExecutor)),
+            Err(ExecutorRegistryError::BuiltIn(_))
+        ));
+        assert_eq!(registry.ids(), ["echo"]);
+
+        let env = ExecutionEnv::new(RepoContext::default(), false);
+        let mut spawned = registry
+            .spawn_follow_up("echo", &std::env::temp_dir(), "hello", "s1", &env)
+            .await
+            .unwrap();
+        let mut output = String::new();
+        let mut stdout = spawned.child.inner().stdout.take().unwrap();
+        stdout.read_to_string(&mut output).await.unwrap();
+        assert_eq!(output, "s1: hello");
+
+        assert!(registry.unregister("echo").is_some());
+        assert!(matches!(
+            registry.spawn("echo", &std::env::temp_dir(), "hello", &env).await,
+            Err(ExecutorError::UnknownExecutorType(id)) if id == "echo"
+        ));
+    }
+
+    #[tokio::test]
+    async fn external_agent_action_spawns_the_registered_executor() {
+        let action = ExecutorAction::new(
+            ExecutorActionType::ExternalAgentRequest(ExternalAgentRequest {
+                executor_id: "echo-action".to_string(),
+                prompt: "hello".to_string(),
+                session_id: None,
+                working_dir: None,
+            }),
+            None,
+        );
+        let env = ExecutionEnv::new(RepoContext::default(), false);
+        let dir = std::env::temp_dir();
+        let spawn = || action.spawn(&dir, Arc::new(NoopExecutorApprovalService), &env);
+        assert!(matches!(
+            spawn().await,
+            Err(ExecutorError::UnknownExecutorType(id)) if id == "echo-action"
+        ));
+
+        ExecutorRegistry::shared()
+            .register("echo-action", Arc::new(EchoExecutor))
+            .unwrap();
+        let mut spawned = spawn().await.unwrap();
+        let mut output = String::new();
+        let mut stdout = spawned.child.inner().stdout.take().unwrap();
+        stdout.read_to_string(&mut output).await.unwrap();
+        assert_eq!(output, "hello");
+        ExecutorRegistry::shared().unregister("echo-action");
+    }
+}
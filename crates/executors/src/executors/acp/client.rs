@@ -7,7 +7,7 @@ use tracing::{debug, warn};
 use workspace_utils::approvals::ApprovalStatus;
 
 use crate::{
-    approvals::{ExecutorApprovalError, ExecutorApprovalService},
+    approvals::{ExecutorApprovalService, ExternalPathGuard},
     executors::acp::{AcpEvent, ApprovalResponse},
 };
 
@@ -16,6 +16,10 @@ use crate::{
 pub struct AcpClient {
     event_tx: mpsc::UnboundedSender<AcpEvent>,
     approvals: Option<Arc<dyn ExecutorApprovalService>>,
+    /// When set, file edits outside its root need explicit approval and in-tree ones don't
+    path_guard: Option<ExternalPathGuard>,
+    /// Approve requests other than guarded out-of-tree edits without asking
+    auto_approve: bool,
     feedback_queue: Arc<Mutex<Vec<String>>>,
 }
 
@@ -28,10 +32,31 @@ impl AcpClient {
         Self {
             event_tx,
             approvals,
+            path_guard: None,
+            auto_approve: false,
             feedback_queue: Arc::new(Mutex::new(Vec::new())),
         }
     }
 
+    /// Approve file edits inside `guard`'s root without asking and send the ones outside it
+    /// to the approval service, denying them when there is none. With `auto_approve`, every
+    /// other request is approved without asking too.
+    pub fn with_path_guard(mut self, guard: ExternalPathGuard, auto_approve: bool) -> Self {
+        self.path_guard = Some(guard);
+        self.auto_approve = auto_approve;
+        self
+    }
+
+    /// Whether `tool_call` edits files outside the guarded root.
+    fn is_guarded_external_edit(&self, tool_call: &acp::ToolCallUpdate) -> bool {
+        self.path_guard.as_ref().is_some_and(|guard| {
+            is_file_edit(tool_call)
+                && !guard
+                    .external_paths(&serde_json::json!({ "tool_call": tool_call }))
+                    .is_empty()
+        })
+    }
+
     pub fn record_user_prompt_event(&self, prompt: &str) {
         self.send_event(AcpEvent::User(prompt.to_string()));
     }
@@ -59,6 +84,14 @@ impl AcpClient {
     }
 }
 
+/// Whether a tool call writes, deletes or moves files.
+fn is_file_edit(tool_call: &acp::ToolCallUpdate) -> bool {
+    matches!(
+        tool_call.fields.kind,
+        Some(acp::ToolKind::Edit | acp::ToolKind::Delete | acp::ToolKind::Move)
+    )
+}
+
 #[async_trait(?Send)]
 impl acp::Client for AcpClient {
     async fn request_permission(
@@ -67,7 +100,12 @@ impl acp::Client for AcpClient {
     ) -> Result<acp::RequestPermissionResponse, acp::Error> {
         self.send_event(AcpEvent::RequestPermission(args.clone()));
 
-        if self.approvals.is_none() {
+        let outside_root = self.is_guarded_external_edit(&args.tool_call);
+        let approve_without_asking = !outside_root
+            && (self.approvals.is_none()
+                || self.auto_approve
+                || (self.path_guard.is_some() && is_file_edit(&args.tool_call)));
+        if approve_without_asking {
             // Auto-approve with best available option when no approval service is configured
             let chosen_option = args
                 .options
@@ -94,11 +132,33 @@ impl acp::Client for AcpClient {
         }
 
         let tool_call_id = args.tool_call.tool_call_id.0.to_string();
-        let status = match self
-            .approvals
-            .as_ref()
-            .ok_or(ExecutorApprovalError::ServiceUnavailable)
-            .map_err(|_| acp::Error::invalid_request())?
+        let Some(approvals) = self.approvals.as_ref() else {
+            // Only guarded out-of-tree edits get here; nobody can approve them
+            warn!("Denying edit outside the working directory: no approval service");
+            let status = ApprovalStatus::Denied {
+                reason: Some(
+                    "Editing files outside the working directory requires approval, but no \
+                     approval service is available."
+                        .to_string(),
+                ),
+            };
+            self.send_event(AcpEvent::ApprovalResponse(ApprovalResponse {
+                tool_call_id,
+                status,
+            }));
+            let outcome = args
+                .options
+                .iter()
+                .find(|o| matches!(o.kind, acp::PermissionOptionKind::RejectOnce))
+                .map(|opt| {
+                    acp::RequestPermissionOutcome::Selected(acp::SelectedPermissionOutcome::new(
+                        opt.option_id.clone(),
+                    ))
+                })
+                .unwrap_or(acp::RequestPermissionOutcome::Cancelled);
+            return Ok(acp::RequestPermissionResponse::new(outcome));
+        };
+        let status = match approvals
             .request_tool_approval(
                 args.tool_call.fields.title.as_deref().unwrap_or("tool"),
                 serde_json::json!({ "tool_call": args.tool_call }),
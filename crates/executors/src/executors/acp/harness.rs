@@ -19,10 +19,15 @@ use workspace_utils::{approvals::ApprovalStatus, stream_lines::LinesStreamExt};
 
 use super::{AcpClient, SessionManager};
 use crate::{
-    approvals::ExecutorApprovalService,
+    approvals::{ExecutorApprovalService, ExternalPathGuard},
     command::{CmdOverrides, CommandParts},
     env::ExecutionEnv,
-    executors::{ExecutorError, ExecutorExitResult, SpawnedChild, acp::AcpEvent},
+    executors::{
+        ExecutorError, ExecutorExitResult, HANDSHAKE_TIMEOUT, ReadinessFuture, ReadinessSignal,
+        SpawnedChild, acp::AcpEvent, await_ready_within, expect_handshake,
+    },
+    spawn_timing::SpawnPhase,
+    stdout_dup::{StdoutPipeMode, create_stdout_pipe_writer_with_mode},
 };
 
 /// Reusable harness for ACP-based connections (Gemini, etc.)
@@ -30,6 +35,8 @@ pub struct AcpAgentHarness {
     session_namespace: String,
     model: Option<String>,
     mode: Option<String>,
+    path_guard: Option<ExternalPathGuard>,
+    auto_approve: bool,
 }
 
 impl Default for AcpAgentHarness {
@@ -46,6 +53,8 @@ impl AcpAgentHarness {
             session_namespace: "gemini_sessions".to_string(),
             model: None,
             mode: None,
+            path_guard: None,
+            auto_approve: false,
         }
     }
 
@@ -55,6 +64,8 @@ impl AcpAgentHarness {
             session_namespace: namespace.into(),
             model: None,
             mode: None,
+            path_guard: None,
+            auto_approve: false,
         }
     }
 
@@ -68,6 +79,13 @@ impl AcpAgentHarness {
         self
     }
 
+    /// Guard file edits outside `guard`'s root, see [`AcpClient::with_path_guard`].
+    pub fn with_path_guard(mut self, guard: ExternalPathGuard, auto_approve: bool) -> Self {
+        self.path_guard = Some(guard);
+        self.auto_approve = auto_approve;
+        self
+    }
+
     pub async fn spawn_with_command(
         &self,
         current_dir: &Path,
@@ -78,6 +96,8 @@ impl AcpAgentHarness {
         approvals: Option<std::sync::Arc<dyn ExecutorApprovalService>>,
     ) -> Result<SpawnedChild, ExecutorError> {
         let (program_path, args) = command_parts.into_resolved().await?;
+        env.mark_spawn_phase(SpawnPhase::CommandResolved);
+        let program = program_path.to_string_lossy().to_string();
         let mut command = Command::new(program_path);
         command
             .kill_on_drop(true)
@@ -91,13 +111,19 @@ impl AcpAgentHarness {
 
         env.clone()
             .with_profile(cmd_overrides)
-            .apply_to_command(&mut command);
+            .apply_to_command(&mut command)
+            .await;
+        cmd_overrides.apply_resource_limits(&mut command);
 
         let mut child = command.group_spawn()?;
+        env.mark_spawn_phase(SpawnPhase::ProcessSpawned);
+        env.capture_prompt(&prompt);
 
         let (exit_tx, exit_rx) = tokio::sync::oneshot::channel::<ExecutorExitResult>();
+        let (ready, readiness) = ReadinessFuture::channel();
         Self::bootstrap_acp_connection(
             &mut child,
+            program,
             current_dir.to_path_buf(),
             None,
             prompt,
@@ -106,14 +132,19 @@ impl AcpAgentHarness {
             self.model.clone(),
             self.mode.clone(),
             approvals,
+            self.path_guard
+                .clone()
+                .map(|guard| (guard, self.auto_approve)),
+            ready.with_spawn_timer(env.spawn_timer.clone()),
         )
         .await?;
 
-        Ok(SpawnedChild {
+        let spawned = SpawnedChild {
             child,
             exit_signal: Some(exit_rx),
             interrupt_sender: None,
-        })
+        };
+        await_ready_within(spawned, readiness, cmd_overrides.spawn_timeout()).await
     }
 
     #[allow(clippy::too_many_arguments)]
@@ -128,6 +159,8 @@ impl AcpAgentHarness {
         approvals: Option<std::sync::Arc<dyn ExecutorApprovalService>>,
     ) -> Result<SpawnedChild, ExecutorError> {
         let (program_path, args) = command_parts.into_resolved().await?;
+        env.mark_spawn_phase(SpawnPhase::CommandResolved);
+        let program = program_path.to_string_lossy().to_string();
         let mut command = Command::new(program_path);
         command
             .kill_on_drop(true)
@@ -141,13 +174,19 @@ impl AcpAgentHarness {
 
         env.clone()
             .with_profile(cmd_overrides)
-            .apply_to_command(&mut command);
+            .apply_to_command(&mut command)
+            .await;
+        cmd_overrides.apply_resource_limits(&mut command);
 
         let mut child = command.group_spawn()?;
+        env.mark_spawn_phase(SpawnPhase::ProcessSpawned);
+        env.capture_prompt(&prompt);
 
         let (exit_tx, exit_rx) = tokio::sync::oneshot::channel::<ExecutorExitResult>();
+        let (ready, readiness) = ReadinessFuture::channel();
         Self::bootstrap_acp_connection(
             &mut child,
+            program,
             current_dir.to_path_buf(),
             Some(session_id.to_string()),
             prompt,
@@ -156,19 +195,27 @@ impl AcpAgentHarness {
             self.model.clone(),
             self.mode.clone(),
             approvals,
+            self.path_guard
+                .clone()
+                .map(|guard| (guard, self.auto_approve)),
+            ready.with_spawn_timer(env.spawn_timer.clone()),
         )
         .await?;
 
-        Ok(SpawnedChild {
+        let spawned = SpawnedChild {
             child,
             exit_signal: Some(exit_rx),
             interrupt_sender: None,
-        })
+        };
+        await_ready_within(spawned, readiness, cmd_overrides.spawn_timeout()).await
     }
 
+    /// Wire the agent's stdio to an ACP client and start its session. `ready` is signalled
+    /// once the session exists, or failed with the reason it could not be created.
     #[allow(clippy::too_many_arguments)]
     async fn bootstrap_acp_connection(
         child: &mut AsyncGroupChild,
+        program: String,
         cwd: PathBuf,
         existing_session: Option<String>,
         prompt: String,
@@ -177,6 +224,8 @@ impl AcpAgentHarness {
         model: Option<String>,
         mode: Option<String>,
         approvals: Option<std::sync::Arc<dyn ExecutorApprovalService>>,
+        path_guard: Option<(ExternalPathGuard, bool)>,
+        mut ready: ReadinessSignal,
     ) -> Result<(), ExecutorError> {
         // Take child's stdio for ACP wiring
         let orig_stdout = child.inner().stdout.take().ok_or_else(|| {
@@ -192,8 +241,9 @@ impl AcpAgentHarness {
             ))
         })?;
 
-        // Create a fresh stdout pipe for logs
-        let writer = crate::stdout_dup::create_stdout_pipe_writer(child)?;
+        // Create a fresh stdout pipe for logs. ACP sessions log one line per event, so batch
+        // the writes rather than paying a syscall per line
+        let writer = create_stdout_pipe_writer_with_mode(child, StdoutPipeMode::batched())?;
         let shared_writer = Arc::new(tokio::sync::Mutex::new(writer));
         let (log_tx, mut log_rx) = mpsc::unbounded_channel::<String>();
 
@@ -286,13 +336,19 @@ impl AcpAgentHarness {
                             Ok(sm) => sm,
                             Err(e) => {
                                 error!("Failed to create session manager: {}", e);
+                                ready.fail(ExecutorError::Io(std::io::Error::other(format!(
+                                    "Failed to create session manager: {e}"
+                                ))));
                                 return;
                             }
                         };
                         let session_manager = std::sync::Arc::new(session_manager);
 
                         // Create ACP client with approvals support
-                        let client = AcpClient::new(event_tx.clone(), approvals.clone());
+                        let mut client = AcpClient::new(event_tx.clone(), approvals.clone());
+                        if let Some((guard, auto_approve)) = path_guard {
+                            client = client.with_path_guard(guard, auto_approve);
+                        }
                         let client_feedback_handle = client.clone();
 
                         client.record_user_prompt_event(&prompt);
@@ -310,9 +366,25 @@ impl AcpAgentHarness {
                         });
 
                         // Initialize
-                        let _ = conn
-                            .initialize(proto::InitializeRequest::new(proto::ProtocolVersion::V1))
-                            .await;
+                        let handshake = expect_handshake(
+                            &program,
+                            HANDSHAKE_TIMEOUT,
+                            conn.initialize(proto::InitializeRequest::new(
+                                proto::ProtocolVersion::V1,
+                            )),
+                            is_server_shutdown,
+                        )
+                        .await;
+                        if let Err(err) = handshake {
+                            error!("{err}");
+                            let _ = log_tx.send(AcpEvent::Error(err.to_string()).to_string());
+                            ready.fail(err);
+                            if let Some(tx) = exit_signal_tx.take() {
+                                let _ = tx.send(ExecutorExitResult::Failure);
+                            }
+                            let _ = shutdown_tx.send(true);
+                            return;
+                        }
 
                         // Handle session creation/forking
                         let (acp_session_id, display_session_id, prompt_to_send) =
@@ -340,6 +412,9 @@ impl AcpAgentHarness {
                                     }
                                     Err(e) => {
                                         error!("Failed to create session: {}", e);
+                                        ready.fail(ExecutorError::Io(std::io::Error::other(
+                                            format!("Failed to create session: {e}"),
+                                        )));
                                         return;
                                     }
                                 }
@@ -355,6 +430,9 @@ impl AcpAgentHarness {
                                     }
                                     Err(e) => {
                                         error!("Failed to create session: {}", e);
+                                        ready.fail(ExecutorError::Io(std::io::Error::other(
+                                            format!("Failed to create session: {e}"),
+                                        )));
                                         return;
                                     }
                                 }
@@ -363,6 +441,7 @@ impl AcpAgentHarness {
                         // Emit session ID
                         let _ = log_tx
                             .send(AcpEvent::SessionStart(display_session_id.clone()).to_string());
+                        ready.ready();
 
                         if let Some(model) = model.clone() {
                             match conn
@@ -450,12 +529,7 @@ impl AcpAgentHarness {
                                 }
                                 Err(e) => {
                                     tracing::debug!("error {} {e} {:?}", e.code, e.data);
-                                    if e.code
-                                        == agent_client_protocol::ErrorCode::INTERNAL_ERROR.code
-                                        && e.data
-                                            .as_ref()
-                                            .is_some_and(|d| d == "server shut down unexpectedly")
-                                    {
+                                    if is_server_shutdown(&e) {
                                         tracing::debug!("ACP server killed");
                                     } else {
                                         let _ = log_tx
@@ -509,3 +583,12 @@ impl AcpAgentHarness {
         Ok(())
     }
 }
+
+/// The error the ACP connection reports when the agent process goes away mid-request.
+fn is_server_shutdown(err: &proto::Error) -> bool {
+    err.code == proto::ErrorCode::INTERNAL_ERROR.code
+        && err
+            .data
+            .as_ref()
+            .is_some_and(|data| data == "server shut down unexpectedly")
+}
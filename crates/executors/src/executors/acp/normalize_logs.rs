@@ -17,12 +17,18 @@ use crate::{
     logs::{
         ActionType, FileChange, NormalizedEntry, NormalizedEntryError, NormalizedEntryType,
         TodoItem, ToolResult, ToolResultValueType, ToolStatus as LogToolStatus,
+        context_window::{context_window_exceeded_entry, is_context_window_exceeded},
         stderr_processor::normalize_stderr_logs,
         utils::{ConversationPatch, EntryIndexProvider},
     },
 };
 
-pub fn normalize_logs(msg_store: Arc<MsgStore>, worktree_path: &Path) {
+/// Normalize the agent's output in `msg_store` into conversation entries. The returned handle
+/// completes once stdout has been fully processed.
+pub fn normalize_logs(
+    msg_store: Arc<MsgStore>,
+    worktree_path: &Path,
+) -> tokio::task::JoinHandle<()> {
     // stderr normalization
     let entry_index = EntryIndexProvider::start_from(&msg_store);
     normalize_stderr_logs(msg_store.clone(), entry_index.clone());
@@ -50,13 +56,18 @@ pub fn normalize_logs(msg_store: Arc<MsgStore>, worktree_path: &Path) {
                     }
                     AcpEvent::Error(msg) => {
                         let idx = entry_index.next();
-                        let entry = NormalizedEntry {
-                            timestamp: None,
-                            entry_type: NormalizedEntryType::ErrorMessage {
-                                error_type: NormalizedEntryError::Other,
-                            },
-                            content: msg,
-                            metadata: None,
+                        let entry = if is_context_window_exceeded(&msg) {
+                            context_window_exceeded_entry(&msg)
+                        } else {
+                            NormalizedEntry {
+                                timestamp: None,
+                                entry_type: NormalizedEntryType::ErrorMessage {
+                                    error_type: NormalizedEntryError::Other,
+                                },
+                                content: msg,
+                                metadata: None,
+                                agent: None,
+                            }
                         };
                         msg_store.push_patch(ConversationPatch::add_normalized_entry(idx, entry));
                     }
@@ -85,6 +96,7 @@ pub fn normalize_logs(msg_store: Arc<MsgStore>, worktree_path: &Path) {
                                     entry_type: NormalizedEntryType::AssistantMessage,
                                     content: s.content.clone(),
                                     metadata: None,
+                                    agent: None,
                                 };
                                 let patch = if is_new {
                                     ConversationPatch::add_normalized_entry(s.index, entry)
@@ -113,6 +125,7 @@ pub fn normalize_logs(msg_store: Arc<MsgStore>, worktree_path: &Path) {
                                     entry_type: NormalizedEntryType::Thinking,
                                     content: s.content.clone(),
                                     metadata: None,
+                                    agent: None,
                                 };
                                 let patch = if is_new {
                                     ConversationPatch::add_normalized_entry(s.index, entry)
@@ -154,6 +167,7 @@ pub fn normalize_logs(msg_store: Arc<MsgStore>, worktree_path: &Path) {
                             },
                             content: "Plan updated".to_string(),
                             metadata: None,
+                            agent: None,
                         };
                         msg_store.push_patch(ConversationPatch::add_normalized_entry(idx, entry));
                     }
@@ -168,6 +182,7 @@ pub fn normalize_logs(msg_store: Arc<MsgStore>, worktree_path: &Path) {
                             entry_type: NormalizedEntryType::SystemMessage,
                             content: body,
                             metadata: None,
+                            agent: None,
                         };
                         msg_store.push_patch(ConversationPatch::add_normalized_entry(idx, entry));
                     }
@@ -178,6 +193,7 @@ pub fn normalize_logs(msg_store: Arc<MsgStore>, worktree_path: &Path) {
                             entry_type: NormalizedEntryType::SystemMessage,
                             content: format!("Current mode: {}", mode_id.0),
                             metadata: None,
+                            agent: None,
                         };
                         msg_store.push_patch(ConversationPatch::add_normalized_entry(idx, entry));
                     }
@@ -247,6 +263,7 @@ pub fn normalize_logs(msg_store: Arc<MsgStore>, worktree_path: &Path) {
                                     .trim()
                                     .to_string(),
                                 metadata: None,
+                                agent: None,
                             };
                             msg_store
                                 .push_patch(ConversationPatch::add_normalized_entry(idx, entry));
@@ -287,6 +304,7 @@ pub fn normalize_logs(msg_store: Arc<MsgStore>, worktree_path: &Path) {
                     tool_call_id: tool_data.id.0.to_string(),
                 })
                 .ok(),
+                agent: None,
             };
             let patch = if is_new {
                 ConversationPatch::add_normalized_entry(tool_data.index, entry)
@@ -427,6 +445,26 @@ pub fn normalize_logs(msg_store: Arc<MsgStore>, worktree_path: &Path) {
                 agent_client_protocol::ToolKind::SwitchMode => ActionType::Other {
                     description: "switch_mode".to_string(),
                 },
+                agent_client_protocol::ToolKind::Move
+                    if tc.path.is_some() && tc.move_target.is_some() =>
+                {
+                    ActionType::FileEdit {
+                        path: tc
+                            .path
+                            .clone()
+                            .unwrap_or_default()
+                            .to_string_lossy()
+                            .to_string(),
+                        changes: vec![FileChange::Rename {
+                            new_path: tc
+                                .move_target
+                                .clone()
+                                .unwrap_or_default()
+                                .to_string_lossy()
+                                .to_string(),
+                        }],
+                    }
+                }
                 agent_client_protocol::ToolKind::Other
                 | agent_client_protocol::ToolKind::Move
                 | _ => {
@@ -603,7 +641,7 @@ pub fn normalize_logs(msg_store: Arc<MsgStore>, worktree_path: &Path) {
                 }
             }
         }
-    });
+    })
 }
 
 struct PartialToolCallData {
@@ -613,6 +651,8 @@ struct PartialToolCallData {
     title: String,
     status: agent_client_protocol::ToolCallStatus,
     path: Option<PathBuf>,
+    /// Destination of a move, taken from the second reported location.
+    move_target: Option<PathBuf>,
     content: Vec<agent_client_protocol::ToolCallContent>,
     raw_input: Option<serde_json::Value>,
     raw_output: Option<serde_json::Value>,
@@ -631,12 +671,14 @@ impl PartialToolCallData {
             self.status = tc.status;
         }
         if !tc.locations.is_empty() {
-            self.path = tc.locations.first().map(|l| {
+            let relative = |l: &agent_client_protocol::ToolCallLocation| {
                 PathBuf::from(workspace_utils::path::make_path_relative(
                     &l.path.to_string_lossy(),
                     &worktree_path.to_string_lossy(),
                 ))
-            });
+            };
+            self.path = tc.locations.first().map(relative);
+            self.move_target = tc.locations.get(1).map(relative);
         }
         if !tc.content.is_empty() {
             self.content = tc.content.clone();
@@ -659,6 +701,7 @@ impl Default for PartialToolCallData {
             title: String::new(),
             status: Default::default(),
             path: None,
+            move_target: None,
             content: Vec::new(),
             raw_input: None,
             raw_output: None,
@@ -767,3 +810,112 @@ struct EditInput {
     #[serde(default)]
     new_string: Option<String>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logs::{CommandExitStatus, utils::patch::collect_normalized_entries};
+
+    const WORKTREE: &str = "/tmp/acp-worktree";
+
+    /// Feed recorded ACP event lines through `normalize_logs` and return the
+    /// final state of every normalized entry, in index order.
+    async fn normalize(lines: &[&str]) -> Vec<NormalizedEntry> {
+        let msg_store = Arc::new(MsgStore::new());
+        for line in lines {
+            msg_store.push_stdout(format!("{line}\n"));
+        }
+        msg_store.push_finished();
+
+        normalize_logs(msg_store.clone(), Path::new(WORKTREE))
+            .await
+            .unwrap();
+        collect_normalized_entries(&msg_store)
+    }
+
+    #[tokio::test]
+    async fn thoughts_and_messages_stream_into_single_entries() {
+        let entries = normalize(&[
+            r#"{"Thought":{"type":"text","text":"Looking at "}}"#,
+            r#"{"Thought":{"type":"text","text":"the tests"}}"#,
+            r#"{"Message":{"type":"text","text":"All "}}"#,
+            r#"{"Message":{"type":"text","text":"green."}}"#,
+        ])
+        .await;
+
+        assert_eq!(entries.len(), 2);
+        assert!(matches!(
+            entries[0].entry_type,
+            NormalizedEntryType::Thinking
+        ));
+        assert_eq!(entries[0].content, "Looking at the tests");
+        assert!(matches!(
+            entries[1].entry_type,
+            NormalizedEntryType::AssistantMessage
+        ));
+        assert_eq!(entries[1].content, "All green.");
+    }
+
+    #[tokio::test]
+    async fn execute_tool_call_updates_in_place_until_completed() {
+        let entries = normalize(&[
+            r#"{"ToolCall":{"toolCallId":"run_shell_command-1","title":"cargo test [current working directory /tmp/acp-worktree]","kind":"execute","status":"pending"}}"#,
+            r#"{"ToolUpdate":{"toolCallId":"run_shell_command-1","status":"completed","content":[{"type":"content","content":{"type":"text","text":"test result: ok"}}]}}"#,
+        ])
+        .await;
+
+        assert_eq!(entries.len(), 1);
+        let NormalizedEntryType::ToolUse {
+            action_type: ActionType::CommandRun { command, result },
+            status,
+            ..
+        } = &entries[0].entry_type
+        else {
+            panic!("expected command run, got {:?}", entries[0].entry_type);
+        };
+        assert_eq!(command, "cargo test");
+        assert!(matches!(status, LogToolStatus::Success));
+        let result = result.as_ref().unwrap();
+        assert!(matches!(
+            result.exit_status,
+            Some(CommandExitStatus::Success { success: true })
+        ));
+        assert_eq!(result.output.as_deref(), Some("test result: ok\n"));
+    }
+
+    #[tokio::test]
+    async fn edit_and_move_tool_calls_map_to_file_changes() {
+        let entries = normalize(&[
+            r#"{"ToolCall":{"toolCallId":"replace-1","title":"Edit lib.rs","kind":"edit","status":"completed","locations":[{"path":"/tmp/acp-worktree/src/lib.rs"}],"content":[{"type":"diff","path":"/tmp/acp-worktree/src/lib.rs","oldText":"fn a() {}\n","newText":"fn b() {}\n"}]}}"#,
+            r#"{"ToolCall":{"toolCallId":"move-2","title":"Move old.rs","kind":"move","status":"completed","locations":[{"path":"/tmp/acp-worktree/src/old.rs"},{"path":"/tmp/acp-worktree/src/new.rs"}]}}"#,
+        ])
+        .await;
+
+        assert_eq!(entries.len(), 2);
+        let NormalizedEntryType::ToolUse {
+            action_type: ActionType::FileEdit { path, changes },
+            ..
+        } = &entries[0].entry_type
+        else {
+            panic!("expected file edit, got {:?}", entries[0].entry_type);
+        };
+        assert_eq!(path, "src/lib.rs");
+        assert!(matches!(
+            changes.as_slice(),
+            [FileChange::Edit { unified_diff, .. }] if unified_diff.contains("+fn b() {}")
+        ));
+
+        let NormalizedEntryType::ToolUse {
+            action_type: ActionType::FileEdit { path, changes },
+            ..
+        } = &entries[1].entry_type
+        else {
+            panic!("expected file move, got {:?}", entries[1].entry_type);
+        };
+        assert_eq!(path, "src/old.rs");
+        assert!(matches!(
+            changes.as_slice(),
+            [FileChange::Rename { new_path }] if new_path == "src/new.rs"
+        ));
+    }
+}
@@ -35,15 +35,24 @@ use crate::{
     },
     env::ExecutionEnv,
     executors::{
-        AppendPrompt, AvailabilityInfo, ExecutorError, SpawnedChild, StandardCodingAgentExecutor,
+        AppendPrompt, AvailabilityInfo, BaseCodingAgent, CommandPreview, ExecutorError,
+        ReadinessFuture, SpawnedChild, StandardCodingAgentExecutor, await_ready_within,
         codex::client::LogWriter,
     },
     logs::{
         ActionType, FileChange, NormalizedEntry, NormalizedEntryError, NormalizedEntryType,
         TodoItem, ToolStatus,
+        context_window::{context_window_exceeded_entry, is_context_window_exceeded},
+        paths::relativize_normalized_entries,
+        plan::plan_steps,
         stderr_processor::normalize_stderr_logs,
-        utils::{EntryIndexProvider, patch::ConversationPatch},
+        utils::{
+            EntryIndexProvider,
+            patch::{ConversationPatch, tag_normalized_entries},
+        },
     },
+    mcp_config::{EphemeralMcpConfig, McpConfig},
+    spawn_timing::SpawnPhase,
     stdout_dup::create_stdout_pipe_writer,
 };
 
@@ -56,7 +65,7 @@ static CLAUDE_ROUTER_COMMAND: LazyLock<String> =
 const FALLBACK_CLAUDE_COMMAND: &str = "npx -y @anthropic-ai/claude-code@2.1.7";
 const FALLBACK_CLAUDE_ROUTER_COMMAND: &str = "npx -y @musistudio/claude-code-router@1.0.66 code";
 
-fn base_command(claude_code_router: bool) -> &'static str {
+pub fn base_command(claude_code_router: bool) -> &'static str {
     if claude_code_router {
         CLAUDE_ROUTER_COMMAND.as_str()
     } else {
@@ -97,7 +106,7 @@ pub struct ClaudeCode {
     #[serde(skip)]
     #[ts(skip)]
     #[derivative(Debug = "ignore", PartialEq = "ignore")]
-    approvals_service: Option<Arc<dyn ExecutorApprovalService>>,
+    pub(crate) approvals_service: Option<Arc<dyn ExecutorApprovalService>>,
 }
 
 impl ClaudeCode {
@@ -113,6 +122,9 @@ impl ClaudeCode {
         }
 
         let mut builder = CommandBuilder::new(base).params(["-p"]);
+        if self.claude_code_router.unwrap_or(false) {
+            builder = builder.subcommand("code");
+        }
 
         let plan = self.plan.unwrap_or(false);
         let approvals = self.approvals.unwrap_or(false);
@@ -130,8 +142,8 @@ impl ClaudeCode {
         if self.dangerously_skip_permissions.unwrap_or(false) {
             builder = builder.extend_params(["--dangerously-skip-permissions"]);
         }
-        if let Some(model) = &self.model {
-            builder = builder.extend_params(["--model", model]);
+        if let Some(model) = BaseCodingAgent::ClaudeCode.resolve_model(self.model.as_deref()) {
+            builder = builder.extend_params(["--model", model.as_str()]);
         }
         builder = builder.extend_params([
             "--verbose",
@@ -164,6 +176,12 @@ impl ClaudeCode {
         .await
     }
 
+    /// The command the npx fallback would run for a new session, resolved without spawning it.
+    /// The fallback is never used when `base_command_override` is set.
+    pub async fn fallback_command_parts(&self) -> Result<CommandParts, CommandBuildError> {
+        self.build_fallback_command_builder().await?.build_initial()
+    }
+
     fn should_fallback_to_npx(&self, err: &ExecutorError) -> bool {
         if self.cmd.base_command_override.is_some() {
             return false;
@@ -171,6 +189,16 @@ impl ClaudeCode {
         matches!(err, ExecutorError::ExecutableNotFound { .. })
     }
 
+    /// Resume `session_id` into a new session. `--fork-session` makes Claude copy the transcript
+    /// under a fresh session id, so the original session is never appended to.
+    fn follow_up_args(session_id: &str) -> Vec<String> {
+        vec![
+            "--fork-session".to_string(),
+            "--resume".to_string(),
+            session_id.to_string(),
+        ]
+    }
+
     pub fn permission_mode(&self) -> PermissionMode {
         if self.plan.unwrap_or(false) {
             PermissionMode::Plan
@@ -245,8 +273,7 @@ impl StandardCodingAgentExecutor for ClaudeCode {
             Ok(child) => Ok(child),
             Err(err) => {
                 if self.should_fallback_to_npx(&err) {
-                    let fallback_builder = self.build_fallback_command_builder().await?;
-                    let fallback_parts = fallback_builder.build_initial()?;
+                    let fallback_parts = self.fallback_command_parts().await?;
                     return self
                         .spawn_internal(current_dir, prompt, fallback_parts, env)
                         .await;
@@ -264,11 +291,7 @@ impl StandardCodingAgentExecutor for ClaudeCode {
         env: &ExecutionEnv,
     ) -> Result<SpawnedChild, ExecutorError> {
         let command_builder = self.build_command_builder().await?;
-        let follow_up_args = vec![
-            "--fork-session".to_string(),
-            "--resume".to_string(),
-            session_id.to_string(),
-        ];
+        let follow_up_args = Self::follow_up_args(session_id);
         let command_parts = command_builder.build_follow_up(&follow_up_args)?;
         match self
             .spawn_internal(current_dir, prompt, command_parts, env)
@@ -288,7 +311,18 @@ impl StandardCodingAgentExecutor for ClaudeCode {
         }
     }
 
+    async fn preview_command(&self) -> Result<CommandPreview, ExecutorError> {
+        let parts = self.build_command_builder().await?.build_initial()?;
+        let fallback = match self.cmd.base_command_override {
+            None => Some(self.fallback_command_parts().await?),
+            Some(_) => None,
+        };
+        CommandPreview::resolve(parts, fallback, &self.cmd).await
+    }
+
     fn normalize_logs(&self, msg_store: Arc<MsgStore>, current_dir: &Path) {
+        tag_normalized_entries(&msg_store, BaseCodingAgent::ClaudeCode);
+        relativize_normalized_entries(&msg_store, current_dir);
         let entry_index_provider = EntryIndexProvider::start_from(&msg_store);
 
         // Process stdout logs (Claude's JSON output)
@@ -334,8 +368,29 @@ impl ClaudeCode {
         command_parts: CommandParts,
         env: &ExecutionEnv,
     ) -> Result<SpawnedChild, ExecutorError> {
-        let (program_path, args) = command_parts.into_resolved().await?;
-        let combined_prompt = self.append_prompt.combine_prompt(prompt);
+        let (program_path, mut args) = command_parts.into_resolved().await?;
+        env.mark_spawn_phase(SpawnPhase::CommandResolved);
+        let combined_prompt = self
+            .append_prompt
+            .combine_prompt_with_mcp_meta(prompt, env)
+            .await?;
+
+        // Extra MCP servers for this run only, passed via --mcp-config so ~/.claude.json is untouched
+        let ephemeral_mcp = match &env.ephemeral_mcp_servers {
+            Some(servers) => {
+                let mcp_config = McpConfig::new(
+                    vec!["mcpServers".to_string()],
+                    serde_json::json!({ "mcpServers": {} }),
+                    serde_json::Value::Null,
+                    false,
+                );
+                let ephemeral = EphemeralMcpConfig::write(&mcp_config, servers, "mcp.json").await?;
+                args.push("--mcp-config".to_string());
+                args.push(ephemeral.config_path().to_string_lossy().to_string());
+                Some(ephemeral)
+            }
+            None => None,
+        };
 
         let mut command = Command::new(program_path);
         command
@@ -348,7 +403,9 @@ impl ClaudeCode {
 
         env.clone()
             .with_profile(&self.cmd)
-            .apply_to_command(&mut command);
+            .apply_to_command(&mut command)
+            .await;
+        self.cmd.apply_resource_limits(&mut command);
 
         // Remove ANTHROPIC_API_KEY if disable_api_key is enabled
         if self.disable_api_key.unwrap_or(false) {
@@ -357,6 +414,8 @@ impl ClaudeCode {
         }
 
         let mut child = command.group_spawn()?;
+        env.mark_spawn_phase(SpawnPhase::ProcessSpawned);
+        env.capture_prompt(&combined_prompt);
         let child_stdout = child.inner().stdout.take().ok_or_else(|| {
             ExecutorError::Io(std::io::Error::other("Claude Code missing stdout"))
         })?;
@@ -371,6 +430,8 @@ impl ClaudeCode {
 
         // Create interrupt channel for graceful shutdown
         let (interrupt_tx, interrupt_rx) = tokio::sync::oneshot::channel::<()>();
+        let (ready, readiness) = ReadinessFuture::channel();
+        let ready = ready.with_spawn_timer(env.spawn_timer.clone());
 
         // Spawn task to handle the SDK client with control protocol
         let prompt_clone = combined_prompt.clone();
@@ -379,8 +440,13 @@ impl ClaudeCode {
         tokio::spawn(async move {
             let log_writer = LogWriter::new(new_stdout);
             let client = ClaudeAgentClient::new(log_writer.clone(), approvals_clone, repo_context);
-            let protocol_peer =
-                ProtocolPeer::spawn(child_stdin, child_stdout, client.clone(), interrupt_rx);
+            let protocol_peer = ProtocolPeer::spawn(
+                child_stdin,
+                child_stdout,
+                client.clone(),
+                interrupt_rx,
+                ready,
+            );
 
             // Initialize control protocol
             if let Err(e) = protocol_peer.initialize(hooks).await {
@@ -402,13 +468,20 @@ impl ClaudeCode {
                     .log_raw(&format!("Error: Failed to send prompt - {e}"))
                     .await;
             }
+
+            // Keep the per-run MCP config on disk until the session ends
+            if let Some(ephemeral_mcp) = ephemeral_mcp {
+                protocol_peer.closed().await;
+                drop(ephemeral_mcp);
+            }
         });
 
-        Ok(SpawnedChild {
+        let spawned = SpawnedChild {
             child,
             exit_signal: None,
             interrupt_sender: Some(interrupt_tx),
-        })
+        };
+        await_ready_within(spawned, readiness, self.cmd.spawn_timeout()).await
     }
 }
 
@@ -466,7 +539,7 @@ impl ClaudeLogProcessor {
     ) {
         let current_dir_clone = current_dir.to_owned();
         tokio::spawn(async move {
-            let mut stream = msg_store.history_plus_stream();
+            let mut stream = msg_store.normalization_stream();
             let mut buffer = String::new();
             let worktree_path = current_dir_clone.to_string_lossy().to_string();
             let mut session_id_extracted = false;
@@ -531,6 +604,7 @@ impl ClaudeLogProcessor {
                                     entry_type: NormalizedEntryType::SystemMessage,
                                     content: trimmed.to_string(),
                                     metadata: None,
+                                    agent: None,
                                 };
 
                                 let patch_id = entry_index_provider.next();
@@ -553,6 +627,7 @@ impl ClaudeLogProcessor {
                     entry_type: NormalizedEntryType::SystemMessage,
                     content: buffer.trim().to_string(),
                     metadata: None,
+                    agent: None,
                 };
 
                 let patch_id = entry_index_provider.next();
@@ -593,6 +668,7 @@ impl ClaudeLogProcessor {
                     },
                     content: "Claude Code + ANTHROPIC_API_KEY detected. Usage will be billed via Anthropic pay-as-you-go instead of your Claude subscription. If this is unintended, please select the `disable_api_key` checkbox in the conding-agent-configurations settings page.".to_string(),
                     metadata: None,
+                    agent: None,
                 })
             }
             _ => None,
@@ -656,6 +732,7 @@ impl ClaudeLogProcessor {
                     metadata: Some(
                         serde_json::to_value(content_item).unwrap_or(serde_json::Value::Null),
                     ),
+                    agent: None,
                 })
             }
             ClaudeContentItem::Thinking { thinking } => Some(NormalizedEntry {
@@ -665,6 +742,7 @@ impl ClaudeLogProcessor {
                 metadata: Some(
                     serde_json::to_value(content_item).unwrap_or(serde_json::Value::Null),
                 ),
+                agent: None,
             }),
             ClaudeContentItem::ToolUse { tool_data, id } => {
                 let name = tool_data.get_name();
@@ -691,6 +769,7 @@ impl ClaudeLogProcessor {
                     },
                     content,
                     metadata: Some(metadata),
+                    agent: None,
                 })
             }
             ClaudeContentItem::ToolResult { .. } => {
@@ -778,9 +857,10 @@ impl ClaudeLogProcessor {
                     description: task_description,
                 }
             }
-            ClaudeToolData::ExitPlanMode { plan } => {
-                ActionType::PlanPresentation { plan: plan.clone() }
-            }
+            ClaudeToolData::ExitPlanMode { plan } => ActionType::PlanPresentation {
+                plan: plan.clone(),
+                steps: plan_steps(plan),
+            },
             ClaudeToolData::NotebookEdit { .. } => ActionType::Tool {
                 tool_name: "NotebookEdit".to_string(),
                 arguments: Some(serde_json::to_value(tool_data).unwrap_or(serde_json::Value::Null)),
@@ -891,6 +971,7 @@ impl ClaudeLogProcessor {
                                 serde_json::to_value(claude_json)
                                     .unwrap_or(serde_json::Value::Null),
                             ),
+                            agent: None,
                         };
                         let idx = entry_index_provider.next();
                         patches.push(ConversationPatch::add_normalized_entry(idx, entry));
@@ -904,6 +985,7 @@ impl ClaudeLogProcessor {
                                 serde_json::to_value(claude_json)
                                     .unwrap_or(serde_json::Value::Null),
                             ),
+                            agent: None,
                         };
                         let idx = entry_index_provider.next();
                         patches.push(ConversationPatch::add_normalized_entry(idx, entry));
@@ -954,6 +1036,7 @@ impl ClaudeLogProcessor {
                                 },
                                 content: content_text.clone(),
                                 metadata: Some(metadata),
+                                agent: None,
                             };
                             let is_new = entry_index.is_none();
                             let id_num = entry_index.unwrap_or_else(|| entry_index_provider.next());
@@ -1023,6 +1106,7 @@ impl ClaudeLogProcessor {
                                 metadata: Some(
                                     serde_json::to_value(item).unwrap_or(serde_json::Value::Null),
                                 ),
+                                agent: None,
                             };
                             let id = entry_index_provider.next();
                             patches.push(ConversationPatch::add_normalized_entry(id, entry));
@@ -1038,6 +1122,7 @@ impl ClaudeLogProcessor {
                                 entry_type: NormalizedEntryType::SystemMessage,
                                 content: text.clone(),
                                 metadata: None,
+                                agent: None,
                             };
                             let id = entry_index_provider.next();
                             patches.push(ConversationPatch::add_normalized_entry(id, entry));
@@ -1116,6 +1201,7 @@ impl ClaudeLogProcessor {
                                 },
                                 content: info.content.clone(),
                                 metadata: None,
+                                agent: None,
                             };
                             patches.push(ConversationPatch::replace(info.entry_index, entry));
                         } else if matches!(
@@ -1170,6 +1256,7 @@ impl ClaudeLogProcessor {
                                 },
                                 content: info.content.clone(),
                                 metadata: None,
+                                agent: None,
                             };
                             patches.push(ConversationPatch::replace(info.entry_index, entry));
                         }
@@ -1195,6 +1282,7 @@ impl ClaudeLogProcessor {
                     metadata: Some(
                         serde_json::to_value(claude_json).unwrap_or(serde_json::Value::Null),
                     ),
+                    agent: None,
                 };
                 let idx = entry_index_provider.next();
                 patches.push(ConversationPatch::add_normalized_entry(idx, entry));
@@ -1279,6 +1367,8 @@ impl ClaudeLogProcessor {
             },
             ClaudeJson::Result {
                 is_error,
+                result,
+                error,
                 model_usage,
                 ..
             } => {
@@ -1293,7 +1383,21 @@ impl ClaudeLogProcessor {
                     patches.push(self.add_token_usage_entry(entry_index_provider));
                 }
 
-                if matches!(self.strategy, HistoryStrategy::AmpResume) && is_error.unwrap_or(false)
+                let error_text = result
+                    .as_ref()
+                    .and_then(serde_json::Value::as_str)
+                    .or(error.as_deref());
+                if is_error.unwrap_or(false)
+                    && let Some(error_text) = error_text
+                    && is_context_window_exceeded(error_text)
+                {
+                    let idx = entry_index_provider.next();
+                    patches.push(ConversationPatch::add_normalized_entry(
+                        idx,
+                        context_window_exceeded_entry(error_text),
+                    ));
+                } else if matches!(self.strategy, HistoryStrategy::AmpResume)
+                    && is_error.unwrap_or(false)
                 {
                     let entry = NormalizedEntry {
                         timestamp: None,
@@ -1305,6 +1409,7 @@ impl ClaudeLogProcessor {
                         metadata: Some(
                             serde_json::to_value(claude_json).unwrap_or(serde_json::Value::Null),
                         ),
+                        agent: None,
                     };
                     let idx = entry_index_provider.next();
                     patches.push(ConversationPatch::add_normalized_entry(idx, entry));
@@ -1330,6 +1435,7 @@ impl ClaudeLogProcessor {
                             .filter(|s| !s.is_empty())
                             .unwrap_or_else(|| "User denied this tool use request".to_string()),
                         metadata: None,
+                        agent: None,
                     }),
                     ApprovalStatus::TimedOut => Some(NormalizedEntry {
                         timestamp: None,
@@ -1338,6 +1444,7 @@ impl ClaudeLogProcessor {
                         },
                         content: format!("Approval timed out for tool {tool_name}"),
                         metadata: None,
+                        agent: None,
                     }),
                 };
 
@@ -1355,6 +1462,7 @@ impl ClaudeLogProcessor {
                         serde_json::to_value(data).unwrap_or_default()
                     ),
                     metadata: None,
+                    agent: None,
                 };
                 let idx = entry_index_provider.next();
                 patches.push(ConversationPatch::add_normalized_entry(idx, entry));
@@ -1400,7 +1508,7 @@ impl ClaudeLogProcessor {
                 }
                 _ => tool_data.get_name().to_string(),
             },
-            ActionType::PlanPresentation { plan } => plan.clone(),
+            ActionType::PlanPresentation { plan, .. } => plan.clone(),
             ActionType::TodoManagement { .. } => "TODO list updated".to_string(),
             ActionType::Other { description: _ } => match tool_data {
                 ClaudeToolData::LS { path } => {
@@ -1473,6 +1581,7 @@ impl ClaudeLogProcessor {
                 self.context_tokens_used, self.main_model_context_window
             ),
             metadata: None,
+            agent: None,
         };
         let idx = entry_index_provider.next();
         ConversationPatch::add_normalized_entry(idx, entry)
@@ -1493,6 +1602,7 @@ fn extract_model_name(
             entry_type: NormalizedEntryType::SystemMessage,
             content: format!("System initialized with model: {model}"),
             metadata: None,
+            agent: None,
         };
         let id = entry_index_provider.next();
         Some(ConversationPatch::add_normalized_entry(id, entry))
@@ -2039,7 +2149,10 @@ impl ClaudeToolData {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::logs::utils::{EntryIndexProvider, patch::extract_normalized_entry_from_patch};
+    use crate::{
+        executors::{BaseAgentCapability, CodingAgent},
+        logs::utils::{EntryIndexProvider, patch::extract_normalized_entry_from_patch},
+    };
 
     fn patches_to_entries(patches: &[json_patch::Patch]) -> Vec<NormalizedEntry> {
         patches
@@ -2114,6 +2227,22 @@ mod tests {
         assert_eq!(entries.len(), 0); // Should be ignored like in old implementation
     }
 
+    #[test]
+    fn test_prompt_too_long_result_suggests_compaction() {
+        let result_json = r#"{"type":"result","subtype":"success","is_error":true,"duration_ms":812,"result":"Prompt is too long"}"#;
+        let parsed: ClaudeJson = serde_json::from_str(result_json).unwrap();
+
+        let entries = normalize(&parsed, "");
+        assert_eq!(entries.len(), 1);
+        assert!(matches!(
+            entries[0].entry_type,
+            NormalizedEntryType::ErrorMessage {
+                error_type: NormalizedEntryError::ContextWindowExceeded
+            }
+        ));
+        assert!(entries[0].content.starts_with("Prompt is too long\n\n"));
+    }
+
     #[test]
     fn test_thinking_content() {
         let thinking_json = r#"{"type":"assistant","message":{"role":"assistant","content":[{"type":"thinking","thinking":"Let me think about this..."}]}}"#;
@@ -2229,6 +2358,7 @@ mod tests {
                 base_command_override: None,
                 additional_params: None,
                 env: None,
+                ..Default::default()
             },
             approvals_service: None,
             disable_api_key: None,
@@ -2279,6 +2409,42 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_exit_plan_mode_exposes_plan_steps() {
+        let assistant_with_plan = r###"{
+            "type":"assistant",
+            "message":{
+                "role":"assistant",
+                "content":[
+                    {"type":"tool_use","id":"t1","name":"ExitPlanMode","input":{"plan":"## Plan\n1. Add the `retries` option\n2. Thread it through the client\n3. Add tests"}}
+                ]
+            }
+        }"###;
+        let parsed: ClaudeJson = serde_json::from_str(assistant_with_plan).unwrap();
+        let entries = normalize(&parsed, "/tmp/work");
+        assert_eq!(entries.len(), 1);
+        let NormalizedEntryType::ToolUse {
+            action_type: ActionType::PlanPresentation { plan, steps },
+            ..
+        } = &entries[0].entry_type
+        else {
+            panic!("Expected PlanPresentation, got {:?}", entries[0].entry_type);
+        };
+        assert!(plan.starts_with("## Plan"));
+        let steps: Vec<_> = steps
+            .iter()
+            .map(|step| (step.content.as_str(), step.status.as_str()))
+            .collect();
+        assert_eq!(
+            steps,
+            [
+                ("Add the `retries` option", "pending"),
+                ("Thread it through the client", "pending"),
+                ("Add tests", "pending"),
+            ]
+        );
+    }
+
     #[test]
     fn test_amp_tool_aliases_create_file_and_edit_file() {
         // Amp "create_file" should deserialize into Write with alias field "path"
@@ -2553,4 +2719,47 @@ mod tests {
 
         // ToolResult entry is ignored - no third entry
     }
+
+    #[tokio::test]
+    async fn test_follow_up_resumes_into_forked_session() {
+        // Point the base command at a binary that always exists so resolution succeeds
+        let claude: ClaudeCode =
+            serde_json::from_value(serde_json::json!({ "base_command_override": "sh" })).unwrap();
+        let builder = claude.build_command_builder().await.unwrap();
+
+        let (_, initial_args) = builder
+            .build_initial()
+            .unwrap()
+            .into_resolved()
+            .await
+            .unwrap();
+        assert!(!initial_args.iter().any(|a| a == "--resume"));
+
+        let (_, follow_up_args) = builder
+            .build_follow_up(&ClaudeCode::follow_up_args("session-123"))
+            .unwrap()
+            .into_resolved()
+            .await
+            .unwrap();
+        assert!(
+            follow_up_args
+                .windows(2)
+                .any(|w| w[0] == "--resume" && w[1] == "session-123")
+        );
+        assert!(follow_up_args.iter().any(|a| a == "--fork-session"));
+    }
+
+    #[test]
+    fn test_capabilities_include_image_input() {
+        let claude: ClaudeCode = serde_json::from_value(serde_json::json!({})).unwrap();
+        let agent = CodingAgent::ClaudeCode(claude);
+        assert_eq!(
+            agent.capabilities(),
+            [
+                BaseAgentCapability::SessionFork,
+                BaseAgentCapability::ImageInput,
+            ]
+        );
+        assert!(agent.supports_image_input());
+    }
 }
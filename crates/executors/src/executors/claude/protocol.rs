@@ -2,14 +2,16 @@ use std::sync::Arc;
 
 use futures::FutureExt;
 use tokio::{
-    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    io::{AsyncWriteExt, BufReader},
     process::{ChildStdin, ChildStdout},
     sync::{Mutex, oneshot},
 };
+use tokio_util::sync::CancellationToken;
+use workspace_utils::stream_lines::read_line_lossy;
 
 use super::types::{CLIMessage, ControlRequestType, ControlResponseMessage, ControlResponseType};
 use crate::executors::{
-    ExecutorError,
+    ExecutorError, ReadinessSignal,
     claude::{
         client::ClaudeAgentClient,
         types::{Message, PermissionMode, SDKControlRequest, SDKControlRequestType},
@@ -20,6 +22,7 @@ use crate::executors::{
 #[derive(Clone)]
 pub struct ProtocolPeer {
     stdin: Arc<Mutex<ChildStdin>>,
+    closed: CancellationToken,
 }
 
 impl ProtocolPeer {
@@ -28,43 +31,57 @@ impl ProtocolPeer {
         stdout: ChildStdout,
         client: Arc<ClaudeAgentClient>,
         interrupt_rx: oneshot::Receiver<()>,
+        ready: ReadinessSignal,
     ) -> Self {
         let peer = Self {
             stdin: Arc::new(Mutex::new(stdin)),
+            closed: CancellationToken::new(),
         };
 
         let reader_peer = peer.clone();
         tokio::spawn(async move {
-            if let Err(e) = reader_peer.read_loop(stdout, client, interrupt_rx).await {
+            if let Err(e) = reader_peer
+                .read_loop(stdout, client, interrupt_rx, ready)
+                .await
+            {
                 tracing::error!("Protocol reader loop error: {}", e);
             }
+            reader_peer.closed.cancel();
         });
 
         peer
     }
 
+    /// Resolves once the reader loop has stopped (EOF, result or read error).
+    pub async fn closed(&self) {
+        self.closed.cancelled().await
+    }
+
+    /// Handle the CLI's messages until it is done. `ready` is signalled by the first message,
+    /// or failed if the CLI stops without sending any.
     async fn read_loop(
         &self,
         stdout: ChildStdout,
         client: Arc<ClaudeAgentClient>,
         interrupt_rx: oneshot::Receiver<()>,
+        mut ready: ReadinessSignal,
     ) -> Result<(), ExecutorError> {
         let mut reader = BufReader::new(stdout);
-        let mut buffer = String::new();
+        let mut buffer = Vec::new();
         // Fuse the receiver so it returns Pending forever after completing
         let mut interrupt_rx = interrupt_rx.fuse();
 
         loop {
-            buffer.clear();
             tokio::select! {
-                line_result = reader.read_line(&mut buffer) => {
+                line_result = read_line_lossy(&mut reader, &mut buffer) => {
                     match line_result {
-                        Ok(0) => break, // EOF
-                        Ok(_) => {
-                            let line = buffer.trim();
+                        Ok(None) => break, // EOF
+                        Ok(Some(raw)) => {
+                            let line = raw.trim();
                             if line.is_empty() {
                                 continue;
                             }
+                            ready.ready();
                             // Parse message using typed enum
                             match serde_json::from_str::<CLIMessage>(line) {
                                 Ok(CLIMessage::ControlRequest {
@@ -97,6 +114,9 @@ impl ProtocolPeer {
                 }
             }
         }
+        ready.fail(ExecutorError::Io(std::io::Error::other(
+            "Claude Code exited before sending any output",
+        )));
         Ok(())
     }
 
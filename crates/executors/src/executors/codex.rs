@@ -7,7 +7,8 @@ use std::{
     collections::HashMap,
     env,
     path::{Path, PathBuf},
-    sync::{Arc, LazyLock},
+    sync::{Arc, LazyLock, Mutex},
+    time::{Duration, Instant, SystemTime},
 };
 
 static CODEX_COMMAND: LazyLock<String> =
@@ -15,12 +16,34 @@ static CODEX_COMMAND: LazyLock<String> =
 
 const FALLBACK_CODEX_COMMAND: &str = "npx -y @openai/codex@0.77.0";
 
+static FALLBACK_CODEX_COMMAND_OVERRIDE: LazyLock<String> =
+    LazyLock::new(|| env_command_or_default("VK_CODEX_FALLBACK", FALLBACK_CODEX_COMMAND));
+
+const DEFAULT_APP_SERVER_SUBCOMMAND: &str = "app-server";
+
+/// Wait before the first respawn of an app server that failed to initialize; doubled for each
+/// retry after that.
+const CONNECT_RETRY_BASE_DELAY: Duration = Duration::from_millis(250);
+
+/// How long [`Codex::check_auth`] waits for a short-lived app server to start and report its
+/// auth status, when no `spawn_timeout_secs` is set.
+const AUTH_STATUS_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long a status from [`Codex::check_auth`] is reused. A login or logout that rewrites
+/// `auth.json` invalidates it sooner.
+const AUTH_STATUS_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// Statuses from [`Codex::check_auth`], keyed by the serialized config that was asked, so
+/// polling auth doesn't start an app server every time.
+static AUTH_STATUS_CACHE: LazyLock<Mutex<HashMap<String, CachedAuthStatus>>> =
+    LazyLock::new(Default::default);
+
 pub fn base_command() -> &'static str {
     CODEX_COMMAND.as_str()
 }
 
 pub fn fallback_command() -> &'static str {
-    FALLBACK_CODEX_COMMAND
+    FALLBACK_CODEX_COMMAND_OVERRIDE.as_str()
 }
 
 /// Returns the Codex home directory.
@@ -41,34 +64,45 @@ use codex_app_server_protocol::{NewConversationParams, ReviewTarget};
 use codex_protocol::{
     config_types::SandboxMode as CodexSandboxMode, protocol::AskForApproval as CodexAskForApproval,
 };
-use command_group::AsyncCommandGroup;
+use command_group::{AsyncCommandGroup, AsyncGroupChild};
 use derivative::Derivative;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use strum_macros::AsRefStr;
-use tokio::process::Command;
+use tokio::{
+    io::AsyncWrite,
+    process::{ChildStdin, ChildStdout, Command},
+};
 use ts_rs::TS;
 use workspace_utils::msg_store::MsgStore;
 
 use self::{
-    client::{AppServerClient, LogWriter},
+    client::{AppServerClient, CommandRules, LogWriter},
     jsonrpc::JsonRpcPeer,
     normalize_logs::normalize_logs,
-    session::SessionHandler,
 };
 use crate::{
+    actions::{
+        ExecutorAction, ExecutorActionType,
+        review::ReviewScope,
+        script::{ScriptContext, ScriptRequest, ScriptRequestLanguage},
+    },
     approvals::ExecutorApprovalService,
     command::{
         CmdOverrides, CommandBuildError, CommandBuilder, CommandParts, apply_overrides,
         env_command_or_default,
     },
-    env::ExecutionEnv,
+    env::{ExecutionEnv, RepoContext},
     executors::{
-        AppendPrompt, AvailabilityInfo, ExecutorError, ExecutorExitResult, SpawnedChild,
-        StandardCodingAgentExecutor,
+        AppendPrompt, AuthStatus, AvailabilityInfo, BaseCodingAgent, CommandPreview, ExecutorError,
+        ExecutorExitResult, HANDSHAKE_TIMEOUT, ReadinessFuture, ReadinessSignal, SandboxDescriptor,
+        SandboxLevel, SpawnedChild, StandardCodingAgentExecutor, await_ready_for,
+        build_uncommitted_review_prompt,
         codex::{jsonrpc::ExitSignalSender, normalize_logs::Error},
     },
+    logs::{paths::relativize_normalized_entries, utils::patch::tag_normalized_entries},
+    spawn_timing::SpawnPhase,
     stdout_dup::create_stdout_pipe_writer,
 };
 
@@ -133,6 +167,65 @@ pub enum ReasoningSummaryFormat {
     Experimental,
 }
 
+/// Known third-party model providers. Selecting one registers the matching
+/// `model_providers` entry with Codex, so only the API key env var has to be set.
+///
+/// - `OpenRouter`: authenticated with `OPENROUTER_API_KEY`.
+/// - `AzureOpenAi`: authenticated with `AZURE_OPENAI_API_KEY`, with the resource
+///   name read from `AZURE_OPENAI_RESOURCE`.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, TS, JsonSchema)]
+pub enum ProviderPreset {
+    #[serde(rename = "openrouter")]
+    OpenRouter,
+    #[serde(rename = "azure-openai")]
+    AzureOpenAi,
+}
+
+impl ProviderPreset {
+    /// Provider id the preset is registered under in `model_providers`
+    pub fn provider_id(self) -> &'static str {
+        match self {
+            ProviderPreset::OpenRouter => "openrouter",
+            ProviderPreset::AzureOpenAi => "azure",
+        }
+    }
+
+    /// Env var holding the provider's API key
+    pub fn api_key_env(self) -> &'static str {
+        match self {
+            ProviderPreset::OpenRouter => "OPENROUTER_API_KEY",
+            ProviderPreset::AzureOpenAi => "AZURE_OPENAI_API_KEY",
+        }
+    }
+
+    fn provider_config(
+        self,
+        lookup_env: impl Fn(&str) -> Option<String>,
+    ) -> Result<Value, ExecutorError> {
+        Ok(match self {
+            ProviderPreset::OpenRouter => serde_json::json!({
+                "name": "OpenRouter",
+                "base_url": "https://openrouter.ai/api/v1",
+                "env_key": "OPENROUTER_API_KEY",
+                "wire_api": "chat",
+            }),
+            ProviderPreset::AzureOpenAi => {
+                let resource = lookup_env("AZURE_OPENAI_RESOURCE").ok_or_else(|| {
+                    ExecutorError::MissingEnvVar("AZURE_OPENAI_RESOURCE".to_string())
+                })?;
+                serde_json::json!({
+                    "name": "Azure OpenAI",
+                    "base_url": format!("https://{resource}.openai.azure.com/openai"),
+                    "env_http_headers": { "api-key": "AZURE_OPENAI_API_KEY" },
+                    "query_params": { "api-version": "2025-04-01-preview" },
+                    "wire_api": "responses",
+                })
+            }
+        })
+    }
+}
+
+#[derive(Clone)]
 enum CodexSessionAction {
     Chat { prompt: String },
     Review { target: ReviewTarget },
@@ -147,8 +240,28 @@ pub struct Codex {
     pub sandbox: Option<SandboxMode>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub ask_for_approval: Option<AskForApproval>,
+    /// Shortcut for Codex's `--full-auto`: the `workspace-write` sandbox with `never` approval.
+    /// An explicit `sandbox` or `ask_for_approval` takes precedence over the preset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub full_auto: Option<bool>,
+    /// Commands approved without asking, e.g. `git status` or `cargo test *`. An entry covers
+    /// exactly the command it names; a trailing `*` also covers any further arguments. Everything
+    /// else goes through approvals
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pre_approved_commands: Option<Vec<String>>,
+    /// Commands always denied, as a safety floor for unattended runs. `*` matches anything;
+    /// otherwise an entry also covers the command with extra arguments. Codex can only be
+    /// stopped from running a command it asks about, so without `ask_for_approval` it is made
+    /// to ask about every command, and only `unless-trusted` can be combined with this: any
+    /// other policy (or `full_auto`) is rejected
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub forbidden_commands: Option<Vec<String>>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub oss: Option<bool>,
+    /// Let Codex run outside a git repository, e.g. in a scratch directory. Passed to Codex as the
+    /// `skip_git_repo_check` config key
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub skip_git_repo_check: Option<bool>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub model: Option<String>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
@@ -163,19 +276,48 @@ pub struct Codex {
     pub base_instructions: Option<String>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub include_apply_patch_tool: Option<bool>,
+    /// Enable or disable Codex's web search tool
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub web_search: Option<bool>,
+    /// Codex `model_providers` id to use. When unset (and no preset is chosen), Codex's
+    /// built-in OpenAI provider is used, which honors `OPENAI_BASE_URL` and `OPENAI_API_KEY`
+    /// from the environment; a provider set here takes precedence over those variables.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub model_provider: Option<String>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider_preset: Option<ProviderPreset>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub compact_prompt: Option<String>,
+    /// Once the conversation reaches this many tokens, Codex compacts it (with
+    /// `compact_prompt` when set) and carries on. Unset leaves automatic compaction off
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auto_compact_token_limit: Option<u32>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub developer_instructions: Option<String>,
+    /// Extra directories Codex may write to, relative to the workspace unless absolute
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub additional_dirs: Option<Vec<PathBuf>>,
+    /// Subcommand that starts the Codex app server, for wrappers or releases that rename it.
+    /// Defaults to `app-server`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub app_server_subcommand: Option<String>,
+    /// How many times to respawn an app server that fails to initialize, e.g. one that exits
+    /// before answering the handshake, waiting twice as long before each attempt. While retries
+    /// remain, spawning waits for the app server to initialize. Defaults to 3; set 0 to return as
+    /// soon as the app server is launched
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub connect_retries: Option<u8>,
+    /// Directory that rollout files forked for follow-ups are written to, instead of the
+    /// `sessions` directory in the Codex home. Relative to the Codex home unless absolute
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rollout_dir: Option<PathBuf>,
     #[serde(flatten)]
     pub cmd: CmdOverrides,
 
     #[serde(skip)]
     #[ts(skip)]
     #[derivative(Debug = "ignore", PartialEq = "ignore")]
-    approvals: Option<Arc<dyn ExecutorApprovalService>>,
+    pub(crate) approvals: Option<Arc<dyn ExecutorApprovalService>>,
 }
 
 #[async_trait]
@@ -190,22 +332,22 @@ impl StandardCodingAgentExecutor for Codex {
         prompt: &str,
         env: &ExecutionEnv,
     ) -> Result<SpawnedChild, ExecutorError> {
-        let combined_prompt = self.append_prompt.combine_prompt(prompt);
+        let combined_prompt = self
+            .append_prompt
+            .combine_prompt_with_mcp_meta(prompt, env)
+            .await?;
         let action = CodexSessionAction::Chat {
-            prompt: combined_prompt.clone(),
+            prompt: combined_prompt,
         };
         let command_parts = self.build_command_builder()?.build_initial()?;
         match self
-            .spawn_inner(current_dir, command_parts, action, None, env)
+            .spawn_inner(current_dir, command_parts, action.clone(), None, env)
             .await
         {
             Ok(child) => Ok(child),
             Err(err) => {
                 if self.should_fallback_to_npx(&err) {
-                    let fallback_parts = self.build_fallback_command_builder()?.build_initial()?;
-                    let action = CodexSessionAction::Chat {
-                        prompt: combined_prompt,
-                    };
+                    let fallback_parts = self.fallback_command_parts()?;
                     return self
                         .spawn_inner(current_dir, fallback_parts, action, None, env)
                         .await;
@@ -222,7 +364,10 @@ impl StandardCodingAgentExecutor for Codex {
         session_id: &str,
         env: &ExecutionEnv,
     ) -> Result<SpawnedChild, ExecutorError> {
-        let combined_prompt = self.append_prompt.combine_prompt(prompt);
+        let combined_prompt = self
+            .append_prompt
+            .combine_prompt_with_mcp_meta(prompt, env)
+            .await?;
         let action = CodexSessionAction::Chat {
             prompt: combined_prompt.clone(),
         };
@@ -249,7 +394,18 @@ impl StandardCodingAgentExecutor for Codex {
         }
     }
 
+    async fn preview_command(&self) -> Result<CommandPreview, ExecutorError> {
+        let parts = self.build_command_builder()?.build_initial()?;
+        let fallback = match self.cmd.base_command_override {
+            None => Some(self.fallback_command_parts()?),
+            Some(_) => None,
+        };
+        CommandPreview::resolve(parts, fallback, &self.cmd).await
+    }
+
     fn normalize_logs(&self, msg_store: Arc<MsgStore>, worktree_path: &Path) {
+        tag_normalized_entries(&msg_store, BaseCodingAgent::Codex);
+        relativize_normalized_entries(&msg_store, worktree_path);
         normalize_logs(msg_store, worktree_path);
     }
 
@@ -257,32 +413,89 @@ impl StandardCodingAgentExecutor for Codex {
         codex_home().map(|home| home.join("config.toml"))
     }
 
-    fn get_availability_info(&self) -> AvailabilityInfo {
-        if let Some(timestamp) = codex_home()
-            .and_then(|home| std::fs::metadata(home.join("auth.json")).ok())
-            .and_then(|m| m.modified().ok())
-            .and_then(|modified| modified.duration_since(std::time::UNIX_EPOCH).ok())
-            .map(|d| d.as_secs() as i64)
-        {
-            return AvailabilityInfo::LoginDetected {
-                last_auth_timestamp: timestamp,
-            };
-        }
+    fn default_sandbox(&self) -> Option<SandboxDescriptor> {
+        let level = match self.sandbox_mode().as_ref() {
+            // Auto matches the WorkspaceWrite preset in codex
+            None | Some(SandboxMode::Auto) | Some(SandboxMode::WorkspaceWrite) => {
+                SandboxLevel::WorkspaceWrite
+            }
+            Some(SandboxMode::ReadOnly) => SandboxLevel::ReadOnly,
+            Some(SandboxMode::DangerFullAccess) => SandboxLevel::FullAccess,
+        };
+        Some(SandboxDescriptor {
+            level,
+            requires_approval: !self.auto_approve()
+                && !matches!(self.approval_mode(), Some(AskForApproval::Never)),
+        })
+    }
+
+    async fn get_setup_helper_action(&self) -> Result<ExecutorAction, ExecutorError> {
+        let build_login = |base: &str| {
+            apply_overrides(
+                CommandBuilder::new(base).extend_params(["login"]),
+                &self.cmd,
+            )?
+            .build_initial()
+        };
+
+        let (program_path, args) = match build_login(base_command())?.into_resolved().await {
+            Ok(resolved) => resolved,
+            Err(ExecutorError::ExecutableNotFound { program })
+                if self.cmd.base_command_override.is_none() =>
+            {
+                tracing::info!(%program, "Codex executable not found. Falling back to npx.");
+                build_login(fallback_command())?.into_resolved().await?
+            }
+            Err(err) => return Err(err),
+        };
 
-        let mcp_config_found = self
-            .default_mcp_config_path()
-            .map(|p| p.exists())
-            .unwrap_or(false);
+        let login_request = ScriptRequest {
+            script: format!("{} {}", program_path.to_string_lossy(), args.join(" ")),
+            language: ScriptRequestLanguage::Bash,
+            context: ScriptContext::ToolInstallScript,
+            working_dir: None,
+        };
+        Ok(ExecutorAction::new(
+            ExecutorActionType::ScriptRequest(login_request),
+            None,
+        ))
+    }
 
-        let installation_indicator_found = codex_home()
-            .map(|home| home.join("version.json").exists())
-            .unwrap_or(false);
+    fn get_availability_info(&self) -> AvailabilityInfo {
+        codex_home()
+            .map(|home| availability_in_codex_home(&home))
+            .unwrap_or(AvailabilityInfo::NotFound)
+            .or_api_key_login(self.uses_api_key())
+    }
 
-        if mcp_config_found || installation_indicator_found {
-            AvailabilityInfo::InstallationFound
-        } else {
-            AvailabilityInfo::NotFound
+    /// Asks the installed Codex, falling back to `auth.json` when it can't be started or
+    /// doesn't answer (e.g. offline or not installed). The answer is cached, see
+    /// [`AUTH_STATUS_CACHE`].
+    async fn check_auth(&self) -> Result<AuthStatus, ExecutorError> {
+        let key = serde_json::to_string(self)?;
+        let auth_file = codex_home().and_then(|home| auth_file_modified(&home));
+        if let Some(cached) = AUTH_STATUS_CACHE.lock().unwrap().get(&key)
+            && cached.is_fresh(auth_file)
+        {
+            return Ok(cached.status.clone());
         }
+
+        let status = match self.query_auth_status().await {
+            Ok(status) => status,
+            Err(err) => {
+                tracing::debug!("Codex auth status unavailable, using auth.json: {err}");
+                AuthStatus::from(&self.get_availability_info())
+            }
+        };
+        AUTH_STATUS_CACHE.lock().unwrap().insert(
+            key,
+            CachedAuthStatus {
+                status: status.clone(),
+                auth_file,
+                checked_at: Instant::now(),
+            },
+        );
+        Ok(status)
     }
 
     async fn spawn_review(
@@ -290,13 +503,11 @@ impl StandardCodingAgentExecutor for Codex {
         current_dir: &Path,
         prompt: &str,
         session_id: Option<&str>,
+        scope: ReviewScope,
         env: &ExecutionEnv,
     ) -> Result<SpawnedChild, ExecutorError> {
-        let instructions = prompt.to_string();
         let action = CodexSessionAction::Review {
-            target: ReviewTarget::Custom {
-                instructions: instructions.clone(),
-            },
+            target: review_target(prompt, scope),
         };
         let command_parts = self.build_command_builder()?.build_initial()?;
         match self
@@ -306,9 +517,9 @@ impl StandardCodingAgentExecutor for Codex {
             Ok(child) => Ok(child),
             Err(err) => {
                 if self.should_fallback_to_npx(&err) {
-                    let fallback_parts = self.build_fallback_command_builder()?.build_initial()?;
+                    let fallback_parts = self.fallback_command_parts()?;
                     let action = CodexSessionAction::Review {
-                        target: ReviewTarget::Custom { instructions },
+                        target: review_target(prompt, scope),
                     };
                     return self
                         .spawn_inner(current_dir, fallback_parts, action, session_id, env)
@@ -320,13 +531,142 @@ impl StandardCodingAgentExecutor for Codex {
     }
 }
 
+/// A plain uncommitted-changes review uses Codex's built-in working tree target, which reads the
+/// diff itself. Once the prompt carries extra instructions it is reviewed against the prompt, so
+/// they are not dropped.
+fn review_target(prompt: &str, scope: ReviewScope) -> ReviewTarget {
+    match scope {
+        ReviewScope::UncommittedChanges if prompt == build_uncommitted_review_prompt(None) => {
+            ReviewTarget::UncommittedChanges
+        }
+        ReviewScope::Commits | ReviewScope::UncommittedChanges => ReviewTarget::Custom {
+            instructions: prompt.to_string(),
+        },
+    }
+}
+
+/// Report the prompt `action` sends to the agent, if it has one.
+fn capture_action_prompt(action: &CodexSessionAction, env: &ExecutionEnv) {
+    match action {
+        CodexSessionAction::Chat { prompt } => env.capture_prompt(prompt),
+        CodexSessionAction::Review {
+            target: ReviewTarget::Custom { instructions },
+        } => env.capture_prompt(instructions),
+        CodexSessionAction::Review { .. } => {}
+    }
+}
+
+/// Logged in with the reported method, or not requiring OpenAI auth at all (e.g. a local
+/// model provider), counts as authenticated.
+fn auth_status_from_response(
+    method: Option<&impl Serialize>,
+    requires_openai_auth: Option<bool>,
+) -> AuthStatus {
+    match method {
+        Some(method) => AuthStatus::Authenticated {
+            method: serde_json::to_value(method)
+                .ok()
+                .and_then(|value| value.as_str().map(str::to_string)),
+            expires_at: None,
+        },
+        None if requires_openai_auth == Some(false) => AuthStatus::Authenticated {
+            method: None,
+            expires_at: None,
+        },
+        None => AuthStatus::NotAuthenticated,
+    }
+}
+
+struct CachedAuthStatus {
+    status: AuthStatus,
+    /// When `auth.json` was last modified at the time of the check
+    auth_file: Option<SystemTime>,
+    checked_at: Instant,
+}
+
+impl CachedAuthStatus {
+    fn is_fresh(&self, auth_file: Option<SystemTime>) -> bool {
+        self.auth_file == auth_file && self.checked_at.elapsed() < AUTH_STATUS_TTL
+    }
+}
+
+fn auth_file_modified(home: &Path) -> Option<SystemTime> {
+    std::fs::metadata(home.join("auth.json"))
+        .and_then(|m| m.modified())
+        .ok()
+}
+
+/// Availability from the files in a Codex home: `auth.json` means logged in, while a config or
+/// version file without it means Codex is installed but needs a login.
+fn availability_in_codex_home(home: &Path) -> AvailabilityInfo {
+    if let Some(timestamp) = auth_file_modified(home)
+        .and_then(|modified| modified.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|d| d.as_secs() as i64)
+    {
+        return AvailabilityInfo::LoginDetected {
+            last_auth_timestamp: timestamp,
+        };
+    }
+
+    if home.join("config.toml").exists() || home.join("version.json").exists() {
+        AvailabilityInfo::InstalledNeedsLogin
+    } else {
+        AvailabilityInfo::NotFound
+    }
+}
+
+/// The app server's stdout and stdin, plus the writer for the pipe that replaces its stdout in
+/// the log store.
+type AppServerPipes = (ChildStdout, ChildStdin, Box<dyn AsyncWrite + Send + Unpin>);
+
+/// Default for [`Codex::connect_retries`].
+const DEFAULT_CONNECT_RETRIES: u8 = 3;
+
+/// Whether a failure reported while the app server was initializing is worth a fresh attempt:
+/// the process failing to start, its pipes failing, or no handshake, e.g. an app server that
+/// exited before answering. Anything else, such as missing auth or a rejected config, would only
+/// fail the same way again.
+fn is_connect_error(err: &ExecutorError) -> bool {
+    matches!(
+        err,
+        ExecutorError::SpawnError(_)
+            | ExecutorError::Io(_)
+            | ExecutorError::UnexpectedExecutable { .. }
+    )
+}
+
+/// Take over the app server's stdio. If any step fails the child is killed and reaped, so a
+/// spawn error never leaves an orphaned app server behind.
+async fn take_app_server_pipes(
+    child: &mut AsyncGroupChild,
+) -> Result<AppServerPipes, ExecutorError> {
+    let pipes = app_server_pipes(child);
+    if pipes.is_err()
+        && let Err(err) = child.kill().await
+    {
+        tracing::warn!("Failed to kill Codex app server after setup error: {err}");
+    }
+    pipes
+}
+
+fn app_server_pipes(child: &mut AsyncGroupChild) -> Result<AppServerPipes, ExecutorError> {
+    let child_stdout = child.inner().stdout.take().ok_or_else(|| {
+        ExecutorError::Io(std::io::Error::other("Codex app server missing stdout"))
+    })?;
+    let child_stdin = child.inner().stdin.take().ok_or_else(|| {
+        ExecutorError::Io(std::io::Error::other("Codex app server missing stdin"))
+    })?;
+    let new_stdout = create_stdout_pipe_writer(child)?;
+    Ok((child_stdout, child_stdin, Box::new(new_stdout)))
+}
+
 impl Codex {
     fn build_command_builder_with_base(
         &self,
         base: &str,
     ) -> Result<CommandBuilder, CommandBuildError> {
         let mut builder = CommandBuilder::new(base);
-        builder = builder.extend_params(["app-server"]);
+        builder = builder.extend_params([self.app_server_subcommand()]);
         if self.oss.unwrap_or(false) {
             builder = builder.extend_params(["--oss"]);
         }
@@ -334,6 +674,13 @@ impl Codex {
         apply_overrides(builder, &self.cmd)
     }
 
+    fn app_server_subcommand(&self) -> &str {
+        self.app_server_subcommand
+            .as_deref()
+            .filter(|subcommand| !subcommand.trim().is_empty())
+            .unwrap_or(DEFAULT_APP_SERVER_SUBCOMMAND)
+    }
+
     fn build_command_builder(&self) -> Result<CommandBuilder, CommandBuildError> {
         tracing::info!(
             "build_command_builder using system codex command {}",
@@ -350,6 +697,12 @@ impl Codex {
         self.build_command_builder_with_base(fallback_command())
     }
 
+    /// The command the npx fallback would run for a new session, resolved without spawning it.
+    /// The fallback is never used when `base_command_override` is set.
+    pub fn fallback_command_parts(&self) -> Result<CommandParts, CommandBuildError> {
+        self.build_fallback_command_builder()?.build_initial()
+    }
+
     fn should_fallback_to_npx(&self, err: &ExecutorError) -> bool {
         if self.cmd.base_command_override.is_some() {
             return false;
@@ -357,16 +710,99 @@ impl Codex {
         matches!(err, ExecutorError::ExecutableNotFound { .. })
     }
 
+    /// Ask a short-lived app server for Codex's auth status. Only the installed CLI is asked:
+    /// the npx fallback would download Codex just to answer. Starting the app server and
+    /// answering share the spawn timeout, and the app server is killed when it runs out.
+    async fn query_auth_status(&self) -> Result<AuthStatus, ExecutorError> {
+        let limit = self.cmd.spawn_timeout().unwrap_or(AUTH_STATUS_TIMEOUT);
+        tokio::time::timeout(limit, self.ask_app_server_auth_status())
+            .await
+            .map_err(|_| {
+                ExecutorError::timeout("waiting for Codex to report its auth status", limit, None)
+            })?
+    }
+
+    async fn ask_app_server_auth_status(&self) -> Result<AuthStatus, ExecutorError> {
+        let (program_path, args) = self
+            .build_command_builder()?
+            .build_initial()?
+            .into_resolved()
+            .await?;
+        let env = ExecutionEnv::new(RepoContext::default(), false);
+        let mut child = self
+            .app_server_command(&program_path, &args, &std::env::temp_dir(), &env)
+            .await
+            .stderr(std::process::Stdio::null())
+            .spawn()?;
+
+        let client = AppServerClient::new(
+            LogWriter::new(tokio::io::sink()),
+            None,
+            false,
+            CommandRules::default(),
+        );
+        let (exit_tx, _exit_rx) = tokio::sync::oneshot::channel();
+        let (Some(stdin), Some(stdout)) = (child.stdin.take(), child.stdout.take()) else {
+            return Err(ExecutorError::Io(std::io::Error::other(
+                "Codex app server has no stdio pipes",
+            )));
+        };
+        let peer = JsonRpcPeer::spawn(
+            stdin,
+            stdout,
+            client.clone(),
+            ExitSignalSender::new(exit_tx),
+        );
+        client.connect(peer);
+        client
+            .handshake(&program_path.to_string_lossy(), HANDSHAKE_TIMEOUT)
+            .await?;
+        let response = client.get_auth_status().await?;
+        Ok(auth_status_from_response(
+            response.auth_method.as_ref(),
+            response.requires_openai_auth,
+        ))
+    }
+
+    fn auto_approve(&self) -> bool {
+        matches!(
+            (self.sandbox_mode(), self.approval_mode()),
+            (Some(SandboxMode::DangerFullAccess), None)
+        )
+    }
+
+    /// `sandbox`, or `workspace-write` when only the `full_auto` preset is set
+    fn sandbox_mode(&self) -> Option<SandboxMode> {
+        self.sandbox.clone().or_else(|| {
+            self.full_auto
+                .unwrap_or(false)
+                .then_some(SandboxMode::WorkspaceWrite)
+        })
+    }
+
+    /// `ask_for_approval`, or `never` when only the `full_auto` preset is set
+    fn approval_mode(&self) -> Option<AskForApproval> {
+        self.ask_for_approval.clone().or_else(|| {
+            self.full_auto
+                .unwrap_or(false)
+                .then_some(AskForApproval::Never)
+        })
+    }
+
     fn build_new_conversation_params(&self, cwd: &Path) -> NewConversationParams {
-        let sandbox = match self.sandbox.as_ref() {
+        let sandbox_mode = self.sandbox_mode();
+        let sandbox = match sandbox_mode.as_ref() {
             None | Some(SandboxMode::Auto) => Some(CodexSandboxMode::WorkspaceWrite), // match the Auto preset in codex
             Some(SandboxMode::ReadOnly) => Some(CodexSandboxMode::ReadOnly),
             Some(SandboxMode::WorkspaceWrite) => Some(CodexSandboxMode::WorkspaceWrite),
             Some(SandboxMode::DangerFullAccess) => Some(CodexSandboxMode::DangerFullAccess),
         };
 
-        let approval_policy = match self.ask_for_approval.as_ref() {
-            None if matches!(self.sandbox.as_ref(), None | Some(SandboxMode::Auto)) => {
+        let approval_policy = match self.approval_mode().as_ref() {
+            // Have Codex ask about every command so the client can check each against
+            // `forbidden_commands`
+            None if self.has_forbidden_commands() => Some(CodexAskForApproval::UnlessTrusted),
+            None if matches!(sandbox_mode, None | Some(SandboxMode::Auto)) => {
                 // match the Auto preset in codex
                 Some(CodexAskForApproval::OnRequest)
             }
@@ -378,23 +814,131 @@ impl Codex {
         };
 
         NewConversationParams {
-            model: self.model.clone(),
+            model: BaseCodingAgent::Codex.resolve_model(self.model.as_deref()),
             profile: self.profile.clone(),
             cwd: Some(cwd.to_string_lossy().to_string()),
             approval_policy,
             sandbox,
-            config: self.build_config_overrides(),
+            config: self.build_config_overrides(cwd),
             base_instructions: self.base_instructions.clone(),
             include_apply_patch_tool: self.include_apply_patch_tool,
-            model_provider: self.model_provider.clone(),
+            model_provider: self
+                .model_provider
+                .clone()
+                .or_else(|| self.provider_preset.map(|p| p.provider_id().to_string())),
             compact_prompt: self.compact_prompt.clone(),
             developer_instructions: self.developer_instructions.clone(),
         }
     }
 
-    fn build_config_overrides(&self) -> Option<HashMap<String, Value>> {
+    /// `additional_dirs` resolved against the workspace directory
+    fn resolved_additional_dirs(&self, cwd: &Path) -> Vec<PathBuf> {
+        self.additional_dirs
+            .iter()
+            .flatten()
+            .map(|dir| cwd.join(dir))
+            .collect()
+    }
+
+    /// Where rollout files forked for follow-ups go: `rollout_dir` resolved against `home`
+    /// (the user's Codex home when `None`), or `None` for Codex's own sessions directory.
+    /// Relative paths never land in the workspace, where forks would show up in the diff.
+    pub fn resolved_rollout_dir(&self, home: Option<&Path>) -> Option<PathBuf> {
+        let dir = self.rollout_dir.as_ref()?;
+        if dir.is_absolute() {
+            return Some(dir.clone());
+        }
+        home.map(Path::to_path_buf)
+            .or_else(codex_home)
+            .map(|home| home.join(dir))
+    }
+
+    /// Reject `additional_dirs` under the read-only sandbox, which ignores the writable roots
+    /// they are granted through, and any directory that doesn't exist.
+    fn validate_additional_dirs(&self, cwd: &Path) -> Result<(), ExecutorError> {
+        let additional_dirs = self.resolved_additional_dirs(cwd);
+        if !additional_dirs.is_empty() && matches!(self.sandbox_mode(), Some(SandboxMode::ReadOnly))
+        {
+            return Err(ExecutorError::InvalidConfig(
+                "`additional_dirs` cannot be written to under the `read-only` sandbox; use \
+                 `workspace-write` instead"
+                    .to_string(),
+            ));
+        }
+        match additional_dirs.into_iter().find(|dir| !dir.is_dir()) {
+            Some(missing) => Err(ExecutorError::Io(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("Additional directory not found: {}", missing.display()),
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// Whether Codex authenticates with an API key from the environment rather than a login.
+    fn uses_api_key(&self) -> bool {
+        match self.provider_preset {
+            Some(preset) => self.cmd.has_env(&[preset.api_key_env()]),
+            None => self.cmd.has_env(&["OPENAI_API_KEY", "CODEX_API_KEY"]),
+        }
+    }
+
+    /// Fail with the missing variable when the provider preset can't be configured.
+    fn validate_provider_preset(&self) -> Result<(), ExecutorError> {
+        match self.provider_preset {
+            Some(preset) => preset
+                .provider_config(|key| self.cmd.lookup_env(key))
+                .map(|_| ()),
+            None => Ok(()),
+        }
+    }
+
+    fn has_forbidden_commands(&self) -> bool {
+        self.forbidden_commands
+            .iter()
+            .flatten()
+            .any(|command| !command.trim().is_empty())
+    }
+
+    /// Reject `forbidden_commands` under any policy but `unless-trusted`: `never` runs every
+    /// command without asking, and `on-request`/`on-failure` run commands in the sandbox
+    /// without asking, so they could not be blocked.
+    fn validate_forbidden_commands(&self) -> Result<(), ExecutorError> {
+        let Some(mode) = self.approval_mode() else {
+            return Ok(());
+        };
+        if self.has_forbidden_commands() && mode != AskForApproval::UnlessTrusted {
+            return Err(ExecutorError::InvalidConfig(format!(
+                "`forbidden_commands` cannot be enforced with `ask_for_approval: {}`{}, because \
+                 Codex runs commands without asking; use `unless-trusted` or leave it unset",
+                mode.as_ref(),
+                if self.ask_for_approval.is_none() {
+                    " (set by `full_auto`)"
+                } else {
+                    ""
+                },
+            )));
+        }
+        Ok(())
+    }
+
+    fn build_config_overrides(&self, cwd: &Path) -> Option<HashMap<String, Value>> {
         let mut overrides = HashMap::new();
 
+        // The app server has no `--add-dir` flag; extra directories are granted through the
+        // workspace-write sandbox's writable roots, which is what `--add-dir` sets.
+        let additional_dirs = self.resolved_additional_dirs(cwd);
+        if !additional_dirs.is_empty() {
+            overrides.insert(
+                "sandbox_workspace_write.writable_roots".to_string(),
+                Value::Array(
+                    additional_dirs
+                        .iter()
+                        .map(|dir| Value::String(dir.to_string_lossy().to_string()))
+                        .collect(),
+                ),
+            );
+        }
+
         if let Some(effort) = &self.model_reasoning_effort {
             overrides.insert(
                 "model_reasoning_effort".to_string(),
@@ -409,15 +953,49 @@ impl Codex {
             );
         }
 
+        if let Some(web_search) = self.web_search {
+            overrides.insert("tools.web_search".to_string(), Value::Bool(web_search));
+        }
+
+        if let Some(skip) = self.skip_git_repo_check {
+            overrides.insert("skip_git_repo_check".to_string(), Value::Bool(skip));
+        }
+
+        let summaries_disabled = self.model_reasoning_summary == Some(ReasoningSummary::None);
         if let Some(format) = &self.model_reasoning_summary_format
             && format != &ReasoningSummaryFormat::None
         {
+            if summaries_disabled {
+                // A format only shapes summaries Codex actually produces
+                tracing::warn!(
+                    "Ignoring model_reasoning_summary_format `{}` because model_reasoning_summary \
+                     is `none`",
+                    format.as_ref()
+                );
+            } else {
+                overrides.insert(
+                    "model_reasoning_summary_format".to_string(),
+                    Value::String(format.as_ref().to_string()),
+                );
+            }
+        }
+
+        // Codex tracks usage itself and compacts past this limit, so the threshold is handed
+        // over as config rather than watched from the token count events.
+        if let Some(limit) = self.auto_compact_token_limit.filter(|limit| *limit > 0) {
             overrides.insert(
-                "model_reasoning_summary_format".to_string(),
-                Value::String(format.as_ref().to_string()),
+                "model_auto_compact_token_limit".to_string(),
+                Value::from(limit),
             );
         }
 
+        // A preset that can't be configured is rejected by `validate_provider_preset` first
+        if let Some(preset) = self.provider_preset
+            && let Ok(config) = preset.provider_config(|key| self.cmd.lookup_env(key))
+        {
+            overrides.insert(format!("model_providers.{}", preset.provider_id()), config);
+        }
+
         if overrides.is_empty() {
             None
         } else {
@@ -425,16 +1003,62 @@ impl Codex {
         }
     }
 
-    async fn spawn_inner(
+    /// Start a new session without waiting for it to come up. The returned future resolves
+    /// once the app server has created the conversation and is about to send the prompt.
+    pub async fn spawn_deferred(
         &self,
         current_dir: &Path,
-        command_parts: CommandParts,
-        action: CodexSessionAction,
-        resume_session: Option<&str>,
+        prompt: &str,
         env: &ExecutionEnv,
-    ) -> Result<SpawnedChild, ExecutorError> {
-        let (program_path, args) = command_parts.into_resolved().await?;
+    ) -> Result<(SpawnedChild, ReadinessFuture), ExecutorError> {
+        let combined_prompt = self
+            .append_prompt
+            .combine_prompt_with_mcp_meta(prompt, env)
+            .await?;
+        let action = CodexSessionAction::Chat {
+            prompt: combined_prompt.clone(),
+        };
+        let command_parts = self.build_command_builder()?.build_initial()?;
+        let (ready, readiness) = ReadinessFuture::channel();
+        match self
+            .spawn_inner_with_readiness(current_dir, command_parts, action, None, env, ready)
+            .await
+        {
+            Ok(child) => Ok((child, readiness)),
+            Err(err) => {
+                if self.should_fallback_to_npx(&err) {
+                    let fallback_parts = self.fallback_command_parts()?;
+                    let action = CodexSessionAction::Chat {
+                        prompt: combined_prompt,
+                    };
+                    let (ready, readiness) = ReadinessFuture::channel();
+                    let child = self
+                        .spawn_inner_with_readiness(
+                            current_dir,
+                            fallback_parts,
+                            action,
+                            None,
+                            env,
+                            ready,
+                        )
+                        .await?;
+                    return Ok((child, readiness));
+                }
+                Err(err)
+            }
+        }
+    }
 
+    /// The app-server process command. The runtime env and then the profile env are applied
+    /// on top of the inherited environment, so `OPENAI_BASE_URL`/`OPENAI_API_KEY` from either
+    /// reach Codex's built-in OpenAI provider.
+    async fn app_server_command(
+        &self,
+        program_path: &Path,
+        args: &[String],
+        current_dir: &Path,
+        env: &ExecutionEnv,
+    ) -> Command {
         let mut process = Command::new(program_path);
         process
             .kill_on_drop(true)
@@ -442,42 +1066,202 @@ impl Codex {
             .stdout(std::process::Stdio::piped())
             .stderr(std::process::Stdio::piped())
             .current_dir(current_dir)
-            .args(&args)
+            .args(args)
             .env("NODE_NO_WARNINGS", "1")
             .env("NO_COLOR", "1")
             .env("RUST_LOG", "error");
 
         env.clone()
             .with_profile(&self.cmd)
-            .apply_to_command(&mut process);
+            .apply_to_command(&mut process)
+            .await;
+        self.cmd.apply_resource_limits(&mut process);
+        process
+    }
 
-        let mut child = process.group_spawn()?;
+    /// Validate the configuration for a spawn in `current_dir` and resolve the program to run.
+    async fn resolve_spawn(
+        &self,
+        current_dir: &Path,
+        command_parts: CommandParts,
+        env: &ExecutionEnv,
+    ) -> Result<(PathBuf, Vec<String>), ExecutorError> {
+        self.validate_additional_dirs(current_dir)?;
+        self.validate_provider_preset()?;
+        self.validate_forbidden_commands()?;
+        let resolved = command_parts.into_resolved().await?;
+        env.mark_spawn_phase(SpawnPhase::CommandResolved);
+        Ok(resolved)
+    }
+
+    /// How long spawning waits for the app server without a `spawn_timeout_secs`: a capped
+    /// handshake for every attempt while `connect_retries` remain, otherwise not at all.
+    pub(crate) fn handshake_wait(&self) -> Option<Duration> {
+        let retries = self.connect_retries.unwrap_or(DEFAULT_CONNECT_RETRIES);
+        (retries > 0).then(|| HANDSHAKE_TIMEOUT * (u32::from(retries) + 1))
+    }
+
+    async fn spawn_inner(
+        &self,
+        current_dir: &Path,
+        command_parts: CommandParts,
+        action: CodexSessionAction,
+        resume_session: Option<&str>,
+        env: &ExecutionEnv,
+    ) -> Result<SpawnedChild, ExecutorError> {
+        let (program_path, args) = self.resolve_spawn(current_dir, command_parts, env).await?;
+
+        let retries = self.connect_retries.unwrap_or(DEFAULT_CONNECT_RETRIES);
+        let timeout = self.cmd.spawn_timeout();
+        let deadline = timeout.map(|timeout| tokio::time::Instant::now() + timeout);
+        let remaining = || {
+            deadline.map(|deadline| deadline.saturating_duration_since(tokio::time::Instant::now()))
+        };
+
+        let (ready, mut readiness) = ReadinessFuture::channel();
+        let mut child = self
+            .launch_app_server(
+                current_dir,
+                &program_path,
+                &args,
+                &action,
+                resume_session,
+                env,
+                ready,
+            )
+            .await?;
+        env.mark_spawn_phase(SpawnPhase::ProcessSpawned);
+
+        let mut attempt = 0;
+        while attempt < retries {
+            // Wait for the app server to initialize, so a failed connection can be retried.
+            // Without a spawn timeout the wait is capped, so a hung app server can't block
+            // the spawn forever
+            let wait = remaining().unwrap_or(HANDSHAKE_TIMEOUT);
+            let initialized = tokio::time::timeout(wait, &mut readiness).await.ok();
+            match initialized {
+                Some(Err(err)) if is_connect_error(&err) => {
+                    if let Err(kill_err) = child.child.kill().await {
+                        tracing::warn!("Failed to kill Codex app server: {kill_err}");
+                    }
+                    let delay = CONNECT_RETRY_BASE_DELAY * 2u32.saturating_pow(u32::from(attempt));
+                    attempt += 1;
+                    tracing::warn!(
+                        "Codex app server failed to initialize ({err}); retrying in {}ms \
+                         ({attempt}/{retries})",
+                        delay.as_millis()
+                    );
+                    tokio::time::sleep(delay).await;
+
+                    // Spawn errors such as a missing executable are returned here, without
+                    // retrying
+                    let (ready, next_readiness) = ReadinessFuture::channel();
+                    child = self
+                        .launch_app_server(
+                            current_dir,
+                            &program_path,
+                            &args,
+                            &action,
+                            resume_session,
+                            env,
+                            ready,
+                        )
+                        .await?;
+                    readiness = next_readiness;
+                }
+                // Anything else is handled as it would be without retries
+                Some(result) => {
+                    let (mut settled, settled_readiness) = ReadinessFuture::channel();
+                    match result {
+                        Ok(()) => settled.ready(),
+                        Err(err) => settled.fail(err),
+                    }
+                    readiness = settled_readiness;
+                    break;
+                }
+                None => break,
+            }
+        }
+        capture_action_prompt(&action, env);
 
-        let child_stdout = child.inner().stdout.take().ok_or_else(|| {
-            ExecutorError::Io(std::io::Error::other("Codex app server missing stdout"))
-        })?;
-        let child_stdin = child.inner().stdin.take().ok_or_else(|| {
-            ExecutorError::Io(std::io::Error::other("Codex app server missing stdin"))
-        })?;
+        // A timeout is reported as the whole spawn timeout rather than what was left of it
+        match remaining().zip(timeout) {
+            Some((wait, timeout)) => await_ready_for(child, readiness, wait, timeout).await,
+            None => Ok(child),
+        }
+    }
 
-        let new_stdout = create_stdout_pipe_writer(&mut child)?;
+    async fn spawn_inner_with_readiness(
+        &self,
+        current_dir: &Path,
+        command_parts: CommandParts,
+        action: CodexSessionAction,
+        resume_session: Option<&str>,
+        env: &ExecutionEnv,
+        ready: ReadinessSignal,
+    ) -> Result<SpawnedChild, ExecutorError> {
+        let (program_path, args) = self.resolve_spawn(current_dir, command_parts, env).await?;
+        let child = self
+            .launch_app_server(
+                current_dir,
+                &program_path,
+                &args,
+                &action,
+                resume_session,
+                env,
+                ready,
+            )
+            .await?;
+        env.mark_spawn_phase(SpawnPhase::ProcessSpawned);
+        capture_action_prompt(&action, env);
+        Ok(child)
+    }
+
+    /// Spawn the app server and start the session on it in the background. `ready` is
+    /// signalled once the conversation is set up.
+    #[allow(clippy::too_many_arguments)]
+    async fn launch_app_server(
+        &self,
+        current_dir: &Path,
+        program_path: &Path,
+        args: &[String],
+        action: &CodexSessionAction,
+        resume_session: Option<&str>,
+        env: &ExecutionEnv,
+        ready: ReadinessSignal,
+    ) -> Result<SpawnedChild, ExecutorError> {
+        let mut process = self
+            .app_server_command(program_path, args, current_dir, env)
+            .await;
+        let mut child = process.group_spawn()?;
+        let mut ready = ready.with_spawn_timer(env.spawn_timer.clone());
+        let action = action.clone();
+
+        let (child_stdout, child_stdin, new_stdout) = take_app_server_pipes(&mut child).await?;
         let (exit_signal_tx, exit_signal_rx) = tokio::sync::oneshot::channel();
 
         let params = self.build_new_conversation_params(current_dir);
+        let program = program_path.to_string_lossy().to_string();
         let resume_session = resume_session.map(|s| s.to_string());
-        let auto_approve = matches!(
-            (&self.sandbox, &self.ask_for_approval),
-            (Some(SandboxMode::DangerFullAccess), None)
-        );
+        let codex_home = env.isolated_codex_home(&self.cmd);
+        let rollout_dir = self.resolved_rollout_dir(codex_home.as_deref());
+        let auto_approve = self.auto_approve();
         let approvals = self.approvals.clone();
+        let command_rules = CommandRules {
+            pre_approved: self.pre_approved_commands.clone().unwrap_or_default(),
+            forbidden: self.forbidden_commands.clone().unwrap_or_default(),
+        };
         tokio::spawn(async move {
             let exit_signal_tx = ExitSignalSender::new(exit_signal_tx);
             let log_writer = LogWriter::new(new_stdout);
             let launch_result = match action {
                 CodexSessionAction::Chat { prompt } => {
                     Self::launch_codex_app_server(
+                        &program,
                         params,
                         resume_session,
+                        codex_home,
+                        rollout_dir,
                         prompt,
                         child_stdout,
                         child_stdin,
@@ -485,13 +1269,18 @@ impl Codex {
                         exit_signal_tx.clone(),
                         approvals,
                         auto_approve,
+                        command_rules,
+                        &mut ready,
                     )
                     .await
                 }
                 CodexSessionAction::Review { target } => {
                     review::launch_codex_review(
+                        &program,
                         params,
                         resume_session,
+                        codex_home,
+                        rollout_dir,
                         target,
                         child_stdout,
                         child_stdin,
@@ -499,38 +1288,34 @@ impl Codex {
                         exit_signal_tx.clone(),
                         approvals,
                         auto_approve,
+                        command_rules,
+                        &mut ready,
                     )
                     .await
                 }
             };
             if let Err(err) = launch_result {
-                match &err {
+                let log_entry = match &err {
+                    // Broken pipe likely means the parent process exited, so we can ignore it
                     ExecutorError::Io(io_err)
                         if io_err.kind() == std::io::ErrorKind::BrokenPipe =>
                     {
-                        // Broken pipe likely means the parent process exited, so we can ignore it
-                        return;
+                        None
                     }
                     ExecutorError::AuthRequired(message) => {
-                        log_writer
-                            .log_raw(&Error::auth_required(message.clone()).raw())
-                            .await
-                            .ok();
-                        // Send failure signal so the process is marked as failed
-                        exit_signal_tx
-                            .send_exit_signal(ExecutorExitResult::Failure)
-                            .await;
-                        return;
+                        Some(Error::auth_required(message.clone()))
                     }
                     _ => {
                         tracing::error!("Codex spawn error: {}", err);
-                        log_writer
-                            .log_raw(&Error::launch_error(err.to_string()).raw())
-                            .await
-                            .ok();
+                        Some(Error::launch_error(err.to_string()))
                     }
-                }
-                // For other errors, also send failure signal
+                };
+                ready.fail(err);
+                let Some(log_entry) = log_entry else {
+                    return;
+                };
+                log_writer.log_raw(&log_entry.raw()).await.ok();
+                // Send failure signal so the process is marked as failed
                 exit_signal_tx
                     .send_exit_signal(ExecutorExitResult::Failure)
                     .await;
@@ -546,8 +1331,11 @@ impl Codex {
 
     #[allow(clippy::too_many_arguments)]
     async fn launch_codex_app_server(
+        program: &str,
         conversation_params: NewConversationParams,
         resume_session: Option<String>,
+        codex_home: Option<PathBuf>,
+        rollout_dir: Option<PathBuf>,
         combined_prompt: String,
         child_stdout: tokio::process::ChildStdout,
         child_stdin: tokio::process::ChildStdin,
@@ -555,12 +1343,14 @@ impl Codex {
         exit_signal_tx: ExitSignalSender,
         approvals: Option<Arc<dyn ExecutorApprovalService>>,
         auto_approve: bool,
+        command_rules: CommandRules,
+        ready: &mut ReadinessSignal,
     ) -> Result<(), ExecutorError> {
-        let client = AppServerClient::new(log_writer, approvals, auto_approve);
+        let client = AppServerClient::new(log_writer, approvals, auto_approve, command_rules);
         let rpc_peer =
             JsonRpcPeer::spawn(child_stdin, child_stdout, client.clone(), exit_signal_tx);
         client.connect(rpc_peer);
-        client.initialize().await?;
+        client.handshake(program, HANDSHAKE_TIMEOUT).await?;
         let auth_status = client.get_auth_status().await?;
         if auth_status.requires_openai_auth.unwrap_or(true) && auth_status.auth_method.is_none() {
             return Err(ExecutorError::AuthRequired(
@@ -574,26 +1364,24 @@ impl Codex {
                 let conversation_id = response.conversation_id;
                 client.register_session(&conversation_id).await?;
                 client.add_conversation_listener(conversation_id).await?;
+                ready.ready();
                 client
                     .send_user_message(conversation_id, combined_prompt)
                     .await?;
             }
             Some(session_id) => {
-                let (rollout_path, _forked_session_id) =
-                    SessionHandler::fork_rollout_file(&session_id)
-                        .map_err(|e| ExecutorError::FollowUpNotSupported(e.to_string()))?;
-                let overrides = conversation_params;
                 let response = client
-                    .resume_conversation(rollout_path.clone(), overrides)
+                    .resume_session(
+                        &session_id,
+                        conversation_params,
+                        codex_home.as_deref(),
+                        rollout_dir.as_deref(),
+                    )
                     .await?;
-                tracing::debug!(
-                    "resuming session using rollout file {}, response {:?}",
-                    rollout_path.display(),
-                    response
-                );
                 let conversation_id = response.conversation_id;
                 client.register_session(&conversation_id).await?;
                 client.add_conversation_listener(conversation_id).await?;
+                ready.ready();
                 client
                     .send_user_message(conversation_id, combined_prompt)
                     .await?;
@@ -602,3 +1390,700 @@ impl Codex {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executors::{BaseAgentCapability, CodingAgent};
+
+    fn codex(value: Value) -> Codex {
+        serde_json::from_value(value).unwrap()
+    }
+
+    /// Write `body` as an executable shell script in `dir` that stands in for Codex.
+    #[cfg(unix)]
+    fn fake_codex(dir: &Path, body: &str) -> PathBuf {
+        use std::os::unix::fs::PermissionsExt;
+
+        let script = dir.join("codex.sh");
+        std::fs::write(&script, format!("#!/bin/sh\n{body}")).unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+        script
+    }
+
+    #[test]
+    fn api_key_counts_as_logged_in() {
+        let keyed = codex(serde_json::json!({ "env": { "OPENAI_API_KEY": "sk-profile" } }));
+        assert!(keyed.uses_api_key());
+        assert!(matches!(
+            AvailabilityInfo::InstalledNeedsLogin.or_api_key_login(keyed.uses_api_key()),
+            AvailabilityInfo::ApiKeyConfigured
+        ));
+        assert_eq!(
+            AuthStatus::from(&AvailabilityInfo::ApiKeyConfigured),
+            AuthStatus::Authenticated {
+                method: Some("apikey".to_string()),
+                expires_at: None,
+            }
+        );
+
+        let blank = codex(serde_json::json!({
+            "env": { "OPENAI_API_KEY": " ", "CODEX_API_KEY": "" }
+        }));
+        assert!(!blank.uses_api_key());
+
+        let openrouter = codex(serde_json::json!({
+            "provider_preset": "openrouter",
+            "env": { "OPENROUTER_API_KEY": "sk-or" },
+        }));
+        assert!(openrouter.uses_api_key());
+    }
+
+    #[test]
+    fn openrouter_preset_registers_provider() {
+        let codex = codex(serde_json::json!({ "provider_preset": "openrouter" }));
+        let params = codex.build_new_conversation_params(Path::new("/tmp"));
+
+        assert_eq!(params.model_provider.as_deref(), Some("openrouter"));
+        assert_eq!(
+            params.config.unwrap()["model_providers.openrouter"],
+            serde_json::json!({
+                "name": "OpenRouter",
+                "base_url": "https://openrouter.ai/api/v1",
+                "env_key": "OPENROUTER_API_KEY",
+                "wire_api": "chat",
+            })
+        );
+    }
+
+    #[test]
+    fn azure_preset_fills_resource_from_env() {
+        let codex = codex(serde_json::json!({
+            "provider_preset": "azure-openai",
+            "env": { "AZURE_OPENAI_RESOURCE": "contoso" },
+        }));
+        let params = codex.build_new_conversation_params(Path::new("/tmp"));
+
+        assert_eq!(params.model_provider.as_deref(), Some("azure"));
+        assert_eq!(
+            params.config.unwrap()["model_providers.azure"],
+            serde_json::json!({
+                "name": "Azure OpenAI",
+                "base_url": "https://contoso.openai.azure.com/openai",
+                "env_http_headers": { "api-key": "AZURE_OPENAI_API_KEY" },
+                "query_params": { "api-version": "2025-04-01-preview" },
+                "wire_api": "responses",
+            })
+        );
+    }
+
+    #[test]
+    fn azure_preset_without_resource_names_the_missing_variable() {
+        let err = ProviderPreset::AzureOpenAi
+            .provider_config(|_| None)
+            .unwrap_err();
+
+        assert!(matches!(
+            &err,
+            ExecutorError::MissingEnvVar(name) if name == "AZURE_OPENAI_RESOURCE"
+        ));
+        assert!(err.to_string().contains("AZURE_OPENAI_RESOURCE"), "{err}");
+    }
+
+    #[test]
+    fn explicit_model_provider_wins_over_preset() {
+        let codex = codex(serde_json::json!({
+            "provider_preset": "openrouter",
+            "model_provider": "custom",
+        }));
+        let params = codex.build_new_conversation_params(Path::new("/tmp"));
+
+        assert_eq!(params.model_provider.as_deref(), Some("custom"));
+    }
+
+    #[test]
+    fn additional_dirs_become_writable_roots() {
+        let workspace = std::env::temp_dir();
+        let codex = codex(serde_json::json!({
+            "additional_dirs": ["/opt/shared-cache", "../sibling-repo"],
+        }));
+
+        let params = codex.build_new_conversation_params(&workspace);
+        assert_eq!(
+            params.config.unwrap()["sandbox_workspace_write.writable_roots"],
+            serde_json::json!([
+                "/opt/shared-cache",
+                workspace.join("../sibling-repo").to_string_lossy(),
+            ])
+        );
+    }
+
+    #[test]
+    fn missing_additional_dir_is_rejected() {
+        let workspace = tempfile::tempdir().unwrap();
+        let existing = codex(serde_json::json!({ "additional_dirs": ["."] }));
+        assert!(existing.validate_additional_dirs(workspace.path()).is_ok());
+
+        let missing = codex(serde_json::json!({ "additional_dirs": [".", "missing"] }));
+        let err = missing
+            .validate_additional_dirs(workspace.path())
+            .unwrap_err();
+        assert!(
+            matches!(&err, ExecutorError::Io(io) if io.kind() == std::io::ErrorKind::NotFound),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn additional_dirs_are_rejected_under_the_read_only_sandbox() {
+        let workspace = std::env::temp_dir();
+        let read_only = codex(serde_json::json!({
+            "additional_dirs": ["."],
+            "sandbox": "read-only",
+        }));
+        let err = read_only.validate_additional_dirs(&workspace).unwrap_err();
+        assert!(matches!(err, ExecutorError::InvalidConfig(_)), "{err}");
+
+        let read_only_without_dirs = codex(serde_json::json!({ "sandbox": "read-only" }));
+        assert!(
+            read_only_without_dirs
+                .validate_additional_dirs(&workspace)
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn summary_format_requires_enabled_summaries() {
+        let overrides = |value: Value| {
+            codex(value)
+                .build_config_overrides(Path::new("/tmp"))
+                .unwrap_or_default()
+        };
+
+        let disabled = overrides(serde_json::json!({
+            "model_reasoning_summary": "none",
+            "model_reasoning_summary_format": "experimental",
+        }));
+        assert_eq!(
+            disabled.get("model_reasoning_summary"),
+            Some(&Value::from("none"))
+        );
+        assert!(!disabled.contains_key("model_reasoning_summary_format"));
+
+        let enabled = overrides(serde_json::json!({
+            "model_reasoning_summary": "detailed",
+            "model_reasoning_summary_format": "experimental",
+        }));
+        assert_eq!(
+            enabled.get("model_reasoning_summary_format"),
+            Some(&Value::from("experimental"))
+        );
+
+        // Unset summary means Codex's default, which does produce summaries
+        let default_summary =
+            overrides(serde_json::json!({ "model_reasoning_summary_format": "experimental" }));
+        assert_eq!(
+            default_summary.get("model_reasoning_summary_format"),
+            Some(&Value::from("experimental"))
+        );
+
+        let no_format = overrides(serde_json::json!({ "model_reasoning_summary": "none" }));
+        assert!(!no_format.contains_key("model_reasoning_summary_format"));
+    }
+
+    #[tokio::test]
+    async fn openai_env_reaches_app_server_process() {
+        use std::ffi::OsStr;
+
+        use crate::env::RepoContext;
+
+        let codex = codex(serde_json::json!({
+            "env": { "OPENAI_API_KEY": "sk-profile" },
+        }));
+        let mut env = ExecutionEnv::new(RepoContext::default(), false);
+        env.insert("OPENAI_BASE_URL", "http://localhost:4000/v1");
+        env.insert("OPENAI_API_KEY", "sk-runtime");
+
+        let process = codex
+            .app_server_command(Path::new("codex"), &[], Path::new("/tmp"), &env)
+            .await;
+        let envs: HashMap<&OsStr, Option<&OsStr>> = process.as_std().get_envs().collect();
+
+        assert_eq!(
+            envs[OsStr::new("OPENAI_BASE_URL")],
+            Some(OsStr::new("http://localhost:4000/v1"))
+        );
+        // Profile env wins over the runtime env
+        assert_eq!(
+            envs[OsStr::new("OPENAI_API_KEY")],
+            Some(OsStr::new("sk-profile"))
+        );
+    }
+
+    #[test]
+    fn web_search_toggle_is_emitted_only_when_set() {
+        let overrides = |value: Value| {
+            codex(value)
+                .build_config_overrides(Path::new("/tmp"))
+                .unwrap_or_default()
+        };
+
+        assert_eq!(
+            overrides(serde_json::json!({ "web_search": true })).get("tools.web_search"),
+            Some(&Value::Bool(true))
+        );
+        assert_eq!(
+            overrides(serde_json::json!({ "web_search": false })).get("tools.web_search"),
+            Some(&Value::Bool(false))
+        );
+        assert!(!overrides(serde_json::json!({})).contains_key("tools.web_search"));
+    }
+
+    #[test]
+    fn skip_git_repo_check_is_emitted_only_when_set() {
+        let overrides = |value: Value| {
+            codex(value)
+                .build_config_overrides(Path::new("/tmp"))
+                .unwrap_or_default()
+        };
+
+        assert_eq!(
+            overrides(serde_json::json!({ "skip_git_repo_check": true }))
+                .get("skip_git_repo_check"),
+            Some(&Value::Bool(true))
+        );
+        assert_eq!(
+            overrides(serde_json::json!({ "skip_git_repo_check": false }))
+                .get("skip_git_repo_check"),
+            Some(&Value::Bool(false))
+        );
+        assert!(!overrides(serde_json::json!({})).contains_key("skip_git_repo_check"));
+    }
+
+    #[test]
+    fn auto_compact_limit_is_passed_to_codex_only_when_enabled() {
+        let overrides = |value: Value| {
+            codex(value)
+                .build_config_overrides(Path::new("/tmp"))
+                .unwrap_or_default()
+        };
+
+        assert_eq!(
+            overrides(serde_json::json!({ "auto_compact_token_limit": 180000 }))
+                .get("model_auto_compact_token_limit"),
+            Some(&Value::from(180000))
+        );
+        assert!(
+            !overrides(serde_json::json!({ "auto_compact_token_limit": 0 }))
+                .contains_key("model_auto_compact_token_limit")
+        );
+        assert!(!overrides(serde_json::json!({})).contains_key("model_auto_compact_token_limit"));
+    }
+
+    #[test]
+    fn fallback_command_parts_run_app_server_through_npx() {
+        let parts = codex(serde_json::json!({}))
+            .fallback_command_parts()
+            .unwrap();
+        let mut expected = crate::command::split_command_line(fallback_command()).unwrap();
+        expected.push("app-server".to_string());
+
+        assert_eq!(parts.program(), expected[0]);
+        assert_eq!(parts.args(), &expected[1..]);
+    }
+
+    #[test]
+    fn only_connection_failures_are_retried() {
+        assert!(is_connect_error(&ExecutorError::UnexpectedExecutable {
+            program: "codex".to_string(),
+            reason: "it stopped without answering the protocol handshake".to_string(),
+        }));
+        assert!(is_connect_error(&ExecutorError::Io(std::io::Error::other(
+            "initialize request was dropped"
+        ))));
+        assert!(!is_connect_error(&ExecutorError::AuthRequired(
+            "Codex authentication required".to_string()
+        )));
+        assert!(!is_connect_error(&ExecutorError::InvalidConfig(
+            "bad profile".to_string()
+        )));
+        assert!(!is_connect_error(&ExecutorError::Cancelled));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn app_server_that_fails_to_initialize_is_respawned() {
+        use crate::env::RepoContext;
+
+        let dir = tempfile::tempdir().unwrap();
+        let attempts = dir.path().join("attempts");
+        let script = fake_codex(
+            dir.path(),
+            &format!("echo started >> '{}'\n", attempts.display()),
+        );
+        let codex = codex(serde_json::json!({
+            "base_command_override": script,
+            "connect_retries": 2,
+        }));
+
+        // The app server exits before the handshake every time: two retries, then the last
+        // attempt is returned as it would be without retries
+        let env = ExecutionEnv::new(RepoContext::default(), false);
+        let mut spawned = codex.spawn(dir.path(), "hello", &env).await.unwrap();
+        spawned.child.wait().await.unwrap();
+        let started = std::fs::read_to_string(&attempts).unwrap();
+        assert_eq!(started.lines().count(), 3);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn hung_app_server_does_not_block_spawn_without_retries() {
+        use crate::env::RepoContext;
+
+        let dir = tempfile::tempdir().unwrap();
+        let script = fake_codex(dir.path(), "exec sleep 30\n");
+        let codex = codex(serde_json::json!({
+            "base_command_override": script,
+            "connect_retries": 0,
+        }));
+
+        // The app server never answers the handshake. With retries turned off the child is
+        // handed back right away, without waiting on the handshake, and can be stopped
+        let env = ExecutionEnv::new(RepoContext::default(), false);
+        let spawn = codex.spawn(dir.path(), "hello", &env);
+        let mut spawned = tokio::time::timeout(Duration::from_secs(5), spawn)
+            .await
+            .expect("spawn waited for the hung app server")
+            .unwrap();
+        spawned.child.kill().await.unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn auth_status_is_reused_between_checks() {
+        let dir = tempfile::tempdir().unwrap();
+        let starts = dir.path().join("starts");
+        let script = fake_codex(
+            dir.path(),
+            &format!("echo started >> '{}'\n", starts.display()),
+        );
+        let codex = codex(serde_json::json!({ "base_command_override": script }));
+
+        // The app server exits without answering, so the status falls back to auth.json. Either
+        // way the second check doesn't start another app server
+        let first = codex.check_auth().await.unwrap();
+        assert_eq!(codex.check_auth().await.unwrap(), first);
+        let started = std::fs::read_to_string(&starts).unwrap();
+        assert_eq!(started.lines().count(), 1);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn hung_auth_probe_is_bounded_by_the_spawn_timeout() {
+        let dir = tempfile::tempdir().unwrap();
+        let script = fake_codex(dir.path(), "exec sleep 30\n");
+        let codex = codex(serde_json::json!({
+            "base_command_override": script,
+            "spawn_timeout_secs": 1,
+        }));
+
+        let err = tokio::time::timeout(Duration::from_secs(5), codex.query_auth_status())
+            .await
+            .expect("auth probe outlived the spawn timeout")
+            .unwrap_err();
+        assert!(
+            matches!(&err, ExecutorError::Timeout(message) if message.ends_with("after 1s")),
+            "{err}"
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn preview_command_resolves_without_spawning() {
+        let preview = codex(serde_json::json!({
+            "base_command_override": "sh",
+            "env": { "OPENAI_BASE_URL": "http://localhost:8080", "CODEX_HOME": "/tmp/codex" },
+        }))
+        .preview_command()
+        .await
+        .unwrap();
+        assert!(preview.program.is_absolute() && preview.program.ends_with("sh"));
+        assert_eq!(preview.args, ["app-server"]);
+        assert!(!preview.uses_fallback);
+        assert_eq!(preview.env_keys, ["CODEX_HOME", "OPENAI_BASE_URL"]);
+
+        // An override is never replaced by the npx fallback
+        let missing = codex(serde_json::json!({ "base_command_override": "vk-no-such-agent" }));
+        assert!(matches!(
+            missing.preview_command().await,
+            Err(ExecutorError::ExecutableNotFound { program }) if program == "vk-no-such-agent"
+        ));
+    }
+
+    #[test]
+    fn full_auto_preset_sets_workspace_write_and_never() {
+        let params = codex(serde_json::json!({ "full_auto": true }))
+            .build_new_conversation_params(Path::new("/tmp"));
+        assert!(matches!(
+            params.sandbox,
+            Some(CodexSandboxMode::WorkspaceWrite)
+        ));
+        assert!(matches!(
+            params.approval_policy,
+            Some(CodexAskForApproval::Never)
+        ));
+    }
+
+    #[test]
+    fn explicit_sandbox_and_approval_win_over_full_auto() {
+        let params = codex(serde_json::json!({
+            "full_auto": true,
+            "sandbox": "read-only",
+            "ask_for_approval": "on-request",
+        }))
+        .build_new_conversation_params(Path::new("/tmp"));
+        assert!(matches!(params.sandbox, Some(CodexSandboxMode::ReadOnly)));
+        assert!(matches!(
+            params.approval_policy,
+            Some(CodexAskForApproval::OnRequest)
+        ));
+
+        let params = codex(serde_json::json!({ "full_auto": true, "sandbox": "read-only" }))
+            .build_new_conversation_params(Path::new("/tmp"));
+        assert!(matches!(params.sandbox, Some(CodexSandboxMode::ReadOnly)));
+        assert!(matches!(
+            params.approval_policy,
+            Some(CodexAskForApproval::Never)
+        ));
+    }
+
+    #[test]
+    fn forbidden_commands_need_codex_to_ask() {
+        let yolo = codex(serde_json::json!({
+            "sandbox": "danger-full-access",
+            "forbidden_commands": ["rm -rf *"],
+        }));
+        assert!(yolo.validate_forbidden_commands().is_ok());
+        assert!(matches!(
+            yolo.build_new_conversation_params(Path::new("/tmp"))
+                .approval_policy,
+            Some(CodexAskForApproval::UnlessTrusted)
+        ));
+
+        for config in [
+            serde_json::json!({ "full_auto": true, "forbidden_commands": ["rm -rf *"] }),
+            serde_json::json!({ "ask_for_approval": "never", "forbidden_commands": ["rm -rf *"] }),
+            serde_json::json!({
+                "ask_for_approval": "on-request",
+                "forbidden_commands": ["rm -rf *"],
+            }),
+            serde_json::json!({
+                "ask_for_approval": "on-failure",
+                "forbidden_commands": ["rm -rf *"],
+            }),
+        ] {
+            assert!(matches!(
+                codex(config).validate_forbidden_commands(),
+                Err(ExecutorError::InvalidConfig(_))
+            ));
+        }
+        assert!(
+            codex(serde_json::json!({ "full_auto": true, "forbidden_commands": [" "] }))
+                .validate_forbidden_commands()
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn forbidden_commands_make_the_default_sandbox_ask() {
+        let default = codex(serde_json::json!({ "forbidden_commands": ["rm -rf *"] }));
+        assert!(default.validate_forbidden_commands().is_ok());
+        assert!(matches!(
+            default
+                .build_new_conversation_params(Path::new("/tmp"))
+                .approval_policy,
+            Some(CodexAskForApproval::UnlessTrusted)
+        ));
+
+        let unless_trusted = codex(serde_json::json!({
+            "ask_for_approval": "unless-trusted",
+            "forbidden_commands": ["rm -rf *"],
+        }));
+        assert!(unless_trusted.validate_forbidden_commands().is_ok());
+
+        let unrestricted = codex(serde_json::json!({}));
+        assert!(matches!(
+            unrestricted
+                .build_new_conversation_params(Path::new("/tmp"))
+                .approval_policy,
+            Some(CodexAskForApproval::OnRequest)
+        ));
+    }
+
+    #[test]
+    fn app_server_subcommand_override_replaces_default() {
+        let parts = codex(serde_json::json!({
+            "base_command_override": "codex-wrapper",
+            "app_server_subcommand": "serve-app",
+        }))
+        .build_command_builder()
+        .unwrap()
+        .build_initial()
+        .unwrap();
+
+        assert_eq!(parts.program(), "codex-wrapper");
+        assert_eq!(parts.args(), ["serve-app"]);
+    }
+
+    #[test]
+    fn prepend_args_go_before_the_app_server_subcommand() {
+        let parts = codex(serde_json::json!({
+            "prepend_args": ["--verbose", "-c 'model=\"o3\"'"],
+            "additional_params": ["--trace"],
+        }))
+        .build_command_builder()
+        .unwrap()
+        .build_initial()
+        .unwrap();
+
+        assert_eq!(
+            parts.args(),
+            ["--verbose", "-c", "model=\"o3\"", "app-server", "--trace"]
+        );
+    }
+
+    #[test]
+    fn relative_rollout_dirs_resolve_against_the_codex_home() {
+        let home = Path::new("/isolated/codex");
+        let rollout_dir = |dir: &str| {
+            codex(serde_json::json!({ "rollout_dir": dir })).resolved_rollout_dir(Some(home))
+        };
+
+        assert_eq!(rollout_dir("forks"), Some(home.join("forks")));
+        assert_eq!(rollout_dir("/var/forks"), Some(PathBuf::from("/var/forks")));
+        assert_eq!(
+            codex(serde_json::json!({})).resolved_rollout_dir(Some(home)),
+            None
+        );
+    }
+
+    #[test]
+    fn uncommitted_review_targets_working_tree() {
+        let plain = build_uncommitted_review_prompt(None);
+        assert!(matches!(
+            review_target(&plain, ReviewScope::UncommittedChanges),
+            ReviewTarget::UncommittedChanges
+        ));
+
+        let prompt = "Please review the code changes.";
+        assert!(matches!(
+            review_target(prompt, ReviewScope::Commits),
+            ReviewTarget::Custom { instructions } if instructions == prompt
+        ));
+    }
+
+    #[test]
+    fn uncommitted_review_keeps_extra_instructions() {
+        let prompt = build_uncommitted_review_prompt(Some("Focus on the error handling."));
+        assert!(matches!(
+            review_target(&prompt, ReviewScope::UncommittedChanges),
+            ReviewTarget::Custom { instructions } if instructions == prompt
+        ));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn pipe_setup_failure_reaps_the_app_server() {
+        // Without a piped stdout, setup fails after the child is already running
+        let mut child = Command::new("sleep")
+            .arg("30")
+            .stdin(std::process::Stdio::piped())
+            .kill_on_drop(true)
+            .group_spawn()
+            .unwrap();
+
+        let result = take_app_server_pipes(&mut child).await;
+        assert!(matches!(result, Err(ExecutorError::Io(_))));
+        assert!(child.try_wait().unwrap().is_some());
+    }
+
+    #[test]
+    fn app_server_auth_status_maps_to_auth_status() {
+        assert_eq!(
+            auth_status_from_response(Some(&"chatgpt"), Some(true)),
+            AuthStatus::Authenticated {
+                method: Some("chatgpt".to_string()),
+                expires_at: None,
+            }
+        );
+        assert_eq!(
+            auth_status_from_response(None::<&String>, Some(false)),
+            AuthStatus::Authenticated {
+                method: None,
+                expires_at: None,
+            }
+        );
+        assert_eq!(
+            auth_status_from_response(None::<&String>, None),
+            AuthStatus::NotAuthenticated
+        );
+    }
+
+    #[test]
+    fn installed_codex_without_auth_needs_login() {
+        let dir = tempfile::tempdir().unwrap();
+        let home = dir.path();
+        assert!(matches!(
+            availability_in_codex_home(home),
+            AvailabilityInfo::NotFound
+        ));
+
+        std::fs::write(home.join("config.toml"), "").unwrap();
+        let info = availability_in_codex_home(home);
+        assert!(matches!(info, AvailabilityInfo::InstalledNeedsLogin));
+        assert!(!info.is_available());
+
+        std::fs::write(home.join("auth.json"), "{}").unwrap();
+        let info = availability_in_codex_home(home);
+        assert!(matches!(info, AvailabilityInfo::LoginDetected { .. }));
+        assert!(info.is_available());
+    }
+
+    #[test]
+    fn default_sandbox_follows_the_sandbox_mode() {
+        assert_eq!(
+            codex(serde_json::json!({})).default_sandbox(),
+            Some(SandboxDescriptor {
+                level: SandboxLevel::WorkspaceWrite,
+                requires_approval: true,
+            })
+        );
+        assert_eq!(
+            codex(serde_json::json!({ "sandbox": "danger-full-access" })).default_sandbox(),
+            Some(SandboxDescriptor {
+                level: SandboxLevel::FullAccess,
+                requires_approval: false,
+            })
+        );
+    }
+
+    #[test]
+    fn capabilities_include_setup_helper_and_images() {
+        let agent = CodingAgent::Codex(codex(serde_json::json!({})));
+        assert_eq!(
+            agent.capabilities(),
+            [
+                BaseAgentCapability::SessionFork,
+                BaseAgentCapability::SetupHelper,
+                BaseAgentCapability::ImageInput,
+            ]
+        );
+        assert!(agent.supports_image_input());
+    }
+
+    #[test]
+    fn oss_runs_without_network() {
+        assert!(CodingAgent::Codex(codex(serde_json::json!({}))).requires_network());
+        assert!(!CodingAgent::Codex(codex(serde_json::json!({ "oss": true }))).requires_network());
+    }
+}
@@ -2,7 +2,9 @@ use std::{
     borrow::Cow,
     collections::VecDeque,
     io,
+    path::Path,
     sync::{Arc, OnceLock},
+    time::Duration,
 };
 
 use async_trait::async_trait;
@@ -16,6 +18,7 @@ use codex_app_server_protocol::{
     SendUserMessageResponse, ServerNotification, ServerRequest,
 };
 use codex_protocol::{ConversationId, protocol::ReviewDecision};
+use regex::Regex;
 use serde::{Serialize, de::DeserializeOwned};
 use serde_json::{self, Value};
 use tokio::{
@@ -24,10 +27,13 @@ use tokio::{
 };
 use workspace_utils::approvals::ApprovalStatus;
 
-use super::jsonrpc::{JsonRpcCallbacks, JsonRpcPeer};
+use super::{
+    jsonrpc::{JsonRpcCallbacks, JsonRpcPeer},
+    session::SessionHandler,
+};
 use crate::{
     approvals::{ExecutorApprovalError, ExecutorApprovalService},
-    executors::{ExecutorError, codex::normalize_logs::Approval},
+    executors::{ExecutorError, codex::normalize_logs::Approval, expect_handshake},
 };
 
 pub struct AppServerClient {
@@ -37,6 +43,17 @@ pub struct AppServerClient {
     conversation_id: Mutex<Option<ConversationId>>,
     pending_feedback: Mutex<VecDeque<String>>,
     auto_approve: bool,
+    pre_approved: Vec<AllowedCommand>,
+    forbidden: Vec<CommandPattern>,
+}
+
+/// User-configured command lists checked before the approvals service is consulted.
+#[derive(Debug, Clone, Default)]
+pub struct CommandRules {
+    /// Approved without asking.
+    pub pre_approved: Vec<String>,
+    /// Denied without asking, even when approvals are otherwise skipped.
+    pub forbidden: Vec<String>,
 }
 
 impl AppServerClient {
@@ -44,12 +61,19 @@ impl AppServerClient {
         log_writer: LogWriter,
         approvals: Option<Arc<dyn ExecutorApprovalService>>,
         auto_approve: bool,
+        command_rules: CommandRules,
     ) -> Arc<Self> {
         Arc::new(Self {
             rpc: OnceLock::new(),
             log_writer,
             approvals,
             auto_approve,
+            pre_approved: command_rules
+                .pre_approved
+                .iter()
+                .filter_map(|entry| AllowedCommand::parse(entry))
+                .collect(),
+            forbidden: CommandPattern::compile_all(&command_rules.forbidden),
             conversation_id: Mutex::new(None),
             pending_feedback: Mutex::new(VecDeque::new()),
         })
@@ -80,6 +104,16 @@ impl AppServerClient {
         self.send_message(&ClientNotification::Initialized).await
     }
 
+    /// [`Self::initialize`] a freshly spawned `program`, reporting it as
+    /// [`ExecutorError::UnexpectedExecutable`] if it never speaks JSON-RPC.
+    pub async fn handshake(&self, program: &str, timeout: Duration) -> Result<(), ExecutorError> {
+        let rpc = self.rpc();
+        expect_handshake(program, timeout, self.initialize(), |_| {
+            !rpc.has_received_message()
+        })
+        .await?
+    }
+
     pub async fn new_conversation(
         &self,
         params: NewConversationParams,
@@ -108,6 +142,67 @@ impl AppServerClient {
         self.send_request(request, "resumeConversation").await
     }
 
+    pub async fn resume_conversation_by_id(
+        &self,
+        conversation_id: ConversationId,
+        overrides: NewConversationParams,
+    ) -> Result<ResumeConversationResponse, ExecutorError> {
+        let request = ClientRequest::ResumeConversation {
+            request_id: self.next_request_id(),
+            params: ResumeConversationParams {
+                path: None,
+                overrides: Some(overrides),
+                conversation_id: Some(conversation_id),
+                history: None,
+            },
+        };
+        self.send_request(request, "resumeConversation").await
+    }
+
+    /// Resume `session_id`, first by asking the app server to reopen the conversation by id,
+    /// which needs no filesystem access. When the server no longer has it, resumes from a fork
+    /// of its rollout file instead, looked up in `codex_home` (the user's Codex home when
+    /// `None`) and forked to `rollout_dir` when given.
+    pub async fn resume_session(
+        &self,
+        session_id: &str,
+        overrides: NewConversationParams,
+        codex_home: Option<&Path>,
+        rollout_dir: Option<&Path>,
+    ) -> Result<ResumeConversationResponse, ExecutorError> {
+        if let Ok(conversation_id) = ConversationId::from_string(session_id) {
+            match self
+                .resume_conversation_by_id(conversation_id, overrides.clone())
+                .await
+            {
+                Ok(response) => {
+                    tracing::debug!(
+                        "resumed session {session_id} by conversation id, response {:?}",
+                        response
+                    );
+                    return Ok(response);
+                }
+                Err(err) => tracing::debug!(
+                    "resume by conversation id failed for {session_id}, forking its rollout \
+                     file: {err}"
+                ),
+            }
+        }
+
+        let (rollout_path, _forked_session_id) =
+            SessionHandler::fork_rollout_file(session_id, codex_home, rollout_dir)
+                .map_err(|err| ExecutorError::FollowUpNotSupported(err.to_string()))?;
+        let response = self
+            .resume_conversation(rollout_path.clone(), overrides)
+            .await?;
+        tracing::debug!(
+            "resuming session using rollout file {}, response {:?}",
+            rollout_path.display(),
+            response
+        );
+        Ok(response)
+    }
+
     pub async fn add_conversation_listener(
         &self,
         conversation_id: codex_protocol::ConversationId,
@@ -205,10 +300,27 @@ impl AppServerClient {
                 Ok(())
             }
             ServerRequest::ExecCommandApproval { request_id, params } => {
+                if let Some(pattern) = self.forbidden_pattern(&params.command) {
+                    tracing::info!("blocking forbidden command: {:?}", params.command);
+                    self.log_writer
+                        .log_raw(
+                            &Approval::command_blocked(
+                                params.call_id,
+                                command_line(&params.command),
+                                pattern.to_string(),
+                            )
+                            .raw(),
+                        )
+                        .await?;
+                    let response = ExecCommandApprovalResponse {
+                        decision: ReviewDecision::Denied,
+                    };
+                    return send_server_response(peer, request_id, response).await;
+                }
                 let input = serde_json::to_value(&params)
                     .map_err(|err| ExecutorError::Io(io::Error::other(err.to_string())))?;
                 let status = match self
-                    .request_tool_approval("bash", input, &params.call_id)
+                    .request_command_approval(&params.command, input, &params.call_id)
                     .await
                 {
                     Ok(status) => status,
@@ -270,6 +382,27 @@ impl AppServerClient {
             .await?)
     }
 
+    /// Approve `command` outright when it is pre-approved, otherwise ask the approvals service.
+    async fn request_command_approval(
+        &self,
+        command: &[String],
+        tool_input: Value,
+        tool_call_id: &str,
+    ) -> Result<ApprovalStatus, ExecutorError> {
+        if is_pre_approved(&self.pre_approved, command) {
+            tracing::debug!("auto-approving pre-approved command: {command:?}");
+            return Ok(ApprovalStatus::Approved);
+        }
+        self.request_tool_approval("bash", tool_input, tool_call_id)
+            .await
+    }
+
+    /// The `forbidden` pattern `command` matches. Checked ahead of auto-approval, so a
+    /// forbidden command is denied in every approval mode.
+    fn forbidden_pattern(&self, command: &[String]) -> Option<&str> {
+        forbidden_pattern(&self.forbidden, command)
+    }
+
     pub async fn register_session(
         &self,
         conversation_id: &ConversationId,
@@ -529,3 +662,417 @@ impl LogWriter {
         Ok(())
     }
 }
+
+/// The command line Codex wants to run: the script of a `sh -c`/`bash -lc` invocation, or the
+/// argv joined back into a command line.
+fn command_line(command: &[String]) -> String {
+    match command {
+        [shell, flag, script] if shell.ends_with("sh") && matches!(flag.as_str(), "-c" | "-lc") => {
+            script.trim().to_string()
+        }
+        _ => shlex::try_join(command.iter().map(String::as_str))
+            .unwrap_or_else(|_| command.join(" ")),
+    }
+}
+
+/// Whether `command` matches an entry of `allowlist`. Command lines that chain, pipe,
+/// redirect or substitute are never pre-approved, so an allowed prefix cannot smuggle in
+/// another command.
+fn is_pre_approved(allowlist: &[AllowedCommand], command: &[String]) -> bool {
+    let line = command_line(command);
+    if line.contains(['\n', ';', '&', '|', '`', '$', '>', '<']) {
+        return false;
+    }
+    let Some(words) = shlex::split(&line) else {
+        return false;
+    };
+    allowlist.iter().any(|allowed| allowed.matches(&words))
+}
+
+/// A pre-approved [`CommandRules`] entry. It covers exactly the command it names; a trailing
+/// `*` also covers the command followed by any further arguments.
+#[derive(Debug)]
+struct AllowedCommand {
+    words: Vec<String>,
+    extra_args: bool,
+}
+
+impl AllowedCommand {
+    /// `None` for blank entries and ones with unbalanced quotes.
+    fn parse(entry: &str) -> Option<Self> {
+        let entry = entry.trim();
+        let (entry, extra_args) = match entry.strip_suffix('*') {
+            Some(prefix) => (prefix, true),
+            None => (entry, false),
+        };
+        let words = shlex::split(entry)?;
+        (!words.is_empty()).then_some(Self { words, extra_args })
+    }
+
+    fn matches(&self, argv: &[String]) -> bool {
+        if self.extra_args {
+            argv.starts_with(&self.words)
+        } else {
+            argv == self.words.as_slice()
+        }
+    }
+}
+
+/// The first `denylist` pattern matching the command line or any command chained, piped or
+/// backgrounded within it.
+fn forbidden_pattern<'a>(denylist: &'a [CommandPattern], command: &[String]) -> Option<&'a str> {
+    let line = command_line(command);
+    let segments: Vec<&str> = std::iter::once(line.as_str())
+        .chain(line.split(['\n', ';', '&', '|']).map(str::trim))
+        .filter(|segment| !segment.is_empty())
+        .collect();
+    denylist
+        .iter()
+        .find(|pattern| segments.iter().any(|segment| pattern.matches(segment)))
+        .map(|pattern| pattern.source.as_str())
+}
+
+/// A forbidden [`CommandRules`] entry, compiled once when the client is built. `*` matches
+/// any run of characters and the pattern must cover the whole line. Without a wildcard the
+/// pattern matches the line exactly or followed by further arguments.
+#[derive(Debug)]
+struct CommandPattern {
+    source: String,
+    /// Set for patterns containing a `*`.
+    regex: Option<Regex>,
+}
+
+impl CommandPattern {
+    /// Compile the non-blank entries of `patterns`.
+    fn compile_all(patterns: &[String]) -> Vec<Self> {
+        patterns
+            .iter()
+            .map(|pattern| pattern.trim())
+            .filter(|pattern| !pattern.is_empty())
+            .filter_map(Self::compile)
+            .collect()
+    }
+
+    fn compile(pattern: &str) -> Option<Self> {
+        let regex = if pattern.contains('*') {
+            let regex = pattern
+                .split('*')
+                .map(regex::escape)
+                .collect::<Vec<_>>()
+                .join(".*");
+            match Regex::new(&format!("(?s)^{regex}$")) {
+                Ok(regex) => Some(regex),
+                Err(err) => {
+                    tracing::warn!("ignoring command pattern `{pattern}`: {err}");
+                    return None;
+                }
+            }
+        } else {
+            None
+        };
+        Some(Self {
+            source: pattern.to_string(),
+            regex,
+        })
+    }
+
+    fn matches(&self, line: &str) -> bool {
+        match &self.regex {
+            Some(regex) => regex.is_match(line),
+            None => line
+                .strip_prefix(self.source.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with(' ')),
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::process::Stdio;
+
+    use tokio::{process::Command, sync::oneshot};
+
+    use super::*;
+    use crate::executors::codex::jsonrpc::ExitSignalSender;
+
+    const CONVERSATION_ID: &str = "5973b6c0-94b8-487b-a530-2aeb6098ae0e";
+    /// The conversation the mock server answers a rollout-path resume with.
+    const FORKED_CONVERSATION_ID: &str = "0f6e2b8a-3c1d-4e5f-9a7b-8c9d0e1f2a3b";
+
+    /// Answers `resumeConversation` by conversation id or by rollout path, and errors on
+    /// anything else.
+    fn mock_app_server() -> Command {
+        let script = format!(
+            r#"while IFS= read -r line; do
+  id=$(printf '%s' "$line" | sed -n 's/.*"id":\([0-9]*\).*/\1/p')
+  case "$line" in
+    *'"conversationId":"{CONVERSATION_ID}"'*)
+      printf '{{"id":%s,"result":{{"conversationId":"{CONVERSATION_ID}","model":"gpt-5","rolloutPath":"/tmp/rollout.jsonl"}}}}\n' "$id" ;;
+    *'"path":"'*)
+      printf '{{"id":%s,"result":{{"conversationId":"{FORKED_CONVERSATION_ID}","model":"gpt-5","rolloutPath":"/tmp/forked.jsonl"}}}}\n' "$id" ;;
+    *)
+      printf '{{"id":%s,"error":{{"code":-32600,"message":"unexpected request"}}}}\n' "$id" ;;
+  esac
+done"#
+        );
+        let mut command = Command::new("sh");
+        command
+            .arg("-c")
+            .arg(script)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .kill_on_drop(true);
+        command
+    }
+
+    fn connect_client(child: &mut tokio::process::Child) -> Arc<AppServerClient> {
+        let client = AppServerClient::new(
+            LogWriter::new(tokio::io::sink()),
+            None,
+            true,
+            CommandRules::default(),
+        );
+        let (exit_tx, _exit_rx) = oneshot::channel();
+        let peer = JsonRpcPeer::spawn(
+            child.stdin.take().unwrap(),
+            child.stdout.take().unwrap(),
+            client.clone(),
+            ExitSignalSender::new(exit_tx),
+        );
+        client.connect(peer);
+        client
+    }
+
+    fn shell(script: &str) -> tokio::process::Child {
+        Command::new("sh")
+            .arg("-c")
+            .arg(script)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn handshake_flags_a_program_that_is_not_codex() {
+        let mut exits = shell("echo 'codex: unknown command app-server'; exit 2");
+        let client = connect_client(&mut exits);
+        let err = client
+            .handshake("codex", Duration::from_secs(5))
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(
+                &err,
+                ExecutorError::UnexpectedExecutable { program, .. } if program == "codex"
+            ),
+            "{err:?}"
+        );
+
+        let mut hangs = shell("echo 'Welcome to codex!'; sleep 30");
+        let client = connect_client(&mut hangs);
+        let err = client
+            .handshake("codex", Duration::from_millis(300))
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err, ExecutorError::UnexpectedExecutable { .. }),
+            "{err:?}"
+        );
+        hangs.kill().await.unwrap();
+    }
+
+    /// Write a rollout file for `session_id` under `codex_home`.
+    fn write_rollout(codex_home: &Path, session_id: &str) {
+        let day_dir = codex_home.join("sessions/2025/01/01");
+        std::fs::create_dir_all(&day_dir).unwrap();
+        std::fs::write(
+            day_dir.join(format!("rollout-2025-01-01T00-00-00-{session_id}.jsonl")),
+            format!("{{\"type\":\"session_meta\",\"payload\":{{\"id\":\"{session_id}\"}}}}\n"),
+        )
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn resume_session_prefers_the_conversation_id() {
+        // The rollout file is there too, but isn't forked while the server has the conversation
+        let codex_home = tempfile::tempdir().unwrap();
+        write_rollout(codex_home.path(), CONVERSATION_ID);
+        let mut child = mock_app_server().spawn().unwrap();
+        let client = connect_client(&mut child);
+
+        let response = client
+            .resume_session(
+                CONVERSATION_ID,
+                NewConversationParams::default(),
+                Some(codex_home.path()),
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.conversation_id.to_string(), CONVERSATION_ID);
+        let sessions = walkdir::WalkDir::new(codex_home.path().join("sessions"))
+            .into_iter()
+            .filter(|entry| entry.as_ref().unwrap().file_type().is_file())
+            .count();
+        assert_eq!(sessions, 1);
+
+        child.kill().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn resume_session_falls_back_to_the_rollout_file() {
+        // A conversation the server doesn't know any more
+        let session_id = "1d2c3b4a-5e6f-4a7b-8c9d-0e1f2a3b4c5d";
+        let codex_home = tempfile::tempdir().unwrap();
+        write_rollout(codex_home.path(), session_id);
+        let mut child = mock_app_server().spawn().unwrap();
+        let client = connect_client(&mut child);
+
+        let response = client
+            .resume_session(
+                session_id,
+                NewConversationParams::default(),
+                Some(codex_home.path()),
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.conversation_id.to_string(), FORKED_CONVERSATION_ID);
+
+        child.kill().await.unwrap();
+    }
+
+    /// Records which tool calls reached the approvals service and leaves them pending.
+    #[derive(Default)]
+    struct RecordingApprovals {
+        requested: std::sync::Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl ExecutorApprovalService for RecordingApprovals {
+        async fn request_tool_approval(
+            &self,
+            _tool_name: &str,
+            _tool_input: Value,
+            tool_call_id: &str,
+        ) -> Result<ApprovalStatus, ExecutorApprovalError> {
+            self.requested
+                .lock()
+                .unwrap()
+                .push(tool_call_id.to_string());
+            Ok(ApprovalStatus::Pending)
+        }
+    }
+
+    fn argv(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[tokio::test]
+    async fn pre_approved_commands_skip_the_approvals_service() {
+        let approvals = Arc::new(RecordingApprovals::default());
+        let client = AppServerClient::new(
+            LogWriter::new(tokio::io::sink()),
+            Some(approvals.clone()),
+            false,
+            CommandRules {
+                pre_approved: argv(&["git status", "cargo check *"]),
+                forbidden: Vec::new(),
+            },
+        );
+
+        let status = client
+            .request_command_approval(&argv(&["bash", "-lc", "git status"]), Value::Null, "call-1")
+            .await
+            .unwrap();
+        assert!(matches!(status, ApprovalStatus::Approved));
+        let status = client
+            .request_command_approval(&argv(&["cargo", "check", "--all"]), Value::Null, "call-2")
+            .await
+            .unwrap();
+        assert!(matches!(status, ApprovalStatus::Approved));
+
+        for (call_id, command) in [
+            ("call-3", argv(&["bash", "-lc", "rm -rf target"])),
+            (
+                "call-4",
+                argv(&["bash", "-lc", "git status && rm -rf target"]),
+            ),
+            ("call-5", argv(&["git", "status", "--porcelain"])),
+            ("call-6", argv(&["cargo", "checkout"])),
+        ] {
+            let status = client
+                .request_command_approval(&command, Value::Null, call_id)
+                .await
+                .unwrap();
+            assert!(matches!(status, ApprovalStatus::Pending));
+        }
+        assert_eq!(
+            *approvals.requested.lock().unwrap(),
+            ["call-3", "call-4", "call-5", "call-6"]
+        );
+    }
+
+    #[tokio::test]
+    async fn forbidden_commands_are_blocked_even_when_auto_approving() {
+        // Echo the client's responses to stderr so the test can read them back
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg("head -n 2 >&2")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .unwrap();
+        let mut stderr = child.stderr.take().unwrap();
+        let client = AppServerClient::new(
+            LogWriter::new(tokio::io::sink()),
+            None,
+            true,
+            CommandRules {
+                pre_approved: Vec::new(),
+                forbidden: argv(&["rm -rf *", "git push --force"]),
+            },
+        );
+        let (exit_tx, _exit_rx) = oneshot::channel();
+        let peer = JsonRpcPeer::spawn(
+            child.stdin.take().unwrap(),
+            child.stdout.take().unwrap(),
+            client.clone(),
+            ExitSignalSender::new(exit_tx),
+        );
+
+        for (id, command) in [
+            (1, argv(&["bash", "-lc", "git status && rm -rf /"])),
+            (2, argv(&["git", "status"])),
+        ] {
+            let params = serde_json::from_value(serde_json::json!({
+                "conversationId": CONVERSATION_ID,
+                "callId": format!("call-{id}"),
+                "command": command,
+                "cwd": "/tmp",
+                "parsedCmd": [],
+            }))
+            .unwrap();
+            let request = ServerRequest::ExecCommandApproval {
+                request_id: RequestId::Integer(id),
+                params,
+            };
+            client.handle_server_request(&peer, request).await.unwrap();
+        }
+
+        let mut output = String::new();
+        tokio::io::AsyncReadExt::read_to_string(&mut stderr, &mut output)
+            .await
+            .unwrap();
+        let decisions: Vec<Value> = output
+            .lines()
+            .map(|line| serde_json::from_str::<Value>(line).unwrap()["result"]["decision"].clone())
+            .collect();
+        assert_eq!(decisions, ["denied", "approved_for_session"]);
+        child.wait().await.unwrap();
+    }
+}
@@ -11,7 +11,7 @@ use std::{
     io,
     sync::{
         Arc,
-        atomic::{AtomicI64, Ordering},
+        atomic::{AtomicBool, AtomicI64, Ordering},
     },
 };
 
@@ -22,13 +22,22 @@ use codex_app_server_protocol::{
 use serde::{Serialize, de::DeserializeOwned};
 use serde_json::Value;
 use tokio::{
-    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    io::{AsyncWriteExt, BufReader},
     process::{ChildStdin, ChildStdout},
     sync::{Mutex, oneshot},
 };
+use workspace_utils::stream_lines::read_line_lossy;
 
 use crate::executors::{ExecutorError, ExecutorExitResult};
 
+/// Largest frame [`FrameBuffer`] holds while waiting for the rest of it; past this the text is
+/// passed on as non-JSON output.
+const MAX_PENDING_FRAME_BYTES: usize = 64 * 1024;
+
+/// Keys a JSON-RPC message object opens with. Only lines starting like one of these are held
+/// back, so a stray `{` in log output never swallows the frames after it.
+const FRAME_KEYS: [&str; 4] = ["\"jsonrpc\"", "\"id\"", "\"method\"", "\"error\""];
+
 #[derive(Debug)]
 pub enum PendingResponse {
     Result(Value),
@@ -60,6 +69,7 @@ pub struct JsonRpcPeer {
     stdin: Arc<Mutex<ChildStdin>>,
     pending: Arc<Mutex<HashMap<RequestId, oneshot::Sender<PendingResponse>>>>,
     id_counter: Arc<AtomicI64>,
+    received_message: Arc<AtomicBool>,
 }
 
 impl JsonRpcPeer {
@@ -73,6 +83,7 @@ impl JsonRpcPeer {
             stdin: Arc::new(Mutex::new(stdin)),
             pending: Arc::new(Mutex::new(HashMap::new())),
             id_counter: Arc::new(AtomicI64::new(1)),
+            received_message: Arc::new(AtomicBool::new(false)),
         };
 
         let reader_peer = peer.clone();
@@ -80,79 +91,89 @@ impl JsonRpcPeer {
 
         tokio::spawn(async move {
             let mut reader = BufReader::new(stdout);
-            let mut buffer = String::new();
-
-            loop {
-                buffer.clear();
-                match reader.read_line(&mut buffer).await {
-                    Ok(0) => break,
-                    Ok(_) => {
-                        let line = buffer.trim_end_matches(['\n', '\r']);
-                        if line.is_empty() {
-                            continue;
+            let mut buffer = Vec::new();
+            let mut frames = FrameBuffer::default();
+
+            'read: loop {
+                let ready = match read_line_lossy(&mut reader, &mut buffer).await {
+                    Ok(None) => {
+                        if let Some(rest) = frames.finish() {
+                            let _ = callbacks.on_non_json(&rest).await;
                         }
+                        break;
+                    }
+                    Ok(Some(raw)) => frames.push(raw),
+                    Err(err) => {
+                        tracing::warn!("Error reading Codex output: {err}");
+                        break;
+                    }
+                };
+                for frame in ready {
+                    let line = frame.as_str();
+                    if line.is_empty() {
+                        continue;
+                    }
 
-                        match serde_json::from_str::<JSONRPCMessage>(line) {
-                            Ok(JSONRPCMessage::Response(response)) => {
-                                let request_id = response.id.clone();
-                                let result = response.result.clone();
-                                if callbacks
-                                    .on_response(&reader_peer, line, &response)
-                                    .await
-                                    .is_err()
-                                {
-                                    break;
-                                }
-                                reader_peer
-                                    .resolve(request_id, PendingResponse::Result(result))
-                                    .await;
+                    let message = serde_json::from_str::<JSONRPCMessage>(line);
+                    if message.is_ok() {
+                        reader_peer.received_message.store(true, Ordering::Relaxed);
+                    }
+                    match message {
+                        Ok(JSONRPCMessage::Response(response)) => {
+                            let request_id = response.id.clone();
+                            let result = response.result.clone();
+                            if callbacks
+                                .on_response(&reader_peer, line, &response)
+                                .await
+                                .is_err()
+                            {
+                                break 'read;
                             }
-                            Ok(JSONRPCMessage::Error(error)) => {
-                                let request_id = error.id.clone();
-                                if callbacks
-                                    .on_error(&reader_peer, line, &error)
-                                    .await
-                                    .is_err()
-                                {
-                                    break;
-                                }
-                                reader_peer
-                                    .resolve(request_id, PendingResponse::Error(error))
-                                    .await;
+                            reader_peer
+                                .resolve(request_id, PendingResponse::Result(result))
+                                .await;
+                        }
+                        Ok(JSONRPCMessage::Error(error)) => {
+                            let request_id = error.id.clone();
+                            if callbacks
+                                .on_error(&reader_peer, line, &error)
+                                .await
+                                .is_err()
+                            {
+                                break 'read;
                             }
-                            Ok(JSONRPCMessage::Request(request)) => {
-                                if callbacks
-                                    .on_request(&reader_peer, line, request)
-                                    .await
-                                    .is_err()
-                                {
-                                    break;
-                                }
+                            reader_peer
+                                .resolve(request_id, PendingResponse::Error(error))
+                                .await;
+                        }
+                        Ok(JSONRPCMessage::Request(request)) => {
+                            if callbacks
+                                .on_request(&reader_peer, line, request)
+                                .await
+                                .is_err()
+                            {
+                                break 'read;
                             }
-                            Ok(JSONRPCMessage::Notification(notification)) => {
-                                match callbacks
-                                    .on_notification(&reader_peer, line, notification)
-                                    .await
-                                {
-                                    // finished
-                                    Ok(true) => break,
-                                    Ok(false) => {}
-                                    Err(_) => {
-                                        break;
-                                    }
+                        }
+                        Ok(JSONRPCMessage::Notification(notification)) => {
+                            match callbacks
+                                .on_notification(&reader_peer, line, notification)
+                                .await
+                            {
+                                // finished
+                                Ok(true) => break 'read,
+                                Ok(false) => {}
+                                Err(_) => {
+                                    break 'read;
                                 }
                             }
-                            Err(_) => {
-                                if callbacks.on_non_json(line).await.is_err() {
-                                    break;
-                                }
+                        }
+                        Err(_) => {
+                            if callbacks.on_non_json(line).await.is_err() {
+                                break 'read;
                             }
                         }
                     }
-                    Err(err) => {
-                        tracing::warn!("Error reading Codex output: {err}");
-                        break;
-                    }
                 }
             }
 
@@ -163,6 +184,11 @@ impl JsonRpcPeer {
         peer
     }
 
+    /// Whether the other side has sent anything that parsed as JSON-RPC.
+    pub fn has_received_message(&self) -> bool {
+        self.received_message.load(Ordering::Relaxed)
+    }
+
     pub fn next_request_id(&self) -> RequestId {
         RequestId::Integer(self.id_counter.fetch_add(1, Ordering::Relaxed))
     }
@@ -223,6 +249,102 @@ impl JsonRpcPeer {
     }
 }
 
+/// Reassembles JSON-RPC frames that reach us split over several lines. A line that starts a
+/// JSON-RPC object but ends before it closes is held back and joined with the following lines
+/// until the object is complete; everything else passes straight through.
+#[derive(Debug, Default)]
+struct FrameBuffer {
+    pending: String,
+    scan: JsonScan,
+}
+
+impl FrameBuffer {
+    /// Feed one line of output, getting back the complete frames and non-JSON lines it makes
+    /// available, in order.
+    fn push(&mut self, line: String) -> Vec<String> {
+        let mut ready = Vec::new();
+        if !self.pending.is_empty() {
+            if !starts_like_frame(&line) {
+                self.pending.push('\n');
+                self.pending.push_str(&line);
+                self.scan.feed(&line);
+                if self.scan.is_open() && self.pending.len() < MAX_PENDING_FRAME_BYTES {
+                    return ready;
+                }
+                ready.push(self.take());
+                return ready;
+            }
+            // A new frame starts before the held text closed: that text was never a frame
+            ready.push(self.take());
+        }
+
+        if starts_like_frame(&line) {
+            self.scan.feed(&line);
+            if self.scan.is_open() {
+                self.pending = line;
+                return ready;
+            }
+            self.scan = JsonScan::default();
+        }
+        ready.push(line);
+        ready
+    }
+
+    /// Whatever is still held back when the output ends.
+    fn finish(&mut self) -> Option<String> {
+        (!self.pending.is_empty()).then(|| self.take())
+    }
+
+    fn take(&mut self) -> String {
+        self.scan = JsonScan::default();
+        std::mem::take(&mut self.pending)
+    }
+}
+
+/// Whether `text` opens an object with one of the [`FRAME_KEYS`].
+fn starts_like_frame(text: &str) -> bool {
+    text.trim_start().strip_prefix('{').is_some_and(|rest| {
+        FRAME_KEYS
+            .iter()
+            .any(|key| rest.trim_start().starts_with(key))
+    })
+}
+
+/// Tracks how deeply nested the JSON fed so far is, one chunk at a time, so a held-back frame is
+/// never re-parsed from the start.
+#[derive(Debug, Default)]
+struct JsonScan {
+    depth: usize,
+    in_string: bool,
+    escaped: bool,
+}
+
+impl JsonScan {
+    fn feed(&mut self, text: &str) {
+        for byte in text.bytes() {
+            if self.in_string {
+                match byte {
+                    _ if self.escaped => self.escaped = false,
+                    b'\\' => self.escaped = true,
+                    b'"' => self.in_string = false,
+                    _ => {}
+                }
+                continue;
+            }
+            match byte {
+                b'"' => self.in_string = true,
+                b'{' | b'[' => self.depth += 1,
+                b'}' | b']' => self.depth = self.depth.saturating_sub(1),
+                _ => {}
+            }
+        }
+    }
+
+    fn is_open(&self) -> bool {
+        self.depth > 0
+    }
+}
+
 pub type PendingReceiver = oneshot::Receiver<PendingResponse>;
 
 pub async fn await_response<R>(receiver: PendingReceiver, label: &str) -> Result<R, ExecutorError>
@@ -280,3 +402,76 @@ pub trait JsonRpcCallbacks: Send + Sync {
 
     async fn on_non_json(&self, _raw: &str) -> Result<(), ExecutorError>;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn response_split_across_lines_is_reassembled() {
+        let response = r#"{"id":7,"result":{"conversationId":"c1","model":"gpt-5"}}"#;
+        // Pretty-printing servers break frames between tokens, never inside a string
+        let (first, second) = response.split_at(response.find(r#""result""#).unwrap());
+        let mut frames = FrameBuffer::default();
+
+        assert!(frames.push(first.to_string()).is_empty());
+        let ready = frames.push(second.to_string());
+        let [frame] = ready.as_slice() else {
+            panic!("expected one frame, got {ready:?}");
+        };
+        let Ok(JSONRPCMessage::Response(parsed)) = serde_json::from_str::<JSONRPCMessage>(frame)
+        else {
+            panic!("expected a response, got {frame}");
+        };
+        assert_eq!(parsed.id, RequestId::Integer(7));
+        assert_eq!(parsed.result["model"], "gpt-5");
+        assert_eq!(frames.finish(), None);
+    }
+
+    #[test]
+    fn complete_and_non_json_lines_pass_through() {
+        let mut frames = FrameBuffer::default();
+        let notification = r#"{"method":"codex/event","params":{}}"#;
+        assert_eq!(frames.push(notification.to_string()), [notification]);
+        assert_eq!(frames.push("{ not json".to_string()), ["{ not json"]);
+        assert_eq!(frames.push("plain log".to_string()), ["plain log"]);
+        assert_eq!(frames.push(r#"{"note":"}"#.to_string()), [r#"{"note":"}"#]);
+
+        assert!(frames.push(r#"{"id":1,"#.to_string()).is_empty());
+        assert_eq!(frames.finish().as_deref(), Some(r#"{"id":1,"#));
+    }
+
+    #[test]
+    fn stray_brace_line_does_not_hold_back_following_frames() {
+        let mut frames = FrameBuffer::default();
+        let notification = r#"{"method":"codex/event","params":{"msg":"}{"}}"#;
+        let response = r#"{"id":2,"result":{}}"#;
+
+        assert_eq!(frames.push("{".to_string()), ["{"]);
+        assert_eq!(frames.push(notification.to_string()), [notification]);
+
+        // A frame that never closes is given up on as soon as the next frame starts
+        assert!(frames.push(r#"{"id":1,"result":{"#.to_string()).is_empty());
+        assert_eq!(
+            frames.push(response.to_string()),
+            [r#"{"id":1,"result":{"#, response]
+        );
+        assert_eq!(frames.finish(), None);
+    }
+
+    #[test]
+    fn pending_frame_is_capped() {
+        let mut frames = FrameBuffer::default();
+        assert!(frames.push(r#"{"id":1,"result":["#.to_string()).is_empty());
+        let filler = "1,".repeat(1024);
+        let mut released = Vec::new();
+        while released.is_empty() {
+            released = frames.push(filler.clone());
+        }
+        let [held] = released.as_slice() else {
+            panic!("expected the held text once, got {} chunks", released.len());
+        };
+        assert!(held.len() >= MAX_PENDING_FRAME_BYTES);
+        assert_eq!(frames.finish(), None);
+    }
+}
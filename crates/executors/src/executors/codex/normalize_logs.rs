@@ -14,11 +14,12 @@ use codex_protocol::{
     protocol::{
         AgentMessageDeltaEvent, AgentMessageEvent, AgentReasoningDeltaEvent, AgentReasoningEvent,
         AgentReasoningSectionBreakEvent, ApplyPatchApprovalRequestEvent, BackgroundEventEvent,
-        ErrorEvent, EventMsg, ExecApprovalRequestEvent, ExecCommandBeginEvent, ExecCommandEndEvent,
-        ExecCommandOutputDeltaEvent, ExecOutputStream, FileChange as CodexProtoFileChange,
-        McpInvocation, McpToolCallBeginEvent, McpToolCallEndEvent, PatchApplyBeginEvent,
-        PatchApplyEndEvent, StreamErrorEvent, ViewImageToolCallEvent, WarningEvent,
-        WebSearchBeginEvent, WebSearchEndEvent,
+        CodexErrorInfo, ErrorEvent, EventMsg, ExecApprovalRequestEvent, ExecCommandBeginEvent,
+        ExecCommandEndEvent, ExecCommandOutputDeltaEvent, ExecOutputStream, ExitedReviewModeEvent,
+        FileChange as CodexProtoFileChange, McpInvocation, McpStartupStatus, McpStartupUpdateEvent,
+        McpToolCallBeginEvent, McpToolCallEndEvent, PatchApplyBeginEvent, PatchApplyEndEvent,
+        StreamErrorEvent, ViewImageToolCallEvent, WarningEvent, WebSearchBeginEvent,
+        WebSearchEndEvent,
     },
 };
 use futures::StreamExt;
@@ -37,6 +38,8 @@ use crate::{
         ActionType, CommandExitStatus, CommandRunResult, FileChange, NormalizedEntry,
         NormalizedEntryError, NormalizedEntryType, TodoItem, ToolResult, ToolResultValueType,
         ToolStatus,
+        context_window::{context_window_exceeded_entry, is_context_window_exceeded},
+        mcp_startup::{mcp_server_failed_entry, parse_mcp_startup_failure},
         stderr_processor::normalize_stderr_logs,
         utils::{
             ConversationPatch, EntryIndexProvider,
@@ -106,6 +109,7 @@ impl ToNormalizedEntry for CommandState {
                 tool_call_id: self.call_id.clone(),
             })
             .ok(),
+            agent: None,
         }
     }
 }
@@ -133,6 +137,7 @@ impl ToNormalizedEntry for McpToolState {
             },
             content: self.invocation.tool.clone(),
             metadata: None,
+            agent: None,
         }
     }
 }
@@ -166,6 +171,7 @@ impl ToNormalizedEntry for WebSearchState {
                 .clone()
                 .unwrap_or_else(|| "Web search".to_string()),
             metadata: None,
+            agent: None,
         }
     }
 }
@@ -203,10 +209,37 @@ impl ToNormalizedEntry for PatchEntry {
                 tool_call_id: self.call_id.clone(),
             })
             .ok(),
+            agent: None,
         }
     }
 }
 
+/// Metadata attached to the entry emitted when Codex escalates a command out of the sandbox.
+#[derive(Serialize)]
+struct SandboxEscalation {
+    tool_call_id: String,
+    command: String,
+    reason: Option<String>,
+}
+
+fn is_sandbox_retry_reason(reason: &str) -> bool {
+    let reason = reason.to_ascii_lowercase();
+    reason.contains("sandbox") && reason.contains("retry")
+}
+
+fn sandbox_escalation_entry(escalation: SandboxEscalation) -> NormalizedEntry {
+    NormalizedEntry {
+        timestamp: None,
+        entry_type: NormalizedEntryType::SystemMessage,
+        content: format!(
+            "Command failed in the sandbox; retrying without sandbox: `{}`",
+            escalation.command
+        ),
+        metadata: serde_json::to_value(escalation).ok(),
+        agent: None,
+    }
+}
+
 struct LogState {
     entry_index: EntryIndexProvider,
     assistant: Option<StreamingText>,
@@ -215,6 +248,9 @@ struct LogState {
     mcp_tools: HashMap<String, McpToolState>,
     patches: HashMap<String, PatchState>,
     web_searches: HashMap<String, WebSearchState>,
+    /// Commands that exited non-zero, keyed by call id. An approval request for one of these
+    /// is Codex asking to rerun it outside the sandbox.
+    failed_commands: HashMap<String, String>,
 }
 
 enum StreamingTextKind {
@@ -232,6 +268,7 @@ impl LogState {
             mcp_tools: HashMap::new(),
             patches: HashMap::new(),
             web_searches: HashMap::new(),
+            failed_commands: HashMap::new(),
         }
     }
 
@@ -267,6 +304,7 @@ impl LogState {
             },
             content: content.clone(),
             metadata: None,
+            agent: None,
         };
         (normalized_entry, index, is_new)
     }
@@ -346,6 +384,92 @@ fn normalize_file_changes(
         .collect()
 }
 
+/// Parse a shell command that runs `apply_patch`, which is how Codex edits files when the
+/// dedicated apply-patch tool is disabled, into the file changes the patch makes.
+fn parse_apply_patch_command(
+    worktree_path: &str,
+    command: &[String],
+) -> Option<Vec<(String, Vec<FileChange>)>> {
+    let script = command.last()?;
+    let begin = script.find("*** Begin Patch")?;
+    if !script[..begin].contains("apply_patch") && !script[..begin].contains("applypatch") {
+        return None;
+    }
+    let end = script[begin..].find("*** End Patch")? + begin;
+
+    enum Section {
+        Add(String),
+        Update {
+            body: String,
+            move_path: Option<String>,
+        },
+        Delete,
+    }
+
+    let mut sections: Vec<(String, Section)> = Vec::new();
+    for line in script[begin..end].lines().skip(1) {
+        if let Some(path) = line.strip_prefix("*** Add File: ") {
+            sections.push((path.trim().to_string(), Section::Add(String::new())));
+        } else if let Some(path) = line.strip_prefix("*** Update File: ") {
+            sections.push((
+                path.trim().to_string(),
+                Section::Update {
+                    body: String::new(),
+                    move_path: None,
+                },
+            ));
+        } else if let Some(path) = line.strip_prefix("*** Delete File: ") {
+            sections.push((path.trim().to_string(), Section::Delete));
+        } else if let Some(dest) = line.strip_prefix("*** Move to: ") {
+            if let Some((_, Section::Update { move_path, .. })) = sections.last_mut() {
+                *move_path = Some(dest.trim().to_string());
+            }
+        } else if line.starts_with("*** ") {
+            // e.g. `*** End of File`
+            continue;
+        } else {
+            match sections.last_mut() {
+                Some((_, Section::Add(content))) => {
+                    content.push_str(line.strip_prefix('+').unwrap_or(line));
+                    content.push('\n');
+                }
+                Some((_, Section::Update { body, .. })) => {
+                    // Hunk headers carry context text instead of line numbers.
+                    body.push_str(if line.starts_with("@@") { "@@" } else { line });
+                    body.push('\n');
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let changes = sections
+        .into_iter()
+        .map(|(path, section)| {
+            let relative = make_path_relative(&path, worktree_path);
+            let file_changes = match section {
+                Section::Add(content) => vec![FileChange::Write { content }],
+                Section::Delete => vec![FileChange::Delete],
+                Section::Update { body, move_path } => {
+                    let mut edits = Vec::new();
+                    if let Some(dest) = move_path {
+                        edits.push(FileChange::Rename {
+                            new_path: make_path_relative(&dest, worktree_path),
+                        });
+                    }
+                    edits.push(FileChange::Edit {
+                        unified_diff: normalize_unified_diff(&relative, &body),
+                        has_line_numbers: false,
+                    });
+                    edits
+                }
+            };
+            (relative, file_changes)
+        })
+        .collect::<Vec<_>>();
+    (!changes.is_empty()).then_some(changes)
+}
+
 fn format_todo_status(status: &StepStatus) -> String {
     match status {
         StepStatus::Pending => "pending",
@@ -355,7 +479,12 @@ fn format_todo_status(status: &StepStatus) -> String {
     .to_string()
 }
 
-pub fn normalize_logs(msg_store: Arc<MsgStore>, worktree_path: &Path) {
+/// Normalize the agent's output in `msg_store` into conversation entries. The returned handle
+/// completes once stdout has been fully processed.
+pub fn normalize_logs(
+    msg_store: Arc<MsgStore>,
+    worktree_path: &Path,
+) -> tokio::task::JoinHandle<()> {
     let entry_index = EntryIndexProvider::start_from(&msg_store);
     normalize_stderr_logs(msg_store.clone(), entry_index.clone());
 
@@ -473,14 +602,33 @@ pub fn normalize_logs(msg_store: Arc<MsgStore>, worktree_path: &Path) {
                     state.assistant = None;
                     state.thinking = None;
 
+                    let failed_command = state.failed_commands.remove(&call_id);
+                    let sandbox_retry = failed_command.is_some()
+                        || reason.as_deref().is_some_and(is_sandbox_retry_reason);
+
                     let command_text = if command.is_empty() {
-                        reason
-                            .filter(|r| !r.is_empty())
-                            .unwrap_or_else(|| "command execution".to_string())
+                        failed_command.unwrap_or_else(|| {
+                            reason
+                                .clone()
+                                .filter(|r| !r.is_empty())
+                                .unwrap_or_else(|| "command execution".to_string())
+                        })
                     } else {
                         command.join(" ")
                     };
 
+                    if sandbox_retry {
+                        add_normalized_entry(
+                            &msg_store,
+                            &entry_index,
+                            sandbox_escalation_entry(SandboxEscalation {
+                                tool_call_id: call_id.clone(),
+                                command: command_text.clone(),
+                                reason: reason.filter(|r| !r.is_empty()),
+                            }),
+                        );
+                    }
+
                     let command_state = state.commands.entry(call_id.clone()).or_default();
 
                     if command_state.command.is_empty() {
@@ -551,6 +699,43 @@ pub fn normalize_logs(msg_store: Arc<MsgStore>, worktree_path: &Path) {
                 }) => {
                     state.assistant = None;
                     state.thinking = None;
+                    if let Some(changes) = parse_apply_patch_command(&worktree_path_str, &command) {
+                        // Reuse the entry shown while the command awaited approval, if any.
+                        let mut reuse_index = state
+                            .commands
+                            .remove(&call_id)
+                            .and_then(|command_state| command_state.index);
+                        let mut patch_state = PatchState::default();
+                        for (path, file_changes) in changes {
+                            let mut entry = PatchEntry {
+                                index: None,
+                                path,
+                                changes: file_changes,
+                                status: ToolStatus::Created,
+                                awaiting_approval: false,
+                                call_id: call_id.clone(),
+                            };
+                            let index = match reuse_index.take() {
+                                Some(index) => {
+                                    replace_normalized_entry(
+                                        &msg_store,
+                                        index,
+                                        entry.to_normalized_entry(),
+                                    );
+                                    index
+                                }
+                                None => add_normalized_entry(
+                                    &msg_store,
+                                    &entry_index,
+                                    entry.to_normalized_entry(),
+                                ),
+                            };
+                            entry.index = Some(index);
+                            patch_state.entries.push(entry);
+                        }
+                        state.patches.insert(call_id, patch_state);
+                        continue;
+                    }
                     let command_text = command.join(" ");
                     if command_text.is_empty() {
                         continue;
@@ -618,6 +803,27 @@ pub fn normalize_logs(msg_store: Arc<MsgStore>, worktree_path: &Path) {
                     formatted_output,
                     process_id: _,
                 }) => {
+                    if let Some(patch_state) = state.patches.remove(&call_id) {
+                        // An `apply_patch` run through the shell.
+                        let status = if exit_code == 0 {
+                            ToolStatus::Success
+                        } else {
+                            ToolStatus::Failed
+                        };
+                        for mut entry in patch_state.entries {
+                            entry.status = status.clone();
+                            let Some(index) = entry.index else {
+                                tracing::error!("missing entry index for existing patch entry");
+                                continue;
+                            };
+                            replace_normalized_entry(
+                                &msg_store,
+                                index,
+                                entry.to_normalized_entry(),
+                            );
+                        }
+                        continue;
+                    }
                     if let Some(mut command_state) = state.commands.remove(&call_id) {
                         command_state.formatted_output = Some(formatted_output);
                         command_state.exit_code = Some(exit_code);
@@ -625,6 +831,9 @@ pub fn normalize_logs(msg_store: Arc<MsgStore>, worktree_path: &Path) {
                         command_state.status = if exit_code == 0 {
                             ToolStatus::Success
                         } else {
+                            state
+                                .failed_commands
+                                .insert(call_id.clone(), command_state.command.clone());
                             ToolStatus::Failed
                         };
                         let Some(index) = command_state.index else {
@@ -638,6 +847,16 @@ pub fn normalize_logs(msg_store: Arc<MsgStore>, worktree_path: &Path) {
                         );
                     }
                 }
+                EventMsg::McpStartupUpdate(McpStartupUpdateEvent {
+                    server,
+                    status: McpStartupStatus::Failed { error },
+                }) => {
+                    add_normalized_entry(
+                        &msg_store,
+                        &entry_index,
+                        mcp_server_failed_entry(&server, &error),
+                    );
+                }
                 EventMsg::BackgroundEvent(BackgroundEventEvent { message }) => {
                     add_normalized_entry(
                         &msg_store,
@@ -647,6 +866,7 @@ pub fn normalize_logs(msg_store: Arc<MsgStore>, worktree_path: &Path) {
                             entry_type: NormalizedEntryType::SystemMessage,
                             content: format!("Background event: {message}"),
                             metadata: None,
+                            agent: None,
                         },
                     );
                 }
@@ -664,6 +884,7 @@ pub fn normalize_logs(msg_store: Arc<MsgStore>, worktree_path: &Path) {
                             },
                             content: format!("Stream error: {message} {codex_error_info:?}"),
                             metadata: None,
+                            agent: None,
                         },
                     );
                 }
@@ -893,6 +1114,7 @@ pub fn normalize_logs(msg_store: Arc<MsgStore>, worktree_path: &Path) {
                             },
                             content: relative_path.to_string(),
                             metadata: None,
+                            agent: None,
                         },
                     );
                 }
@@ -933,6 +1155,7 @@ pub fn normalize_logs(msg_store: Arc<MsgStore>, worktree_path: &Path) {
                             },
                             content,
                             metadata: None,
+                            agent: None,
                         },
                     );
                 }
@@ -947,6 +1170,7 @@ pub fn normalize_logs(msg_store: Arc<MsgStore>, worktree_path: &Path) {
                             },
                             content: message,
                             metadata: None,
+                            agent: None,
                         },
                     );
                 }
@@ -954,9 +1178,15 @@ pub fn normalize_logs(msg_store: Arc<MsgStore>, worktree_path: &Path) {
                     message,
                     codex_error_info,
                 }) => {
-                    add_normalized_entry(
-                        &msg_store,
-                        &entry_index,
+                    let entry = if matches!(
+                        codex_error_info,
+                        Some(CodexErrorInfo::ContextWindowExceeded)
+                    ) || is_context_window_exceeded(&message)
+                    {
+                        context_window_exceeded_entry(&message)
+                    } else if let Some((server, reason)) = parse_mcp_startup_failure(&message) {
+                        mcp_server_failed_entry(&server, &reason)
+                    } else {
                         NormalizedEntry {
                             timestamp: None,
                             entry_type: NormalizedEntryType::ErrorMessage {
@@ -964,8 +1194,10 @@ pub fn normalize_logs(msg_store: Arc<MsgStore>, worktree_path: &Path) {
                             },
                             content: format!("Error: {message} {codex_error_info:?}"),
                             metadata: None,
-                        },
-                    );
+                            agent: None,
+                        }
+                    };
+                    add_normalized_entry(&msg_store, &entry_index, entry);
                 }
                 EventMsg::TokenCount(payload) => {
                     if let Some(info) = payload.info {
@@ -989,10 +1221,18 @@ pub fn normalize_logs(msg_store: Arc<MsgStore>, worktree_path: &Path) {
                                     info.model_context_window.unwrap_or_default()
                                 ),
                                 metadata: None,
+                                agent: None,
                             },
                         );
                     }
                 }
+                EventMsg::ExitedReviewMode(event) => {
+                    state.assistant = None;
+                    state.thinking = None;
+                    if let Some(entry) = review_summary_entry(&event) {
+                        add_normalized_entry(&msg_store, &entry_index, entry);
+                    }
+                }
                 EventMsg::ContextCompacted(..) => {
                     add_normalized_entry(
                         &msg_store,
@@ -1002,6 +1242,7 @@ pub fn normalize_logs(msg_store: Arc<MsgStore>, worktree_path: &Path) {
                             entry_type: NormalizedEntryType::SystemMessage,
                             content: "Context compacted".to_string(),
                             metadata: None,
+                            agent: None,
                         },
                     );
                 }
@@ -1027,13 +1268,35 @@ pub fn normalize_logs(msg_store: Arc<MsgStore>, worktree_path: &Path) {
                 | EventMsg::TurnAborted(..)
                 | EventMsg::ShutdownComplete
                 | EventMsg::EnteredReviewMode(..)
-                | EventMsg::ExitedReviewMode(..)
                 | EventMsg::TerminalInteraction(..)
                 | EventMsg::ElicitationRequest(..)
                 | EventMsg::TaskComplete(..) => {}
             }
         }
-    });
+    })
+}
+
+/// A review that found nothing ends without any findings to render, which reads like a
+/// failed run. Emit an explicit summary instead.
+fn review_summary_entry(event: &ExitedReviewModeEvent) -> Option<NormalizedEntry> {
+    let output = event.review_output.as_ref()?;
+    if !output.findings.is_empty() {
+        return None;
+    }
+
+    let explanation = output.overall_explanation.trim();
+    let content = if explanation.is_empty() {
+        "Review complete: no issues found.".to_string()
+    } else {
+        format!("Review complete: no issues found.\n\n{explanation}")
+    };
+    Some(NormalizedEntry {
+        timestamp: None,
+        entry_type: NormalizedEntryType::AssistantMessage,
+        content,
+        metadata: None,
+        agent: None,
+    })
 }
 
 fn handle_jsonrpc_response(
@@ -1079,6 +1342,7 @@ fn handle_model_params(
             entry_type: NormalizedEntryType::SystemMessage,
             content: params.join("  ").to_string(),
             metadata: None,
+            agent: None,
         },
     );
 }
@@ -1139,6 +1403,7 @@ impl ToNormalizedEntry for Error {
                 },
                 content: error.clone(),
                 metadata: None,
+                agent: None,
             },
             Error::AuthRequired { error } => NormalizedEntry {
                 timestamp: None,
@@ -1147,6 +1412,7 @@ impl ToNormalizedEntry for Error {
                 },
                 content: error.clone(),
                 metadata: None,
+                agent: None,
             },
         }
     }
@@ -1159,6 +1425,12 @@ pub enum Approval {
         tool_name: String,
         approval_status: ApprovalStatus,
     },
+    /// A command matching `forbidden_commands`, denied without asking.
+    CommandBlocked {
+        call_id: String,
+        command: String,
+        pattern: String,
+    },
 }
 
 impl Approval {
@@ -1174,13 +1446,24 @@ impl Approval {
         }
     }
 
+    pub fn command_blocked(call_id: String, command: String, pattern: String) -> Self {
+        Self::CommandBlocked {
+            call_id,
+            command,
+            pattern,
+        }
+    }
+
     pub fn raw(&self) -> String {
         serde_json::to_string(self).unwrap_or_default()
     }
 
     pub fn display_tool_name(&self) -> String {
-        let Self::ApprovalResponse { tool_name, .. } = self;
-        match tool_name.as_str() {
+        let tool_name = match self {
+            Self::ApprovalResponse { tool_name, .. } => tool_name.as_str(),
+            Self::CommandBlocked { .. } => "codex.exec_command",
+        };
+        match tool_name {
             "codex.exec_command" => "Exec Command".to_string(),
             "codex.apply_patch" => "Edit".to_string(),
             other => other.to_string(),
@@ -1190,11 +1473,26 @@ impl Approval {
 
 impl ToNormalizedEntryOpt for Approval {
     fn to_normalized_entry_opt(&self) -> Option<NormalizedEntry> {
-        let Self::ApprovalResponse {
-            call_id: _,
-            tool_name: _,
-            approval_status,
-        } = self;
+        let approval_status = match self {
+            Self::ApprovalResponse {
+                approval_status, ..
+            } => approval_status,
+            Self::CommandBlocked {
+                command, pattern, ..
+            } => {
+                return Some(NormalizedEntry {
+                    timestamp: None,
+                    entry_type: NormalizedEntryType::ErrorMessage {
+                        error_type: NormalizedEntryError::Other,
+                    },
+                    content: format!(
+                        "Blocked command `{command}`: it matches the forbidden pattern `{pattern}`"
+                    ),
+                    metadata: None,
+                    agent: None,
+                });
+            }
+        };
         let tool_name = self.display_tool_name();
 
         match approval_status {
@@ -1211,6 +1509,7 @@ impl ToNormalizedEntryOpt for Approval {
                     .trim()
                     .to_string(),
                 metadata: None,
+                agent: None,
             }),
             ApprovalStatus::TimedOut => Some(NormalizedEntry {
                 timestamp: None,
@@ -1219,7 +1518,368 @@ impl ToNormalizedEntryOpt for Approval {
                 },
                 content: format!("Approval timed out for tool {tool_name}"),
                 metadata: None,
+                agent: None,
             }),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use workspace_utils::log_msg::LogMsg;
+
+    use super::*;
+    use crate::logs::{
+        context_window::COMPACTION_HINT,
+        utils::patch::{collect_normalized_entries, extract_normalized_entry_from_patch},
+    };
+
+    #[tokio::test]
+    async fn review_without_findings_emits_summary() {
+        let msg_store = Arc::new(MsgStore::new());
+        msg_store.push_stdout(concat!(
+            r#"{"method":"codex/event/exited_review_mode","params":{"msg":{"type":"exited_review_mode","#,
+            r#""review_output":{"findings":[],"overall_correctness":"patch is correct","#,
+            r#""overall_explanation":"The change is small and well covered by tests.","#,
+            r#""overall_confidence_score":0.9}}}}"#,
+            "\n"
+        ));
+        msg_store.push_finished();
+
+        normalize_logs(msg_store.clone(), Path::new("/tmp/review-worktree"))
+            .await
+            .unwrap();
+
+        let entries = collect_normalized_entries(&msg_store);
+
+        assert_eq!(entries.len(), 1);
+        assert!(matches!(
+            entries[0].entry_type,
+            NormalizedEntryType::AssistantMessage
+        ));
+        assert_eq!(
+            entries[0].content,
+            "Review complete: no issues found.\n\nThe change is small and well covered by tests."
+        );
+    }
+
+    #[tokio::test]
+    async fn message_deltas_stream_into_one_entry_then_finalize() {
+        let msg_store = Arc::new(MsgStore::new());
+        for line in [
+            concat!(
+                r#"{"method":"codex/event/agent_message_delta","params":{"msg":{"#,
+                r#""type":"agent_message_delta","delta":"Hel"}}}"#
+            ),
+            concat!(
+                r#"{"method":"codex/event/agent_message_delta","params":{"msg":{"#,
+                r#""type":"agent_message_delta","delta":"lo"}}}"#
+            ),
+            concat!(
+                r#"{"method":"codex/event/agent_message","params":{"msg":{"#,
+                r#""type":"agent_message","message":"Hello, world."}}}"#
+            ),
+        ] {
+            msg_store.push_stdout(format!("{line}\n"));
+        }
+        msg_store.push_finished();
+
+        normalize_logs(msg_store.clone(), Path::new("/tmp/delta-worktree"))
+            .await
+            .unwrap();
+
+        let updates: Vec<(usize, String)> = msg_store
+            .get_history()
+            .into_iter()
+            .filter_map(|msg| match msg {
+                LogMsg::JsonPatch(patch) => extract_normalized_entry_from_patch(&patch),
+                _ => None,
+            })
+            .filter(|(_, entry)| matches!(entry.entry_type, NormalizedEntryType::AssistantMessage))
+            .map(|(index, entry)| (index, entry.content))
+            .collect();
+
+        assert_eq!(
+            updates,
+            vec![
+                (0, "Hel".to_string()),
+                (0, "Hello".to_string()),
+                (0, "Hello, world.".to_string()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn context_window_error_suggests_compaction() {
+        let msg_store = Arc::new(MsgStore::new());
+        msg_store.push_stdout(concat!(
+            r#"{"method":"codex/event/error","params":{"msg":{"type":"error","#,
+            r#""message":"Codex ran out of room in the model's context window. "#,
+            r#"Start a new conversation or clear earlier history before retrying."}}}"#,
+            "\n"
+        ));
+        msg_store.push_finished();
+
+        normalize_logs(msg_store.clone(), Path::new("/tmp/context-worktree"))
+            .await
+            .unwrap();
+
+        let entries = collect_normalized_entries(&msg_store);
+        assert_eq!(entries.len(), 1);
+        assert!(matches!(
+            entries[0].entry_type,
+            NormalizedEntryType::ErrorMessage {
+                error_type: NormalizedEntryError::ContextWindowExceeded,
+            }
+        ));
+        assert!(entries[0].content.ends_with(COMPACTION_HINT));
+    }
+
+    #[tokio::test]
+    async fn rising_usage_past_the_auto_compact_limit_is_compacted() {
+        // Codex compacts by itself once usage passes `model_auto_compact_token_limit`; replay the
+        // events it sends as usage rises past a limit of 1000 tokens
+        let token_count = |total: u32| {
+            let usage = serde_json::json!({
+                "input_tokens": total,
+                "cached_input_tokens": 0,
+                "output_tokens": 0,
+                "reasoning_output_tokens": 0,
+                "total_tokens": total,
+            });
+            serde_json::json!({
+                "method": "codex/event/token_count",
+                "params": { "msg": {
+                    "type": "token_count",
+                    "info": {
+                        "total_token_usage": usage,
+                        "last_token_usage": usage,
+                        "model_context_window": 4000,
+                    },
+                } },
+            })
+            .to_string()
+        };
+        let compacted = r#"{"method":"codex/event/context_compacted","params":{"msg":{"type":"context_compacted"}}}"#;
+        let msg_store = Arc::new(MsgStore::new());
+        for line in [
+            token_count(400),
+            token_count(800),
+            token_count(1100),
+            compacted.to_string(),
+            token_count(300),
+        ] {
+            msg_store.push_stdout(format!("{line}\n"));
+        }
+        msg_store.push_finished();
+
+        normalize_logs(msg_store.clone(), Path::new("/tmp/compaction-worktree"))
+            .await
+            .unwrap();
+
+        let timeline: Vec<String> = collect_normalized_entries(&msg_store)
+            .into_iter()
+            .map(|entry| match entry.entry_type {
+                NormalizedEntryType::TokenUsageInfo(usage) => usage.total_tokens.to_string(),
+                _ => entry.content,
+            })
+            .collect();
+        assert_eq!(timeline, ["400", "800", "1100", "Context compacted", "300"]);
+    }
+
+    #[tokio::test]
+    async fn mcp_startup_failures_name_the_server() {
+        let msg_store = Arc::new(MsgStore::new());
+        for line in [
+            concat!(
+                r#"{"method":"codex/event/mcp_startup_update","params":{"msg":{"#,
+                r#""type":"mcp_startup_update","server":"playwright","#,
+                r#""status":{"state":"failed","error":"No such file or directory (os error 2)"}}}}"#
+            ),
+            concat!(
+                r#"{"method":"codex/event/error","params":{"msg":{"type":"error","#,
+                r#""message":"MCP client for `linear` failed to start: request timed out"}}}"#
+            ),
+        ] {
+            msg_store.push_stdout(format!("{line}\n"));
+        }
+        msg_store.push_finished();
+
+        normalize_logs(msg_store.clone(), Path::new("/tmp/mcp-worktree"))
+            .await
+            .unwrap();
+
+        let entries = collect_normalized_entries(&msg_store);
+        let failures: Vec<(String, String)> = entries
+            .into_iter()
+            .filter_map(|entry| match entry.entry_type {
+                NormalizedEntryType::ErrorMessage {
+                    error_type: NormalizedEntryError::McpServerFailed { server },
+                } => Some((server, entry.content)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(failures.len(), 2, "{failures:?}");
+        assert_eq!(failures[0].0, "playwright");
+        assert!(failures[0].1.contains("No such file or directory"));
+        assert_eq!(failures[1].0, "linear");
+        assert!(failures[1].1.contains("request timed out"));
+    }
+
+    #[tokio::test]
+    async fn blocked_command_is_reported_as_an_error() {
+        let msg_store = Arc::new(MsgStore::new());
+        let blocked = Approval::command_blocked(
+            "call-1".to_string(),
+            "rm -rf /".to_string(),
+            "rm -rf *".to_string(),
+        );
+        msg_store.push_stdout(format!("{}\n", blocked.raw()));
+        msg_store.push_finished();
+
+        normalize_logs(msg_store.clone(), Path::new("/tmp/blocked-worktree"))
+            .await
+            .unwrap();
+
+        let entries = collect_normalized_entries(&msg_store);
+        assert_eq!(entries.len(), 1);
+        assert!(matches!(
+            entries[0].entry_type,
+            NormalizedEntryType::ErrorMessage {
+                error_type: NormalizedEntryError::Other,
+            }
+        ));
+        assert_eq!(
+            entries[0].content,
+            "Blocked command `rm -rf /`: it matches the forbidden pattern `rm -rf *`"
+        );
+    }
+
+    #[tokio::test]
+    async fn sandbox_failure_then_retry_emits_escalation() {
+        let msg_store = Arc::new(MsgStore::new());
+        for line in [
+            concat!(
+                r#"{"method":"codex/event/exec_command_begin","params":{"msg":{"#,
+                r#""type":"exec_command_begin","call_id":"call-1","turn_id":"turn-1","#,
+                r#""command":["cargo","fetch"],"cwd":"/tmp/sandbox-worktree","#,
+                r#""parsed_cmd":[],"source":"agent"}}}"#
+            ),
+            concat!(
+                r#"{"method":"codex/event/exec_command_end","params":{"msg":{"#,
+                r#""type":"exec_command_end","call_id":"call-1","turn_id":"turn-1","#,
+                r#""command":["cargo","fetch"],"cwd":"/tmp/sandbox-worktree","#,
+                r#""parsed_cmd":[],"source":"agent","stdout":"","#,
+                r#""stderr":"network access denied","aggregated_output":"network access denied","#,
+                r#""exit_code":1,"duration":{"secs":0,"nanos":5000000},"#,
+                r#""formatted_output":"network access denied"}}}"#
+            ),
+            concat!(
+                r#"{"method":"codex/event/exec_approval_request","params":{"msg":{"#,
+                r#""type":"exec_approval_request","call_id":"call-1","turn_id":"turn-1","#,
+                r#""command":["cargo","fetch"],"cwd":"/tmp/sandbox-worktree","#,
+                r#""reason":"command failed; retry without sandbox?","parsed_cmd":[]}}}"#
+            ),
+            concat!(
+                r#"{"method":"codex/event/exec_command_begin","params":{"msg":{"#,
+                r#""type":"exec_command_begin","call_id":"call-1","turn_id":"turn-1","#,
+                r#""command":["cargo","fetch"],"cwd":"/tmp/sandbox-worktree","#,
+                r#""parsed_cmd":[],"source":"agent"}}}"#
+            ),
+        ] {
+            msg_store.push_stdout(format!("{line}\n"));
+        }
+        msg_store.push_finished();
+
+        normalize_logs(msg_store.clone(), Path::new("/tmp/sandbox-worktree"))
+            .await
+            .unwrap();
+
+        let entries = collect_normalized_entries(&msg_store);
+
+        let escalations: Vec<&NormalizedEntry> = entries
+            .iter()
+            .filter(|entry| matches!(entry.entry_type, NormalizedEntryType::SystemMessage))
+            .collect();
+        assert_eq!(escalations.len(), 1);
+        assert_eq!(
+            escalations[0].content,
+            "Command failed in the sandbox; retrying without sandbox: `cargo fetch`"
+        );
+        assert_eq!(
+            escalations[0].metadata,
+            Some(serde_json::json!({
+                "tool_call_id": "call-1",
+                "command": "cargo fetch",
+                "reason": "command failed; retry without sandbox?",
+            }))
+        );
+    }
+
+    #[tokio::test]
+    async fn shell_apply_patch_without_patch_tool_produces_diffs() {
+        // With `include_apply_patch_tool: false` Codex edits files through the shell, so no
+        // patch_apply events are emitted.
+        let msg_store = Arc::new(MsgStore::new());
+        let command = serde_json::json!([
+            "bash",
+            "-lc",
+            "apply_patch <<'EOF'\n*** Begin Patch\n*** Update File: src/lib.rs\n\
+             @@ fn answer()\n-    41\n+    42\n*** Add File: NOTES.md\n+answer: 42\n\
+             *** End Patch\nEOF\n",
+        ]);
+        for line in [
+            serde_json::json!({
+                "method": "codex/event/exec_command_begin",
+                "params": {"msg": {
+                    "type": "exec_command_begin", "call_id": "call-1", "turn_id": "turn-1",
+                    "command": command, "cwd": "/tmp/patch-worktree", "parsed_cmd": [],
+                    "source": "agent",
+                }},
+            }),
+            serde_json::json!({
+                "method": "codex/event/exec_command_end",
+                "params": {"msg": {
+                    "type": "exec_command_end", "call_id": "call-1", "turn_id": "turn-1",
+                    "command": command, "cwd": "/tmp/patch-worktree", "parsed_cmd": [],
+                    "source": "agent", "stdout": "Success.", "stderr": "",
+                    "aggregated_output": "Success.", "exit_code": 0,
+                    "duration": {"secs": 0, "nanos": 5000000}, "formatted_output": "Success.",
+                }},
+            }),
+        ] {
+            msg_store.push_stdout(format!("{line}\n"));
+        }
+        msg_store.push_finished();
+
+        normalize_logs(msg_store.clone(), Path::new("/tmp/patch-worktree"))
+            .await
+            .unwrap();
+
+        let mut edits: Vec<(String, Vec<FileChange>)> = collect_normalized_entries(&msg_store)
+            .into_iter()
+            .map(|entry| match entry.entry_type {
+                NormalizedEntryType::ToolUse {
+                    action_type: ActionType::FileEdit { path, changes },
+                    status: ToolStatus::Success,
+                    ..
+                } => (path, changes),
+                other => panic!("unexpected entry: {other:?}"),
+            })
+            .collect();
+        edits.sort_by(|a, b| a.0.cmp(&b.0));
+
+        assert_eq!(edits.len(), 2);
+        assert_eq!(edits[0].0, "NOTES.md");
+        assert!(
+            matches!(&edits[0].1[..], [FileChange::Write { content }] if content == "answer: 42\n")
+        );
+        assert_eq!(edits[1].0, "src/lib.rs");
+        let expected_diff = "--- a/src/lib.rs\n+++ b/src/lib.rs\n@@ -1,1 +1,1 @@\n\
+                             -    41\n+    42\n";
+        assert!(matches!(
+            &edits[1].1[..],
+            [FileChange::Edit { unified_diff, .. }] if unified_diff == expected_diff
+        ));
+    }
+}
@@ -1,18 +1,23 @@
-use std::sync::Arc;
+use std::{path::PathBuf, sync::Arc};
 
 use codex_app_server_protocol::{NewConversationParams, ReviewTarget};
 
 use super::{
-    client::{AppServerClient, LogWriter},
+    client::{AppServerClient, CommandRules, LogWriter},
     jsonrpc::{ExitSignalSender, JsonRpcPeer},
-    session::SessionHandler,
 };
-use crate::{approvals::ExecutorApprovalService, executors::ExecutorError};
+use crate::{
+    approvals::ExecutorApprovalService,
+    executors::{ExecutorError, HANDSHAKE_TIMEOUT, ReadinessSignal},
+};
 
 #[allow(clippy::too_many_arguments)]
 pub async fn launch_codex_review(
+    program: &str,
     conversation_params: NewConversationParams,
     resume_session: Option<String>,
+    codex_home: Option<PathBuf>,
+    rollout_dir: Option<PathBuf>,
     review_target: ReviewTarget,
     child_stdout: tokio::process::ChildStdout,
     child_stdin: tokio::process::ChildStdin,
@@ -20,11 +25,13 @@ pub async fn launch_codex_review(
     exit_signal_tx: ExitSignalSender,
     approvals: Option<Arc<dyn ExecutorApprovalService>>,
     auto_approve: bool,
+    command_rules: CommandRules,
+    ready: &mut ReadinessSignal,
 ) -> Result<(), ExecutorError> {
-    let client = AppServerClient::new(log_writer, approvals, auto_approve);
+    let client = AppServerClient::new(log_writer, approvals, auto_approve, command_rules);
     let rpc_peer = JsonRpcPeer::spawn(child_stdin, child_stdout, client.clone(), exit_signal_tx);
     client.connect(rpc_peer);
-    client.initialize().await?;
+    client.handshake(program, HANDSHAKE_TIMEOUT).await?;
     let auth_status = client.get_auth_status().await?;
     if auth_status.requires_openai_auth.unwrap_or(true) && auth_status.auth_method.is_none() {
         return Err(ExecutorError::AuthRequired(
@@ -34,16 +41,14 @@ pub async fn launch_codex_review(
 
     let conversation_id = match resume_session {
         Some(session_id) => {
-            let (rollout_path, _forked_session_id) = SessionHandler::fork_rollout_file(&session_id)
-                .map_err(|e| ExecutorError::FollowUpNotSupported(e.to_string()))?;
             let response = client
-                .resume_conversation(rollout_path.clone(), conversation_params)
+                .resume_session(
+                    &session_id,
+                    conversation_params,
+                    codex_home.as_deref(),
+                    rollout_dir.as_deref(),
+                )
                 .await?;
-            tracing::debug!(
-                "resuming session for review using rollout file {}, response {:?}",
-                rollout_path.display(),
-                response
-            );
             response.conversation_id
         }
         None => {
@@ -54,6 +59,7 @@ pub async fn launch_codex_review(
 
     client.register_session(&conversation_id).await?;
     client.add_conversation_listener(conversation_id).await?;
+    ready.ready();
 
     client
         .start_review(conversation_id.to_string(), review_target)
@@ -10,7 +10,7 @@ use regex::Regex;
 use serde_json::{Map, Value};
 use thiserror::Error;
 
-use super::codex_home;
+use super::codex_home as default_codex_home;
 
 const FILENAME_TIMESTAMP_FORMAT: &str = "%Y-%m-%dT%H-%M-%S";
 
@@ -57,15 +57,30 @@ impl SessionHandler {
     }
 
     /// Find codex rollout file path for given session_id. Used during follow-up execution.
-    pub fn find_rollout_file_path(session_id: &str) -> Result<PathBuf, SessionError> {
-        let sessions_dir = Self::sessions_root()?;
-        Self::scan_directory(&sessions_dir, session_id)
+    /// The sessions directory of `codex_home` (the user's Codex home when `None`) is searched
+    /// first, then `rollout_dir`, where earlier forks may have been written.
+    pub fn find_rollout_file_path(
+        session_id: &str,
+        codex_home: Option<&Path>,
+        rollout_dir: Option<&Path>,
+    ) -> Result<PathBuf, SessionError> {
+        let in_sessions = Self::sessions_root(codex_home)
+            .and_then(|sessions_dir| Self::scan_directory(&sessions_dir, session_id));
+        match (in_sessions, rollout_dir) {
+            (Err(_), Some(dir)) => Self::scan_directory(dir, session_id),
+            (result, _) => result,
+        }
     }
 
     /// Fork a Codex rollout file by copying it to a temp location and assigning a new session id.
-    /// Returns (new_rollout_path, new_session_id).
-    pub fn fork_rollout_file(session_id: &str) -> Result<(PathBuf, String), SessionError> {
-        let original = Self::find_rollout_file_path(session_id)?;
+    /// The fork is written to `rollout_dir` when given, and to the sessions directory of
+    /// `codex_home` otherwise. Returns (new_rollout_path, new_session_id).
+    pub fn fork_rollout_file(
+        session_id: &str,
+        codex_home: Option<&Path>,
+        rollout_dir: Option<&Path>,
+    ) -> Result<(PathBuf, String), SessionError> {
+        let original = Self::find_rollout_file_path(session_id, codex_home, rollout_dir)?;
         tracing::debug!("Forking rollout file: {}", original.display());
         let file = File::open(&original).map_err(|e| {
             SessionError::Io(format!(
@@ -99,7 +114,7 @@ impl SessionHandler {
 
         let new_session_id = uuid::Uuid::new_v4().to_string();
 
-        let destination = Self::create_new_rollout_path(&new_session_id)?;
+        let destination = Self::create_new_rollout_path(&new_session_id, codex_home, rollout_dir)?;
         let dest_file = File::create(&destination).map_err(|e| {
             SessionError::Io(format!(
                 "Failed to create forked rollout {}: {e}",
@@ -173,10 +188,15 @@ impl SessionHandler {
         }
     }
 
-    fn sessions_root() -> Result<PathBuf, SessionError> {
-        let codex_dir = codex_home().ok_or_else(|| {
-            SessionError::Io("Could not determine Codex home directory".to_string())
-        })?;
+    /// Where Codex stores its rollout files: `sessions` in `codex_home`, or in the user's
+    /// Codex home when `None`.
+    pub fn sessions_root(codex_home: Option<&Path>) -> Result<PathBuf, SessionError> {
+        let codex_dir = codex_home
+            .map(Path::to_path_buf)
+            .or_else(default_codex_home)
+            .ok_or_else(|| {
+                SessionError::Io("Could not determine Codex home directory".to_string())
+            })?;
         Ok(codex_dir.join("sessions"))
     }
 
@@ -220,14 +240,20 @@ impl SessionHandler {
         )))
     }
 
-    fn create_new_rollout_path(new_session_id: &str) -> Result<PathBuf, SessionError> {
-        let sessions_root = Self::sessions_root()?;
+    fn create_new_rollout_path(
+        new_session_id: &str,
+        codex_home: Option<&Path>,
+        rollout_dir: Option<&Path>,
+    ) -> Result<PathBuf, SessionError> {
         let now_local = Local::now();
 
-        let dir = sessions_root
-            .join(now_local.format("%Y").to_string())
-            .join(now_local.format("%m").to_string())
-            .join(now_local.format("%d").to_string());
+        let dir = match rollout_dir {
+            Some(dir) => dir.to_path_buf(),
+            None => Self::sessions_root(codex_home)?
+                .join(now_local.format("%Y").to_string())
+                .join(now_local.format("%m").to_string())
+                .join(now_local.format("%d").to_string()),
+        };
 
         std::fs::create_dir_all(&dir).map_err(|e| {
             SessionError::Io(format!(
@@ -245,3 +271,62 @@ impl SessionHandler {
         format!("rollout-{ts}-{new_id}.jsonl")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn forks_are_written_to_and_found_in_a_custom_rollout_dir() {
+        let rollouts = tempfile::tempdir().unwrap();
+        let dir = rollouts.path();
+        let session_id = uuid::Uuid::new_v4().to_string();
+        let original = dir.join(format!("rollout-2025-01-01T00-00-00-{session_id}.jsonl"));
+        std::fs::write(
+            &original,
+            format!(
+                "{{\"type\":\"session_meta\",\"payload\":{{\"id\":\"{session_id}\"}}}}\n\
+                 {{\"type\":\"event_msg\"}}\n"
+            ),
+        )
+        .unwrap();
+
+        // An empty Codex home, so the user's real sessions are never scanned
+        let codex_home = dir.join("codex-home");
+        let (forked, forked_id) =
+            SessionHandler::fork_rollout_file(&session_id, Some(&codex_home), Some(dir)).unwrap();
+        assert_eq!(forked.parent(), Some(dir));
+        assert_eq!(
+            SessionHandler::extract_session_id_from_rollout_path(forked.clone()).unwrap(),
+            forked_id
+        );
+        let content = std::fs::read_to_string(&forked).unwrap();
+        let meta: Value = serde_json::from_str(content.lines().next().unwrap()).unwrap();
+        assert_eq!(meta["payload"]["id"], forked_id.as_str());
+        assert_eq!(content.lines().nth(1), Some("{\"type\":\"event_msg\"}"));
+
+        // A fork of the fork is found in the same directory
+        assert_eq!(
+            SessionHandler::find_rollout_file_path(&forked_id, Some(&codex_home), Some(dir))
+                .unwrap(),
+            forked
+        );
+    }
+
+    #[test]
+    fn sessions_of_an_isolated_codex_home_can_be_forked() {
+        let codex_home = tempfile::tempdir().unwrap();
+        let day_dir = codex_home.path().join("sessions/2025/01/01");
+        std::fs::create_dir_all(&day_dir).unwrap();
+        let session_id = uuid::Uuid::new_v4().to_string();
+        std::fs::write(
+            day_dir.join(format!("rollout-2025-01-01T00-00-00-{session_id}.jsonl")),
+            format!("{{\"type\":\"session_meta\",\"payload\":{{\"id\":\"{session_id}\"}}}}\n"),
+        )
+        .unwrap();
+
+        let (forked, _) =
+            SessionHandler::fork_rollout_file(&session_id, Some(codex_home.path()), None).unwrap();
+        assert!(forked.starts_with(codex_home.path().join("sessions")));
+    }
+}
@@ -1,7 +1,12 @@
-use std::{path::Path, process::Stdio, sync::Arc};
+use std::{
+    path::Path,
+    process::Stdio,
+    sync::{Arc, LazyLock},
+};
 
 use async_trait::async_trait;
 use command_group::AsyncCommandGroup;
+use futures::StreamExt;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use strum_macros::AsRefStr;
@@ -10,10 +15,18 @@ use ts_rs::TS;
 use workspace_utils::msg_store::MsgStore;
 
 use crate::{
-    command::{CommandBuildError, CommandBuilder, CommandParts},
+    command::{CommandBuildError, CommandBuilder, CommandParts, env_command_or_default},
     env::ExecutionEnv,
-    executors::{AppendPrompt, ExecutorError, SpawnedChild, StandardCodingAgentExecutor},
-    logs::utils::EntryIndexProvider,
+    executors::{
+        AppendPrompt, AvailabilityInfo, BaseCodingAgent, CommandPreview, ExecutorError,
+        ReadinessFuture, SpawnedChild, StandardCodingAgentExecutor, await_ready_within,
+    },
+    logs::{
+        paths::relativize_normalized_entries,
+        utils::{EntryIndexProvider, patch::tag_normalized_entries},
+    },
+    spawn_timing::SpawnPhase,
+    stdout_dup::duplicate_stdout,
 };
 
 pub mod normalize_logs;
@@ -23,6 +36,22 @@ use normalize_logs::normalize_logs;
 
 use self::session::fork_session;
 
+static DROID_COMMAND: LazyLock<String> =
+    LazyLock::new(|| env_command_or_default("VK_DROID", "droid"));
+
+const FALLBACK_DROID_COMMAND: &str = "npx -y droid@0.26.0";
+
+static FALLBACK_DROID_COMMAND_OVERRIDE: LazyLock<String> =
+    LazyLock::new(|| env_command_or_default("VK_DROID_FALLBACK", FALLBACK_DROID_COMMAND));
+
+pub fn base_command() -> &'static str {
+    DROID_COMMAND.as_str()
+}
+
+pub fn fallback_command() -> &'static str {
+    FALLBACK_DROID_COMMAND_OVERRIDE.as_str()
+}
+
 // Configuration types for Droid executor
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, TS, JsonSchema)]
 #[serde(rename_all = "kebab-case")]
@@ -84,9 +113,17 @@ pub struct Droid {
 
 impl Droid {
     pub fn build_command_builder(&self) -> Result<CommandBuilder, CommandBuildError> {
+        self.build_command_builder_with_base(base_command())
+    }
+
+    fn build_command_builder_with_base(
+        &self,
+        base: &str,
+    ) -> Result<CommandBuilder, CommandBuildError> {
         use crate::command::{CommandBuilder, apply_overrides};
-        let mut builder =
-            CommandBuilder::new("droid exec").params(["--output-format", "stream-json"]);
+        let mut builder = CommandBuilder::new(base)
+            .subcommand("exec")
+            .params(["--output-format", "stream-json"]);
         builder = match &self.autonomy {
             Autonomy::Normal => builder,
             Autonomy::Low => builder.extend_params(["--auto", "low"]),
@@ -94,7 +131,7 @@ impl Droid {
             Autonomy::High => builder.extend_params(["--auto", "high"]),
             Autonomy::SkipPermissionsUnsafe => builder.extend_params(["--skip-permissions-unsafe"]),
         };
-        if let Some(model) = &self.model {
+        if let Some(model) = BaseCodingAgent::Droid.resolve_model(self.model.as_deref()) {
             builder = builder.extend_params(["--model", model.as_str()]);
         }
         if let Some(effort) = &self.reasoning_effort {
@@ -103,6 +140,25 @@ impl Droid {
 
         apply_overrides(builder, &self.cmd)
     }
+
+    /// Whether Droid authenticates with `FACTORY_API_KEY` rather than a saved login.
+    fn uses_api_key(&self) -> bool {
+        self.cmd.has_env(&["FACTORY_API_KEY"])
+    }
+
+    /// The command the npx fallback would run for a new session, resolved without spawning it.
+    /// The fallback is never used when `base_command_override` is set.
+    pub fn fallback_command_parts(&self) -> Result<CommandParts, CommandBuildError> {
+        self.build_command_builder_with_base(fallback_command())?
+            .build_initial()
+    }
+
+    fn should_fallback_to_npx(&self, err: &ExecutorError) -> bool {
+        if self.cmd.base_command_override.is_some() {
+            return false;
+        }
+        matches!(err, ExecutorError::ExecutableNotFound { .. })
+    }
 }
 
 async fn spawn_droid(
@@ -113,6 +169,7 @@ async fn spawn_droid(
     cmd_overrides: &crate::command::CmdOverrides,
 ) -> Result<SpawnedChild, ExecutorError> {
     let (program_path, args) = command_parts.into_resolved().await?;
+    env.mark_spawn_phase(SpawnPhase::CommandResolved);
 
     let mut command = Command::new(program_path);
     command
@@ -125,16 +182,38 @@ async fn spawn_droid(
 
     env.clone()
         .with_profile(cmd_overrides)
-        .apply_to_command(&mut command);
+        .apply_to_command(&mut command)
+        .await;
+    cmd_overrides.apply_resource_limits(&mut command);
 
     let mut child = command.group_spawn()?;
+    env.mark_spawn_phase(SpawnPhase::ProcessSpawned);
+
+    // Droid has no server to wait for; it is ready once it starts streaming its session
+    let mut output = duplicate_stdout(&mut child)?;
+    let (ready, readiness) = ReadinessFuture::channel();
+    let mut ready = ready.with_spawn_timer(env.spawn_timer.clone());
+    tokio::spawn(async move {
+        while let Some(chunk) = output.next().await {
+            match chunk {
+                Ok(chunk) if chunk.trim().is_empty() => continue,
+                Ok(_) => ready.ready(),
+                Err(err) => ready.fail(ExecutorError::Io(err)),
+            }
+            return;
+        }
+        ready.fail(ExecutorError::Io(std::io::Error::other(
+            "Droid exited before printing any output",
+        )));
+    });
 
     if let Some(mut stdin) = child.inner().stdin.take() {
         stdin.write_all(prompt.as_bytes()).await?;
         stdin.shutdown().await?;
+        env.capture_prompt(prompt);
     }
 
-    Ok(child.into())
+    await_ready_within(child.into(), readiness, cmd_overrides.spawn_timeout()).await
 }
 
 #[async_trait]
@@ -146,9 +225,25 @@ impl StandardCodingAgentExecutor for Droid {
         env: &ExecutionEnv,
     ) -> Result<SpawnedChild, ExecutorError> {
         let droid_command = self.build_command_builder()?.build_initial()?;
-        let combined_prompt = self.append_prompt.combine_prompt(prompt);
+        let combined_prompt = self
+            .append_prompt
+            .combine_prompt_with_mcp_meta(prompt, env)
+            .await?;
 
-        spawn_droid(droid_command, &combined_prompt, current_dir, env, &self.cmd).await
+        match spawn_droid(droid_command, &combined_prompt, current_dir, env, &self.cmd).await {
+            Err(err) if self.should_fallback_to_npx(&err) => {
+                let fallback_command = self.fallback_command_parts()?;
+                spawn_droid(
+                    fallback_command,
+                    &combined_prompt,
+                    current_dir,
+                    env,
+                    &self.cmd,
+                )
+                .await
+            }
+            result => result,
+        }
     }
 
     async fn spawn_follow_up(
@@ -163,15 +258,45 @@ impl StandardCodingAgentExecutor for Droid {
                 "Failed to fork Droid session {session_id}: {e}"
             ))
         })?;
+        let session_args = ["--session-id".to_string(), forked_session_id];
         let continue_cmd = self
             .build_command_builder()?
-            .build_follow_up(&["--session-id".to_string(), forked_session_id.clone()])?;
-        let combined_prompt = self.append_prompt.combine_prompt(prompt);
+            .build_follow_up(&session_args)?;
+        let combined_prompt = self
+            .append_prompt
+            .combine_prompt_with_mcp_meta(prompt, env)
+            .await?;
+
+        match spawn_droid(continue_cmd, &combined_prompt, current_dir, env, &self.cmd).await {
+            Err(err) if self.should_fallback_to_npx(&err) => {
+                let fallback_command = self
+                    .build_command_builder_with_base(fallback_command())?
+                    .build_follow_up(&session_args)?;
+                spawn_droid(
+                    fallback_command,
+                    &combined_prompt,
+                    current_dir,
+                    env,
+                    &self.cmd,
+                )
+                .await
+            }
+            result => result,
+        }
+    }
 
-        spawn_droid(continue_cmd, &combined_prompt, current_dir, env, &self.cmd).await
+    async fn preview_command(&self) -> Result<CommandPreview, ExecutorError> {
+        let parts = self.build_command_builder()?.build_initial()?;
+        let fallback = match self.cmd.base_command_override {
+            None => Some(self.fallback_command_parts()?),
+            Some(_) => None,
+        };
+        CommandPreview::resolve(parts, fallback, &self.cmd).await
     }
 
     fn normalize_logs(&self, msg_store: Arc<MsgStore>, current_dir: &Path) {
+        tag_normalized_entries(&msg_store, BaseCodingAgent::Droid);
+        relativize_normalized_entries(&msg_store, current_dir);
         normalize_logs(
             msg_store.clone(),
             current_dir,
@@ -182,4 +307,222 @@ impl StandardCodingAgentExecutor for Droid {
     fn default_mcp_config_path(&self) -> Option<std::path::PathBuf> {
         dirs::home_dir().map(|home| home.join(".factory").join("mcp.json"))
     }
+
+    fn get_availability_info(&self) -> AvailabilityInfo {
+        dirs::home_dir()
+            .map(|home| availability_in_factory_dir(&home.join(".factory")))
+            .unwrap_or(AvailabilityInfo::NotFound)
+            .or_api_key_login(self.uses_api_key())
+    }
+}
+
+/// Availability from the files in `~/.factory`: saved credentials mean logged in, while
+/// settings, MCP config or past sessions without them mean Droid is installed but needs a login.
+fn availability_in_factory_dir(factory_dir: &Path) -> AvailabilityInfo {
+    if let Some(timestamp) = std::fs::metadata(factory_dir.join("auth.json"))
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|modified| modified.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|d| d.as_secs() as i64)
+    {
+        return AvailabilityInfo::LoginDetected {
+            last_auth_timestamp: timestamp,
+        };
+    }
+
+    let installed = ["settings.json", "mcp.json", "sessions"]
+        .iter()
+        .any(|marker| factory_dir.join(marker).exists());
+    if installed {
+        AvailabilityInfo::InstalledNeedsLogin
+    } else {
+        AvailabilityInfo::NotFound
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::io::AsyncReadExt;
+
+    use super::*;
+    use crate::{
+        command::CmdOverrides,
+        env::RepoContext,
+        executors::{AppendPromptValue, BaseAgentCapability, CodingAgent},
+        logs::{NormalizedEntry, NormalizedEntryType, utils::patch::ConversationPatch},
+        spawn_timing::SpawnTimer,
+    };
+
+    #[tokio::test]
+    async fn captured_prompt_matches_prompt_written_to_agent() {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let env = ExecutionEnv::new(RepoContext::default(), false).with_prompt_capture(tx);
+        let prompt = AppendPrompt(Some(AppendPromptValue::Single("\nextra".to_string())))
+            .combine_prompt("do the thing")
+            .await
+            .unwrap();
+
+        let mut spawned = spawn_droid(
+            CommandParts::new("cat".to_string(), vec![]),
+            &prompt,
+            &std::env::temp_dir(),
+            &env,
+            &CmdOverrides::default(),
+        )
+        .await
+        .unwrap();
+
+        let mut sent = String::new();
+        spawned
+            .child
+            .inner()
+            .stdout
+            .take()
+            .unwrap()
+            .read_to_string(&mut sent)
+            .await
+            .unwrap();
+
+        assert_eq!(rx.try_recv().unwrap(), sent);
+        assert_eq!(sent, "do the thing\nextra");
+    }
+
+    #[tokio::test]
+    async fn spawn_timings_are_recorded_up_to_first_output() {
+        let timer = SpawnTimer::new();
+        let env = ExecutionEnv::new(RepoContext::default(), false).with_spawn_timer(timer.clone());
+        let msg_store = MsgStore::new();
+        let watcher = timer.watch_first_output(&msg_store);
+        // With a spawn timeout the spawn waits until Droid is ready
+        let cmd = CmdOverrides {
+            spawn_timeout_secs: Some(5),
+            ..Default::default()
+        };
+
+        let mut spawned = spawn_droid(
+            CommandParts::new("cat".to_string(), vec![]),
+            &"hello".to_string(),
+            &std::env::temp_dir(),
+            &env,
+            &cmd,
+        )
+        .await
+        .unwrap();
+        let mut output = String::new();
+        spawned
+            .child
+            .inner()
+            .stdout
+            .take()
+            .unwrap()
+            .read_to_string(&mut output)
+            .await
+            .unwrap();
+        msg_store.push_stdout(output.clone());
+        msg_store.push_patch(ConversationPatch::add_normalized_entry(
+            0,
+            NormalizedEntry {
+                timestamp: None,
+                entry_type: NormalizedEntryType::AssistantMessage,
+                content: output,
+                metadata: None,
+                agent: None,
+            },
+        ));
+        watcher.await.unwrap();
+
+        let timings = timer.timings();
+        let command_resolution = timings.command_resolution.unwrap();
+        let process_spawn = timings.process_spawn.unwrap();
+        let ready = timings.ready.unwrap();
+        let first_output = timings.first_output.unwrap();
+        assert!(command_resolution <= process_spawn);
+        assert!(process_spawn <= ready);
+        assert!(ready <= first_output);
+    }
+
+    #[tokio::test]
+    async fn droid_that_prints_nothing_hits_the_spawn_timeout() {
+        let env = ExecutionEnv::new(RepoContext::default(), false);
+        let cmd = CmdOverrides {
+            spawn_timeout_secs: Some(1),
+            ..Default::default()
+        };
+
+        let err = spawn_droid(
+            CommandParts::new("sleep".to_string(), vec!["30".to_string()]),
+            &"hello".to_string(),
+            &std::env::temp_dir(),
+            &env,
+            &cmd,
+        )
+        .await
+        .unwrap_err();
+        assert!(
+            matches!(&err, ExecutorError::Timeout(message) if message.contains("after 1s.")),
+            "{err:?}"
+        );
+    }
+
+    #[test]
+    fn factory_api_key_counts_as_a_login() {
+        let droid: Droid = serde_json::from_value(serde_json::json!({
+            "env": { "FACTORY_API_KEY": "fk-profile" }
+        }))
+        .unwrap();
+        assert!(droid.uses_api_key());
+        assert!(matches!(
+            AvailabilityInfo::NotFound.or_api_key_login(droid.uses_api_key()),
+            AvailabilityInfo::ApiKeyConfigured
+        ));
+
+        let blank: Droid = serde_json::from_value(serde_json::json!({
+            "env": { "FACTORY_API_KEY": " " }
+        }))
+        .unwrap();
+        assert!(!blank.uses_api_key());
+    }
+
+    #[test]
+    fn factory_dir_markers_decide_availability() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("factory");
+        assert!(matches!(
+            availability_in_factory_dir(&dir),
+            AvailabilityInfo::NotFound
+        ));
+
+        std::fs::create_dir_all(dir.join("sessions")).unwrap();
+        assert!(matches!(
+            availability_in_factory_dir(&dir),
+            AvailabilityInfo::InstalledNeedsLogin
+        ));
+
+        std::fs::write(dir.join("auth.json"), "{}").unwrap();
+        let info = availability_in_factory_dir(&dir);
+        assert!(matches!(info, AvailabilityInfo::LoginDetected { .. }));
+        assert!(info.is_available());
+    }
+
+    #[test]
+    fn configured_model_is_passed_to_droid() {
+        let droid: Droid =
+            serde_json::from_value(serde_json::json!({ "model": "own-model" })).unwrap();
+        let params = droid.build_command_builder().unwrap().params.unwrap();
+        assert!(
+            params
+                .windows(2)
+                .any(|pair| pair == ["--model", "own-model"])
+        );
+    }
+
+    #[test]
+    fn capabilities_are_session_fork_only() {
+        let droid: Droid = serde_json::from_value(serde_json::json!({})).unwrap();
+        let agent = CodingAgent::Droid(droid);
+        assert_eq!(agent.capabilities(), [BaseAgentCapability::SessionFork]);
+        assert!(!agent.supports_image_input());
+    }
 }
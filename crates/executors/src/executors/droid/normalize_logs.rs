@@ -55,6 +55,7 @@ pub fn normalize_logs(
                             },
                             content: error.message,
                             metadata: None,
+                            agent: None,
                         };
                         add_normalized_entry(&msg_store, &entry_index_provider, entry);
                         continue;
@@ -66,6 +67,7 @@ pub fn normalize_logs(
                             entry_type: NormalizedEntryType::SystemMessage,
                             content: strip_ansi_escapes::strip_str(trimmed).to_string(),
                             metadata: None,
+                            agent: None,
                         };
 
                         add_normalized_entry(&msg_store, &entry_index_provider, entry);
@@ -92,6 +94,7 @@ pub fn normalize_logs(
                             entry_type: NormalizedEntryType::SystemMessage,
                             content: format!("model: {model}"),
                             metadata: None,
+                            agent: None,
                         };
                         add_normalized_entry(&msg_store, &entry_index_provider, entry);
                     }
@@ -113,6 +116,7 @@ pub fn normalize_logs(
                         entry_type,
                         content: text.clone(),
                         metadata: None,
+                        agent: None,
                     };
 
                     add_normalized_entry(&msg_store, &entry_index_provider, entry);
@@ -647,6 +651,7 @@ pub fn normalize_logs(
                         entry_type: NormalizedEntryType::AssistantMessage,
                         content: final_text.clone(),
                         metadata: None,
+                        agent: None,
                     };
                     add_normalized_entry(&msg_store, &entry_index_provider, entry);
                     sent_completion = true;
@@ -660,6 +665,7 @@ pub fn normalize_logs(
                         },
                         content: message.clone(),
                         metadata: None,
+                        agent: None,
                     };
                     add_normalized_entry(&msg_store, &state.entry_index, entry);
                 }
@@ -680,6 +686,7 @@ fn normalize_stderr_logs(msg_store: Arc<MsgStore>, entry_index_provider: EntryIn
                 },
                 content,
                 metadata: None,
+                agent: None,
             }))
             .transform_lines(Box::new(|lines| {
                 lines.iter_mut().for_each(|line| {
@@ -1025,6 +1032,7 @@ impl ToNormalizedEntry for FileReadState {
             },
             content: self.path.clone(),
             metadata: None,
+            agent: None,
         }
     }
 }
@@ -1051,6 +1059,7 @@ impl ToNormalizedEntry for FileEditState {
             },
             content: self.path.clone(),
             metadata: None,
+            agent: None,
         }
     }
 }
@@ -1093,6 +1102,7 @@ impl ToNormalizedEntry for CommandRunState {
             },
             content: self.command.clone(),
             metadata: None,
+            agent: None,
         }
     }
 }
@@ -1124,6 +1134,7 @@ impl ToNormalizedEntry for TodoManagementState {
             },
             content,
             metadata: None,
+            agent: None,
         }
     }
 }
@@ -1148,6 +1159,7 @@ impl ToNormalizedEntry for SearchState {
             },
             content: self.query.clone(),
             metadata: None,
+            agent: None,
         }
     }
 }
@@ -1172,6 +1184,7 @@ impl ToNormalizedEntry for WebFetchState {
             },
             content: self.url.clone(),
             metadata: None,
+            agent: None,
         }
     }
 }
@@ -1206,6 +1219,7 @@ impl ToNormalizedEntry for GenericToolState {
             },
             content: self.name.clone(),
             metadata: None,
+            agent: None,
         }
     }
 }
@@ -12,14 +12,17 @@ use workspace_utils::msg_store::MsgStore;
 
 pub use super::acp::AcpAgentHarness;
 use crate::{
-    approvals::ExecutorApprovalService,
+    approvals::{ExecutorApprovalService, ExternalPathGuard},
     command::{
-        CmdOverrides, CommandBuildError, CommandBuilder, apply_overrides, env_command_or_default,
+        CmdOverrides, CommandBuildError, CommandBuilder, CommandParts, apply_overrides,
+        env_command_or_default, split_command_line,
     },
     env::ExecutionEnv,
     executors::{
-        AppendPrompt, AvailabilityInfo, ExecutorError, SpawnedChild, StandardCodingAgentExecutor,
+        AppendPrompt, AvailabilityDetail, AvailabilityInfo, BaseCodingAgent, CommandPreview,
+        ExecutorError, SandboxDescriptor, SandboxLevel, SpawnedChild, StandardCodingAgentExecutor,
     },
+    logs::{paths::relativize_normalized_entries, utils::patch::tag_normalized_entries},
 };
 
 static GEMINI_COMMAND: LazyLock<String> =
@@ -27,12 +30,50 @@ static GEMINI_COMMAND: LazyLock<String> =
 
 const FALLLBACK_GEMINI_COMMAND: &str = "npx -y @google/gemini-cli@0.23.0";
 
+static FALLBACK_GEMINI_COMMAND_OVERRIDE: LazyLock<String> =
+    LazyLock::new(|| env_command_or_default("VK_GEMINI_FALLBACK", FALLLBACK_GEMINI_COMMAND));
+
 pub fn base_command() -> &'static str {
     GEMINI_COMMAND.as_str()
 }
 
 pub fn fallback_command() -> &'static str {
-    FALLLBACK_GEMINI_COMMAND
+    FALLBACK_GEMINI_COMMAND_OVERRIDE.as_str()
+}
+
+/// Tools that only read, allowed in every sandbox mode.
+const READ_ONLY_TOOLS: &[&str] = &[
+    "read_file",
+    "read_many_files",
+    "list_directory",
+    "glob",
+    "search_file_content",
+    "web_fetch",
+    "google_web_search",
+];
+
+/// Tools that edit files, additionally allowed in `workspace-write`.
+const FILE_EDIT_TOOLS: &[&str] = &["write_file", "replace"];
+
+/// How much Gemini may do without asking, mirroring Codex's sandbox modes. Gemini itself runs
+/// unsandboxed, so a mode only decides which tools skip approval.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, TS, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum GeminiSandboxMode {
+    ReadOnly,
+    WorkspaceWrite,
+    DangerFullAccess,
+}
+
+impl GeminiSandboxMode {
+    /// The tools passed to `--allowed-tools`, which Gemini runs without asking.
+    fn allowed_tools(self) -> Vec<&'static str> {
+        match self {
+            Self::ReadOnly => READ_ONLY_TOOLS.to_vec(),
+            Self::WorkspaceWrite => [READ_ONLY_TOOLS, FILE_EDIT_TOOLS].concat(),
+            Self::DangerFullAccess => vec!["run_shell_command"],
+        }
+    }
 }
 
 #[derive(Derivative, Clone, Serialize, Deserialize, TS, JsonSchema)]
@@ -44,6 +85,21 @@ pub struct Gemini {
     pub model: Option<String>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub yolo: Option<bool>,
+    /// Which tools run without asking for approval. Takes precedence over `yolo`; `yolo: true`
+    /// alone is treated as `danger-full-access`.
+    ///
+    /// - `read-only`: reading and searching files and the web (`read_file`, `read_many_files`,
+    ///   `list_directory`, `glob`, `search_file_content`, `web_fetch`, `google_web_search`).
+    ///   Edits and shell commands ask for approval.
+    /// - `workspace-write`: the read-only tools plus file edits (`write_file`, `replace`).
+    ///   Shell commands ask for approval.
+    /// - `danger-full-access`: yolo mode; every tool runs without approval.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sandbox: Option<GeminiSandboxMode>,
+    /// Ask before editing files outside the working directory in `workspace-write` and
+    /// `danger-full-access`, where edits otherwise run without asking
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub guard_external_paths: Option<bool>,
     #[serde(flatten)]
     pub cmd: CmdOverrides,
     #[serde(skip)]
@@ -53,19 +109,131 @@ pub struct Gemini {
 }
 
 impl Gemini {
+    /// Whether Gemini authenticates with an API key or Vertex AI from the environment rather
+    /// than a Google login.
+    fn uses_api_key(&self) -> bool {
+        self.cmd.has_env(&["GEMINI_API_KEY", "GOOGLE_API_KEY"])
+            || self
+                .cmd
+                .lookup_env("GOOGLE_GENAI_USE_VERTEXAI")
+                .is_some_and(|value| matches!(value.trim(), "true" | "1"))
+    }
+
+    /// The configured `sandbox`, or `danger-full-access` for the older `yolo` flag.
+    fn sandbox_mode(&self) -> Option<GeminiSandboxMode> {
+        match (self.sandbox, self.yolo) {
+            (Some(mode), _) => Some(mode),
+            (None, Some(true)) => Some(GeminiSandboxMode::DangerFullAccess),
+            (None, _) => None,
+        }
+    }
+
+    fn full_access(&self) -> bool {
+        self.sandbox_mode() == Some(GeminiSandboxMode::DangerFullAccess)
+    }
+
+    /// Whether edits go through the external path guard. Only modes that let edits run
+    /// without asking need it.
+    fn guards_external_paths(&self) -> bool {
+        self.guard_external_paths.unwrap_or(false)
+            && matches!(
+                self.sandbox_mode(),
+                Some(GeminiSandboxMode::WorkspaceWrite | GeminiSandboxMode::DangerFullAccess)
+            )
+    }
+
+    /// The approval service tool calls go through; none in unguarded full access, where
+    /// nothing asks.
+    fn approval_service(&self) -> Option<Arc<dyn ExecutorApprovalService>> {
+        if self.full_access() && !self.guards_external_paths() {
+            None
+        } else {
+            self.approvals.clone()
+        }
+    }
+
+    /// The ACP harness for a run in `current_dir`. With the path guard, Gemini asks before
+    /// every edit and the client approves the in-tree ones itself.
+    fn harness(&self, current_dir: &Path) -> AcpAgentHarness {
+        let harness = AcpAgentHarness::new();
+        if self.guards_external_paths() {
+            harness.with_path_guard(ExternalPathGuard::new(current_dir), self.full_access())
+        } else {
+            harness
+        }
+    }
+
+    /// Reject `additional_params` that contradict yolo mode, and warn about ones that quietly
+    /// enable it.
+    fn validate_flag_combos(&self) -> Result<(), CommandBuildError> {
+        let Some(extra) = self
+            .cmd
+            .additional_params
+            .as_ref()
+            .filter(|p| !p.is_empty())
+        else {
+            return Ok(());
+        };
+        let params = split_command_line(&extra.join(" "))?;
+        let flag_value = |flag: &str| {
+            params.iter().enumerate().find_map(|(i, param)| {
+                if param == flag {
+                    Some(params.get(i + 1).map(String::as_str).unwrap_or_default())
+                } else {
+                    param.strip_prefix(flag)?.strip_prefix('=')
+                }
+            })
+        };
+        if self.full_access() {
+            if let Some(tools) = flag_value("--allowed-tools") {
+                return Err(CommandBuildError::InvalidOverride(format!(
+                    "`--allowed-tools {tools}` restricts tools, but yolo mode allows all of them"
+                )));
+            }
+            if let Some(mode) = flag_value("--approval-mode")
+                && mode != "yolo"
+            {
+                return Err(CommandBuildError::InvalidOverride(format!(
+                    "`--approval-mode {mode}` contradicts yolo mode"
+                )));
+            }
+        } else if params
+            .iter()
+            .any(|param| param == "--yolo" || param == "-y")
+            || flag_value("--approval-mode") == Some("yolo")
+        {
+            tracing::warn!(
+                "Gemini additional params enable yolo mode while `yolo` is off; tool approvals \
+                 will be skipped"
+            );
+        }
+        Ok(())
+    }
+
     fn build_command_builder_with_base(
         &self,
         base: &str,
     ) -> Result<CommandBuilder, CommandBuildError> {
+        self.validate_flag_combos()?;
         let mut builder = CommandBuilder::new(base);
 
-        if let Some(model) = &self.model {
+        if let Some(model) = BaseCodingAgent::Gemini.resolve_model(self.model.as_deref()) {
             builder = builder.extend_params(["--model", model.as_str()]);
         }
 
-        if self.yolo.unwrap_or(false) {
-            builder = builder.extend_params(["--yolo"]);
-            builder = builder.extend_params(["--allowed-tools", "run_shell_command"]);
+        if let Some(mode) = self.sandbox_mode() {
+            // Guarded edits must reach the client, so they can't be pre-approved
+            let guarded = self.guards_external_paths();
+            if mode == GeminiSandboxMode::DangerFullAccess && !guarded {
+                builder = builder.extend_params(["--yolo"]);
+            }
+            let allowed_tools = mode
+                .allowed_tools()
+                .into_iter()
+                .filter(|tool| !(guarded && FILE_EDIT_TOOLS.contains(tool)))
+                .collect::<Vec<_>>()
+                .join(",");
+            builder = builder.extend_params(["--allowed-tools", allowed_tools.as_str()]);
         }
 
         builder = builder.extend_params(["--experimental-acp"]);
@@ -89,6 +257,12 @@ impl Gemini {
         self.build_command_builder_with_base(fallback_command())
     }
 
+    /// The command the npx fallback would run for a new session, resolved without spawning it.
+    /// The fallback is never used when `base_command_override` is set.
+    pub fn fallback_command_parts(&self) -> Result<CommandParts, CommandBuildError> {
+        self.build_fallback_command_builder()?.build_initial()
+    }
+
     fn should_fallback_to_npx(&self, err: &ExecutorError) -> bool {
         if self.cmd.base_command_override.is_some() {
             return false;
@@ -109,13 +283,12 @@ impl StandardCodingAgentExecutor for Gemini {
         prompt: &str,
         env: &ExecutionEnv,
     ) -> Result<SpawnedChild, ExecutorError> {
-        let harness = AcpAgentHarness::new();
-        let combined_prompt = self.append_prompt.combine_prompt(prompt);
-        let approvals = if self.yolo.unwrap_or(false) {
-            None
-        } else {
-            self.approvals.clone()
-        };
+        let harness = self.harness(current_dir);
+        let combined_prompt = self
+            .append_prompt
+            .combine_prompt_with_mcp_meta(prompt, env)
+            .await?;
+        let approvals = self.approval_service();
         let gemini_command = self.build_command_builder()?.build_initial()?;
         match harness
             .spawn_with_command(
@@ -131,8 +304,7 @@ impl StandardCodingAgentExecutor for Gemini {
             Ok(child) => Ok(child),
             Err(err) => {
                 if self.should_fallback_to_npx(&err) {
-                    let fallback_command =
-                        self.build_fallback_command_builder()?.build_initial()?;
+                    let fallback_command = self.fallback_command_parts()?;
                     return harness
                         .spawn_with_command(
                             current_dir,
@@ -156,13 +328,12 @@ impl StandardCodingAgentExecutor for Gemini {
         session_id: &str,
         env: &ExecutionEnv,
     ) -> Result<SpawnedChild, ExecutorError> {
-        let harness = AcpAgentHarness::new();
-        let combined_prompt = self.append_prompt.combine_prompt(prompt);
-        let approvals = if self.yolo.unwrap_or(false) {
-            None
-        } else {
-            self.approvals.clone()
-        };
+        let harness = self.harness(current_dir);
+        let combined_prompt = self
+            .append_prompt
+            .combine_prompt_with_mcp_meta(prompt, env)
+            .await?;
+        let approvals = self.approval_service();
         let gemini_command = self.build_command_builder()?.build_follow_up(&[])?;
         match harness
             .spawn_follow_up_with_command(
@@ -199,7 +370,18 @@ impl StandardCodingAgentExecutor for Gemini {
         }
     }
 
+    async fn preview_command(&self) -> Result<CommandPreview, ExecutorError> {
+        let parts = self.build_command_builder()?.build_initial()?;
+        let fallback = match self.cmd.base_command_override {
+            None => Some(self.fallback_command_parts()?),
+            Some(_) => None,
+        };
+        CommandPreview::resolve(parts, fallback, &self.cmd).await
+    }
+
     fn normalize_logs(&self, msg_store: Arc<MsgStore>, worktree_path: &Path) {
+        tag_normalized_entries(&msg_store, BaseCodingAgent::Gemini);
+        relativize_normalized_entries(&msg_store, worktree_path);
         super::acp::normalize_logs(msg_store, worktree_path);
     }
 
@@ -207,31 +389,261 @@ impl StandardCodingAgentExecutor for Gemini {
         dirs::home_dir().map(|home| home.join(".gemini").join("settings.json"))
     }
 
+    fn default_sandbox(&self) -> Option<SandboxDescriptor> {
+        // Gemini runs unsandboxed; only the approval gate changes with the sandbox mode
+        Some(SandboxDescriptor {
+            level: SandboxLevel::FullAccess,
+            requires_approval: !self.full_access(),
+        })
+    }
+
     fn get_availability_info(&self) -> AvailabilityInfo {
-        if let Some(timestamp) = dirs::home_dir()
-            .and_then(|home| std::fs::metadata(home.join(".gemini").join("oauth_creds.json")).ok())
-            .and_then(|m| m.modified().ok())
-            .and_then(|modified| modified.duration_since(std::time::UNIX_EPOCH).ok())
-            .map(|d| d.as_secs() as i64)
-        {
-            return AvailabilityInfo::LoginDetected {
-                last_auth_timestamp: timestamp,
-            };
+        dirs::home_dir()
+            .map(|home| availability_in_gemini_dir(&home.join(".gemini")))
+            .unwrap_or(AvailabilityInfo::NotFound)
+            .or_api_key_login(self.uses_api_key())
+    }
+
+    fn get_availability_detail(&self) -> AvailabilityDetail {
+        AvailabilityDetail {
+            info: self.get_availability_info(),
+            configured_mcp_servers: self
+                .default_mcp_config_path()
+                .and_then(|path| configured_mcp_server_count(&path)),
         }
+    }
+}
 
-        let mcp_config_found = self
-            .default_mcp_config_path()
-            .map(|p| p.exists())
-            .unwrap_or(false);
+/// Availability from the files in `~/.gemini`: saved OAuth credentials mean logged in, while
+/// settings or an installation id without them mean Gemini is installed but needs a login.
+fn availability_in_gemini_dir(gemini_dir: &Path) -> AvailabilityInfo {
+    if let Some(timestamp) = std::fs::metadata(gemini_dir.join("oauth_creds.json"))
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|modified| modified.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|d| d.as_secs() as i64)
+    {
+        return AvailabilityInfo::LoginDetected {
+            last_auth_timestamp: timestamp,
+        };
+    }
 
-        let installation_indicator_found = dirs::home_dir()
-            .map(|home| home.join(".gemini").join("installation_id").exists())
-            .unwrap_or(false);
+    if gemini_dir.join("settings.json").exists() || gemini_dir.join("installation_id").exists() {
+        AvailabilityInfo::InstalledNeedsLogin
+    } else {
+        AvailabilityInfo::NotFound
+    }
+}
 
-        if mcp_config_found || installation_indicator_found {
-            AvailabilityInfo::InstallationFound
-        } else {
-            AvailabilityInfo::NotFound
+/// Number of servers under `mcpServers` in a Gemini settings file, or `None` if the file is
+/// missing or not valid JSON.
+fn configured_mcp_server_count(settings_path: &Path) -> Option<usize> {
+    let content = std::fs::read_to_string(settings_path).ok()?;
+    let settings: serde_json::Value = serde_json::from_str(&content).ok()?;
+    Some(
+        settings
+            .get("mcpServers")
+            .and_then(serde_json::Value::as_object)
+            .map_or(0, |servers| servers.len()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executors::{BaseAgentCapability, CodingAgent};
+
+    fn gemini(value: serde_json::Value) -> Gemini {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn api_key_or_vertex_counts_as_logged_in() {
+        let blank = serde_json::json!({
+            "GEMINI_API_KEY": "",
+            "GOOGLE_API_KEY": "",
+            "GOOGLE_GENAI_USE_VERTEXAI": "",
+        });
+        assert!(!gemini(serde_json::json!({ "env": blank })).uses_api_key());
+
+        for (key, value) in [
+            ("GEMINI_API_KEY", "key"),
+            ("GOOGLE_API_KEY", "key"),
+            ("GOOGLE_GENAI_USE_VERTEXAI", "true"),
+        ] {
+            let mut env = blank.clone();
+            env[key] = value.into();
+            assert!(
+                gemini(serde_json::json!({ "env": env })).uses_api_key(),
+                "{key}"
+            );
         }
     }
+
+    #[test]
+    fn yolo_with_allowed_tools_is_rejected() {
+        let gemini = gemini(serde_json::json!({
+            "yolo": true,
+            "additional_params": ["--allowed-tools=read_file"],
+        }));
+
+        let Err(CommandBuildError::InvalidOverride(message)) = gemini.build_command_builder()
+        else {
+            panic!("expected an invalid override error");
+        };
+        assert!(message.contains("read_file"), "{message}");
+    }
+
+    #[test]
+    fn yolo_with_unrelated_params_builds() {
+        let gemini = gemini(serde_json::json!({
+            "yolo": true,
+            "additional_params": ["--debug", "--approval-mode yolo"],
+        }));
+
+        let params = gemini.build_command_builder().unwrap().params.unwrap();
+        assert!(params.contains(&"--yolo".to_string()));
+        assert!(params.contains(&"--debug".to_string()));
+    }
+
+    #[test]
+    fn sandbox_modes_map_to_allowed_tools() {
+        let allowed_tools = |value: serde_json::Value| {
+            let params = gemini(value)
+                .build_command_builder()
+                .unwrap()
+                .params
+                .unwrap();
+            let yolo = params.contains(&"--yolo".to_string());
+            let index = params.iter().position(|param| param == "--allowed-tools");
+            (yolo, index.map(|i| params[i + 1].clone()))
+        };
+
+        let (yolo, tools) = allowed_tools(serde_json::json!({ "sandbox": "read-only" }));
+        assert!(!yolo);
+        assert_eq!(tools.as_deref(), Some(READ_ONLY_TOOLS.join(",").as_str()));
+
+        let (yolo, tools) = allowed_tools(serde_json::json!({ "sandbox": "workspace-write" }));
+        assert!(!yolo);
+        let tools = tools.unwrap();
+        assert!(tools.contains("read_file") && tools.contains("write_file"));
+        assert!(!tools.contains("run_shell_command"));
+
+        assert_eq!(allowed_tools(serde_json::json!({})), (false, None));
+
+        // Guarded edits have to reach the client, so they are never pre-approved
+        let (yolo, tools) = allowed_tools(serde_json::json!({
+            "sandbox": "workspace-write",
+            "guard_external_paths": true,
+        }));
+        assert!(!yolo);
+        assert_eq!(tools.as_deref(), Some(READ_ONLY_TOOLS.join(",").as_str()));
+        let (yolo, tools) = allowed_tools(serde_json::json!({
+            "sandbox": "danger-full-access",
+            "guard_external_paths": true,
+        }));
+        assert!(!yolo);
+        assert_eq!(tools.as_deref(), Some("run_shell_command"));
+    }
+
+    #[test]
+    fn sandbox_wins_over_yolo() {
+        let gemini_with = |value| {
+            let gemini = gemini(value);
+            (gemini.sandbox_mode(), gemini.full_access())
+        };
+
+        assert_eq!(
+            gemini_with(serde_json::json!({ "yolo": true })),
+            (Some(GeminiSandboxMode::DangerFullAccess), true)
+        );
+        assert_eq!(
+            gemini_with(serde_json::json!({ "yolo": true, "sandbox": "read-only" })),
+            (Some(GeminiSandboxMode::ReadOnly), false)
+        );
+    }
+
+    #[test]
+    fn fallback_command_parts_run_acp_through_npx() {
+        let parts = gemini(serde_json::json!({ "model": "gemini-2.5-pro" }))
+            .fallback_command_parts()
+            .unwrap();
+        let mut expected = split_command_line(fallback_command()).unwrap();
+        expected.extend(["--model", "gemini-2.5-pro", "--experimental-acp"].map(String::from));
+
+        assert_eq!(parts.program(), expected[0]);
+        assert_eq!(parts.args(), &expected[1..]);
+    }
+
+    #[test]
+    fn counts_mcp_servers_in_settings_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("settings.json");
+        std::fs::write(
+            &path,
+            r#"{
+                "theme": "Default",
+                "mcpServers": {
+                    "github": { "command": "github-mcp" },
+                    "playwright": { "command": "npx", "args": ["@playwright/mcp"] },
+                    "vibe_kanban": { "command": "npx", "args": ["vibe-kanban", "--mcp"] }
+                }
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(configured_mcp_server_count(&path), Some(3));
+
+        std::fs::write(&path, r#"{ "theme": "Default" }"#).unwrap();
+        assert_eq!(configured_mcp_server_count(&path), Some(0));
+
+        std::fs::write(&path, "{ not json").unwrap();
+        assert_eq!(configured_mcp_server_count(&path), None);
+
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(configured_mcp_server_count(&path), None);
+    }
+
+    #[test]
+    fn installed_gemini_without_credentials_needs_login() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        assert!(matches!(
+            availability_in_gemini_dir(dir),
+            AvailabilityInfo::NotFound
+        ));
+
+        std::fs::write(dir.join("installation_id"), "id").unwrap();
+        let info = availability_in_gemini_dir(dir);
+        assert!(matches!(info, AvailabilityInfo::InstalledNeedsLogin));
+        assert!(!info.is_available());
+
+        std::fs::write(dir.join("oauth_creds.json"), "{}").unwrap();
+        let info = availability_in_gemini_dir(dir);
+        assert!(matches!(info, AvailabilityInfo::LoginDetected { .. }));
+        assert!(info.is_available());
+    }
+
+    #[test]
+    fn default_sandbox_asks_unless_yolo() {
+        assert_eq!(
+            gemini(serde_json::json!({}))
+                .default_sandbox()
+                .map(|sandbox| sandbox.requires_approval),
+            Some(true)
+        );
+        assert_eq!(
+            gemini(serde_json::json!({ "yolo": true })).default_sandbox(),
+            Some(SandboxDescriptor {
+                level: SandboxLevel::FullAccess,
+                requires_approval: false,
+            })
+        );
+    }
+
+    #[test]
+    fn capabilities_are_session_fork_only() {
+        let agent = CodingAgent::Gemini(gemini(serde_json::json!({})));
+        assert_eq!(agent.capabilities(), [BaseAgentCapability::SessionFork]);
+        assert!(!agent.supports_image_input());
+    }
 }
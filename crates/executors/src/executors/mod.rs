@@ -1,4 +1,12 @@
-use std::{path::Path, sync::Arc};
+use std::{
+    collections::HashMap,
+    future::Future,
+    path::{Path, PathBuf},
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::Duration,
+};
 
 use async_trait::async_trait;
 use command_group::AsyncGroupChild;
@@ -9,20 +17,25 @@ use serde::{Deserialize, Serialize};
 use sqlx::Type;
 use strum_macros::{Display, EnumDiscriminants, EnumString, VariantNames};
 use thiserror::Error;
+use tokio::io::{AsyncBufReadExt, BufReader};
 use ts_rs::TS;
 use workspace_utils::msg_store::MsgStore;
 
 #[cfg(feature = "qa-mode")]
 use crate::executors::qa_mock::QaMockExecutor;
 use crate::{
-    actions::{ExecutorAction, review::RepoReviewContext},
-    approvals::ExecutorApprovalService,
-    command::CommandBuildError,
+    actions::{
+        Executable, ExecutorAction,
+        review::{RepoReviewContext, ReviewScope},
+    },
+    approvals::{ExecutorApprovalService, NoopExecutorApprovalService},
+    command::{CmdOverrides, CommandBuildError, CommandParts},
     env::ExecutionEnv,
     executors::{
         claude::ClaudeCode, codex::Codex, droid::Droid, gemini::Gemini, opencode::Opencode,
     },
-    mcp_config::McpConfig,
+    mcp_config::{McpConfig, layered_meta_prompt_instructions},
+    spawn_timing::{SpawnPhase, SpawnTimer},
 };
 
 pub mod acp;
@@ -41,6 +54,68 @@ pub enum BaseAgentCapability {
     SessionFork,
     /// Agent requires a setup script before it can run (e.g., login, installation)
     SetupHelper,
+    /// Agent can read images attached to the prompt (copied into the worktree before spawn)
+    ImageInput,
+}
+
+/// How much of the filesystem an agent may modify without asking.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, TS)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+#[ts(use_ts_enum)]
+pub enum SandboxLevel {
+    ReadOnly,
+    WorkspaceWrite,
+    FullAccess,
+}
+
+/// Effective sandbox an agent runs with when no explicit sandbox is configured
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, TS)]
+#[ts(export)]
+pub struct SandboxDescriptor {
+    pub level: SandboxLevel,
+    /// Whether actions outside the sandbox are gated on user approval
+    pub requires_approval: bool,
+}
+
+/// What an executor would run for a new session, resolved on PATH without spawning anything.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct CommandPreview {
+    pub program: PathBuf,
+    pub args: Vec<String>,
+    /// The configured command was not found on PATH, so the npx fallback would run instead
+    pub uses_fallback: bool,
+    /// Env vars set from the executor's profile, sorted
+    pub env_keys: Vec<String>,
+}
+
+impl CommandPreview {
+    /// Resolve `parts`, or `fallback` when there is one and the program of `parts` is not on
+    /// PATH, the same way spawning does.
+    pub async fn resolve(
+        parts: CommandParts,
+        fallback: Option<CommandParts>,
+        cmd: &CmdOverrides,
+    ) -> Result<Self, ExecutorError> {
+        let ((program, args), uses_fallback) = match (parts.into_resolved().await, fallback) {
+            (Err(ExecutorError::ExecutableNotFound { .. }), Some(fallback)) => {
+                (fallback.into_resolved().await?, true)
+            }
+            (resolved, _) => (resolved?, false),
+        };
+        let mut env_keys: Vec<String> = cmd
+            .env
+            .iter()
+            .flatten()
+            .map(|(key, _)| key.clone())
+            .collect();
+        env_keys.sort();
+        Ok(Self {
+            program,
+            args,
+            uses_fallback,
+            env_keys,
+        })
+    }
 }
 
 #[derive(Debug, Error)]
@@ -60,15 +135,51 @@ pub enum ExecutorError {
     #[error(transparent)]
     TomlDeserialize(#[from] toml::de::Error),
     #[error(transparent)]
+    TomlEdit(#[from] toml_edit::TomlError),
+    #[error(transparent)]
     ExecutorApprovalError(#[from] crate::approvals::ExecutorApprovalError),
     #[error(transparent)]
     CommandBuild(#[from] CommandBuildError),
     #[error("Executable `{program}` not found in PATH")]
     ExecutableNotFound { program: String },
+    #[error(
+        "`{program}` does not look like the expected agent ({reason}); another program or shell \
+         alias with the same name may be shadowing it on PATH"
+    )]
+    UnexpectedExecutable { program: String, reason: String },
+    #[error(
+        "Could not write `{path}`: the disk or the user's disk quota is full ({source}). Free up \
+         space and try again"
+    )]
+    DiskFull {
+        path: String,
+        source: std::io::Error,
+    },
     #[error("Setup helper not supported")]
     SetupHelperNotSupported,
+    #[error("Command preview not supported")]
+    CommandPreviewNotSupported,
+    #[error("Setup helper failed: {0}")]
+    SetupHelperFailed(String),
     #[error("Auth required: {0}")]
     AuthRequired(String),
+    /// Built with [`ExecutorError::timeout`], so the message always names what was waited on
+    /// and for how long, followed by the last lines the agent printed when those were captured
+    #[error("Operation timed out: {0}")]
+    Timeout(String),
+    #[error("Invalid model: {0}")]
+    InvalidModel(String),
+    #[error("Invalid reasoning effort: {0}")]
+    InvalidReasoningEffort(String),
+    #[error("Environment variable `{0}` is required but not set")]
+    MissingEnvVar(String),
+    #[error("Invalid configuration: {0}")]
+    InvalidConfig(String),
+    /// `location` is the config file position or profile option at fault
+    #[error("Invalid agent configuration at `{location}`: {message}")]
+    InvalidAgentConfig { location: String, message: String },
+    #[error("Operation cancelled")]
+    Cancelled,
 }
 
 #[enum_dispatch]
@@ -96,6 +207,60 @@ pub enum CodingAgent {
     QaMock(QaMockExecutor),
 }
 
+impl ExecutorError {
+    /// Wrap an error from writing `path`, calling out a full disk or exhausted quota
+    /// (`ENOSPC`/`EDQUOT`) since that otherwise reads as an opaque I/O failure.
+    pub fn from_write_error(path: &Path, err: std::io::Error) -> Self {
+        match err.kind() {
+            std::io::ErrorKind::StorageFull | std::io::ErrorKind::QuotaExceeded => {
+                ExecutorError::DiskFull {
+                    path: path.display().to_string(),
+                    source: err,
+                }
+            }
+            _ => ExecutorError::Io(err),
+        }
+    }
+
+    /// A [`ExecutorError::Timeout`] for giving up on `operation` after `after`, with the tail of
+    /// the agent's output if any was captured.
+    pub fn timeout(operation: &str, after: Duration, output_tail: Option<&str>) -> Self {
+        let mut message = format!("{operation} after {after:?}");
+        if let Some(tail) = output_tail {
+            message.push_str(".\nOutput tail:\n");
+            message.push_str(tail);
+        }
+        ExecutorError::Timeout(message)
+    }
+}
+
+impl BaseCodingAgent {
+    /// Env var holding the team-wide default model for this agent, e.g. `VK_DEFAULT_MODEL_CODEX`.
+    pub fn default_model_env_var(self) -> String {
+        format!("VK_DEFAULT_MODEL_{self}")
+    }
+
+    /// The model to run the agent with. Precedence: the `model` set in the agent's config, then
+    /// `VK_DEFAULT_MODEL_<AGENT>`, then (when this returns `None`) the agent CLI's own default.
+    pub fn resolve_model(self, configured: Option<&str>) -> Option<String> {
+        self.resolve_model_with(configured, |key| std::env::var(key).ok())
+    }
+
+    /// [`Self::resolve_model`], reading env vars through `lookup_env`.
+    fn resolve_model_with(
+        self,
+        configured: Option<&str>,
+        lookup_env: impl Fn(&str) -> Option<String>,
+    ) -> Option<String> {
+        if let Some(model) = configured {
+            return Some(model.to_string());
+        }
+        lookup_env(&self.default_model_env_var())
+            .map(|model| model.trim().to_string())
+            .filter(|model| !model.is_empty())
+    }
+}
+
 impl CodingAgent {
     pub fn get_mcp_config(&self) -> McpConfig {
         match self {
@@ -141,35 +306,230 @@ impl CodingAgent {
 
     pub fn capabilities(&self) -> Vec<BaseAgentCapability> {
         match self {
-            Self::ClaudeCode(_) | Self::Gemini(_) | Self::Droid(_) | Self::Opencode(_) => {
+            Self::Gemini(_) | Self::Droid(_) | Self::Opencode(_) => {
                 vec![BaseAgentCapability::SessionFork]
             }
+            Self::ClaudeCode(_) => vec![
+                BaseAgentCapability::SessionFork,
+                BaseAgentCapability::ImageInput,
+            ],
             Self::Codex(_) => vec![
                 BaseAgentCapability::SessionFork,
                 BaseAgentCapability::SetupHelper,
+                BaseAgentCapability::ImageInput,
             ],
             #[cfg(feature = "qa-mode")]
             Self::QaMock(_) => vec![], // QA mock doesn't need special capabilities
         }
     }
+
+    /// Whether images attached to a prompt should be offered to this agent.
+    pub fn supports_image_input(&self) -> bool {
+        self.capabilities()
+            .contains(&BaseAgentCapability::ImageInput)
+    }
+
+    fn cmd_overrides(&self) -> Option<&CmdOverrides> {
+        match self {
+            Self::ClaudeCode(claude) => Some(&claude.cmd),
+            Self::Gemini(gemini) => Some(&gemini.cmd),
+            Self::Codex(codex) => Some(&codex.cmd),
+            Self::Opencode(opencode) => Some(&opencode.cmd),
+            Self::Droid(droid) => Some(&droid.cmd),
+            #[cfg(feature = "qa-mode")]
+            Self::QaMock(_) => None,
+        }
+    }
+
+    /// The command this agent launches, for error messages: its `base_command_override`, or
+    /// the default command including any `VK_*` env override.
+    fn program(&self) -> String {
+        if let Some(base) = self
+            .cmd_overrides()
+            .and_then(|cmd| cmd.base_command_override.as_deref())
+        {
+            return base.to_string();
+        }
+        match self {
+            Self::ClaudeCode(claude) => {
+                claude::base_command(claude.claude_code_router.unwrap_or(false)).to_string()
+            }
+            Self::Gemini(_) => gemini::base_command().to_string(),
+            Self::Codex(_) => codex::base_command().to_string(),
+            Self::Opencode(_) => opencode::base_command().to_string(),
+            Self::Droid(_) => droid::base_command().to_string(),
+            #[cfg(feature = "qa-mode")]
+            Self::QaMock(_) => "qa-mock".to_string(),
+        }
+    }
+
+    /// The longest spawning this agent may wait for it to become ready: its `spawn_timeout_secs`
+    /// override, or the wait the agent falls back to without one.
+    pub fn spawn_timeout(&self) -> Option<Duration> {
+        if let Some(timeout) = self.cmd_overrides().and_then(CmdOverrides::spawn_timeout) {
+            return Some(timeout);
+        }
+        match self {
+            Self::Opencode(_) => Some(opencode::SERVER_STARTUP_TIMEOUT),
+            Self::Codex(codex) => codex.handshake_wait(),
+            _ => None,
+        }
+    }
+
+    /// Wall-clock limit after which the agent is stopped, from its `max_runtime_secs` override.
+    pub fn max_runtime(&self) -> Option<Duration> {
+        let secs = self.cmd_overrides()?.max_runtime_secs?;
+        Some(Duration::from_secs(u64::from(secs)))
+    }
+
+    /// `prompt`, preceded by a summary of the git state of `current_dir` when the
+    /// `include_git_status` override is set.
+    pub async fn prompt_with_git_status(&self, current_dir: &Path, prompt: &str) -> String {
+        match self.cmd_overrides() {
+            Some(cmd) if cmd.include_git_status.unwrap_or(false) => {
+                let include_diff = cmd.include_git_diff.unwrap_or(false);
+                crate::git_context::prepend_git_status(prompt, current_dir, include_diff).await
+            }
+            _ => prompt.to_string(),
+        }
+    }
+
+    /// Whether this agent needs network access to a hosted model provider.
+    /// Agents configured for local models (Codex `--oss`, OpenCode local providers) can run offline.
+    pub fn requires_network(&self) -> bool {
+        match self {
+            Self::Codex(codex) => !codex.oss.unwrap_or(false),
+            Self::Opencode(opencode) => !opencode.uses_local_provider(),
+            Self::ClaudeCode(_) | Self::Gemini(_) | Self::Droid(_) => true,
+            #[cfg(feature = "qa-mode")]
+            Self::QaMock(_) => false,
+        }
+    }
+
+    /// A copy of this agent's configuration for persisting, without runtime state such as the
+    /// approvals service.
+    pub fn clone_without_approvals(&self) -> Self {
+        let mut agent = self.clone();
+        match &mut agent {
+            Self::ClaudeCode(claude) => claude.approvals_service = None,
+            Self::Codex(codex) => codex.approvals = None,
+            Self::Gemini(gemini) => gemini.approvals = None,
+            Self::Opencode(opencode) => opencode.approvals = None,
+            Self::Droid(_) => {}
+            #[cfg(feature = "qa-mode")]
+            Self::QaMock(_) => {}
+        }
+        agent
+    }
+
+    /// TypeScript declarations for every executor config type, in the order they appear in
+    /// `shared/types.ts`. The frontend depends on these shapes, so they are snapshot-tested.
+    pub fn serialize_for_ts() -> Vec<String> {
+        vec![
+            claude::ClaudeCode::decl(),
+            gemini::Gemini::decl(),
+            gemini::GeminiSandboxMode::decl(),
+            codex::Codex::decl(),
+            codex::SandboxMode::decl(),
+            codex::AskForApproval::decl(),
+            codex::ReasoningEffort::decl(),
+            codex::ReasoningSummary::decl(),
+            codex::ReasoningSummaryFormat::decl(),
+            codex::ProviderPreset::decl(),
+            opencode::Opencode::decl(),
+            droid::Droid::decl(),
+            droid::Autonomy::decl(),
+            droid::ReasoningEffortLevel::decl(),
+            AppendPrompt::decl(),
+            AppendPromptValue::decl(),
+        ]
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, TS)]
 #[serde(tag = "type", rename_all = "SCREAMING_SNAKE_CASE")]
 #[ts(export)]
 pub enum AvailabilityInfo {
-    LoginDetected { last_auth_timestamp: i64 },
+    LoginDetected {
+        last_auth_timestamp: i64,
+    },
+    /// The agent authenticates with an API key from its profile or environment. A key leaves no
+    /// credentials file, so there is no login time to report.
+    ApiKeyConfigured,
     InstallationFound,
+    /// The agent is installed but has no saved credentials, so it cannot run until the user
+    /// logs in.
+    InstalledNeedsLogin,
     NotFound,
 }
 
+/// Availability plus details read from the agent's own config, for display.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct AvailabilityDetail {
+    pub info: AvailabilityInfo,
+    /// MCP servers already configured in the agent's own config file, when it could be read
+    pub configured_mcp_servers: Option<usize>,
+}
+
+/// Whether the agent has working credentials, in the same shape for every agent.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[serde(tag = "type", rename_all = "SCREAMING_SNAKE_CASE")]
+#[ts(export)]
+pub enum AuthStatus {
+    Authenticated {
+        /// How the agent is logged in (e.g. `apikey`, `chatgpt`), when it reports it
+        method: Option<String>,
+        /// Unix timestamp at which the credentials expire, when known
+        expires_at: Option<i64>,
+    },
+    NotAuthenticated,
+    /// The agent gives no way to tell whether it is logged in
+    Unknown,
+}
+
+impl From<&AvailabilityInfo> for AuthStatus {
+    /// Read auth from the file heuristics behind [`AvailabilityInfo`]: saved credentials mean
+    /// logged in, and an agent that is missing or installed without them is not. A config file
+    /// alone says nothing about auth.
+    fn from(info: &AvailabilityInfo) -> Self {
+        match info {
+            AvailabilityInfo::LoginDetected { .. } => AuthStatus::Authenticated {
+                method: None,
+                expires_at: None,
+            },
+            AvailabilityInfo::ApiKeyConfigured => AuthStatus::Authenticated {
+                method: Some("apikey".to_string()),
+                expires_at: None,
+            },
+            AvailabilityInfo::InstalledNeedsLogin | AvailabilityInfo::NotFound => {
+                AuthStatus::NotAuthenticated
+            }
+            AvailabilityInfo::InstallationFound => AuthStatus::Unknown,
+        }
+    }
+}
+
 impl AvailabilityInfo {
+    /// Whether the agent looks ready to run. `InstalledNeedsLogin` is not.
     pub fn is_available(&self) -> bool {
         matches!(
             self,
-            AvailabilityInfo::LoginDetected { .. } | AvailabilityInfo::InstallationFound
+            AvailabilityInfo::LoginDetected { .. }
+                | AvailabilityInfo::ApiKeyConfigured
+                | AvailabilityInfo::InstallationFound
         )
     }
+
+    /// `self`, upgraded to [`AvailabilityInfo::ApiKeyConfigured`] when the agent has an API key
+    /// and no login was detected.
+    pub fn or_api_key_login(self, has_api_key: bool) -> Self {
+        match self {
+            AvailabilityInfo::LoginDetected { .. } => self,
+            _ if has_api_key => AvailabilityInfo::ApiKeyConfigured,
+            _ => self,
+        }
+    }
 }
 
 #[async_trait]
@@ -196,6 +556,7 @@ pub trait StandardCodingAgentExecutor {
         current_dir: &Path,
         prompt: &str,
         session_id: Option<&str>,
+        _scope: ReviewScope,
         env: &ExecutionEnv,
     ) -> Result<SpawnedChild, ExecutorError> {
         match session_id {
@@ -213,6 +574,28 @@ pub trait StandardCodingAgentExecutor {
         Err(ExecutorError::SetupHelperNotSupported)
     }
 
+    /// Run the setup helper action to completion, streaming its output into `msg_store`.
+    async fn run_setup_helper(
+        &self,
+        current_dir: &Path,
+        env: &ExecutionEnv,
+        msg_store: Arc<MsgStore>,
+    ) -> Result<(), ExecutorError> {
+        let action = self.get_setup_helper_action().await?;
+        run_setup_action(&action, current_dir, env, msg_store).await
+    }
+
+    /// The program and args a new session would run, without spawning it. Executors whose
+    /// command cannot be resolved up front keep the default, which errors.
+    async fn preview_command(&self) -> Result<CommandPreview, ExecutorError> {
+        Err(ExecutorError::CommandPreviewNotSupported)
+    }
+
+    /// The sandbox this agent will use given its current configuration, if it has one.
+    fn default_sandbox(&self) -> Option<SandboxDescriptor> {
+        None
+    }
+
     fn get_availability_info(&self) -> AvailabilityInfo {
         let config_files_found = self
             .default_mcp_config_path()
@@ -225,6 +608,21 @@ pub trait StandardCodingAgentExecutor {
             AvailabilityInfo::NotFound
         }
     }
+
+    /// [`Self::get_availability_info`] plus whatever details the agent can read cheaply from
+    /// its own config. Unreadable config only drops the details, never the availability.
+    fn get_availability_detail(&self) -> AvailabilityDetail {
+        AvailabilityDetail {
+            info: self.get_availability_info(),
+            configured_mcp_servers: None,
+        }
+    }
+
+    /// Whether the agent is logged in. Defaults to the saved-credential heuristics of
+    /// [`Self::get_availability_info`]; agents that can ask their CLI do so instead.
+    async fn check_auth(&self) -> Result<AuthStatus, ExecutorError> {
+        Ok(AuthStatus::from(&self.get_availability_info()))
+    }
 }
 
 /// Result communicated through the exit signal
@@ -234,6 +632,9 @@ pub enum ExecutorExitResult {
     Success,
     /// Process should be marked as failed (non-zero exit)
     Failure,
+    /// Process ran past its configured maximum runtime; the container should interrupt it,
+    /// kill it after a grace period and mark it as failed
+    MaxRuntimeExceeded,
 }
 
 /// Optional exit notification from an executor.
@@ -264,27 +665,539 @@ impl From<AsyncGroupChild> for SpawnedChild {
     }
 }
 
+impl SpawnedChild {
+    /// Resolve the exit signal with [`ExecutorExitResult::MaxRuntimeExceeded`] once the agent
+    /// has been running for `max_runtime`, unless the executor reports a result or the process
+    /// exits first. A `None` limit leaves the child untouched.
+    pub fn with_max_runtime(mut self, max_runtime: Option<Duration>) -> Self {
+        let Some(max_runtime) = max_runtime else {
+            return self;
+        };
+        let inner_exit = self.exit_signal.take();
+        let (mut exit_tx, exit_rx) = tokio::sync::oneshot::channel();
+        self.exit_signal = Some(exit_rx);
+
+        tokio::spawn(async move {
+            let executor_exit = async move {
+                match inner_exit {
+                    Some(rx) => rx.await.ok(),
+                    None => std::future::pending().await,
+                }
+            };
+            let result = tokio::select! {
+                // The container stops listening once the process has exited on its own
+                _ = exit_tx.closed() => return,
+                // A dropped executor sender is passed on by dropping ours
+                result = executor_exit => result,
+                _ = tokio::time::sleep(max_runtime) => {
+                    tracing::warn!(
+                        "Agent exceeded its maximum runtime of {}s",
+                        max_runtime.as_secs()
+                    );
+                    Some(ExecutorExitResult::MaxRuntimeExceeded)
+                }
+            };
+            if let Some(result) = result {
+                let _ = exit_tx.send(result);
+            }
+        });
+        self
+    }
+}
+
+/// How long a freshly spawned agent gets to answer its protocol handshake. Generous, because
+/// agents launched through `npx` may first have to download their package.
+pub const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Await the protocol handshake of a freshly spawned agent. A program that never answers,
+/// or that fails the handshake (`closed_early`) without ever speaking the protocol, is
+/// reported as [`ExecutorError::UnexpectedExecutable`]. Errors from an agent that did answer
+/// are returned as the inner result.
+pub async fn expect_handshake<T, E>(
+    program: &str,
+    timeout: Duration,
+    handshake: impl Future<Output = Result<T, E>>,
+    closed_early: impl FnOnce(&E) -> bool,
+) -> Result<Result<T, E>, ExecutorError> {
+    let unexpected = |reason: String| ExecutorError::UnexpectedExecutable {
+        program: program.to_string(),
+        reason,
+    };
+    match tokio::time::timeout(timeout, handshake).await {
+        Ok(Err(err)) if closed_early(&err) => Err(unexpected(
+            "it stopped without answering the protocol handshake".to_string(),
+        )),
+        Ok(result) => Ok(result),
+        Err(_) => Err(unexpected(format!(
+            "no protocol handshake within {timeout:?}"
+        ))),
+    }
+}
+
+/// Check up front that `agent` looks ready to run, so scripts fail with a clear error instead
+/// of partway through a spawn. No local install found only means availability couldn't be
+/// verified, so the agent is rejected as missing only when its program can't be resolved.
+pub async fn require_available(agent: &CodingAgent) -> Result<(), ExecutorError> {
+    match agent.get_availability_info() {
+        info if info.is_available() => Ok(()),
+        AvailabilityInfo::InstalledNeedsLogin => Err(ExecutorError::AuthRequired(format!(
+            "{} is installed but not logged in; log in with `{}` and try again",
+            BaseCodingAgent::from(agent),
+            agent.program()
+        ))),
+        _ => match agent.preview_command().await {
+            Err(ExecutorError::CommandPreviewNotSupported) => {
+                let program = agent.program();
+                let executable = program.split_whitespace().next().unwrap_or_default();
+                match workspace_utils::shell::resolve_executable_path(executable).await {
+                    Some(_) => Ok(()),
+                    None => Err(ExecutorError::ExecutableNotFound { program }),
+                }
+            }
+            preview => preview.map(|_| ()),
+        },
+    }
+}
+
+/// Check that the agent's own config file parses, if it has one, so a broken file is reported
+/// with its path and line instead of making the agent fail opaquely at startup. The file is
+/// skipped when `env` or the profile isolates the agent's home, since the agent then doesn't
+/// read it.
+pub async fn check_agent_config(
+    agent: &CodingAgent,
+    env: &ExecutionEnv,
+) -> Result<(), ExecutorError> {
+    let isolated = env.isolated_home.is_some()
+        || agent
+            .cmd_overrides()
+            .is_some_and(|cmd| cmd.isolated_home.is_some());
+    match agent.default_mcp_config_path() {
+        Some(path) if !isolated => check_config_file(&path, &agent.get_mcp_config()).await,
+        _ => Ok(()),
+    }
+}
+
+/// JSON configs are parsed as JSONC, since agents such as Gemini and OpenCode accept comments
+/// and trailing commas in theirs.
+async fn check_config_file(path: &Path, mcp_config: &McpConfig) -> Result<(), ExecutorError> {
+    let Ok(content) = tokio::fs::read_to_string(path).await else {
+        return Ok(());
+    };
+    if content.trim().is_empty() {
+        return Ok(());
+    }
+    let invalid = |line: usize, column: usize, message: String| ExecutorError::InvalidAgentConfig {
+        location: format!("{}:{line}:{column}", path.display()),
+        message,
+    };
+    if mcp_config.is_toml_config {
+        let Err(err) = toml::from_str::<toml::Value>(&content) else {
+            return Ok(());
+        };
+        let offset = err.span().map_or(0, |span| span.start.min(content.len()));
+        let before = &content[..offset];
+        let line = before.matches('\n').count() + 1;
+        let column = offset - before.rfind('\n').map_or(0, |newline| newline + 1) + 1;
+        return Err(invalid(line, column, err.message().trim().to_string()));
+    }
+    let Err(err) = serde_json::from_str::<serde_json::Value>(&strip_jsonc(&content)) else {
+        return Ok(());
+    };
+    let (line, column) = (err.line(), err.column());
+    let message = err.to_string();
+    let message = message
+        .strip_suffix(&format!(" at line {line} column {column}"))
+        .unwrap_or(&message);
+    Err(invalid(line, column, message.to_string()))
+}
+
+/// `content` with JSONC comments and trailing commas blanked out. Every other byte keeps its
+/// line and column, so parse errors still point into the original file.
+fn strip_jsonc(content: &str) -> String {
+    let bytes = content.as_bytes();
+    let mut out = bytes.to_vec();
+    let blank = |out: &mut Vec<u8>, range: std::ops::Range<usize>| {
+        for byte in &mut out[range] {
+            if *byte != b'\n' {
+                *byte = b' ';
+            }
+        }
+    };
+    let (mut i, mut in_string, mut pending_comma) = (0, false, None);
+    while i < bytes.len() {
+        match (in_string, bytes[i], bytes.get(i + 1)) {
+            (true, b'\\', _) => i += 1,
+            (true, b'"', _) => in_string = false,
+            (true, _, _) => {}
+            (false, b'/', Some(b'/')) => {
+                let end = bytes[i..]
+                    .iter()
+                    .position(|&b| b == b'\n')
+                    .map_or(bytes.len(), |n| i + n);
+                blank(&mut out, i..end);
+                i = end;
+                continue;
+            }
+            (false, b'/', Some(b'*')) => {
+                let end = content[i + 2..]
+                    .find("*/")
+                    .map_or(bytes.len(), |n| i + 2 + n + 2);
+                blank(&mut out, i..end);
+                i = end;
+                continue;
+            }
+            (false, b',', _) => pending_comma = Some(i),
+            (false, byte, _) if byte.is_ascii_whitespace() => {}
+            (false, byte, _) => {
+                if let Some(comma) = pending_comma.take()
+                    && matches!(byte, b'}' | b']')
+                {
+                    out[comma] = b' ';
+                }
+                in_string = byte == b'"';
+            }
+        }
+        i += 1;
+    }
+    String::from_utf8(out).expect("only whole comments and ASCII commas are blanked")
+}
+
+/// Wait up to `timeout` for a spawned agent to become ready for work. An agent that fails to
+/// start or is not ready in time has its process group killed; a timeout is reported as
+/// [`ExecutorError::Timeout`] with the tail of the agent's stderr. Without a timeout the child
+/// is returned right away and startup stays unbounded.
+pub async fn await_ready_within(
+    spawned: SpawnedChild,
+    readiness: ReadinessFuture,
+    timeout: Option<Duration>,
+) -> Result<SpawnedChild, ExecutorError> {
+    match timeout {
+        Some(timeout) => await_ready_for(spawned, readiness, timeout, timeout).await,
+        None => Ok(spawned),
+    }
+}
+
+/// [`await_ready_within`] for an agent that already used part of its spawn timeout: waits
+/// `wait`, but reports a timeout as the whole `timeout`.
+pub(crate) async fn await_ready_for(
+    mut spawned: SpawnedChild,
+    readiness: ReadinessFuture,
+    wait: Duration,
+    timeout: Duration,
+) -> Result<SpawnedChild, ExecutorError> {
+    let failed = match tokio::time::timeout(wait, readiness).await {
+        Ok(Ok(())) => return Ok(spawned),
+        Ok(Err(err)) => Some(err),
+        Err(_) => None,
+    };
+
+    if let Err(kill_err) = spawned.child.kill().await {
+        tracing::warn!("Failed to kill agent that did not start: {kill_err}");
+    }
+    match failed {
+        Some(err) => Err(err),
+        None => {
+            let stderr = spawned.child.inner().stderr.take();
+            let output_tail = read_output_tail(stderr).await;
+            Err(ExecutorError::timeout(
+                "waiting for the agent to be ready",
+                timeout,
+                Some(&output_tail),
+            ))
+        }
+    }
+}
+
+/// How long to keep reading a killed agent's stderr; a grandchild that outlived the group
+/// kill can hold the pipe open.
+const OUTPUT_TAIL_READ_TIMEOUT: Duration = Duration::from_secs(1);
+
+async fn read_output_tail(stderr: Option<tokio::process::ChildStderr>) -> String {
+    let Some(stderr) = stderr else {
+        return String::new();
+    };
+    let mut lines = BufReader::new(stderr).lines();
+    let mut captured = Vec::new();
+    let _ = tokio::time::timeout(OUTPUT_TAIL_READ_TIMEOUT, async {
+        while let Ok(Some(line)) = lines.next_line().await {
+            captured.push(line);
+        }
+    })
+    .await;
+    format_tail(captured)
+}
+
+/// The last lines of captured agent output, for error messages.
+pub(crate) fn format_tail(captured: Vec<String>) -> String {
+    captured
+        .into_iter()
+        .rev()
+        .take(12)
+        .collect::<Vec<_>>()
+        .into_iter()
+        .rev()
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Spawn `action` (without its follow-up actions), forward its stdout/stderr lines into
+/// `msg_store`, and wait for it to exit before marking the store finished. A non-zero exit
+/// is reported as [`ExecutorError::SetupHelperFailed`].
+pub async fn run_setup_action(
+    action: &ExecutorAction,
+    current_dir: &Path,
+    env: &ExecutionEnv,
+    msg_store: Arc<MsgStore>,
+) -> Result<(), ExecutorError> {
+    let mut spawned = action
+        .typ()
+        .spawn(current_dir, Arc::new(NoopExecutorApprovalService), env)
+        .await?;
+    let stdout = spawned.child.inner().stdout.take();
+    let stderr = spawned.child.inner().stderr.take();
+
+    let forward_stdout = async {
+        if let Some(stdout) = stdout {
+            let mut lines = BufReader::new(stdout).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                msg_store.push_stdout(format!("{line}\n"));
+            }
+        }
+    };
+    let forward_stderr = async {
+        if let Some(stderr) = stderr {
+            let mut lines = BufReader::new(stderr).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                msg_store.push_stderr(format!("{line}\n"));
+            }
+        }
+    };
+    tokio::join!(forward_stdout, forward_stderr);
+
+    let status = spawned.child.wait().await.map_err(ExecutorError::Io)?;
+    msg_store.push_finished();
+    if status.success() {
+        Ok(())
+    } else {
+        Err(ExecutorError::SetupHelperFailed(format!(
+            "setup helper exited with {status}"
+        )))
+    }
+}
+
+/// Resolves once an agent started with `spawn_deferred` is ready for work (server up,
+/// conversation created), or with the startup error if it never gets there.
+#[derive(Debug)]
+pub struct ReadinessFuture(tokio::sync::oneshot::Receiver<Result<(), ExecutorError>>);
+
+impl ReadinessFuture {
+    pub fn channel() -> (ReadinessSignal, Self) {
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        let signal = ReadinessSignal {
+            tx: Some(tx),
+            spawn_timer: None,
+        };
+        (signal, Self(rx))
+    }
+}
+
+impl Future for ReadinessFuture {
+    type Output = Result<(), ExecutorError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.0).poll(cx).map(|result| {
+            // The signal is dropped when the agent is stopped before it became ready
+            result.unwrap_or(Err(ExecutorError::Cancelled))
+        })
+    }
+}
+
+/// Executor side of a [`ReadinessFuture`]. Only the first `ready`/`fail` call is delivered;
+/// the default signal has no listener and ignores both.
+#[derive(Debug, Default)]
+pub struct ReadinessSignal {
+    tx: Option<tokio::sync::oneshot::Sender<Result<(), ExecutorError>>>,
+    spawn_timer: Option<SpawnTimer>,
+}
+
+impl ReadinessSignal {
+    /// Also record [`SpawnPhase::Ready`] in `timer` when the agent becomes ready.
+    pub fn with_spawn_timer(mut self, timer: Option<SpawnTimer>) -> Self {
+        self.spawn_timer = timer;
+        self
+    }
+
+    pub fn ready(&mut self) {
+        if let Some(timer) = &self.spawn_timer {
+            timer.mark(SpawnPhase::Ready);
+        }
+        if let Some(tx) = self.tx.take() {
+            let _ = tx.send(Ok(()));
+        }
+    }
+
+    pub fn fail(&mut self, err: ExecutorError) {
+        if let Some(tx) = self.tx.take() {
+            let _ = tx.send(Err(err));
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, TS, JsonSchema)]
 #[serde(transparent)]
 #[schemars(
     title = "Append Prompt",
-    description = "Extra text appended to the prompt",
+    description = "Extra text appended to the prompt, or `@snippet:<name>` to append a shared snippet. A list appends each entry in order, separated by blank lines",
     extend("format" = "textarea")
 )]
 #[derive(Default)]
-pub struct AppendPrompt(pub Option<String>);
+// The schema stays a plain string so the settings form keeps its textarea; lists are written in
+// the profile JSON directly.
+pub struct AppendPrompt(#[schemars(with = "Option<String>")] pub Option<AppendPromptValue>);
+
+/// A single append prompt, or an ordered list of them.
+// Plain strings keep deserializing as before, so existing profiles are unaffected.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, TS)]
+#[serde(untagged)]
+pub enum AppendPromptValue {
+    Single(String),
+    Multiple(Vec<String>),
+}
+
+/// Marks an append prompt as a reference to a named entry in the shared snippets file.
+const SNIPPET_PREFIX: &str = "@snippet:";
+
+/// Separates the entries of a list-valued append prompt.
+const APPEND_PROMPT_SEPARATOR: &str = "\n\n";
 
 impl AppendPrompt {
+    /// The text appended to the prompt, with `@snippet:` references left unresolved.
     pub fn get(&self) -> Option<String> {
-        self.0.clone()
+        self.join(|value| value.to_string())
     }
 
-    pub fn combine_prompt(&self, prompt: &str) -> String {
-        match self {
-            AppendPrompt(Some(value)) => format!("{prompt}{value}"),
-            AppendPrompt(None) => prompt.to_string(),
+    /// `prompt` followed by the append prompt. Fails with
+    /// [`ExecutorError::InvalidConfig`] naming the snippet when an `@snippet:` reference
+    /// cannot be resolved, rather than sending the reference to the agent as text.
+    pub async fn combine_prompt(&self, prompt: &str) -> Result<String, ExecutorError> {
+        self.combine_prompt_with_snippets(prompt, &workspace_utils::assets::prompt_snippets_path())
+            .await
+    }
+
+    /// [`AppendPrompt::combine_prompt`], followed by the `prompt_append` instructions stored
+    /// in the `meta` of the agent's MCP servers in `env`, persisted and ephemeral, each after a
+    /// blank line.
+    pub async fn combine_prompt_with_mcp_meta(
+        &self,
+        prompt: &str,
+        env: &ExecutionEnv,
+    ) -> Result<String, ExecutorError> {
+        let mut combined = self.combine_prompt(prompt).await?;
+        let layers: Vec<&serde_json::Value> = [
+            env.persisted_mcp_servers.as_ref(),
+            env.ephemeral_mcp_servers.as_ref(),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        for instruction in layered_meta_prompt_instructions(&layers) {
+            combined.push_str(APPEND_PROMPT_SEPARATOR);
+            combined.push_str(&instruction);
+        }
+        Ok(combined)
+    }
+
+    async fn combine_prompt_with_snippets(
+        &self,
+        prompt: &str,
+        snippets_path: &Path,
+    ) -> Result<String, ExecutorError> {
+        Ok(match self.resolve(snippets_path).await? {
+            Some(value) => format!("{prompt}{value}"),
+            None => prompt.to_string(),
+        })
+    }
+
+    /// Replace each `@snippet:<name>` entry with the named snippet; inline text is kept as-is.
+    /// The snippets file is only read when an entry references it.
+    async fn resolve(&self, snippets_path: &Path) -> Result<Option<String>, ExecutorError> {
+        let names: Vec<&str> = self
+            .entries()
+            .iter()
+            .filter_map(|value| snippet_name(value))
+            .collect();
+        let snippets = if names.is_empty() {
+            HashMap::new()
+        } else {
+            load_snippets(snippets_path).await
+        };
+        if let Some(missing) = names.iter().find(|name| !snippets.contains_key(**name)) {
+            return Err(ExecutorError::InvalidConfig(format!(
+                "prompt snippet '{missing}' not found in {}",
+                snippets_path.display()
+            )));
+        }
+        Ok(self.join(|value| match snippet_name(value) {
+            Some(name) => snippets[name].clone(),
+            None => value.to_string(),
+        }))
+    }
+
+    fn entries(&self) -> &[String] {
+        match &self.0 {
+            None => &[],
+            Some(AppendPromptValue::Single(value)) => std::slice::from_ref(value),
+            Some(AppendPromptValue::Multiple(values)) => values,
         }
     }
+
+    /// A single string is appended verbatim, as it always has been. List entries are each
+    /// mapped through `entry` and appended in order, each preceded by a blank line; empty
+    /// entries are skipped.
+    fn join(&self, entry: impl Fn(&str) -> String) -> Option<String> {
+        match self.0.as_ref()? {
+            AppendPromptValue::Single(value) => Some(entry(value)),
+            AppendPromptValue::Multiple(values) => {
+                let joined: String = values
+                    .iter()
+                    .filter(|value| !value.trim().is_empty())
+                    .map(|value| format!("{APPEND_PROMPT_SEPARATOR}{}", entry(value)))
+                    .collect();
+                (!joined.is_empty()).then_some(joined)
+            }
+        }
+    }
+}
+
+fn snippet_name(value: &str) -> Option<&str> {
+    value
+        .trim()
+        .strip_prefix(SNIPPET_PREFIX)
+        .map(|name| name.trim())
+}
+
+/// Read the snippets file, a JSON object of name to text. A missing or invalid file is
+/// reported and treated as empty.
+async fn load_snippets(snippets_path: &Path) -> HashMap<String, String> {
+    let content = match tokio::fs::read_to_string(snippets_path).await {
+        Ok(content) => content,
+        Err(err) => {
+            tracing::warn!(
+                "Failed to read prompt snippets from {}: {}",
+                snippets_path.display(),
+                err
+            );
+            return HashMap::new();
+        }
+    };
+    serde_json::from_str(&content).unwrap_or_else(|err| {
+        tracing::warn!(
+            "Invalid prompt snippets file {}: {}",
+            snippets_path.display(),
+            err
+        );
+        HashMap::new()
+    })
 }
 
 pub fn build_review_prompt(
@@ -304,6 +1217,13 @@ pub fn build_review_prompt(
                 "Use `git diff {}..HEAD` to see the changes.\n",
                 repo.base_commit
             ));
+            if repo.include_commit_log {
+                prompt.push_str(&format!(
+                    "Read the commit messages with `git log {}..HEAD` and start your review \
+                     with a short summary of them.\n",
+                    repo.base_commit
+                ));
+            }
             prompt.push('\n');
         }
     }
@@ -315,6 +1235,18 @@ pub fn build_review_prompt(
     prompt
 }
 
+pub fn build_uncommitted_review_prompt(additional_prompt: Option<&str>) -> String {
+    let mut prompt = String::from("Please review the uncommitted changes in the working tree.\n\n");
+    prompt.push_str("Use `git diff HEAD` to see both staged and unstaged changes, ");
+    prompt.push_str("and `git status` to find new files that are not yet tracked.\n\n");
+
+    if let Some(additional) = additional_prompt {
+        prompt.push_str(additional);
+    }
+
+    prompt
+}
+
 #[cfg(test)]
 mod tests {
     use std::str::FromStr;
@@ -327,4 +1259,551 @@ mod tests {
         assert!(result.is_ok(), "CLAUDE_CODE should be valid");
         assert_eq!(result.unwrap(), BaseCodingAgent::ClaudeCode);
     }
+
+    #[test]
+    fn test_clone_without_approvals_keeps_config_and_drops_service() {
+        let mut agent: CodingAgent = serde_json::from_value(serde_json::json!({
+            "OPENCODE": { "model": "anthropic/claude-sonnet-4", "auto_approve": false }
+        }))
+        .unwrap();
+        agent.use_approvals(Arc::new(NoopExecutorApprovalService));
+
+        let cloned = agent.clone_without_approvals();
+        assert_eq!(cloned, agent);
+        let CodingAgent::Opencode(opencode) = cloned else {
+            panic!("expected an OpenCode config, got {cloned:?}");
+        };
+        assert!(opencode.approvals.is_none());
+        assert_eq!(opencode.model.as_deref(), Some("anthropic/claude-sonnet-4"));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_enospc_and_edquot_map_to_disk_full() {
+        let path = Path::new("/home/dev/.codex/config.toml");
+        for errno in [28, 122] {
+            let err =
+                ExecutorError::from_write_error(path, std::io::Error::from_raw_os_error(errno));
+            assert!(matches!(err, ExecutorError::DiskFull { .. }), "{err:?}");
+            assert!(
+                err.to_string().contains("/home/dev/.codex/config.toml"),
+                "{err}"
+            );
+        }
+
+        let err = ExecutorError::from_write_error(
+            path,
+            std::io::Error::from(std::io::ErrorKind::PermissionDenied),
+        );
+        assert!(matches!(err, ExecutorError::Io(_)), "{err:?}");
+    }
+
+    #[test]
+    fn test_uncommitted_review_prompt_points_at_working_tree() {
+        let prompt = build_uncommitted_review_prompt(Some("Focus on error handling."));
+        assert!(prompt.contains("uncommitted changes"));
+        assert!(prompt.contains("`git diff HEAD`"));
+        assert!(!prompt.contains(".."));
+        assert!(prompt.ends_with("Focus on error handling."));
+    }
+
+    #[test]
+    fn test_review_prompt_asks_for_commit_log_when_enabled() {
+        let mut repo = RepoReviewContext {
+            repo_id: uuid::Uuid::nil(),
+            repo_name: "backend".to_string(),
+            base_commit: "abc123".to_string(),
+            include_commit_log: true,
+        };
+        let prompt = build_review_prompt(Some(std::slice::from_ref(&repo)), None);
+        assert!(prompt.contains("`git log abc123..HEAD`"), "{prompt}");
+        assert!(prompt.contains("summary of them"), "{prompt}");
+
+        repo.include_commit_log = false;
+        let prompt = build_review_prompt(Some(&[repo]), None);
+        assert!(!prompt.contains("git log"), "{prompt}");
+    }
+
+    #[test]
+    fn test_timeout_error_display() {
+        let err =
+            ExecutorError::timeout("waiting for the server", Duration::from_millis(1500), None);
+        assert_eq!(
+            err.to_string(),
+            "Operation timed out: waiting for the server after 1.5s"
+        );
+
+        let err = ExecutorError::timeout(
+            "waiting for the server",
+            Duration::from_secs(20),
+            Some("loading config"),
+        );
+        assert_eq!(
+            err.to_string(),
+            "Operation timed out: waiting for the server after 20s.\nOutput tail:\nloading config"
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_spawn_timeout_kills_agent_and_reports_stderr_tail() {
+        use command_group::AsyncCommandGroup;
+
+        let child = tokio::process::Command::new("sh")
+            .args(["-c", "echo 'loading config' >&2; sleep 30"])
+            .stderr(std::process::Stdio::piped())
+            .kill_on_drop(true)
+            .group_spawn()
+            .unwrap();
+        let (_signal, readiness) = ReadinessFuture::channel();
+
+        let started = std::time::Instant::now();
+        let err = await_ready_within(child.into(), readiness, Some(Duration::from_millis(300)))
+            .await
+            .unwrap_err();
+        assert!(started.elapsed() < Duration::from_secs(5));
+        let ExecutorError::Timeout(message) = err else {
+            panic!("expected a spawn timeout, got {err:?}");
+        };
+        assert_eq!(
+            message,
+            "waiting for the agent to be ready after 300ms.\nOutput tail:\nloading config"
+        );
+    }
+
+    #[cfg(unix)]
+    fn script_action(script: &str) -> ExecutorAction {
+        use crate::actions::{
+            ExecutorActionType,
+            script::{ScriptContext, ScriptRequest, ScriptRequestLanguage},
+        };
+
+        ExecutorAction::new(
+            ExecutorActionType::ScriptRequest(ScriptRequest {
+                script: script.to_string(),
+                language: ScriptRequestLanguage::Bash,
+                context: ScriptContext::ToolInstallScript,
+                working_dir: None,
+            }),
+            None,
+        )
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_run_setup_action_streams_output_and_reports_exit() {
+        use crate::env::RepoContext;
+
+        let env = ExecutionEnv::new(RepoContext::default(), false);
+        let dir = std::env::temp_dir();
+
+        let msg_store = Arc::new(MsgStore::new());
+        run_setup_action(
+            &script_action("echo logged in; echo warning >&2"),
+            &dir,
+            &env,
+            msg_store.clone(),
+        )
+        .await
+        .unwrap();
+        let history = msg_store.get_history();
+        assert!(history.iter().any(|msg| matches!(
+            msg,
+            workspace_utils::log_msg::LogMsg::Stdout(line) if line == "logged in\n"
+        )));
+        assert!(history.iter().any(|msg| matches!(
+            msg,
+            workspace_utils::log_msg::LogMsg::Stderr(line) if line == "warning\n"
+        )));
+
+        let err = run_setup_action(
+            &script_action("exit 3"),
+            &dir,
+            &env,
+            Arc::new(MsgStore::new()),
+        )
+        .await
+        .unwrap_err();
+        assert!(
+            matches!(err, ExecutorError::SetupHelperFailed(_)),
+            "{err:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_readiness_future_reports_signal_outcome() {
+        let (mut signal, readiness) = ReadinessFuture::channel();
+        signal.ready();
+        signal.fail(ExecutorError::Cancelled);
+        assert!(readiness.await.is_ok());
+
+        // The original error is passed through, not flattened into a message
+        let (mut signal, readiness) = ReadinessFuture::channel();
+        signal.fail(ExecutorError::ExecutableNotFound {
+            program: "codex".to_string(),
+        });
+        assert!(matches!(
+            readiness.await,
+            Err(ExecutorError::ExecutableNotFound { program }) if program == "codex"
+        ));
+
+        let (signal, readiness) = ReadinessFuture::channel();
+        drop(signal);
+        assert!(matches!(readiness.await, Err(ExecutorError::Cancelled)));
+
+        let (mut signal, readiness) = ReadinessFuture::channel();
+        signal.fail(ExecutorError::Cancelled);
+        assert!(matches!(readiness.await, Err(ExecutorError::Cancelled)));
+    }
+
+    #[test]
+    fn test_executor_config_ts_matches_snapshot() {
+        let snapshot = include_str!("../../../../shared/types.ts");
+        for decl in CodingAgent::serialize_for_ts() {
+            let decl = format!("export {}", decl.trim_start().trim_start_matches("export "));
+            assert!(
+                snapshot.contains(&decl),
+                "shared/types.ts is out of date for:\n{decl}\nRun `npm run generate-types`."
+            );
+        }
+    }
+
+    fn agent(value: serde_json::Value) -> CodingAgent {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn test_spawn_timeout_falls_back_to_the_agents_own_wait() {
+        let timeout = |value| agent(value).spawn_timeout();
+        assert_eq!(
+            timeout(serde_json::json!({ "OPENCODE": {} })),
+            Some(opencode::SERVER_STARTUP_TIMEOUT)
+        );
+        assert_eq!(
+            timeout(serde_json::json!({ "OPENCODE": { "spawn_timeout_secs": 240 } })),
+            Some(Duration::from_secs(240))
+        );
+        assert_eq!(
+            timeout(serde_json::json!({ "CODEX": {} })),
+            Some(HANDSHAKE_TIMEOUT * 4)
+        );
+        assert_eq!(
+            timeout(serde_json::json!({ "CODEX": { "connect_retries": 0 } })),
+            None
+        );
+        assert_eq!(timeout(serde_json::json!({ "GEMINI": {} })), None);
+    }
+
+    #[tokio::test]
+    async fn test_malformed_agent_config_is_reported_with_its_location() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let toml_path = dir.path().join("config.toml");
+        std::fs::write(&toml_path, "model = \"o3\"\n[mcp_servers\nfoo = 1\n").unwrap();
+        let codex = agent(serde_json::json!({ "CODEX": {} })).get_mcp_config();
+        let err = check_config_file(&toml_path, &codex).await.unwrap_err();
+        assert!(
+            matches!(
+                &err,
+                ExecutorError::InvalidAgentConfig { location, .. }
+                    if location.starts_with(&format!("{}:2:", toml_path.display()))
+            ),
+            "{err:?}"
+        );
+
+        let json_path = dir.path().join("settings.json");
+        std::fs::write(
+            &json_path,
+            "{\n  \"mcpServers\": {}\n  \"theme\": \"Default\"\n}\n",
+        )
+        .unwrap();
+        let gemini = agent(serde_json::json!({ "GEMINI": {} })).get_mcp_config();
+        let err = check_config_file(&json_path, &gemini).await.unwrap_err();
+        assert!(
+            matches!(
+                &err,
+                ExecutorError::InvalidAgentConfig { location, .. }
+                    if *location == format!("{}:3:3", json_path.display())
+            ),
+            "{err:?}"
+        );
+
+        // Gemini and OpenCode read their JSON configs as JSONC
+        std::fs::write(
+            &json_path,
+            "{\n  // servers \"added\" by hand\n  \"mcpServers\": { \"a\": \"//x\", },\n  \
+             /* theme, */ \"theme\": \"Default\",\n}\n",
+        )
+        .unwrap();
+        check_config_file(&json_path, &gemini).await.unwrap();
+
+        std::fs::write(&json_path, "{ \"mcpServers\": {} }").unwrap();
+        check_config_file(&json_path, &gemini).await.unwrap();
+        check_config_file(&dir.path().join("missing.json"), &gemini)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_agent_config_is_not_checked_in_an_isolated_home() {
+        use crate::env::RepoContext;
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("opencode.json"), "{ \"mcp\": }").unwrap();
+        let opencode = agent(serde_json::json!({
+            "OPENCODE": { "config_dir_override": dir.path() }
+        }));
+
+        let env = ExecutionEnv::new(RepoContext::default(), false);
+        assert!(matches!(
+            check_agent_config(&opencode, &env).await,
+            Err(ExecutorError::InvalidAgentConfig { .. })
+        ));
+        let isolated = env.with_isolated_home(dir.path().join("home"));
+        check_agent_config(&opencode, &isolated).await.unwrap();
+    }
+
+    #[test]
+    fn test_default_model_from_env() {
+        assert_eq!(
+            BaseCodingAgent::Droid.default_model_env_var(),
+            "VK_DEFAULT_MODEL_DROID"
+        );
+
+        let env = std::collections::HashMap::from([
+            ("VK_DEFAULT_MODEL_DROID", " team-model "),
+            ("VK_DEFAULT_MODEL_CODEX", "  "),
+        ]);
+        let lookup_env = |key: &str| env.get(key).map(|value| value.to_string());
+        let resolve = |agent: BaseCodingAgent, configured: Option<&str>| {
+            agent.resolve_model_with(configured, lookup_env)
+        };
+        assert_eq!(
+            resolve(BaseCodingAgent::Droid, None).as_deref(),
+            Some("team-model")
+        );
+        assert_eq!(
+            resolve(BaseCodingAgent::Droid, Some("own-model")).as_deref(),
+            Some("own-model")
+        );
+        assert_eq!(resolve(BaseCodingAgent::Codex, None), None);
+        assert_eq!(resolve(BaseCodingAgent::Gemini, None), None);
+    }
+
+    #[tokio::test]
+    async fn test_append_prompt_resolves_named_snippet() {
+        let snippets = tempfile::NamedTempFile::new().unwrap();
+        let path = snippets.path();
+        std::fs::write(path, r#"{ "tests": "\nAlways run the test suite." }"#).unwrap();
+
+        let snippet = AppendPrompt(Some(AppendPromptValue::Single(
+            "@snippet:tests".to_string(),
+        )));
+        assert_eq!(
+            snippet
+                .combine_prompt_with_snippets("Fix the bug", path)
+                .await
+                .unwrap(),
+            "Fix the bug\nAlways run the test suite."
+        );
+
+        let inline = AppendPrompt(Some(AppendPromptValue::Single("\nBe brief.".to_string())));
+        assert_eq!(
+            inline
+                .combine_prompt_with_snippets("Fix the bug", path)
+                .await
+                .unwrap(),
+            "Fix the bug\nBe brief."
+        );
+
+        let missing = AppendPrompt(Some(AppendPromptValue::Single(
+            "@snippet:unknown".to_string(),
+        )));
+        let err = missing
+            .combine_prompt_with_snippets("Fix the bug", path)
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(&err, ExecutorError::InvalidConfig(message) if message.contains("'unknown'")),
+            "{err}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_append_prompt_list_joins_entries_in_order() {
+        let snippets = tempfile::NamedTempFile::new().unwrap();
+        let path = snippets.path();
+        std::fs::write(path, r#"{ "tests": "Always run the test suite." }"#).unwrap();
+
+        let append: AppendPrompt = serde_json::from_value(serde_json::json!([
+            "Be brief.",
+            "@snippet:tests",
+            " ",
+            "Ask first."
+        ]))
+        .unwrap();
+        assert_eq!(
+            append
+                .combine_prompt_with_snippets("Fix the bug", path)
+                .await
+                .unwrap(),
+            "Fix the bug\n\nBe brief.\n\nAlways run the test suite.\n\nAsk first."
+        );
+        assert_eq!(
+            append.get().as_deref(),
+            Some("\n\nBe brief.\n\n@snippet:tests\n\nAsk first.")
+        );
+
+        let single: AppendPrompt =
+            serde_json::from_value(serde_json::json!("\nBe brief.")).unwrap();
+        assert_eq!(
+            single,
+            AppendPrompt(Some(AppendPromptValue::Single("\nBe brief.".to_string())))
+        );
+        assert_eq!(
+            single
+                .combine_prompt_with_snippets("Fix the bug", path)
+                .await
+                .unwrap(),
+            "Fix the bug\nBe brief."
+        );
+    }
+
+    #[tokio::test]
+    async fn test_mcp_meta_instructions_are_appended() {
+        use crate::env::RepoContext;
+
+        let persisted = serde_json::json!({
+            "playwright": { "command": "npx", "args": ["@playwright/mcp@latest"] },
+            "context7": { "type": "http", "url": "https://mcp.context7.com/mcp" },
+            "meta": {
+                "playwright": {
+                    "name": "Playwright",
+                    "prompt_append": "Use the Playwright MCP server to check UI changes."
+                },
+                "context7": { "prompt_append": "Look up library docs with Context7." },
+                "exa": { "prompt_append": "Exa is not configured, so this is skipped." }
+            }
+        });
+        let append = AppendPrompt(Some(AppendPromptValue::Single("\nBe brief.".to_string())));
+        let env = ExecutionEnv::new(RepoContext::default(), false);
+
+        assert_eq!(
+            append
+                .combine_prompt_with_mcp_meta("Fix the bug", &env)
+                .await
+                .unwrap(),
+            "Fix the bug\nBe brief."
+        );
+
+        let env = env.with_persisted_mcp_servers(persisted);
+        assert_eq!(
+            append
+                .combine_prompt_with_mcp_meta("Fix the bug", &env)
+                .await
+                .unwrap(),
+            "Fix the bug\nBe brief.\n\nLook up library docs with Context7.\n\n\
+             Use the Playwright MCP server to check UI changes."
+        );
+
+        // A server added for this run replaces the saved one of the same name, meta included
+        let env = env.with_ephemeral_mcp_servers(serde_json::json!({
+            "context7": { "type": "http", "url": "http://localhost:3000/mcp" },
+            "exa": { "command": "npx", "args": ["exa-mcp-server"] },
+            "meta": { "exa": { "prompt_append": "Search the web with Exa." } }
+        }));
+        assert_eq!(
+            append
+                .combine_prompt_with_mcp_meta("Fix the bug", &env)
+                .await
+                .unwrap(),
+            "Fix the bug\nBe brief.\n\nSearch the web with Exa.\n\n\
+             Use the Playwright MCP server to check UI changes."
+        );
+    }
+
+    #[cfg(unix)]
+    fn long_running_child() -> AsyncGroupChild {
+        use command_group::AsyncCommandGroup;
+
+        tokio::process::Command::new("sleep")
+            .arg("30")
+            .kill_on_drop(true)
+            .group_spawn()
+            .unwrap()
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_max_runtime_signals_exit_for_long_running_child() {
+        let mut spawned = SpawnedChild::from(long_running_child())
+            .with_max_runtime(Some(Duration::from_millis(100)));
+
+        let exit_signal = spawned.exit_signal.take().unwrap();
+        let result = tokio::time::timeout(Duration::from_secs(5), exit_signal)
+            .await
+            .expect("max runtime never fired")
+            .unwrap();
+        assert!(matches!(result, ExecutorExitResult::MaxRuntimeExceeded));
+        // Interrupting and killing is left to the container, so nothing has stopped the child
+        assert!(spawned.child.try_wait().unwrap().is_none());
+
+        spawned.child.kill().await.unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_max_runtime_forwards_executor_result_that_arrives_first() {
+        let (exit_tx, exit_rx) = tokio::sync::oneshot::channel();
+        let mut spawned = SpawnedChild {
+            child: long_running_child(),
+            exit_signal: Some(exit_rx),
+            interrupt_sender: None,
+        }
+        .with_max_runtime(Some(Duration::from_secs(30)));
+
+        exit_tx.send(ExecutorExitResult::Success).unwrap();
+        let exit_signal = spawned.exit_signal.take().unwrap();
+        let result = tokio::time::timeout(Duration::from_secs(5), exit_signal)
+            .await
+            .expect("executor result was not forwarded")
+            .unwrap();
+        assert!(matches!(result, ExecutorExitResult::Success));
+
+        spawned.child.kill().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_require_available_accepts_an_installed_agent() {
+        let dir = tempfile::tempdir().unwrap();
+        let opencode =
+            agent(serde_json::json!({ "OPENCODE": { "config_dir_override": dir.path() } }));
+
+        assert!(require_available(&opencode).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_require_available_tries_an_unverified_agent_on_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("opencode");
+        let opencode = agent(serde_json::json!({
+            "OPENCODE": { "config_dir_override": missing, "base_command_override": "sh -c true" }
+        }));
+
+        assert!(require_available(&opencode).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_require_available_names_the_missing_executable() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("opencode");
+        let opencode = agent(serde_json::json!({
+            "OPENCODE": { "config_dir_override": missing, "base_command_override": "my-opencode" }
+        }));
+
+        assert!(matches!(
+            require_available(&opencode).await,
+            Err(ExecutorError::ExecutableNotFound { program }) if program == "my-opencode"
+        ));
+    }
 }
@@ -1,5 +1,5 @@
 use std::{
-    path::Path,
+    path::{Path, PathBuf},
     sync::{Arc, LazyLock},
     time::Duration,
 };
@@ -9,21 +9,25 @@ use command_group::AsyncCommandGroup;
 use derivative::Derivative;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use tokio::{io::AsyncBufReadExt, process::Command};
+use tokio::process::Command;
+use tokio_util::sync::CancellationToken;
 use ts_rs::TS;
-use workspace_utils::msg_store::MsgStore;
+use workspace_utils::{msg_store::MsgStore, stream_lines::read_line_lossy};
 
 use crate::{
-    approvals::ExecutorApprovalService,
+    approvals::{ExecutorApprovalService, ExternalPathGuard},
     command::{
         CmdOverrides, CommandBuildError, CommandBuilder, CommandParts, apply_overrides,
         env_command_or_default,
     },
     env::ExecutionEnv,
     executors::{
-        AppendPrompt, AvailabilityInfo, ExecutorError, ExecutorExitResult, SpawnedChild,
-        StandardCodingAgentExecutor,
+        AppendPrompt, AvailabilityInfo, BaseCodingAgent, ExecutorError, ExecutorExitResult,
+        ReadinessFuture, SandboxDescriptor, SandboxLevel, SpawnedChild,
+        StandardCodingAgentExecutor, format_tail,
     },
+    logs::{paths::relativize_normalized_entries, utils::patch::tag_normalized_entries},
+    spawn_timing::SpawnPhase,
     stdout_dup::create_stdout_pipe_writer,
 };
 
@@ -31,7 +35,7 @@ mod normalize_logs;
 mod sdk;
 mod types;
 
-use sdk::{LogWriter, RunConfig, run_session};
+use sdk::{LogWriter, RunConfig, run_session, validate_model};
 
 static CODEX_COMMAND: LazyLock<String> =
     LazyLock::new(|| env_command_or_default("VK_OPENCODE", "opencode"));
@@ -55,9 +59,18 @@ pub struct Opencode {
     pub model: Option<String>,
     #[serde(default, skip_serializing_if = "Option::is_none", alias = "agent")]
     pub mode: Option<String>,
+    /// Reasoning effort for models that support it (`minimal`, `low`, `medium` or `high`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoning_effort: Option<String>,
     /// Auto-approve agent actions
     #[serde(default = "default_to_true")]
     pub auto_approve: bool,
+    /// Ask before editing files outside the working directory, even when auto-approving
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub guard_external_paths: Option<bool>,
+    /// Directory OpenCode loads its config, including MCP servers, from instead of the default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub config_dir_override: Option<PathBuf>,
     #[serde(flatten)]
     pub cmd: CmdOverrides,
     #[serde(skip)]
@@ -66,7 +79,48 @@ pub struct Opencode {
     pub approvals: Option<Arc<dyn ExecutorApprovalService>>,
 }
 
+/// OpenCode providers that serve models from the local machine
+const LOCAL_PROVIDERS: &[&str] = &["ollama", "lmstudio", "llama.cpp"];
+
+/// Reasoning effort levels accepted by providers that support OpenCode's `reasoningEffort`
+/// model option.
+const REASONING_EFFORTS: &[&str] = &["minimal", "low", "medium", "high"];
+
+/// OpenCode's default primary agent, used when no `mode` is configured.
+const DEFAULT_AGENT: &str = "build";
+
 impl Opencode {
+    /// Whether the configured model (`provider/model`) is served by a local provider.
+    pub fn uses_local_provider(&self) -> bool {
+        self.resolved_model()
+            .as_deref()
+            .and_then(|model| model.split_once('/'))
+            .is_some_and(|(provider, _)| LOCAL_PROVIDERS.contains(&provider))
+    }
+
+    /// The configured model, falling back to `VK_DEFAULT_MODEL_OPENCODE`.
+    fn resolved_model(&self) -> Option<String> {
+        BaseCodingAgent::Opencode.resolve_model(self.model.as_deref())
+    }
+
+    fn guards_external_paths(&self) -> bool {
+        self.guard_external_paths.unwrap_or(false)
+    }
+
+    /// Env for the spawned server: the runtime and profile env, the reasoning effort, any
+    /// config directory override and the permissions approvals need.
+    fn server_env(&self, env: &ExecutionEnv) -> ExecutionEnv {
+        let mut env = setup_reasoning_env(
+            self.mode.as_deref(),
+            self.reasoning_effort.as_deref(),
+            &env.clone().with_profile(&self.cmd),
+        );
+        if let Some(dir) = &self.config_dir_override {
+            env.insert("OPENCODE_CONFIG_DIR", dir.to_string_lossy());
+        }
+        setup_approvals_env(self.auto_approve, self.guards_external_paths(), &env)
+    }
+
     fn build_command_builder_with_base(
         &self,
         base: &str,
@@ -94,6 +148,12 @@ impl Opencode {
         self.build_command_builder_with_base(fallback_command())
     }
 
+    /// The command the npx fallback would run for a new session, resolved without spawning it.
+    /// The fallback is never used when `base_command_override` is set.
+    pub fn fallback_command_parts(&self) -> Result<CommandParts, CommandBuildError> {
+        self.build_fallback_command_builder()?.build_initial()
+    }
+
     fn should_fallback_to_npx(&self, err: &ExecutorError) -> bool {
         if self.cmd.base_command_override.is_some() {
             return false;
@@ -101,6 +161,32 @@ impl Opencode {
         matches!(err, ExecutorError::ExecutableNotFound { .. })
     }
 
+    /// Start a new session without waiting for the server to come up. The returned future
+    /// resolves once the OpenCode server has printed its listening URL.
+    pub async fn spawn_deferred(
+        &self,
+        current_dir: &Path,
+        prompt: &str,
+        env: &ExecutionEnv,
+    ) -> Result<(SpawnedChild, ReadinessFuture), ExecutorError> {
+        let command_parts = self.build_command_builder()?.build_initial()?;
+        match self
+            .spawn_inner_deferred(current_dir, prompt, None, command_parts, env)
+            .await
+        {
+            Ok(spawned) => Ok(spawned),
+            Err(err) => {
+                if self.should_fallback_to_npx(&err) {
+                    let fallback_parts = self.fallback_command_parts()?;
+                    return self
+                        .spawn_inner_deferred(current_dir, prompt, None, fallback_parts, env)
+                        .await;
+                }
+                Err(err)
+            }
+        }
+    }
+
     async fn spawn_inner(
         &self,
         current_dir: &Path,
@@ -109,8 +195,38 @@ impl Opencode {
         command_parts: CommandParts,
         env: &ExecutionEnv,
     ) -> Result<SpawnedChild, ExecutorError> {
-        let combined_prompt = self.append_prompt.combine_prompt(prompt);
+        let (mut child, readiness) = self
+            .spawn_inner_deferred(current_dir, prompt, resume_session, command_parts, env)
+            .await?;
+        if let Err(err) = readiness.await {
+            // Take the whole process group down, not just the server itself
+            let _ = child.child.kill().await;
+            return Err(err);
+        }
+        Ok(child)
+    }
+
+    async fn spawn_inner_deferred(
+        &self,
+        current_dir: &Path,
+        prompt: &str,
+        resume_session: Option<&str>,
+        command_parts: CommandParts,
+        env: &ExecutionEnv,
+    ) -> Result<(SpawnedChild, ReadinessFuture), ExecutorError> {
+        let model = self.resolved_model();
+        if let Some(model) = &model {
+            validate_model(model)?;
+        }
+        if let Some(effort) = &self.reasoning_effort {
+            validate_reasoning_effort(effort)?;
+        }
+        let combined_prompt = self
+            .append_prompt
+            .combine_prompt_with_mcp_meta(prompt, env)
+            .await?;
         let (program_path, args) = command_parts.into_resolved().await?;
+        env.mark_spawn_phase(SpawnPhase::CommandResolved);
 
         let mut command = Command::new(program_path);
         command
@@ -123,11 +239,12 @@ impl Opencode {
             .env("NODE_NO_WARNINGS", "1")
             .env("NO_COLOR", "1");
 
-        env.clone()
-            .with_profile(&self.cmd)
-            .apply_to_command(&mut command);
+        self.server_env(env).apply_to_command(&mut command).await;
+        self.cmd.apply_resource_limits(&mut command);
 
         let mut child = command.group_spawn()?;
+        env.mark_spawn_phase(SpawnPhase::ProcessSpawned);
+        env.capture_prompt(&combined_prompt);
         let server_stdout = child.inner().stdout.take().ok_or_else(|| {
             ExecutorError::Io(std::io::Error::other(
                 "OpenCode server missing stdout (needed to parse listening URL)",
@@ -140,29 +257,58 @@ impl Opencode {
         let (exit_signal_tx, exit_signal_rx) = tokio::sync::oneshot::channel();
         let (interrupt_tx, interrupt_rx) = tokio::sync::oneshot::channel();
 
+        let (ready, readiness) = ReadinessFuture::channel();
+        let mut ready = ready.with_spawn_timer(env.spawn_timer.clone());
+
         let directory = current_dir.to_string_lossy().to_string();
-        let base_url = wait_for_server_url(server_stdout).await?;
-        let approvals = if self.auto_approve {
+        let path_guard = self
+            .guards_external_paths()
+            .then(|| ExternalPathGuard::new(current_dir));
+        let approvals = if self.auto_approve && path_guard.is_none() {
             None
         } else {
             self.approvals.clone()
         };
-
-        let config = RunConfig {
-            base_url,
-            directory,
-            prompt: combined_prompt,
-            resume_session_id: resume_session.map(|s| s.to_string()),
-            model: self.model.clone(),
-            agent: self.mode.clone(),
-            approvals,
-            auto_approve: self.auto_approve,
-        };
+        let resume_session_id = resume_session.map(|s| s.to_string());
+        let agent = self.mode.clone();
+        let auto_approve = self.auto_approve;
+        let startup_timeout = self.cmd.spawn_timeout().unwrap_or(SERVER_STARTUP_TIMEOUT);
+        let server_exited = CancellationToken::new();
 
         tokio::spawn(async move {
+            let base_url =
+                match wait_for_server_url(server_stdout, startup_timeout, server_exited.clone())
+                    .await
+                {
+                    Ok(base_url) => {
+                        ready.ready();
+                        base_url
+                    }
+                    Err(err) => {
+                        let message = format!("OpenCode executor error: {err}");
+                        ready.fail(err);
+                        let _ = log_writer.log_error(message).await;
+                        let _ = exit_signal_tx.send(ExecutorExitResult::Failure);
+                        return;
+                    }
+                };
+
+            let config = RunConfig {
+                base_url,
+                directory,
+                prompt: combined_prompt,
+                resume_session_id,
+                model,
+                agent,
+                approvals,
+                auto_approve,
+                path_guard,
+                server_exited,
+            };
             let result = run_session(config, log_writer.clone(), interrupt_rx).await;
             let exit_result = match result {
-                Ok(()) => ExecutorExitResult::Success,
+                // An interrupted session is stopped on purpose, not failed
+                Ok(()) | Err(ExecutorError::Cancelled) => ExecutorExitResult::Success,
                 Err(err) => {
                     let _ = log_writer
                         .log_error(format!("OpenCode executor error: {err}"))
@@ -173,40 +319,48 @@ impl Opencode {
             let _ = exit_signal_tx.send(exit_result);
         });
 
-        Ok(SpawnedChild {
-            child,
-            exit_signal: Some(exit_signal_rx),
-            interrupt_sender: Some(interrupt_tx),
-        })
+        Ok((
+            SpawnedChild {
+                child,
+                exit_signal: Some(exit_signal_rx),
+                interrupt_sender: Some(interrupt_tx),
+            },
+            readiness,
+        ))
     }
 }
 
-fn format_tail(captured: Vec<String>) -> String {
-    captured
-        .into_iter()
-        .rev()
-        .take(12)
-        .collect::<Vec<_>>()
-        .into_iter()
-        .rev()
-        .collect::<Vec<_>>()
-        .join("\n")
-}
-
-async fn wait_for_server_url(stdout: tokio::process::ChildStdout) -> Result<String, ExecutorError> {
-    let mut lines = tokio::io::BufReader::new(stdout).lines();
-    let deadline = tokio::time::Instant::now() + Duration::from_secs(180);
+/// How long the OpenCode server gets to print its listening URL, unless `spawn_timeout_secs`
+/// is set
+pub(crate) const SERVER_STARTUP_TIMEOUT: Duration = Duration::from_secs(180);
+
+/// Read the server's stdout until it announces its listening URL. `opencode serve` has no
+/// structured output flag (`--format json` belongs to `opencode run`, which this executor does
+/// not use), so this line is the only output that is scraped; the session itself is driven
+/// through the server's JSON `/event` stream in [`run_session`]. `exited` is
+/// cancelled once stdout closes, i.e. when the server process is gone.
+async fn wait_for_server_url(
+    stdout: tokio::process::ChildStdout,
+    timeout: Duration,
+    exited: CancellationToken,
+) -> Result<String, ExecutorError> {
+    let mut reader = tokio::io::BufReader::new(stdout);
+    let mut buf = Vec::new();
+    let deadline = tokio::time::Instant::now() + timeout;
     let mut captured: Vec<String> = Vec::new();
 
     loop {
-        if tokio::time::Instant::now() > deadline {
-            return Err(ExecutorError::Io(std::io::Error::other(format!(
-                "Timed out waiting for OpenCode server to print listening URL.\nServer output tail:\n{}",
-                format_tail(captured)
-            ))));
+        if tokio::time::Instant::now() >= deadline {
+            return Err(ExecutorError::timeout(
+                "waiting for the OpenCode server to start",
+                timeout,
+                Some(&format_tail(captured)),
+            ));
         }
 
-        let line = match tokio::time::timeout_at(deadline, lines.next_line()).await {
+        let line = match tokio::time::timeout_at(deadline, read_line_lossy(&mut reader, &mut buf))
+            .await
+        {
             Ok(Ok(Some(line))) => line,
             Ok(Ok(None)) => {
                 return Err(ExecutorError::Io(std::io::Error::other(format!(
@@ -225,8 +379,8 @@ async fn wait_for_server_url(stdout: tokio::process::ChildStdout) -> Result<Stri
         if let Some(url) = line.trim().strip_prefix("opencode server listening on ") {
             // Keep draining stdout to avoid backpressure on the server, but don't block startup.
             tokio::spawn(async move {
-                let mut lines = tokio::io::BufReader::new(lines.into_inner()).lines();
-                while let Ok(Some(_)) = lines.next_line().await {}
+                while let Ok(Some(_)) = read_line_lossy(&mut reader, &mut buf).await {}
+                exited.cancel();
             });
             return Ok(url.trim().to_string());
         }
@@ -245,18 +399,17 @@ impl StandardCodingAgentExecutor for Opencode {
         prompt: &str,
         env: &ExecutionEnv,
     ) -> Result<SpawnedChild, ExecutorError> {
-        let env = setup_approvals_env(self.auto_approve, env);
         let command_parts = self.build_command_builder()?.build_initial()?;
         match self
-            .spawn_inner(current_dir, prompt, None, command_parts, &env)
+            .spawn_inner(current_dir, prompt, None, command_parts, env)
             .await
         {
             Ok(child) => Ok(child),
             Err(err) => {
                 if self.should_fallback_to_npx(&err) {
-                    let fallback_parts = self.build_fallback_command_builder()?.build_initial()?;
+                    let fallback_parts = self.fallback_command_parts()?;
                     return self
-                        .spawn_inner(current_dir, prompt, None, fallback_parts, &env)
+                        .spawn_inner(current_dir, prompt, None, fallback_parts, env)
                         .await;
                 }
                 Err(err)
@@ -271,18 +424,17 @@ impl StandardCodingAgentExecutor for Opencode {
         session_id: &str,
         env: &ExecutionEnv,
     ) -> Result<SpawnedChild, ExecutorError> {
-        let env = setup_approvals_env(self.auto_approve, env);
         let command_parts = self.build_command_builder()?.build_initial()?;
         match self
-            .spawn_inner(current_dir, prompt, Some(session_id), command_parts, &env)
+            .spawn_inner(current_dir, prompt, Some(session_id), command_parts, env)
             .await
         {
             Ok(child) => Ok(child),
             Err(err) => {
                 if self.should_fallback_to_npx(&err) {
-                    let fallback_parts = self.build_fallback_command_builder()?.build_initial()?;
+                    let fallback_parts = self.fallback_command_parts()?;
                     return self
-                        .spawn_inner(current_dir, prompt, Some(session_id), fallback_parts, &env)
+                        .spawn_inner(current_dir, prompt, Some(session_id), fallback_parts, env)
                         .await;
                 }
                 Err(err)
@@ -291,10 +443,15 @@ impl StandardCodingAgentExecutor for Opencode {
     }
 
     fn normalize_logs(&self, msg_store: Arc<MsgStore>, worktree_path: &Path) {
+        tag_normalized_entries(&msg_store, BaseCodingAgent::Opencode);
+        relativize_normalized_entries(&msg_store, worktree_path);
         normalize_logs::normalize_logs(msg_store, worktree_path);
     }
 
     fn default_mcp_config_path(&self) -> Option<std::path::PathBuf> {
+        if let Some(dir) = &self.config_dir_override {
+            return Some(dir.join("opencode.json"));
+        }
         #[cfg(unix)]
         {
             xdg::BaseDirectories::with_prefix("opencode").get_config_file("opencode.json")
@@ -305,15 +462,25 @@ impl StandardCodingAgentExecutor for Opencode {
         }
     }
 
+    fn default_sandbox(&self) -> Option<SandboxDescriptor> {
+        Some(SandboxDescriptor {
+            level: SandboxLevel::FullAccess,
+            requires_approval: !self.auto_approve,
+        })
+    }
+
     fn get_availability_info(&self) -> AvailabilityInfo {
         let mcp_config_found = self
             .default_mcp_config_path()
             .map(|p| p.exists())
             .unwrap_or(false);
 
-        let installation_indicator_found = dirs::config_dir()
-            .map(|config| config.join("opencode").exists())
-            .unwrap_or(false);
+        let installation_indicator_found = match &self.config_dir_override {
+            Some(dir) => dir.exists(),
+            None => dirs::config_dir()
+                .map(|config| config.join("opencode").exists())
+                .unwrap_or(false),
+        };
 
         if mcp_config_found || installation_indicator_found {
             AvailabilityInfo::InstallationFound
@@ -327,10 +494,401 @@ fn default_to_true() -> bool {
     true
 }
 
-fn setup_approvals_env(auto_approve: bool, env: &ExecutionEnv) -> ExecutionEnv {
+/// What OpenCode does when a tool in a permission category is used.
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum PermissionAction {
+    Ask,
+}
+
+/// Value of OpenCode's `OPENCODE_PERMISSION` env var, one field per permission category.
+/// Unset categories keep OpenCode's own default.
+#[derive(Debug, Clone, Default, Serialize, PartialEq, Eq)]
+struct OpencodePermissions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    edit: Option<PermissionAction>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bash: Option<PermissionAction>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    webfetch: Option<PermissionAction>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    doom_loop: Option<PermissionAction>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    external_directory: Option<PermissionAction>,
+}
+
+impl OpencodePermissions {
+    /// Ask before every category, so each tool call goes through the approval service.
+    fn ask_all() -> Self {
+        Self {
+            edit: Some(PermissionAction::Ask),
+            bash: Some(PermissionAction::Ask),
+            webfetch: Some(PermissionAction::Ask),
+            doom_loop: Some(PermissionAction::Ask),
+            external_directory: Some(PermissionAction::Ask),
+        }
+    }
+
+    /// Route edits through the path guard; in-tree ones are still approved automatically.
+    fn ask_for_edits() -> Self {
+        Self {
+            edit: Some(PermissionAction::Ask),
+            external_directory: Some(PermissionAction::Ask),
+            ..Self::default()
+        }
+    }
+
+    fn to_env_value(&self) -> String {
+        serde_json::to_string(self).expect("OpenCode permissions serialize to JSON")
+    }
+}
+
+/// Set `OPENCODE_PERMISSION` so approvals see the tool calls they need to. A permission the
+/// user already set (in `env` or inherited) is kept, except that the path guard still needs
+/// edits to ask unless the user denies them outright.
+fn setup_approvals_env(
+    auto_approve: bool,
+    guard_external_paths: bool,
+    env: &ExecutionEnv,
+) -> ExecutionEnv {
     let mut env = env.clone();
-    if !auto_approve && !env.contains_key("OPENCODE_PERMISSION") {
-        env.insert("OPENCODE_PERMISSION", r#"{"edit": "ask", "bash": "ask", "webfetch": "ask", "doom_loop": "ask", "external_directory": "ask"}"#);
+    let user_permission = env
+        .vars
+        .get("OPENCODE_PERMISSION")
+        .cloned()
+        .or_else(|| std::env::var("OPENCODE_PERMISSION").ok());
+    let value = match user_permission {
+        Some(user_permission) if guard_external_paths => guard_user_permission(&user_permission),
+        Some(_) => return env,
+        None if !auto_approve => OpencodePermissions::ask_all().to_env_value(),
+        None if guard_external_paths => OpencodePermissions::ask_for_edits().to_env_value(),
+        None => return env,
+    };
+    env.insert("OPENCODE_PERMISSION", value);
+    env
+}
+
+/// The user's `OPENCODE_PERMISSION` with the categories the path guard inspects set to
+/// `ask`. A category the user denies stays denied, since that is stricter than asking.
+fn guard_user_permission(user_permission: &str) -> String {
+    let mut permission = match serde_json::from_str::<serde_json::Value>(user_permission) {
+        Ok(serde_json::Value::Object(permission)) => permission,
+        _ => {
+            tracing::warn!(
+                "OPENCODE_PERMISSION is not a JSON object; the external path guard replaces it"
+            );
+            return OpencodePermissions::ask_for_edits().to_env_value();
+        }
+    };
+    for category in ["edit", "external_directory"] {
+        if permission.get(category).and_then(serde_json::Value::as_str) != Some("deny") {
+            permission.insert(
+                category.to_string(),
+                serde_json::json!(PermissionAction::Ask),
+            );
+        }
+    }
+    serde_json::Value::Object(permission).to_string()
+}
+
+fn validate_reasoning_effort(effort: &str) -> Result<(), ExecutorError> {
+    if REASONING_EFFORTS.contains(&effort) {
+        return Ok(());
     }
+    Err(ExecutorError::InvalidReasoningEffort(format!(
+        "`{effort}` must be one of {}",
+        REASONING_EFFORTS.join(", ")
+    )))
+}
+
+/// Hand the reasoning effort to the session's agent through `OPENCODE_CONFIG_CONTENT`,
+/// merged into any config content the profile or the process env already sets.
+/// OpenCode passes agent options it does not recognize to the provider as model options.
+fn setup_reasoning_env(
+    agent: Option<&str>,
+    reasoning_effort: Option<&str>,
+    env: &ExecutionEnv,
+) -> ExecutionEnv {
+    let mut env = env.clone();
+    let Some(effort) = reasoning_effort else {
+        return env;
+    };
+    let existing = env
+        .vars
+        .get("OPENCODE_CONFIG_CONTENT")
+        .cloned()
+        .or_else(|| std::env::var("OPENCODE_CONFIG_CONTENT").ok());
+    let mut config = match existing
+        .as_deref()
+        .map(serde_json::from_str::<serde_json::Value>)
+    {
+        None => serde_json::json!({}),
+        Some(Ok(config)) if config.is_object() => config,
+        Some(_) => {
+            tracing::warn!(
+                "OPENCODE_CONFIG_CONTENT is not a JSON object; not setting reasoning effort"
+            );
+            return env;
+        }
+    };
+    let agent = agent.unwrap_or(DEFAULT_AGENT);
+    if !config["agent"].is_object() {
+        config["agent"] = serde_json::json!({});
+    }
+    if !config["agent"][agent].is_object() {
+        config["agent"][agent] = serde_json::json!({});
+    }
+    config["agent"][agent]["reasoningEffort"] = effort.into();
+    env.insert("OPENCODE_CONFIG_CONTENT", config.to_string());
     env
 }
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::{
+        env::RepoContext,
+        executors::{BaseAgentCapability, CodingAgent},
+    };
+
+    #[test]
+    fn permission_env_matches_opencode_categories() {
+        assert_eq!(
+            OpencodePermissions::ask_all().to_env_value(),
+            r#"{"edit":"ask","bash":"ask","webfetch":"ask","doom_loop":"ask","external_directory":"ask"}"#
+        );
+        assert_eq!(
+            OpencodePermissions::ask_for_edits().to_env_value(),
+            r#"{"edit":"ask","external_directory":"ask"}"#
+        );
+
+        let base = ExecutionEnv::new(RepoContext::default(), false);
+        let env = setup_approvals_env(false, false, &base);
+        assert_eq!(
+            env.vars.get("OPENCODE_PERMISSION"),
+            Some(&OpencodePermissions::ask_all().to_env_value())
+        );
+        let env = setup_approvals_env(true, false, &base);
+        assert!(!env.contains_key("OPENCODE_PERMISSION"));
+    }
+
+    #[test]
+    fn path_guard_is_merged_into_the_users_permission() {
+        let mut base = ExecutionEnv::new(RepoContext::default(), false);
+        base.insert(
+            "OPENCODE_PERMISSION",
+            r#"{"edit":"allow","bash":"allow","external_directory":"deny"}"#,
+        );
+
+        let env = setup_approvals_env(true, true, &base);
+        let permission: serde_json::Value =
+            serde_json::from_str(&env.vars["OPENCODE_PERMISSION"]).unwrap();
+        assert_eq!(
+            permission,
+            serde_json::json!({ "edit": "ask", "bash": "allow", "external_directory": "deny" })
+        );
+
+        // Without the guard the user's permission is passed through untouched
+        let env = setup_approvals_env(false, false, &base);
+        assert_eq!(
+            env.vars["OPENCODE_PERMISSION"],
+            base.vars["OPENCODE_PERMISSION"]
+        );
+    }
+
+    #[test]
+    fn reasoning_effort_reaches_the_session_agent_config() {
+        let base = ExecutionEnv::new(RepoContext::default(), false);
+        let env = setup_reasoning_env(Some("plan"), Some("high"), &base);
+        let config: serde_json::Value =
+            serde_json::from_str(&env.vars["OPENCODE_CONFIG_CONTENT"]).unwrap();
+        assert_eq!(config["agent"]["plan"]["reasoningEffort"], "high");
+
+        let env = setup_reasoning_env(None, Some("low"), &base);
+        let config: serde_json::Value =
+            serde_json::from_str(&env.vars["OPENCODE_CONFIG_CONTENT"]).unwrap();
+        assert_eq!(config["agent"]["build"]["reasoningEffort"], "low");
+
+        assert!(!setup_reasoning_env(None, None, &base).contains_key("OPENCODE_CONFIG_CONTENT"));
+        assert!(validate_reasoning_effort("medium").is_ok());
+        assert!(matches!(
+            validate_reasoning_effort("extreme"),
+            Err(ExecutorError::InvalidReasoningEffort(_))
+        ));
+    }
+
+    #[test]
+    fn reasoning_effort_merges_into_the_profile_config_content() {
+        let opencode: Opencode = serde_json::from_value(serde_json::json!({
+            "mode": "plan",
+            "reasoning_effort": "high",
+            "env": {
+                "OPENCODE_CONFIG_CONTENT": serde_json::json!({
+                    "model": "openai/gpt-5",
+                    "agent": { "plan": { "temperature": 0.2 } }
+                }).to_string()
+            }
+        }))
+        .unwrap();
+        let env = opencode.server_env(&ExecutionEnv::new(RepoContext::default(), false));
+        let config: serde_json::Value =
+            serde_json::from_str(&env.vars["OPENCODE_CONFIG_CONTENT"]).unwrap();
+        assert_eq!(config["model"], "openai/gpt-5");
+        assert_eq!(config["agent"]["plan"]["temperature"], 0.2);
+        assert_eq!(config["agent"]["plan"]["reasoningEffort"], "high");
+    }
+
+    #[test]
+    fn config_dir_override_reaches_server_env_and_mcp_path() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("opencode");
+        let opencode: Opencode = serde_json::from_value(serde_json::json!({
+            "config_dir_override": dir,
+        }))
+        .unwrap();
+
+        let env = opencode.server_env(&ExecutionEnv::new(RepoContext::default(), false));
+        assert_eq!(
+            env.vars.get("OPENCODE_CONFIG_DIR").map(String::as_str),
+            Some(dir.to_str().unwrap())
+        );
+        assert_eq!(
+            opencode.default_mcp_config_path(),
+            Some(dir.join("opencode.json"))
+        );
+        assert!(matches!(
+            opencode.get_availability_info(),
+            AvailabilityInfo::NotFound
+        ));
+
+        std::fs::create_dir_all(&dir).unwrap();
+        assert!(matches!(
+            opencode.get_availability_info(),
+            AvailabilityInfo::InstallationFound
+        ));
+    }
+
+    /// An OpenCode that runs `script` as its server, written to a temp dir that is removed
+    /// when the returned [`tempfile::TempDir`] is dropped.
+    fn fake_server(script: &str) -> (Opencode, tempfile::TempDir) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("opencode.sh");
+        std::fs::write(&path, script).unwrap();
+        let opencode = serde_json::from_value(serde_json::json!({
+            "base_command_override": format!("sh {}", path.display()),
+        }))
+        .unwrap();
+        (opencode, dir)
+    }
+
+    #[tokio::test]
+    async fn spawn_deferred_resolves_once_server_is_listening() {
+        let (opencode, dir) = fake_server(
+            "sleep 0.3\necho 'opencode server listening on http://127.0.0.1:9'\nsleep 5\n",
+        );
+        let env = ExecutionEnv::new(RepoContext::default(), false);
+
+        let (mut spawned, mut readiness) = opencode
+            .spawn_deferred(dir.path(), "hello", &env)
+            .await
+            .unwrap();
+
+        assert!(
+            tokio::time::timeout(Duration::from_millis(50), &mut readiness)
+                .await
+                .is_err(),
+            "readiness resolved before the server printed its URL"
+        );
+        tokio::time::timeout(Duration::from_secs(5), readiness)
+            .await
+            .expect("server never became ready")
+            .unwrap();
+
+        spawned.child.kill().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn spawn_deferred_reports_server_exit_before_ready() {
+        let (opencode, dir) = fake_server("echo 'failed to start'\n");
+        let env = ExecutionEnv::new(RepoContext::default(), false);
+
+        let (_spawned, readiness) = opencode
+            .spawn_deferred(dir.path(), "hello", &env)
+            .await
+            .unwrap();
+
+        let err = tokio::time::timeout(Duration::from_secs(5), readiness)
+            .await
+            .expect("readiness never resolved")
+            .unwrap_err();
+        assert!(err.to_string().contains("failed to start"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn wait_for_server_url_times_out() {
+        let mut child = Command::new("sh")
+            .args(["-c", "echo starting; sleep 5"])
+            .stdout(std::process::Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .unwrap();
+        let stdout = child.stdout.take().unwrap();
+
+        let err = wait_for_server_url(stdout, Duration::from_millis(200), CancellationToken::new())
+            .await
+            .unwrap_err();
+        let ExecutorError::Timeout(message) = &err else {
+            panic!("expected timeout, got {err:?}");
+        };
+        assert!(
+            message.starts_with("waiting for the OpenCode server to start after 200ms"),
+            "{message}"
+        );
+        assert!(message.ends_with("Output tail:\nstarting"), "{message}");
+        assert_eq!(err.to_string(), format!("Operation timed out: {message}"));
+    }
+
+    #[test]
+    fn fallback_command_parts_serve_through_npx() {
+        let opencode: Opencode = serde_json::from_value(serde_json::json!({})).unwrap();
+        let parts = opencode.fallback_command_parts().unwrap();
+        let mut expected = crate::command::split_command_line(fallback_command()).unwrap();
+        expected.extend(["serve", "--hostname", "127.0.0.1", "--port", "0"].map(String::from));
+
+        assert_eq!(parts.program(), expected[0]);
+        assert_eq!(parts.args(), &expected[1..]);
+    }
+
+    fn opencode(value: serde_json::Value) -> Opencode {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn default_sandbox_asks_only_without_auto_approve() {
+        let requires_approval = |value| {
+            opencode(value)
+                .default_sandbox()
+                .map(|sandbox| sandbox.requires_approval)
+        };
+        assert_eq!(requires_approval(serde_json::json!({})), Some(false));
+        assert_eq!(
+            requires_approval(serde_json::json!({ "auto_approve": false })),
+            Some(true)
+        );
+    }
+
+    #[test]
+    fn capabilities_are_session_fork_only() {
+        let agent = CodingAgent::Opencode(opencode(serde_json::json!({})));
+        assert_eq!(agent.capabilities(), [BaseAgentCapability::SessionFork]);
+        assert!(!agent.supports_image_input());
+    }
+
+    #[test]
+    fn local_model_runs_without_network() {
+        let local = opencode(serde_json::json!({ "model": "ollama/qwen3" }));
+        assert!(!CodingAgent::Opencode(local).requires_network());
+        let hosted = opencode(serde_json::json!({ "model": "anthropic/claude-sonnet-4-5" }));
+        assert!(CodingAgent::Opencode(hosted).requires_network());
+    }
+}
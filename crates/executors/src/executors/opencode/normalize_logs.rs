@@ -14,6 +14,7 @@ use crate::{
     logs::{
         ActionType, CommandExitStatus, CommandRunResult, FileChange, NormalizedEntry,
         NormalizedEntryError, NormalizedEntryType, TodoItem, ToolResult, ToolStatus,
+        context_window::{context_window_exceeded_entry, is_context_window_exceeded},
         stderr_processor::normalize_stderr_logs,
         utils::{
             EntryIndexProvider,
@@ -28,10 +29,16 @@ fn system_message(content: String) -> NormalizedEntry {
         entry_type: NormalizedEntryType::SystemMessage,
         content,
         metadata: None,
+        agent: None,
     }
 }
 
-pub fn normalize_logs(msg_store: Arc<MsgStore>, worktree_path: &Path) {
+/// Normalize the agent's output in `msg_store` into conversation entries. The returned handle
+/// completes once stdout has been fully processed.
+pub fn normalize_logs(
+    msg_store: Arc<MsgStore>,
+    worktree_path: &Path,
+) -> tokio::task::JoinHandle<()> {
     let entry_index = EntryIndexProvider::start_from(&msg_store);
     normalize_stderr_logs(msg_store.clone(), entry_index.clone());
 
@@ -89,6 +96,7 @@ pub fn normalize_logs(msg_store: Arc<MsgStore>, worktree_path: &Path) {
                                 },
                                 content: message,
                                 metadata: None,
+                                agent: None,
                             },
                         ),
                     );
@@ -96,7 +104,7 @@ pub fn normalize_logs(msg_store: Arc<MsgStore>, worktree_path: &Path) {
                 OpencodeExecutorEvent::Done => {}
             }
         }
-    });
+    })
 }
 
 fn parse_event(line: &str) -> Option<OpencodeExecutorEvent> {
@@ -192,6 +200,13 @@ impl LogState {
             | SdkEvent::SessionDiff
             | SdkEvent::TuiSessionSelect => {}
             SdkEvent::SessionError(event) => {
+                if let Some(message) = event.error.as_ref().and_then(|err| err.message())
+                    && is_context_window_exceeded(&message)
+                {
+                    self.add_normalized_entry(context_window_exceeded_entry(&message));
+                    return;
+                }
+
                 let (error_type, message) = match event.error {
                     Some(err) if err.kind() == "ProviderAuthError" => (
                         NormalizedEntryError::SetupRequired,
@@ -216,6 +231,7 @@ impl LogState {
                         entry_type: NormalizedEntryType::ErrorMessage { error_type },
                         content: message,
                         metadata: None,
+                        agent: None,
                     },
                 );
             }
@@ -276,6 +292,7 @@ impl LogState {
             },
             content: "TODO list updated".to_string(),
             metadata: None,
+            agent: None,
         };
 
         if let Some(index) = self.todo_update_entry {
@@ -332,7 +349,7 @@ impl LogState {
                     &entry_index,
                     text,
                     NormalizedEntryType::AssistantMessage,
-                    &part.message_id,
+                    part.stream_key(),
                     &mut self.assistant_text,
                     msg_store,
                     mode,
@@ -350,7 +367,7 @@ impl LogState {
                     &entry_index,
                     text,
                     NormalizedEntryType::Thinking,
-                    &part.message_id,
+                    part.stream_key(),
                     &mut self.thinking_text,
                     msg_store,
                     mode,
@@ -416,6 +433,7 @@ impl LogState {
                         .trim()
                         .to_string(),
                     metadata: None,
+                    agent: None,
                 },
             );
         }
@@ -522,7 +540,7 @@ fn update_streaming_text(
     entry_index: &EntryIndexProvider,
     text: &str,
     entry_type: NormalizedEntryType,
-    message_id: &str,
+    key: &str,
     map: &mut HashMap<String, StreamingText>,
     msg_store: &Arc<MsgStore>,
     mode: UpdateMode,
@@ -531,18 +549,16 @@ fn update_streaming_text(
         return;
     }
 
-    let is_new = !map.contains_key(message_id);
+    let is_new = !map.contains_key(key);
 
     if is_new && text == "\n" {
         return;
     }
 
-    let state = map
-        .entry(message_id.to_string())
-        .or_insert_with(|| StreamingText {
-            index: entry_index.next(),
-            content: String::new(),
-        });
+    let state = map.entry(key.to_string()).or_insert_with(|| StreamingText {
+        index: entry_index.next(),
+        content: String::new(),
+    });
 
     match mode {
         UpdateMode::Append => state.content.push_str(text),
@@ -554,6 +570,7 @@ fn update_streaming_text(
         entry_type,
         content: state.content.clone(),
         metadata: None,
+        agent: None,
     };
     upsert_normalized_entry(msg_store, state.index, entry, is_new);
 }
@@ -926,6 +943,7 @@ impl ToolCallState {
                 tool_call_id: self.call_id.clone(),
             })
             .ok(),
+            agent: None,
         }
     }
 
@@ -1152,3 +1170,70 @@ fn extract_file_path_from_permission_metadata(metadata: &Value) -> Option<&str>
         Some(trimmed)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use workspace_utils::log_msg::LogMsg;
+
+    use super::*;
+    use crate::logs::utils::patch::extract_normalized_entry_from_patch;
+
+    fn sdk_event(event: Value) -> String {
+        let line = serde_json::to_string(&OpencodeExecutorEvent::SdkEvent { event }).unwrap();
+        format!("{line}\n")
+    }
+
+    fn text_part(part_id: &str, text: &str, delta: Option<&str>) -> String {
+        sdk_event(serde_json::json!({
+            "type": "message.part.updated",
+            "properties": {
+                "part": {
+                    "type": "text",
+                    "id": part_id,
+                    "messageID": "msg-1",
+                    "text": text,
+                },
+                "delta": delta,
+            },
+        }))
+    }
+
+    #[tokio::test]
+    async fn text_deltas_stream_then_finalize_per_part() {
+        let msg_store = Arc::new(MsgStore::new());
+        msg_store.push_stdout(sdk_event(serde_json::json!({
+            "type": "message.updated",
+            "properties": { "info": { "id": "msg-1", "role": "assistant" } },
+        })));
+        msg_store.push_stdout(text_part("part-1", "Hel", Some("Hel")));
+        msg_store.push_stdout(text_part("part-1", "Hello", Some("lo")));
+        msg_store.push_stdout(text_part("part-1", "Hello, world.", None));
+        msg_store.push_stdout(text_part("part-2", "Done.", None));
+        msg_store.push_finished();
+
+        normalize_logs(msg_store.clone(), Path::new("/tmp/opencode-worktree"))
+            .await
+            .unwrap();
+
+        let updates: Vec<(usize, String)> = msg_store
+            .get_history()
+            .into_iter()
+            .filter_map(|msg| match msg {
+                LogMsg::JsonPatch(patch) => extract_normalized_entry_from_patch(&patch),
+                _ => None,
+            })
+            .filter(|(_, entry)| matches!(entry.entry_type, NormalizedEntryType::AssistantMessage))
+            .map(|(index, entry)| (index, entry.content))
+            .collect();
+
+        assert_eq!(
+            updates,
+            vec![
+                (0, "Hel".to_string()),
+                (0, "Hello".to_string()),
+                (0, "Hello, world.".to_string()),
+                (1, "Done.".to_string()),
+            ]
+        );
+    }
+}
@@ -1,5 +1,5 @@
 use std::{
-    collections::HashSet,
+    collections::{BTreeMap, HashSet},
     io,
     sync::{Arc, Once},
     time::Duration,
@@ -8,7 +8,7 @@ use std::{
 use eventsource_stream::Eventsource;
 use futures::{FutureExt, StreamExt};
 use reqwest::header::{HeaderMap, HeaderValue};
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Serialize, de::IgnoredAny};
 use serde_json::Value;
 use tokio::{
     io::{AsyncWrite, AsyncWriteExt, BufWriter},
@@ -19,7 +19,7 @@ use workspace_utils::approvals::ApprovalStatus;
 
 use super::types::OpencodeExecutorEvent;
 use crate::{
-    approvals::{ExecutorApprovalError, ExecutorApprovalService},
+    approvals::{ExecutorApprovalError, ExecutorApprovalService, ExternalPathGuard},
     executors::ExecutorError,
 };
 
@@ -77,6 +77,11 @@ pub struct RunConfig {
     pub agent: Option<String>,
     pub approvals: Option<Arc<dyn ExecutorApprovalService>>,
     pub auto_approve: bool,
+    /// When set, edits outside `directory` need explicit approval even with `auto_approve`
+    pub path_guard: Option<ExternalPathGuard>,
+    /// Cancelled once the server process has exited, after which a dropped connection is
+    /// final rather than something to reconnect through.
+    pub server_exited: CancellationToken,
 }
 
 #[derive(Debug, Deserialize)]
@@ -107,6 +112,30 @@ struct ModelSpec {
     model_id: String,
 }
 
+/// A configured model: either `provider/model`, or a bare model name whose provider is looked
+/// up on the server.
+#[derive(Debug)]
+enum ModelRef {
+    Qualified(ModelSpec),
+    Bare(String),
+}
+
+/// `GET /config/providers`: the configured providers with their models, and each provider's
+/// default model.
+#[derive(Debug, Deserialize)]
+struct ConfigProviders {
+    providers: Vec<ProviderInfo>,
+    #[serde(default)]
+    default: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProviderInfo {
+    id: String,
+    #[serde(default)]
+    models: BTreeMap<String, IgnoredAny>,
+}
+
 #[derive(Debug, Serialize)]
 struct TextPartInput {
     r#type: &'static str,
@@ -121,6 +150,10 @@ enum ControlEvent {
     Disconnected,
 }
 
+/// How many times a session waits out a dropped server connection before failing.
+const MAX_RECONNECT_ATTEMPTS: u32 = 5;
+const RECONNECT_BASE_DELAY: Duration = Duration::from_millis(250);
+
 pub async fn run_session(
     config: RunConfig,
     log_writer: LogWriter,
@@ -150,7 +183,7 @@ pub async fn run_session(
             }
             res = &mut session_fut => {
                 if interrupted {
-                    return Ok(());
+                    return Err(ExecutorError::Cancelled);
                 }
                 return res;
             }
@@ -188,12 +221,20 @@ async fn run_session_inner(
         })
         .await?;
 
-    let model = config.model.as_deref().and_then(parse_model);
+    let model = match config.model.as_deref().map(parse_model).transpose()? {
+        Some(model) => {
+            Some(resolve_model(&client, &config.base_url, &config.directory, model).await?)
+        }
+        None => None,
+    };
 
     let (control_tx, mut control_rx) = mpsc::unbounded_channel::<ControlEvent>();
 
     let event_resp = tokio::select! {
-        _ = cancel.cancelled() => return Ok(()),
+        _ = cancel.cancelled() => {
+            send_abort(&client, &config.base_url, &config.directory, &session_id).await;
+            return Ok(());
+        }
         res = connect_event_stream(&client, &config.base_url, &config.directory, None) => res?,
     };
     let event_handle = tokio::spawn(spawn_event_listener(
@@ -205,6 +246,7 @@ async fn run_session_inner(
             log_writer: log_writer.clone(),
             approvals: config.approvals.clone(),
             auto_approve: config.auto_approve,
+            path_guard: config.path_guard.clone(),
             control_tx,
         },
         event_resp,
@@ -222,10 +264,12 @@ async fn run_session_inner(
         config.agent.clone(),
         &mut control_rx,
         cancel.clone(),
+        &config.server_exited,
     )
     .await;
 
     if cancel.is_cancelled() {
+        // Ask the server to stop the active session before the child is torn down.
         send_abort(&client, &config.base_url, &config.directory, &session_id).await;
         event_handle.abort();
         return Ok(());
@@ -271,6 +315,7 @@ async fn run_prompt_with_control(
     agent: Option<String>,
     control_rx: &mut mpsc::UnboundedReceiver<ControlEvent>,
     cancel: CancellationToken,
+    server_exited: &CancellationToken,
 ) -> Result<(), ExecutorError> {
     let mut idle_seen = false;
     let mut session_error: Option<String> = None;
@@ -306,7 +351,19 @@ async fn run_prompt_with_control(
         if cancel.is_cancelled() {
             return Ok(());
         }
-        return Err(err);
+        if !is_connection_drop(&err) {
+            return Err(err);
+        }
+        // The prompt keeps running server-side; once the server answers again, follow it
+        // through the event stream (which reconnects on its own) instead of re-sending it.
+        tracing::warn!("OpenCode server connection dropped mid-prompt, reconnecting: {err}");
+        let busy = tokio::select! {
+            _ = cancel.cancelled() => return Ok(()),
+            res = reconnect(&ctx, server_exited) => res?,
+        };
+        if !busy {
+            idle_seen = true;
+        }
     }
 
     if !idle_seen {
@@ -340,16 +397,110 @@ async fn run_prompt_with_control(
     Ok(())
 }
 
+/// Whether `err` came from the HTTP connection itself (refused, timed out, reset, truncated
+/// body) rather than from the server answering with an error or from a malformed request.
+fn is_connection_drop(err: &ExecutorError) -> bool {
+    let ExecutorError::Io(err) = err else {
+        return false;
+    };
+    // reqwest reports a truncated body as a decode error wrapping a body error, and a reset
+    // while sending as a request error wrapping the io error, so look through the whole chain.
+    let mut source: Option<&(dyn std::error::Error + 'static)> = err.get_ref().map(|e| e as _);
+    while let Some(err) = source {
+        if let Some(err) = err.downcast_ref::<reqwest::Error>()
+            && (err.is_connect() || err.is_timeout() || err.is_body())
+        {
+            return true;
+        }
+        if let Some(err) = err.downcast_ref::<io::Error>()
+            && matches!(
+                err.kind(),
+                io::ErrorKind::ConnectionReset
+                    | io::ErrorKind::ConnectionAborted
+                    | io::ErrorKind::BrokenPipe
+                    | io::ErrorKind::UnexpectedEof
+            )
+        {
+            return true;
+        }
+        source = err.source();
+    }
+    false
+}
+
+/// Wait for the server to answer again after a dropped connection, then report whether the
+/// session is still busy. Gives up once the server process exits or after
+/// [`MAX_RECONNECT_ATTEMPTS`].
+async fn reconnect(
+    ctx: &SessionRequestContext<'_>,
+    server_exited: &CancellationToken,
+) -> Result<bool, ExecutorError> {
+    let mut last_err = String::new();
+    for attempt in 1..=MAX_RECONNECT_ATTEMPTS {
+        tokio::select! {
+            _ = server_exited.cancelled() => {
+                return Err(ExecutorError::Io(io::Error::other(
+                    "OpenCode server exited while the session was running",
+                )));
+            }
+            _ = tokio::time::sleep(exponential_backoff(RECONNECT_BASE_DELAY, attempt)) => {}
+        }
+        match session_is_busy(ctx).await {
+            Ok(busy) => return Ok(busy),
+            Err(err) => last_err = err.to_string(),
+        }
+    }
+    Err(ExecutorError::Io(io::Error::other(format!(
+        "OpenCode server connection dropped and did not recover after \
+         {MAX_RECONNECT_ATTEMPTS} attempts: {last_err}"
+    ))))
+}
+
+/// Sessions missing from `/session/status` are idle.
+async fn session_is_busy(ctx: &SessionRequestContext<'_>) -> Result<bool, ExecutorError> {
+    let resp = ctx
+        .client
+        .get(format!("{}/session/status", ctx.base_url))
+        .query(&[("directory", ctx.directory)])
+        .send()
+        .await
+        .map_err(|err| ExecutorError::Io(io::Error::other(err)))?;
+
+    if !resp.status().is_success() {
+        return Err(ExecutorError::Io(io::Error::other(format!(
+            "OpenCode session.status failed: HTTP {}",
+            resp.status()
+        ))));
+    }
+
+    let statuses = resp
+        .json::<Value>()
+        .await
+        .map_err(|err| ExecutorError::Io(io::Error::other(err)))?;
+    Ok(statuses
+        .get(ctx.session_id)
+        .and_then(|status| status.get("type"))
+        .and_then(Value::as_str)
+        .is_some_and(|status| status != "idle"))
+}
+
+/// How long the OpenCode server gets to report itself healthy once it is listening.
+const HEALTH_TIMEOUT: Duration = Duration::from_secs(20);
+
 async fn wait_for_health(client: &reqwest::Client, base_url: &str) -> Result<(), ExecutorError> {
-    let deadline = tokio::time::Instant::now() + Duration::from_secs(20);
+    let deadline = tokio::time::Instant::now() + HEALTH_TIMEOUT;
     let mut last_err: Option<String> = None;
 
     loop {
         if tokio::time::Instant::now() > deadline {
-            return Err(ExecutorError::Io(io::Error::other(format!(
-                "Timed out waiting for OpenCode server health: {}",
-                last_err.unwrap_or_else(|| "unknown error".to_string())
-            ))));
+            return Err(ExecutorError::timeout(
+                &format!(
+                    "waiting for OpenCode server health ({})",
+                    last_err.unwrap_or_else(|| "unknown error".to_string())
+                ),
+                HEALTH_TIMEOUT,
+                None,
+            ));
         }
 
         let resp = client.get(format!("{base_url}/global/health")).send().await;
@@ -516,18 +667,89 @@ async fn send_abort(client: &reqwest::Client, base_url: &str, directory: &str, s
     .await;
 }
 
-fn parse_model(model: &str) -> Option<ModelSpec> {
-    let (provider_id, model_id) = match model.split_once('/') {
-        Some((provider, rest)) => (provider.to_string(), rest.to_string()),
-        None => (model.to_string(), String::new()),
+/// Split an OpenCode `provider/model` string into the IDs the server expects. The model
+/// part may itself contain slashes (e.g. `openrouter/anthropic/claude-sonnet-4`). A name
+/// without a provider is kept bare for [`resolve_model`].
+fn parse_model(model: &str) -> Result<ModelRef, ExecutorError> {
+    let model = model.trim();
+    match model.split_once('/') {
+        None if !model.is_empty() => Ok(ModelRef::Bare(model.to_string())),
+        Some((provider, model_id)) if !provider.is_empty() && !model_id.is_empty() => {
+            Ok(ModelRef::Qualified(ModelSpec {
+                provider_id: provider.to_string(),
+                model_id: model_id.to_string(),
+            }))
+        }
+        _ => Err(ExecutorError::InvalidModel(format!(
+            "`{model}` must be a model name or `provider/model`, e.g. \
+             `anthropic/claude-sonnet-4-5`"
+        ))),
+    }
+}
+
+/// Check that a configured model string is a model name or `provider/model` combo.
+pub fn validate_model(model: &str) -> Result<(), ExecutorError> {
+    parse_model(model).map(|_| ())
+}
+
+/// The provider and model IDs for `model`. A bare model name goes to the provider that has it
+/// as its default model, or else the first configured provider that offers it.
+async fn resolve_model(
+    client: &reqwest::Client,
+    base_url: &str,
+    directory: &str,
+    model: ModelRef,
+) -> Result<ModelSpec, ExecutorError> {
+    let model_id = match model {
+        ModelRef::Qualified(spec) => return Ok(spec),
+        ModelRef::Bare(model_id) => model_id,
     };
 
-    Some(ModelSpec {
+    let resp = client
+        .get(format!("{base_url}/config/providers"))
+        .query(&[("directory", directory)])
+        .send()
+        .await
+        .map_err(|err| ExecutorError::Io(io::Error::other(err)))?;
+
+    if !resp.status().is_success() {
+        return Err(ExecutorError::Io(io::Error::other(format!(
+            "OpenCode config.providers failed: HTTP {}",
+            resp.status()
+        ))));
+    }
+
+    let providers = resp
+        .json::<ConfigProviders>()
+        .await
+        .map_err(|err| ExecutorError::Io(io::Error::other(err)))?;
+    let provider_id = provider_for_model(&providers, &model_id).ok_or_else(|| {
+        ExecutorError::InvalidModel(format!(
+            "no configured OpenCode provider offers `{model_id}`; use `provider/model` form, \
+             e.g. `anthropic/claude-sonnet-4-5`"
+        ))
+    })?;
+    Ok(ModelSpec {
         provider_id,
         model_id,
     })
 }
 
+fn provider_for_model(providers: &ConfigProviders, model_id: &str) -> Option<String> {
+    providers
+        .default
+        .iter()
+        .find(|(_, default)| *default == model_id)
+        .map(|(provider_id, _)| provider_id.clone())
+        .or_else(|| {
+            providers
+                .providers
+                .iter()
+                .find(|provider| provider.models.contains_key(model_id))
+                .map(|provider| provider.id.clone())
+        })
+}
+
 async fn connect_event_stream(
     client: &reqwest::Client,
     base_url: &str,
@@ -570,6 +792,7 @@ struct EventListenerConfig {
     log_writer: LogWriter,
     approvals: Option<Arc<dyn ExecutorApprovalService>>,
     auto_approve: bool,
+    path_guard: Option<ExternalPathGuard>,
     control_tx: mpsc::UnboundedSender<ControlEvent>,
 }
 
@@ -582,6 +805,7 @@ async fn spawn_event_listener(config: EventListenerConfig, initial_resp: reqwest
         log_writer,
         approvals,
         auto_approve,
+        path_guard,
         control_tx,
     } = config;
 
@@ -633,6 +857,7 @@ async fn spawn_event_listener(config: EventListenerConfig, initial_resp: reqwest
                 log_writer: &log_writer,
                 approvals: approvals.clone(),
                 auto_approve,
+                path_guard: path_guard.as_ref(),
                 control_tx: &control_tx,
                 base_retry_delay: &mut base_retry_delay,
                 last_event_id: &mut last_event_id,
@@ -681,6 +906,7 @@ struct EventStreamContext<'a> {
     log_writer: &'a LogWriter,
     approvals: Option<Arc<dyn ExecutorApprovalService>>,
     auto_approve: bool,
+    path_guard: Option<&'a ExternalPathGuard>,
     control_tx: &'a mpsc::UnboundedSender<ControlEvent>,
     base_retry_delay: &'a mut Duration,
     last_event_id: &'a mut Option<String>,
@@ -791,9 +1017,11 @@ async fn process_event_stream(
                 let directory = ctx.directory.to_string();
                 let log_writer = ctx.log_writer.clone();
                 let auto_approve = ctx.auto_approve;
+                let path_guard = ctx.path_guard.cloned();
                 tokio::spawn(async move {
-                    let status = request_permission_approval(
+                    let status = decide_permission(
                         auto_approve,
+                        path_guard.as_ref(),
                         approvals,
                         &permission,
                         tool_input,
@@ -890,8 +1118,46 @@ fn event_matches_session(event_type: &str, event: &Value, session_id: &str) -> b
     extracted == Some(session_id)
 }
 
+/// Whether a permission request edits files outside the guarded working directory.
+fn is_guarded_external_edit(
+    path_guard: Option<&ExternalPathGuard>,
+    permission: &str,
+    tool_input: &Value,
+) -> bool {
+    path_guard.is_some_and(|guard| match permission {
+        "external_directory" => true,
+        "edit" => !guard.external_paths(tool_input).is_empty(),
+        _ => false,
+    })
+}
+
+/// Answer an OpenCode permission request. With `auto_approve` everything is approved except
+/// edits `path_guard` flags as outside the working directory, which need explicit approval.
+async fn decide_permission(
+    auto_approve: bool,
+    path_guard: Option<&ExternalPathGuard>,
+    approvals: Option<Arc<dyn ExecutorApprovalService>>,
+    permission: &str,
+    tool_input: Value,
+    tool_call_id: &str,
+) -> ApprovalStatus {
+    let outside_worktree = is_guarded_external_edit(path_guard, permission, &tool_input);
+    request_permission_approval(
+        auto_approve && !outside_worktree,
+        outside_worktree,
+        approvals,
+        permission,
+        tool_input,
+        tool_call_id,
+    )
+    .await
+}
+
+/// `require_explicit` is set for guarded out-of-tree edits: they are denied rather than
+/// waved through when no approval service can ask the user.
 async fn request_permission_approval(
     auto_approve: bool,
+    require_explicit: bool,
     approvals: Option<Arc<dyn ExecutorApprovalService>>,
     tool_name: &str,
     tool_input: Value,
@@ -901,8 +1167,20 @@ async fn request_permission_approval(
         return ApprovalStatus::Approved;
     }
 
+    let unavailable = if require_explicit {
+        ApprovalStatus::Denied {
+            reason: Some(
+                "Editing files outside the working directory requires approval, but no approval \
+                 service is available."
+                    .to_string(),
+            ),
+        }
+    } else {
+        ApprovalStatus::Approved
+    };
+
     let Some(approvals) = approvals else {
-        return ApprovalStatus::Approved;
+        return unavailable;
     };
 
     match approvals
@@ -912,9 +1190,376 @@ async fn request_permission_approval(
         Ok(status) => status,
         Err(
             ExecutorApprovalError::ServiceUnavailable | ExecutorApprovalError::SessionNotRegistered,
-        ) => ApprovalStatus::Approved,
+        ) => unavailable,
         Err(err) => ApprovalStatus::Denied {
             reason: Some(format!("Approval request failed: {err}")),
         },
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+
+    use axum::{
+        Json, Router,
+        extract::{Path, State},
+        response::sse::{Event, Sse},
+        routing::{get, post},
+    };
+
+    use super::*;
+
+    #[derive(Clone)]
+    struct MockServerState {
+        prompted: mpsc::UnboundedSender<()>,
+        aborted: mpsc::UnboundedSender<String>,
+    }
+
+    async fn spawn_mock_server(state: MockServerState) -> String {
+        let app = Router::new()
+            .route(
+                "/global/health",
+                get(|| async { Json(serde_json::json!({ "healthy": true, "version": "test" })) }),
+            )
+            .route(
+                "/session",
+                post(|| async { Json(serde_json::json!({ "id": "ses_test" })) }),
+            )
+            .route(
+                "/event",
+                get(|| async { Sse::new(futures::stream::pending::<Result<Event, Infallible>>()) }),
+            )
+            .route(
+                "/session/{id}/message",
+                post(|State(state): State<MockServerState>| async move {
+                    let _ = state.prompted.send(());
+                    // Keep the prompt running until the executor interrupts it
+                    futures::future::pending::<()>().await;
+                    Json(serde_json::json!({}))
+                }),
+            )
+            .route(
+                "/session/{id}/abort",
+                post(
+                    |State(state): State<MockServerState>, Path(id): Path<String>| async move {
+                        let _ = state.aborted.send(id);
+                        Json(serde_json::json!(true))
+                    },
+                ),
+            )
+            .with_state(state);
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        format!("http://{addr}")
+    }
+
+    #[tokio::test]
+    async fn interrupt_calls_session_abort() {
+        let (prompted_tx, mut prompted_rx) = mpsc::unbounded_channel();
+        let (aborted_tx, mut aborted_rx) = mpsc::unbounded_channel();
+        let base_url = spawn_mock_server(MockServerState {
+            prompted: prompted_tx,
+            aborted: aborted_tx,
+        })
+        .await;
+
+        let config = RunConfig {
+            base_url,
+            directory: std::env::temp_dir().to_string_lossy().to_string(),
+            prompt: "hello".to_string(),
+            resume_session_id: None,
+            model: None,
+            agent: None,
+            approvals: None,
+            auto_approve: true,
+            path_guard: None,
+            server_exited: CancellationToken::new(),
+        };
+        let (interrupt_tx, interrupt_rx) = oneshot::channel();
+        let session = tokio::spawn(run_session(
+            config,
+            LogWriter::new(tokio::io::sink()),
+            interrupt_rx,
+        ));
+
+        tokio::time::timeout(Duration::from_secs(10), prompted_rx.recv())
+            .await
+            .expect("prompt was never sent");
+        interrupt_tx.send(()).unwrap();
+
+        let result = tokio::time::timeout(Duration::from_secs(10), session)
+            .await
+            .expect("session did not stop after interrupt")
+            .unwrap();
+        assert!(
+            matches!(result, Err(ExecutorError::Cancelled)),
+            "{result:?}"
+        );
+        assert_eq!(aborted_rx.try_recv().unwrap(), "ses_test");
+    }
+
+    #[derive(Clone)]
+    struct FlakyServerState {
+        prompts: Arc<std::sync::atomic::AtomicUsize>,
+        status_calls: Arc<std::sync::atomic::AtomicUsize>,
+    }
+
+    /// A server whose prompt connection drops mid-response and whose status endpoint fails
+    /// once before recovering; the session then finishes on the event stream.
+    async fn spawn_flaky_server(state: FlakyServerState) -> String {
+        use std::sync::atomic::Ordering;
+
+        let app = Router::new()
+            .route(
+                "/global/health",
+                get(|| async { Json(serde_json::json!({ "healthy": true, "version": "test" })) }),
+            )
+            .route(
+                "/session",
+                post(|| async { Json(serde_json::json!({ "id": "ses_test" })) }),
+            )
+            .route(
+                "/event",
+                get(|| async {
+                    let idle = futures::stream::once(async {
+                        tokio::time::sleep(Duration::from_millis(500)).await;
+                        Ok::<_, Infallible>(
+                            Event::default().data(
+                                serde_json::json!({
+                                    "type": "session.idle",
+                                    "properties": { "sessionID": "ses_test" }
+                                })
+                                .to_string(),
+                            ),
+                        )
+                    });
+                    Sse::new(idle.chain(futures::stream::pending()))
+                }),
+            )
+            .route(
+                "/session/{id}/message",
+                post(|State(state): State<FlakyServerState>| async move {
+                    state.prompts.fetch_add(1, Ordering::SeqCst);
+                    axum::body::Body::from_stream(futures::stream::once(async {
+                        Err::<bytes::Bytes, _>(io::Error::other("connection dropped"))
+                    }))
+                }),
+            )
+            .route(
+                "/session/status",
+                get(|State(state): State<FlakyServerState>| async move {
+                    if state.status_calls.fetch_add(1, Ordering::SeqCst) == 0 {
+                        return Err(axum::http::StatusCode::SERVICE_UNAVAILABLE);
+                    }
+                    Ok(Json(serde_json::json!({ "ses_test": { "type": "busy" } })))
+                }),
+            )
+            .with_state(state);
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        format!("http://{addr}")
+    }
+
+    #[tokio::test]
+    async fn dropped_prompt_connection_reconnects_and_resumes() {
+        use std::sync::atomic::Ordering;
+
+        let state = FlakyServerState {
+            prompts: Arc::default(),
+            status_calls: Arc::default(),
+        };
+        let base_url = spawn_flaky_server(state.clone()).await;
+
+        let config = RunConfig {
+            base_url,
+            directory: std::env::temp_dir().to_string_lossy().to_string(),
+            prompt: "hello".to_string(),
+            resume_session_id: None,
+            model: None,
+            agent: None,
+            approvals: None,
+            auto_approve: true,
+            path_guard: None,
+            server_exited: CancellationToken::new(),
+        };
+        let (_interrupt_tx, interrupt_rx) = oneshot::channel();
+        let result = tokio::time::timeout(
+            Duration::from_secs(10),
+            run_session(config, LogWriter::new(tokio::io::sink()), interrupt_rx),
+        )
+        .await
+        .expect("session never finished after reconnecting");
+
+        assert!(result.is_ok(), "{result:?}");
+        // The in-flight prompt is resumed, not sent again
+        assert_eq!(state.prompts.load(Ordering::SeqCst), 1);
+        assert_eq!(state.status_calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn only_connection_failures_count_as_drops() {
+        // Bind then drop a listener so nothing is accepting on the port
+        let addr = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        ensure_rustls_crypto_provider();
+        let refused = reqwest::get(format!("http://{addr}")).await.unwrap_err();
+        assert!(is_connection_drop(&ExecutorError::Io(io::Error::other(
+            refused
+        ))));
+
+        let reset = io::Error::from(io::ErrorKind::ConnectionReset);
+        assert!(is_connection_drop(&ExecutorError::Io(io::Error::other(
+            reset
+        ))));
+
+        let bad_json = serde_json::from_str::<Value>("{").unwrap_err();
+        assert!(!is_connection_drop(&ExecutorError::Io(io::Error::other(
+            bad_json
+        ))));
+        assert!(!is_connection_drop(&ExecutorError::Io(io::Error::other(
+            "OpenCode session.prompt failed: HTTP 500"
+        ))));
+    }
+
+    #[test]
+    fn parse_model_splits_provider_and_model() {
+        let Ok(ModelRef::Qualified(spec)) = parse_model("anthropic/claude-sonnet-4-5") else {
+            panic!("expected a provider and model");
+        };
+        assert_eq!(spec.provider_id, "anthropic");
+        assert_eq!(spec.model_id, "claude-sonnet-4-5");
+
+        let Ok(ModelRef::Qualified(spec)) = parse_model("openrouter/anthropic/claude-sonnet-4")
+        else {
+            panic!("expected a provider and model");
+        };
+        assert_eq!(spec.provider_id, "openrouter");
+        assert_eq!(spec.model_id, "anthropic/claude-sonnet-4");
+
+        let Ok(ModelRef::Bare(model_id)) = parse_model(" claude-sonnet-4-5 ") else {
+            panic!("expected a bare model name");
+        };
+        assert_eq!(model_id, "claude-sonnet-4-5");
+    }
+
+    #[test]
+    fn parse_model_rejects_empty_provider_or_model() {
+        for model in ["/claude-sonnet-4-5", "anthropic/", "", " "] {
+            let Err(ExecutorError::InvalidModel(message)) = parse_model(model) else {
+                panic!("expected invalid model error for {model:?}");
+            };
+            assert!(message.contains("provider/model"), "{message}");
+        }
+    }
+
+    #[test]
+    fn bare_model_goes_to_the_provider_that_offers_it() {
+        let providers: ConfigProviders = serde_json::from_value(serde_json::json!({
+            "providers": [
+                { "id": "openrouter", "models": { "gpt-5": {}, "claude-sonnet-4-5": {} } },
+                { "id": "anthropic", "models": { "claude-sonnet-4-5": {} } },
+            ],
+            "default": { "anthropic": "claude-sonnet-4-5" },
+        }))
+        .unwrap();
+
+        assert_eq!(
+            provider_for_model(&providers, "claude-sonnet-4-5").as_deref(),
+            Some("anthropic")
+        );
+        assert_eq!(
+            provider_for_model(&providers, "gpt-5").as_deref(),
+            Some("openrouter")
+        );
+        assert_eq!(provider_for_model(&providers, "o3"), None);
+    }
+
+    struct RecordingApprovals {
+        requested: std::sync::Mutex<Vec<String>>,
+    }
+
+    #[async_trait::async_trait]
+    impl ExecutorApprovalService for RecordingApprovals {
+        async fn request_tool_approval(
+            &self,
+            tool_name: &str,
+            _tool_input: Value,
+            _tool_call_id: &str,
+        ) -> Result<ApprovalStatus, ExecutorApprovalError> {
+            self.requested.lock().unwrap().push(tool_name.to_string());
+            Ok(ApprovalStatus::Denied { reason: None })
+        }
+    }
+
+    async fn guarded_edit_status(
+        approvals: &Arc<RecordingApprovals>,
+        tool_input: Value,
+    ) -> ApprovalStatus {
+        let guard = ExternalPathGuard::new("/work/repo");
+        decide_permission(
+            true,
+            Some(&guard),
+            Some(approvals.clone()),
+            "edit",
+            tool_input,
+            "call_1",
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn guarded_in_tree_edit_is_auto_approved() {
+        let approvals = Arc::new(RecordingApprovals {
+            requested: Default::default(),
+        });
+        let input = serde_json::json!({ "metadata": { "filepath": "/work/repo/src/main.rs" } });
+
+        let status = guarded_edit_status(&approvals, input).await;
+
+        assert!(matches!(status, ApprovalStatus::Approved));
+        assert!(approvals.requested.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn guarded_out_of_tree_edit_is_prompted() {
+        let approvals = Arc::new(RecordingApprovals {
+            requested: Default::default(),
+        });
+        let input = serde_json::json!({ "metadata": { "filepath": "/home/user/.bashrc" } });
+
+        let status = guarded_edit_status(&approvals, input).await;
+
+        assert!(matches!(status, ApprovalStatus::Denied { reason: None }));
+        assert_eq!(
+            *approvals.requested.lock().unwrap(),
+            vec!["edit".to_string()]
+        );
+    }
+
+    #[tokio::test]
+    async fn guarded_out_of_tree_edit_without_approvals_is_denied() {
+        let status = decide_permission(
+            true,
+            Some(&ExternalPathGuard::new("/work/repo")),
+            None,
+            "edit",
+            serde_json::json!({ "metadata": { "filepath": "/etc/hosts" } }),
+            "call_1",
+        )
+        .await;
+
+        assert!(matches!(status, ApprovalStatus::Denied { reason: Some(_) }));
+    }
+}
@@ -211,11 +211,21 @@ pub(super) enum Part {
 
 #[derive(Debug, Deserialize)]
 pub(super) struct TextPart {
+    #[serde(default)]
+    pub(super) id: Option<String>,
     #[serde(rename = "messageID")]
     pub(super) message_id: String,
     pub(super) text: String,
 }
 
+impl TextPart {
+    /// Key the part's streamed text is accumulated under. A message can carry several text
+    /// parts, so prefer the part id and fall back to the message id for older servers.
+    pub(super) fn stream_key(&self) -> &str {
+        self.id.as_deref().unwrap_or(&self.message_id)
+    }
+}
+
 /// Same structure as TextPart, used for reasoning content
 pub(super) type ReasoningPart = TextPart;
 
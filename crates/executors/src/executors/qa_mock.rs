@@ -19,10 +19,13 @@ use workspace_utils::msg_store::MsgStore;
 use crate::{
     env::ExecutionEnv,
     executors::{
-        ExecutorError, SpawnedChild, StandardCodingAgentExecutor,
+        BaseCodingAgent, ExecutorError, SpawnedChild, StandardCodingAgentExecutor,
         claude::{ClaudeContentItem, ClaudeJson, ClaudeMessage, ClaudeToolData},
     },
-    logs::utils::EntryIndexProvider,
+    logs::{
+        paths::relativize_normalized_entries,
+        utils::{EntryIndexProvider, patch::tag_normalized_entries},
+    },
 };
 
 /// Mock executor for QA testing
@@ -35,7 +38,7 @@ impl StandardCodingAgentExecutor for QaMockExecutor {
         &self,
         current_dir: &Path,
         prompt: &str,
-        _env: &ExecutionEnv,
+        env: &ExecutionEnv,
     ) -> Result<SpawnedChild, ExecutorError> {
         info!("QA Mock Executor: spawning mock execution");
 
@@ -69,6 +72,7 @@ impl StandardCodingAgentExecutor for QaMockExecutor {
             .stderr(Stdio::piped());
 
         let child = cmd.group_spawn().map_err(ExecutorError::Io)?;
+        env.capture_prompt(prompt);
         Ok(SpawnedChild::from(child))
     }
 
@@ -85,6 +89,8 @@ impl StandardCodingAgentExecutor for QaMockExecutor {
     }
 
     fn normalize_logs(&self, msg_store: Arc<MsgStore>, current_dir: &Path) {
+        tag_normalized_entries(&msg_store, BaseCodingAgent::QaMock);
+        relativize_normalized_entries(&msg_store, current_dir);
         // Reuse Claude's log processor since we output ClaudeJson format
         let entry_index_provider = EntryIndexProvider::start_from(&msg_store);
         crate::executors::claude::ClaudeLogProcessor::process_logs(
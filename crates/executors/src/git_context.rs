@@ -0,0 +1,141 @@
+//! Opt-in summary of the working tree's git state, prepended to an agent's prompt so it
+//! starts out knowing about uncommitted work instead of rediscovering it.
+
+use std::path::Path;
+
+use tokio::process::Command;
+
+/// Upper bound on each git section, so a huge dirty tree cannot crowd out the prompt itself.
+const MAX_SECTION_CHARS: usize = 2000;
+
+/// `git status --porcelain` for `dir`, plus `git diff --stat HEAD` when `include_diff` is set,
+/// formatted for the top of a prompt. `None` when `dir` is not inside a git work tree.
+pub async fn git_status_summary(dir: &Path, include_diff: bool) -> Option<String> {
+    let status = run_git(dir, &["status", "--porcelain"]).await?;
+    let mut summary = String::from("Current `git status --porcelain` of the working directory:\n");
+    push_section(&mut summary, &status);
+
+    if include_diff
+        && let Some(diff) = run_git(dir, &["diff", "--stat", "HEAD"]).await
+        && !diff.trim().is_empty()
+    {
+        summary.push_str("\nUncommitted changes (`git diff --stat HEAD`):\n");
+        push_section(&mut summary, &diff);
+    }
+    Some(summary)
+}
+
+/// Prepend [`git_status_summary`] to `prompt`, leaving the prompt unchanged when there is no
+/// summary.
+pub async fn prepend_git_status(prompt: &str, dir: &Path, include_diff: bool) -> String {
+    match git_status_summary(dir, include_diff).await {
+        Some(summary) => format!("{summary}\n{prompt}"),
+        None => prompt.to_string(),
+    }
+}
+
+async fn run_git(dir: &Path, args: &[&str]) -> Option<String> {
+    let output = Command::new("git")
+        .arg("--no-optional-locks")
+        .args(args)
+        .current_dir(dir)
+        .kill_on_drop(true)
+        .output()
+        .await
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn push_section(summary: &mut String, output: &str) {
+    let output = output.trim_end();
+    if output.is_empty() {
+        summary.push_str("(clean)\n");
+        return;
+    }
+    summary.push_str("```\n");
+    summary.push_str(&truncate_lines(output, MAX_SECTION_CHARS));
+    summary.push_str("\n```\n");
+}
+
+/// Keep whole lines up to `max_chars`, noting how many lines were left out.
+fn truncate_lines(output: &str, max_chars: usize) -> String {
+    let mut kept = String::new();
+    let mut lines = output.lines();
+    for line in lines.by_ref() {
+        if !kept.is_empty() && kept.len() + line.len() + 1 > max_chars {
+            let omitted = lines.count() + 1;
+            kept.push_str(&format!("\n… ({omitted} more lines)"));
+            return kept;
+        }
+        if !kept.is_empty() {
+            kept.push('\n');
+        }
+        kept.push_str(line);
+    }
+    kept
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn git(dir: &Path, args: &[&str]) {
+        let status = Command::new("git")
+            .args(args)
+            .current_dir(dir)
+            .status()
+            .await
+            .unwrap();
+        assert!(status.success(), "git {args:?} failed");
+    }
+
+    #[tokio::test]
+    async fn prompt_starts_with_status_summary() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        git(dir, &["init", "-q"]).await;
+        git(dir, &["config", "user.email", "test@example.com"]).await;
+        git(dir, &["config", "user.name", "Test"]).await;
+        std::fs::write(dir.join("tracked.txt"), "one\n").unwrap();
+        git(dir, &["add", "tracked.txt"]).await;
+        git(
+            dir,
+            &[
+                "-c",
+                "commit.gpgsign=false",
+                "commit",
+                "-q",
+                "-m",
+                "initial",
+            ],
+        )
+        .await;
+        std::fs::write(dir.join("tracked.txt"), "one\ntwo\n").unwrap();
+        std::fs::write(dir.join("notes.txt"), "todo\n").unwrap();
+
+        let prompt = prepend_git_status("Fix the bug", dir, true).await;
+        assert!(
+            prompt.starts_with("Current `git status --porcelain`"),
+            "{prompt}"
+        );
+        assert!(prompt.contains(" M tracked.txt\n?? notes.txt"), "{prompt}");
+        assert!(prompt.contains("tracked.txt | 1 +"), "{prompt}");
+        assert!(prompt.ends_with("\nFix the bug"), "{prompt}");
+    }
+
+    #[test]
+    fn long_output_is_cut_at_a_line_boundary() {
+        let output = (0..100)
+            .map(|i| format!("?? file-{i:02}.txt"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let truncated = truncate_lines(&output, 40);
+        assert_eq!(
+            truncated,
+            "?? file-00.txt\n?? file-01.txt\n… (98 more lines)"
+        );
+    }
+}
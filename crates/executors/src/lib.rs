@@ -2,8 +2,14 @@ pub mod actions;
 pub mod approvals;
 pub mod command;
 pub mod env;
+pub mod executor_registry;
 pub mod executors;
+pub mod git_context;
 pub mod logs;
 pub mod mcp_config;
 pub mod profile;
+pub mod restart;
+pub mod spawn_limit;
+pub mod spawn_registry;
+pub mod spawn_timing;
 pub mod stdout_dup;
@@ -0,0 +1,67 @@
+//! Recognize errors caused by a conversation outgrowing the model's context window, so they
+//! surface as [`NormalizedEntryError::ContextWindowExceeded`] with a hint to compact instead of
+//! as a generic error.
+
+use crate::logs::{NormalizedEntry, NormalizedEntryError, NormalizedEntryType};
+
+/// Appended to context window errors; compacting (or starting over) is the only way forward.
+pub const COMPACTION_HINT: &str = "The conversation no longer fits in the model's context \
+                                   window. Compact the session or start a new one to continue.";
+
+/// Lowercase fragments agents and providers use in context overflow errors.
+const CONTEXT_WINDOW_PATTERNS: &[&str] = &[
+    "context window",
+    "context_length_exceeded",
+    "maximum context length",
+    "context length exceeded",
+    "prompt is too long",
+    "exceeds the maximum number of tokens",
+];
+
+/// Whether an error message reports that the request no longer fits in the context window.
+pub fn is_context_window_exceeded(message: &str) -> bool {
+    let message = message.to_lowercase();
+    CONTEXT_WINDOW_PATTERNS
+        .iter()
+        .any(|pattern| message.contains(pattern))
+}
+
+/// The error entry for a context overflow: the agent's own message followed by
+/// [`COMPACTION_HINT`].
+pub fn context_window_exceeded_entry(message: &str) -> NormalizedEntry {
+    let message = message.trim();
+    let content = if message.is_empty() {
+        COMPACTION_HINT.to_string()
+    } else {
+        format!("{message}\n\n{COMPACTION_HINT}")
+    };
+    NormalizedEntry {
+        timestamp: None,
+        entry_type: NormalizedEntryType::ErrorMessage {
+            error_type: NormalizedEntryError::ContextWindowExceeded,
+        },
+        content,
+        metadata: None,
+        agent: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognizes_provider_overflow_messages() {
+        for message in [
+            "Codex ran out of room in the model's context window. Start a new conversation.",
+            "This model's maximum context length is 128000 tokens.",
+            r#"{"code":"context_length_exceeded"}"#,
+            "Prompt is too long",
+        ] {
+            assert!(is_context_window_exceeded(message), "{message}");
+        }
+        assert!(!is_context_window_exceeded(
+            "stream disconnected before completion"
+        ));
+    }
+}
@@ -0,0 +1,228 @@
+//! Render normalized conversation entries as markdown, e.g. for pasting a session into a PR
+//! description or docs.
+
+use workspace_utils::msg_store::MsgStore;
+
+use crate::logs::{
+    ActionType, FileChange, NormalizedEntry, NormalizedEntryType, ToolResultValueType, ToolStatus,
+    utils::patch::collect_normalized_entries,
+};
+
+impl NormalizedEntry {
+    /// Render this entry as a markdown section. Entries that only matter to the live UI
+    /// (loading indicators, next actions, token usage) render as `None`.
+    pub fn to_markdown(&self) -> Option<String> {
+        let content = self.content.trim();
+        let markdown = match &self.entry_type {
+            NormalizedEntryType::UserMessage => format!("## User\n\n{content}"),
+            NormalizedEntryType::UserFeedback { denied_tool } => {
+                format!("## User feedback on `{denied_tool}`\n\n{content}")
+            }
+            NormalizedEntryType::AssistantMessage => format!("## Assistant\n\n{content}"),
+            NormalizedEntryType::Thinking => {
+                let quoted = content
+                    .lines()
+                    .map(|line| format!("> {line}").trim_end().to_string())
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                format!("> **Thinking**\n>\n{quoted}")
+            }
+            NormalizedEntryType::SystemMessage => format!("**System:** {content}"),
+            NormalizedEntryType::ErrorMessage { .. } => format!("**Error:** {content}"),
+            NormalizedEntryType::ToolUse {
+                tool_name,
+                action_type,
+                status,
+            } => {
+                let status = match status {
+                    ToolStatus::Failed => " (failed)",
+                    ToolStatus::Denied { .. } => " (denied)",
+                    ToolStatus::TimedOut => " (timed out)",
+                    _ => "",
+                };
+                let body = action_markdown(action_type).unwrap_or_else(|| content.to_string());
+                format!("### Tool: {tool_name}{status}\n\n{body}")
+            }
+            NormalizedEntryType::Loading
+            | NormalizedEntryType::NextAction { .. }
+            | NormalizedEntryType::TokenUsageInfo(_) => return None,
+        };
+        Some(markdown.trim_end().to_string())
+    }
+}
+
+/// Replay the normalized entries in `store` and render them, in order, as one markdown
+/// document.
+pub fn render_session_markdown(store: &MsgStore) -> String {
+    let sections: Vec<String> = collect_normalized_entries(store)
+        .iter()
+        .filter_map(NormalizedEntry::to_markdown)
+        .collect();
+    sections.join("\n\n")
+}
+
+fn action_markdown(action_type: &ActionType) -> Option<String> {
+    let markdown = match action_type {
+        ActionType::FileRead { path } => format!("Read `{path}`"),
+        ActionType::FileEdit { path, changes } => changes
+            .iter()
+            .map(|change| match change {
+                FileChange::Write { .. } => format!("Wrote `{path}`"),
+                FileChange::Delete => format!("Deleted `{path}`"),
+                FileChange::Rename { new_path } => format!("Renamed `{path}` to `{new_path}`"),
+                FileChange::Edit { unified_diff, .. } => fenced("diff", unified_diff),
+            })
+            .collect::<Vec<_>>()
+            .join("\n\n"),
+        ActionType::CommandRun { command, result } => {
+            let mut markdown = fenced("sh", command);
+            if let Some(output) = result.as_ref().and_then(|result| result.output.as_deref())
+                && !output.trim().is_empty()
+            {
+                markdown.push_str("\n\n");
+                markdown.push_str(&fenced("", output));
+            }
+            markdown
+        }
+        ActionType::Search { query } => format!("Searched for `{query}`"),
+        ActionType::WebFetch { url } => format!("Fetched <{url}>"),
+        ActionType::Tool {
+            arguments, result, ..
+        } => {
+            let mut parts = Vec::new();
+            if let Some(arguments) = arguments {
+                parts.push(fenced("json", &pretty_json(arguments)));
+            }
+            if let Some(result) = result {
+                parts.push(match (&result.r#type, result.value.as_str()) {
+                    (ToolResultValueType::Markdown, Some(markdown)) => markdown.to_string(),
+                    _ => fenced("json", &pretty_json(&result.value)),
+                });
+            }
+            if parts.is_empty() {
+                return None;
+            }
+            parts.join("\n\n")
+        }
+        ActionType::TaskCreate { description } => description.clone(),
+        ActionType::PlanPresentation { plan, .. } => plan.clone(),
+        ActionType::TodoManagement { todos, .. } => todos
+            .iter()
+            .map(|todo| {
+                let checked = if todo.status.eq_ignore_ascii_case("completed") {
+                    "x"
+                } else {
+                    " "
+                };
+                format!("- [{checked}] {}", todo.content)
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+        ActionType::Other { .. } => return None,
+    };
+    Some(markdown)
+}
+
+/// Wrap `body` in a code fence longer than any backtick run it contains.
+fn fenced(language: &str, body: &str) -> String {
+    let longest_run = body.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    let fence = "`".repeat(longest_run.max(2) + 1);
+    format!(
+        "{fence}{language}\n{}\n{fence}",
+        body.trim_end_matches('\n')
+    )
+}
+
+fn pretty_json(value: &serde_json::Value) -> String {
+    serde_json::to_string_pretty(value).unwrap_or_else(|_| value.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logs::{CommandExitStatus, CommandRunResult, utils::patch::ConversationPatch};
+
+    fn entry(entry_type: NormalizedEntryType, content: &str) -> NormalizedEntry {
+        NormalizedEntry {
+            timestamp: None,
+            entry_type,
+            content: content.to_string(),
+            metadata: None,
+            agent: None,
+        }
+    }
+
+    #[test]
+    fn renders_session_with_tool_headings_and_fenced_diffs() {
+        let store = MsgStore::new();
+        store.push_patch(ConversationPatch::add_normalized_entry(
+            0,
+            entry(NormalizedEntryType::UserMessage, "Fix the typo"),
+        ));
+        store.push_patch(ConversationPatch::add_normalized_entry(
+            1,
+            entry(NormalizedEntryType::Loading, ""),
+        ));
+        store.push_patch(ConversationPatch::remove(1));
+        store.push_patch(ConversationPatch::add_normalized_entry(
+            1,
+            entry(
+                NormalizedEntryType::ToolUse {
+                    tool_name: "bash".to_string(),
+                    action_type: ActionType::CommandRun {
+                        command: "grep -rn teh src".to_string(),
+                        result: Some(CommandRunResult {
+                            exit_status: Some(CommandExitStatus::ExitCode { code: 0 }),
+                            output: Some("src/lib.rs:1:// teh answer\n".to_string()),
+                        }),
+                    },
+                    status: ToolStatus::Success,
+                },
+                "grep -rn teh src",
+            ),
+        ));
+        store.push_patch(ConversationPatch::add_normalized_entry(
+            2,
+            entry(
+                NormalizedEntryType::ToolUse {
+                    tool_name: "edit".to_string(),
+                    action_type: ActionType::FileEdit {
+                        path: "src/lib.rs".to_string(),
+                        changes: vec![FileChange::Edit {
+                            unified_diff: "--- a/src/lib.rs\n+++ b/src/lib.rs\n@@ -1 +1 @@\n\
+                                           -// teh answer\n+// the answer\n"
+                                .to_string(),
+                            has_line_numbers: true,
+                        }],
+                    },
+                    status: ToolStatus::Success,
+                },
+                "src/lib.rs",
+            ),
+        ));
+        store.push_patch(ConversationPatch::add_normalized_entry(
+            3,
+            entry(NormalizedEntryType::AssistantMessage, "Working"),
+        ));
+        store.push_patch(ConversationPatch::replace(
+            3,
+            entry(NormalizedEntryType::AssistantMessage, "Fixed the typo."),
+        ));
+
+        let markdown = render_session_markdown(&store);
+        assert_eq!(
+            markdown,
+            "## User\n\nFix the typo\n\n\
+             ### Tool: bash\n\n```sh\ngrep -rn teh src\n```\n\n\
+             ```\nsrc/lib.rs:1:// teh answer\n```\n\n\
+             ### Tool: edit\n\n```diff\n--- a/src/lib.rs\n+++ b/src/lib.rs\n@@ -1 +1 @@\n\
+             -// teh answer\n+// the answer\n```\n\n\
+             ## Assistant\n\nFixed the typo."
+        );
+    }
+
+    #[test]
+    fn fences_outgrow_backticks_in_the_body() {
+        assert_eq!(fenced("", "use ``` here"), "````\nuse ``` here\n````");
+    }
+}
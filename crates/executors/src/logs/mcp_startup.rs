@@ -0,0 +1,56 @@
+//! Recognize MCP servers an agent failed to start, so they surface as
+//! [`NormalizedEntryError::McpServerFailed`] naming the server instead of as free-form text.
+
+use std::sync::LazyLock;
+
+use regex::Regex;
+
+use crate::logs::{NormalizedEntry, NormalizedEntryError, NormalizedEntryType};
+
+/// Codex reports startup failures as "MCP client for `name` failed to start: reason".
+static MCP_STARTUP_FAILURE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?s)MCP client for [`'"]?([^`'"\s]+)[`'"]? failed to start:?\s*(.*)"#).unwrap()
+});
+
+/// The server name and reason of an MCP startup failure reported as text.
+pub fn parse_mcp_startup_failure(message: &str) -> Option<(String, String)> {
+    let captures = MCP_STARTUP_FAILURE.captures(message)?;
+    Some((captures[1].to_string(), captures[2].trim().to_string()))
+}
+
+/// The error entry for an MCP server that failed to start.
+pub fn mcp_server_failed_entry(server: &str, reason: &str) -> NormalizedEntry {
+    let reason = reason.trim();
+    let mut content = format!("MCP server `{server}` failed to start");
+    if !reason.is_empty() {
+        content.push_str(&format!(": {reason}"));
+    }
+    content.push_str("\n\nCheck the server's command and arguments in your MCP configuration.");
+    NormalizedEntry {
+        timestamp: None,
+        entry_type: NormalizedEntryType::ErrorMessage {
+            error_type: NormalizedEntryError::McpServerFailed {
+                server: server.to_string(),
+            },
+        },
+        content,
+        metadata: None,
+        agent: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_codex_startup_failure_text() {
+        assert_eq!(
+            parse_mcp_startup_failure(
+                "MCP client for `playwright` failed to start: program not found"
+            ),
+            Some(("playwright".to_string(), "program not found".to_string()))
+        );
+        assert_eq!(parse_mcp_startup_failure("stream disconnected"), None);
+    }
+}
@@ -3,8 +3,16 @@ use serde::{Deserialize, Serialize};
 use ts_rs::TS;
 use workspace_utils::approvals::ApprovalStatus;
 
+use crate::executors::BaseCodingAgent;
+
+pub mod context_window;
+pub mod markdown;
+pub mod mcp_startup;
+pub mod paths;
 pub mod plain_text_processor;
+pub mod plan;
 pub mod stderr_processor;
+pub mod summary;
 pub mod utils;
 
 #[derive(Debug, Clone, Serialize, Deserialize, TS)]
@@ -67,6 +75,11 @@ pub struct NormalizedConversation {
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum NormalizedEntryError {
     SetupRequired,
+    ContextWindowExceeded,
+    /// An MCP server from the agent's configuration could not be started.
+    McpServerFailed {
+        server: String,
+    },
     Other,
 }
 
@@ -111,9 +124,40 @@ pub struct NormalizedEntry {
     pub content: String,
     #[ts(skip)]
     pub metadata: Option<serde_json::Value>,
+    /// The agent that produced this entry, set when its normalizer tags the log store.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub agent: Option<BaseCodingAgent>,
+}
+
+/// Remove ANSI escape sequences (colors, cursor movement) from CLI output.
+pub fn strip_ansi(text: &str) -> String {
+    if text.contains('\u{1b}') {
+        strip_ansi_escapes::strip_str(text)
+    } else {
+        text.to_string()
+    }
 }
 
 impl NormalizedEntry {
+    /// Strip ANSI escape sequences from the entry's text and any command output.
+    pub fn without_ansi(mut self) -> Self {
+        self.content = strip_ansi(&self.content);
+        if let NormalizedEntryType::ToolUse {
+            action_type:
+                ActionType::CommandRun {
+                    result: Some(result),
+                    ..
+                },
+            ..
+        } = &mut self.entry_type
+            && let Some(output) = &mut result.output
+        {
+            *output = strip_ansi(output);
+        }
+        self
+    }
+
     pub fn with_tool_status(&self, status: ToolStatus) -> Option<Self> {
         if let NormalizedEntryType::ToolUse {
             tool_name,
@@ -212,6 +256,9 @@ pub enum ActionType {
     },
     PlanPresentation {
         plan: String,
+        /// Steps parsed from the plan's checklist or numbered list, for rendering as a checklist
+        #[serde(default)]
+        steps: Vec<TodoItem>,
     },
     TodoManagement {
         todos: Vec<TodoItem>,
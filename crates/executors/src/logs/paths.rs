@@ -0,0 +1,194 @@
+//! Rewrite the absolute paths agents report in tool calls to worktree-relative form, which is
+//! what the UI displays and links. Paths outside the worktree stay absolute.
+
+use std::path::Path;
+
+use serde_json::Value;
+use workspace_utils::{msg_store::MsgStore, path::make_path_relative_lexically};
+
+use crate::logs::utils::patch::normalized_entry_contents_mut;
+
+/// Relativize the tool-call paths of every normalized entry pushed to `msg_store` from now on.
+pub fn relativize_normalized_entries(msg_store: &MsgStore, worktree_path: &Path) {
+    let worktree = worktree_path.to_string_lossy().into_owned();
+    msg_store.add_patch_transform(move |patch| {
+        for entry in normalized_entry_contents_mut(patch) {
+            relativize_tool_call(entry, &worktree);
+        }
+    });
+}
+
+/// Make file paths in a serialized tool call (read/edit targets, rename destinations and diff
+/// headers) relative to `worktree`, editing the JSON in place. Other entries are left untouched.
+fn relativize_tool_call(entry: &mut Value, worktree: &str) {
+    if entry.pointer("/entry_type/type").and_then(Value::as_str) != Some("tool_use") {
+        return;
+    }
+    let Some(action) = entry.pointer_mut("/entry_type/action_type") else {
+        return;
+    };
+    let kind = action
+        .get("action")
+        .and_then(Value::as_str)
+        .map(str::to_owned);
+    match kind.as_deref() {
+        Some("file_read") => relativize_field(action, "path", worktree),
+        Some("file_edit") => {
+            relativize_field(action, "path", worktree);
+            let changes = action.get_mut("changes").and_then(Value::as_array_mut);
+            for change in changes.into_iter().flatten() {
+                match change.get("action").and_then(Value::as_str) {
+                    Some("rename") => relativize_field(change, "new_path", worktree),
+                    Some("edit") => {
+                        if let Some(Value::String(diff)) = change.get_mut("unified_diff") {
+                            *diff = relativize_diff_headers(diff, worktree);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+fn relativize_field(object: &mut Value, key: &str, worktree: &str) {
+    if let Some(Value::String(path)) = object.get_mut(key) {
+        *path = make_path_relative_lexically(path, worktree);
+    }
+}
+
+/// Relativize the file names on a diff's `---`/`+++` lines, keeping any trailing timestamp.
+fn relativize_diff_headers(diff: &str, worktree: &str) -> String {
+    diff.split_inclusive('\n')
+        .map(|line| {
+            let Some(marker) = ["--- ", "+++ "].into_iter().find(|m| line.starts_with(m)) else {
+                return line.to_string();
+            };
+            let rest = &line[marker.len()..];
+            let end = rest.find(['\t', '\n']).unwrap_or(rest.len());
+            let path = make_path_relative_lexically(&rest[..end], worktree);
+            format!("{marker}{path}{}", &rest[end..])
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::logs::{
+        ActionType, FileChange, NormalizedEntry, NormalizedEntryType, ToolStatus,
+        utils::{
+            EntryIndexProvider,
+            patch::{add_normalized_entry, collect_normalized_entries},
+        },
+    };
+
+    fn relativized(entry: &NormalizedEntry, worktree: &str) -> NormalizedEntry {
+        let mut value = serde_json::to_value(entry).unwrap();
+        relativize_tool_call(&mut value, worktree);
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn file_edit(path: &str, changes: Vec<FileChange>) -> NormalizedEntry {
+        NormalizedEntry {
+            timestamp: None,
+            entry_type: NormalizedEntryType::ToolUse {
+                tool_name: "edit".to_string(),
+                action_type: ActionType::FileEdit {
+                    path: path.to_string(),
+                    changes,
+                },
+                status: ToolStatus::Success,
+            },
+            content: path.to_string(),
+            metadata: None,
+            agent: None,
+        }
+    }
+
+    #[test]
+    fn in_tree_paths_become_relative_and_external_paths_stay_absolute() {
+        let worktree = "/work/repo";
+        let entry = file_edit(
+            "/work/repo/src/lib.rs",
+            vec![
+                FileChange::Rename {
+                    new_path: "/work/repo/src/main.rs".to_string(),
+                },
+                FileChange::Edit {
+                    unified_diff: "--- /work/repo/src/lib.rs\n+++ /work/repo/src/main.rs\n\
+                                   @@ -1 +1 @@\n-a\n+b\n"
+                        .to_string(),
+                    has_line_numbers: true,
+                },
+            ],
+        );
+        let entry = relativized(&entry, worktree);
+
+        let NormalizedEntryType::ToolUse {
+            action_type: ActionType::FileEdit { path, changes },
+            ..
+        } = &entry.entry_type
+        else {
+            panic!("expected a file edit");
+        };
+        assert_eq!(path, "src/lib.rs");
+        assert!(matches!(
+            &changes[0],
+            FileChange::Rename { new_path } if new_path == "src/main.rs"
+        ));
+        assert!(matches!(
+            &changes[1],
+            FileChange::Edit { unified_diff, .. }
+                if unified_diff == "--- src/lib.rs\n+++ src/main.rs\n@@ -1 +1 @@\n-a\n+b\n"
+        ));
+
+        let external = relativized(&file_edit("/etc/hosts", vec![FileChange::Delete]), worktree);
+        assert!(matches!(
+            &external.entry_type,
+            NormalizedEntryType::ToolUse {
+                action_type: ActionType::FileEdit { path, .. },
+                ..
+            } if path == "/etc/hosts"
+        ));
+    }
+
+    #[test]
+    fn store_relativizes_tool_paths_inside_the_worktree() {
+        let msg_store = Arc::new(MsgStore::new());
+        relativize_normalized_entries(&msg_store, Path::new("/work/repo"));
+
+        let index_provider = EntryIndexProvider::test_new();
+        for path in ["/work/repo/src/lib.rs", "/etc/hosts"] {
+            let entry = NormalizedEntry {
+                timestamp: None,
+                entry_type: NormalizedEntryType::ToolUse {
+                    tool_name: "read".to_string(),
+                    action_type: ActionType::FileRead {
+                        path: path.to_string(),
+                    },
+                    status: ToolStatus::Success,
+                },
+                content: path.to_string(),
+                metadata: None,
+                agent: None,
+            };
+            add_normalized_entry(&msg_store, &index_provider, entry);
+        }
+
+        let paths: Vec<String> = collect_normalized_entries(&msg_store)
+            .into_iter()
+            .map(|entry| match entry.entry_type {
+                NormalizedEntryType::ToolUse {
+                    action_type: ActionType::FileRead { path },
+                    ..
+                } => path,
+                _ => panic!("expected a file read"),
+            })
+            .collect();
+        assert_eq!(paths, ["src/lib.rs", "/etc/hosts"]);
+    }
+}
@@ -185,6 +185,7 @@ pub struct PlainTextLogProcessor {
     transform_lines: Option<LinesTransformFn>,
     message_boundary_predicate: Option<MessageBoundaryPredicateFn>,
     normalized_entry_producer: NormalizedEntryProducerFn,
+    collapse_repeats: bool,
     last_chunk_arrival_time: Instant, // time since last chunk arrived
     current_entry_index: Option<usize>,
 }
@@ -291,7 +292,11 @@ impl PlainTextLogProcessor {
 
     /// Create patch
     fn create_patch(&mut self, lines: Vec<String>) -> Patch {
-        let content = lines.concat();
+        let content = if self.collapse_repeats {
+            collapse_repeated_lines(&lines)
+        } else {
+            lines.concat()
+        };
         let entry = (self.normalized_entry_producer)(content);
 
         let added = self.current_entry_index.is_some();
@@ -312,6 +317,21 @@ impl PlainTextLogProcessor {
     }
 }
 
+/// Join lines, rendering each run of identical complete lines once as `line × N`.
+fn collapse_repeated_lines(lines: &[String]) -> String {
+    let mut content = String::new();
+    for run in lines.chunk_by(|a, b| a == b) {
+        let line = &run[0];
+        match line.strip_suffix('\n') {
+            Some(text) if run.len() > 1 => {
+                content.push_str(&format!("{text} × {}\n", run.len()));
+            }
+            _ => run.iter().for_each(|line| content.push_str(line)),
+        }
+    }
+    content
+}
+
 #[bon]
 impl PlainTextLogProcessor {
     /// Create a builder for configuring PlainTextLogProcessor.
@@ -322,6 +342,7 @@ impl PlainTextLogProcessor {
     /// * `time_gap` - Optional time gap between individual entries. When new content arrives after this duration, it is considered a new entry.
     /// * `format_chunk` - Optional function to fix raw output before creating normalized entries.
     /// * `message_boundary_predicate` - Optional function to determine custom message boundaries. Useful when content is heterogeneous (e.g., tool calls interleaved with assistant messages).
+    /// * `collapse_repeats` - Optional flag to show consecutive identical lines once, as `line × N`. Useful for CLIs that spam progress lines. Defaults to `false`.
     /// * `index_provider` - Required sharable atomic counter for tracking entry indices.
     ///
     /// When both `size_threshold` and `time_gap` are `None`, a default size threshold of 8 KiB is used.
@@ -333,6 +354,7 @@ impl PlainTextLogProcessor {
         format_chunk: Option<FormatChunkFn>,
         transform_lines: Option<LinesTransformFn>,
         message_boundary_predicate: Option<MessageBoundaryPredicateFn>,
+        collapse_repeats: Option<bool>,
         index_provider: EntryIndexProvider,
     ) -> Self {
         Self {
@@ -353,6 +375,7 @@ impl PlainTextLogProcessor {
                 Box::new(p) as Box<dyn Fn(&[String]) -> Option<MessageBoundary> + Send + 'static>
             }),
             normalized_entry_producer: Box::new(normalized_entry_producer),
+            collapse_repeats: collapse_repeats.unwrap_or(false),
             last_chunk_arrival_time: Instant::now(),
             current_entry_index: None,
         }
@@ -362,7 +385,9 @@ impl PlainTextLogProcessor {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::logs::{NormalizedEntryType, ToolStatus};
+    use crate::logs::{
+        NormalizedEntryType, ToolStatus, utils::patch::extract_normalized_entry_from_patch,
+    };
 
     #[test]
     fn test_plain_buffer_flush() {
@@ -407,6 +432,7 @@ mod tests {
                 entry_type: NormalizedEntryType::SystemMessage,
                 content: content.to_string(),
                 metadata: None,
+                agent: None,
             }
         };
 
@@ -436,6 +462,7 @@ mod tests {
                     },
                     content,
                     metadata: None,
+                    agent: None,
                 }
             } else {
                 NormalizedEntry {
@@ -443,6 +470,7 @@ mod tests {
                     entry_type: NormalizedEntryType::SystemMessage,
                     content: content.to_string(),
                     metadata: None,
+                    agent: None,
                 }
             }
         };
@@ -464,6 +492,7 @@ mod tests {
                 entry_type: NormalizedEntryType::SystemMessage,
                 content,
                 metadata: None,
+                agent: None,
             }
         };
 
@@ -488,4 +517,38 @@ mod tests {
         let patches = processor.process("real content\n".to_string());
         assert_eq!(patches.len(), 1);
     }
+
+    #[test]
+    fn test_processor_collapses_repeated_lines() {
+        let producer = |content: String| -> NormalizedEntry {
+            NormalizedEntry {
+                timestamp: None,
+                entry_type: NormalizedEntryType::SystemMessage,
+                content,
+                metadata: None,
+                agent: None,
+            }
+        };
+
+        let mut processor = PlainTextLogProcessor::builder()
+            .normalized_entry_producer(producer)
+            .collapse_repeats(true)
+            .index_provider(EntryIndexProvider::test_new())
+            .build();
+
+        let patches: Vec<Patch> = (0..5)
+            .flat_map(|_| processor.process("Downloading packages...\n".to_string()))
+            .collect();
+
+        let entries: Vec<(usize, NormalizedEntry)> = patches
+            .iter()
+            .filter_map(extract_normalized_entry_from_patch)
+            .collect();
+        // Every update targets the same single entry
+        assert!(entries.iter().all(|(index, _)| *index == entries[0].0));
+        assert_eq!(
+            entries.last().unwrap().1.content,
+            "Downloading packages... × 5\n"
+        );
+    }
 }
@@ -0,0 +1,91 @@
+//! Pull the steps out of a plan an agent presents as markdown, so the UI can render it as a
+//! checklist next to the plan text.
+
+use std::sync::LazyLock;
+
+use regex::Regex;
+
+use crate::logs::TodoItem;
+
+/// `- [ ] step` / `* [x] step` checklist items.
+static CHECKLIST_ITEM: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\s*[-*+]\s+\[([ xX])\]\s+(.+)$").unwrap());
+
+/// `1. step` / `2) step` items that are not nested under another item.
+static NUMBERED_ITEM: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^\d+[.)]\s+(.+)$").unwrap());
+
+/// The steps of a markdown plan. Checklist items are used when the plan has any, keeping
+/// their checked state; otherwise top-level numbered items become pending steps. A plan
+/// without either yields no steps.
+pub fn plan_steps(plan: &str) -> Vec<TodoItem> {
+    let checklist: Vec<TodoItem> = plan
+        .lines()
+        .filter_map(|line| CHECKLIST_ITEM.captures(line))
+        .map(|captures| {
+            let status = if &captures[1] == " " {
+                "pending"
+            } else {
+                "completed"
+            };
+            step(&captures[2], status)
+        })
+        .collect();
+    if !checklist.is_empty() {
+        return checklist;
+    }
+
+    plan.lines()
+        .filter_map(|line| NUMBERED_ITEM.captures(line))
+        .map(|captures| step(&captures[1], "pending"))
+        .collect()
+}
+
+fn step(content: &str, status: &str) -> TodoItem {
+    TodoItem {
+        content: content.trim().trim_matches('*').trim().to_string(),
+        status: status.to_string(),
+        priority: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn steps(plan: &str) -> Vec<(String, String)> {
+        plan_steps(plan)
+            .into_iter()
+            .map(|todo| (todo.content, todo.status))
+            .collect()
+    }
+
+    #[test]
+    fn checklist_items_keep_their_state() {
+        let plan = "## Plan\n\n- [x] Read the config loader\n- [ ] Add the `timeout` field\n  \
+                    - [ ] Cover it in tests\n\nNotes:\n1. Not a step";
+        assert_eq!(
+            steps(plan),
+            [
+                (
+                    "Read the config loader".to_string(),
+                    "completed".to_string()
+                ),
+                ("Add the `timeout` field".to_string(), "pending".to_string()),
+                ("Cover it in tests".to_string(), "pending".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn top_level_numbered_items_become_pending_steps() {
+        let plan = "1. **Add the migration**\n   1. nested detail\n2) Update the model\n- aside";
+        assert_eq!(
+            steps(plan),
+            [
+                ("Add the migration".to_string(), "pending".to_string()),
+                ("Update the model".to_string(), "pending".to_string()),
+            ]
+        );
+        assert!(plan_steps("Just refactor the parser.").is_empty());
+    }
+}
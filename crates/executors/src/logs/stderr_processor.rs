@@ -2,6 +2,7 @@
 //!
 //! Uses `PlainTextLogProcessor` with a 2-second `latency_threshold` to split stderr streams into entries.
 //! Each entry is normalized as `ErrorMessage` and emitted as JSON patches to the message store.
+//! ANSI escape sequences are stripped by `ConversationPatch` like for every other normalizer.
 //!
 //! Example:
 //! ```rust,ignore
@@ -47,8 +48,9 @@ pub fn normalize_stderr_logs(msg_store: Arc<MsgStore>, entry_index_provider: Ent
                 entry_type: NormalizedEntryType::ErrorMessage {
                     error_type: NormalizedEntryError::Other,
                 },
-                content: strip_ansi_escapes::strip_str(&content),
+                content,
                 metadata: None,
+                agent: None,
             }))
             .time_gap(Duration::from_secs(2)) // Break messages if they are 2 seconds apart
             .index_provider(entry_index_provider)
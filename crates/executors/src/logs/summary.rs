@@ -0,0 +1,166 @@
+//! Post-run summary of what an agent left in its log store.
+
+use std::sync::Arc;
+
+use workspace_utils::msg_store::MsgStore;
+
+use crate::{
+    logs::{NormalizedEntryType, utils::patch::collect_normalized_entries},
+    spawn_timing::SpawnTimings,
+};
+
+/// Rewrites the agent's final answer before it lands in a [`SessionSummary`], e.g. to strip
+/// boilerplate or keep only a code block.
+pub type FinalMessageTransform = Arc<dyn Fn(String) -> String + Send + Sync>;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SessionSummary {
+    /// The last non-empty assistant message.
+    pub last_assistant_message: Option<String>,
+    pub assistant_messages: usize,
+    pub tool_calls: usize,
+    /// The run produced neither assistant messages nor tool calls, which for a successful
+    /// exit usually means the agent no-oped (e.g. it was misconfigured) rather than finished.
+    pub no_assistant_output: bool,
+    /// Exit code of the agent process, when it exited on its own rather than being stopped
+    /// or killed by a signal.
+    pub exit_code: Option<i32>,
+    /// How long the agent took to start, when the spawn was timed.
+    pub spawn_timings: Option<SpawnTimings>,
+}
+
+impl SessionSummary {
+    pub fn from_msg_store(msg_store: &MsgStore) -> Self {
+        let mut summary = Self::default();
+        for entry in collect_normalized_entries(msg_store) {
+            match entry.entry_type {
+                NormalizedEntryType::AssistantMessage => {
+                    let content = entry.content.trim();
+                    if !content.is_empty() {
+                        summary.assistant_messages += 1;
+                        summary.last_assistant_message = Some(content.to_string());
+                    }
+                }
+                NormalizedEntryType::ToolUse { .. } => summary.tool_calls += 1,
+                _ => {}
+            }
+        }
+        summary.no_assistant_output = summary.assistant_messages == 0 && summary.tool_calls == 0;
+        summary
+    }
+
+    pub fn with_exit_code(mut self, exit_code: Option<i32>) -> Self {
+        self.exit_code = exit_code;
+        self
+    }
+
+    pub fn with_spawn_timings(mut self, spawn_timings: Option<SpawnTimings>) -> Self {
+        self.spawn_timings = spawn_timings;
+        self
+    }
+
+    /// Pass the final assistant message through `transform`.
+    pub fn with_final_message_transform(mut self, transform: &FinalMessageTransform) -> Self {
+        self.last_assistant_message = self.last_assistant_message.map(transform.as_ref());
+        self
+    }
+
+    /// A line explaining a non-zero exit, for surfacing next to the agent's own output.
+    pub fn exit_diagnostic(&self) -> Option<String> {
+        match self.exit_code {
+            Some(0) | None => None,
+            Some(code) => Some(format!("Agent process exited with code {code}")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logs::{NormalizedEntry, utils::patch::ConversationPatch};
+
+    fn entry(entry_type: NormalizedEntryType, content: &str) -> NormalizedEntry {
+        NormalizedEntry {
+            timestamp: None,
+            entry_type,
+            content: content.to_string(),
+            metadata: None,
+            agent: None,
+        }
+    }
+
+    #[test]
+    fn empty_session_is_flagged() {
+        let msg_store = MsgStore::new();
+        msg_store.push_stdout("\n");
+        msg_store.push_patch(ConversationPatch::add_normalized_entry(
+            0,
+            entry(NormalizedEntryType::UserMessage, "Add a README"),
+        ));
+        msg_store.push_patch(ConversationPatch::add_normalized_entry(
+            1,
+            entry(NormalizedEntryType::AssistantMessage, "  "),
+        ));
+
+        let summary = SessionSummary::from_msg_store(&msg_store);
+        assert!(summary.no_assistant_output);
+        assert_eq!(summary.last_assistant_message, None);
+    }
+
+    #[test]
+    fn assistant_reply_is_not_flagged() {
+        let msg_store = MsgStore::new();
+        msg_store.push_patch(ConversationPatch::add_normalized_entry(
+            0,
+            entry(NormalizedEntryType::AssistantMessage, "Added the README."),
+        ));
+
+        let summary = SessionSummary::from_msg_store(&msg_store);
+        assert!(!summary.no_assistant_output);
+        assert_eq!(summary.assistant_messages, 1);
+        assert_eq!(
+            summary.last_assistant_message.as_deref(),
+            Some("Added the README.")
+        );
+    }
+
+    #[test]
+    fn non_zero_exit_code_is_reported() {
+        let msg_store = MsgStore::new();
+        let summary = SessionSummary::from_msg_store(&msg_store).with_exit_code(Some(3));
+        assert_eq!(summary.exit_code, Some(3));
+        assert_eq!(
+            summary.exit_diagnostic().as_deref(),
+            Some("Agent process exited with code 3")
+        );
+
+        let summary = SessionSummary::from_msg_store(&msg_store).with_exit_code(Some(0));
+        assert_eq!(summary.exit_diagnostic(), None);
+    }
+
+    #[test]
+    fn final_message_transform_can_extract_a_code_block() {
+        let msg_store = MsgStore::new();
+        msg_store.push_patch(ConversationPatch::add_normalized_entry(
+            0,
+            entry(
+                NormalizedEntryType::AssistantMessage,
+                "Here is the fix:\n```rust\nfn main() {}\n```\nLet me know if it helps!",
+            ),
+        ));
+        let extract_code_block: FinalMessageTransform = Arc::new(|message: String| {
+            message
+                .split("```")
+                .nth(1)
+                .and_then(|block| block.split_once('\n'))
+                .map_or(message.clone(), |(_, code)| code.trim_end().to_string())
+        });
+
+        let summary = SessionSummary::from_msg_store(&msg_store)
+            .with_final_message_transform(&extract_code_block);
+        assert_eq!(
+            summary.last_assistant_message.as_deref(),
+            Some("fn main() {}")
+        );
+    }
+}
@@ -1,12 +1,15 @@
-use std::sync::Arc;
+use std::{collections::BTreeMap, sync::Arc};
 
 use json_patch::Patch;
 use serde::{Deserialize, Serialize};
 use serde_json::{from_value, json, to_value};
 use ts_rs::TS;
-use workspace_utils::{diff::Diff, msg_store::MsgStore};
+use workspace_utils::{diff::Diff, log_msg::LogMsg, msg_store::MsgStore};
 
-use crate::logs::{NormalizedEntry, utils::EntryIndexProvider};
+use crate::{
+    executors::BaseCodingAgent,
+    logs::{NormalizedEntry, utils::EntryIndexProvider},
+};
 
 #[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, TS)]
 #[serde(rename_all = "lowercase")]
@@ -46,7 +49,7 @@ impl ConversationPatch {
         let patch_entry = PatchEntry {
             op: PatchOperation::Add,
             path: format!("/entries/{entry_index}"),
-            value: PatchType::NormalizedEntry(entry),
+            value: PatchType::NormalizedEntry(entry.without_ansi()),
         };
 
         from_value(json!([patch_entry])).unwrap()
@@ -110,7 +113,7 @@ impl ConversationPatch {
         let patch_entry = PatchEntry {
             op: PatchOperation::Replace,
             path: format!("/entries/{entry_index}"),
-            value: PatchType::NormalizedEntry(entry),
+            value: PatchType::NormalizedEntry(entry.without_ansi()),
         };
 
         from_value(json!([patch_entry])).unwrap()
@@ -142,6 +145,33 @@ pub fn extract_normalized_entry_from_patch(patch: &Patch) -> Option<(usize, Norm
     })
 }
 
+/// Replay the patches in `msg_store`'s history and return the normalized entries as they
+/// currently stand, in index order.
+pub fn collect_normalized_entries(msg_store: &MsgStore) -> Vec<NormalizedEntry> {
+    let mut entries = BTreeMap::new();
+    for msg in msg_store.get_history() {
+        let LogMsg::JsonPatch(patch) = msg else {
+            continue;
+        };
+        if let Some((index, entry)) = extract_normalized_entry_from_patch(&patch) {
+            entries.insert(index, entry);
+            continue;
+        }
+        for op in &patch.0 {
+            if let json_patch::PatchOperation::Remove(op) = op
+                && let Some(index) = op
+                    .path
+                    .as_str()
+                    .strip_prefix("/entries/")
+                    .and_then(|index| index.parse::<usize>().ok())
+            {
+                entries.remove(&index);
+            }
+        }
+    }
+    entries.into_values().collect()
+}
+
 pub fn upsert_normalized_entry(
     msg_store: &Arc<MsgStore>,
     index: usize,
@@ -175,3 +205,85 @@ pub fn replace_normalized_entry(
 ) {
     upsert_normalized_entry(msg_store, index, normalized_entry, false);
 }
+
+/// The `content` of every normalized entry that `patch` adds or replaces.
+pub(crate) fn normalized_entry_contents_mut(
+    patch: &mut Patch,
+) -> impl Iterator<Item = &mut serde_json::Value> {
+    patch.0.iter_mut().filter_map(|op| {
+        let value = match op {
+            json_patch::PatchOperation::Add(op) => &mut op.value,
+            json_patch::PatchOperation::Replace(op) => &mut op.value,
+            _ => return None,
+        };
+        if value.get("type").and_then(|t| t.as_str()) != Some("NORMALIZED_ENTRY") {
+            return None;
+        }
+        value.get_mut("content")
+    })
+}
+
+/// Tag every normalized entry pushed to `msg_store` from now on with `agent`, so entries stay
+/// attributable when several agents' conversations are shown together.
+pub fn tag_normalized_entries(msg_store: &MsgStore, agent: BaseCodingAgent) {
+    let agent = json!(agent);
+    msg_store.add_patch_transform(move |patch| {
+        for content in normalized_entry_contents_mut(patch) {
+            if let Some(content) = content.as_object_mut() {
+                content.insert("agent".to_string(), agent.clone());
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logs::NormalizedEntryType;
+
+    #[test]
+    fn normalized_entry_patches_strip_ansi_codes() {
+        let entry = NormalizedEntry {
+            timestamp: None,
+            entry_type: NormalizedEntryType::SystemMessage,
+            content: "\u{1b}[32mok\u{1b}[0m: 3 passed".to_string(),
+            metadata: None,
+            agent: None,
+        };
+
+        for patch in [
+            ConversationPatch::add_normalized_entry(0, entry.clone()),
+            ConversationPatch::replace(0, entry.clone()),
+        ] {
+            let (_, normalized) = extract_normalized_entry_from_patch(&patch).unwrap();
+            assert_eq!(normalized.content, "ok: 3 passed");
+        }
+    }
+
+    #[test]
+    fn tagged_store_stamps_agent_on_added_and_replaced_entries() {
+        let msg_store = Arc::new(MsgStore::new());
+        tag_normalized_entries(&msg_store, BaseCodingAgent::Codex);
+
+        let entry = NormalizedEntry {
+            timestamp: None,
+            entry_type: NormalizedEntryType::AssistantMessage,
+            content: "done".to_string(),
+            metadata: None,
+            agent: None,
+        };
+        let index_provider = EntryIndexProvider::test_new();
+        let index = add_normalized_entry(&msg_store, &index_provider, entry.clone());
+        replace_normalized_entry(&msg_store, index, entry);
+
+        let history = msg_store.get_history();
+        assert_eq!(history.len(), 2);
+        for msg in history {
+            let workspace_utils::log_msg::LogMsg::JsonPatch(patch) = msg else {
+                panic!("expected a JSON patch");
+            };
+            let (_, normalized) = extract_normalized_entry_from_patch(&patch).unwrap();
+            assert_eq!(normalized.agent, Some(BaseCodingAgent::Codex));
+        }
+    }
+}
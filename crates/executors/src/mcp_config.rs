@@ -2,14 +2,21 @@
 //!
 //! These helpers abstract over JSON vs TOML formats used by different agents.
 
-use std::{collections::HashMap, sync::LazyLock};
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::LazyLock,
+};
 
 use serde::{Deserialize, Serialize};
 use serde_json::{Map, Value};
+use tempfile::TempDir;
 use tokio::fs;
+use toml_edit::{DocumentMut, Item, Table, TableLike};
 use ts_rs::TS;
 
-use crate::executors::{CodingAgent, ExecutorError};
+use crate::executors::{CodingAgent, ExecutorError, StandardCodingAgentExecutor};
 
 static DEFAULT_MCP_JSON: &str = include_str!("../default_mcp.json");
 pub static PRECONFIGURED_MCP_SERVERS: LazyLock<Value> = LazyLock::new(|| {
@@ -43,6 +50,39 @@ impl McpConfig {
     pub fn set_servers(&mut self, servers: HashMap<String, serde_json::Value>) {
         self.servers = servers;
     }
+
+    /// Add the agent's preconfigured servers to the user's servers. A server the user already
+    /// defines is never overwritten, even if its config differs from the preconfigured one.
+    pub fn merge_preconfigured_respecting_user(&mut self) -> PreconfiguredMerge {
+        let mut merge = PreconfiguredMerge::default();
+        let Some(preconfigured) = self.preconfigured.as_object() else {
+            return merge;
+        };
+        for (name, server) in preconfigured {
+            // `meta` holds UI metadata for the preconfigured servers, not a server
+            if name == "meta" {
+                continue;
+            }
+            if self.servers.contains_key(name) {
+                merge.skipped.push(name.clone());
+            } else {
+                self.servers.insert(name.clone(), server.clone());
+                merge.added.push(name.clone());
+            }
+        }
+        merge.added.sort();
+        merge.skipped.sort();
+        merge
+    }
+}
+
+/// The outcome of [`McpConfig::merge_preconfigured_respecting_user`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PreconfiguredMerge {
+    /// Preconfigured servers added to the user's servers, sorted by name
+    pub added: Vec<String>,
+    /// Preconfigured servers left out because the user defines a server of the same name
+    pub skipped: Vec<String>,
 }
 
 /// Read an agent's external config file (JSON or TOML) and normalize it to serde_json::Value.
@@ -51,11 +91,11 @@ pub async fn read_agent_config(
     mcp_config: &McpConfig,
 ) -> Result<Value, ExecutorError> {
     if let Ok(file_content) = fs::read_to_string(config_path).await {
+        if file_content.trim().is_empty() {
+            return Ok(serde_json::json!({}));
+        }
         if mcp_config.is_toml_config {
             // Parse TOML then convert to JSON Value
-            if file_content.trim().is_empty() {
-                return Ok(serde_json::json!({}));
-            }
             let toml_val: toml::Value = toml::from_str(&file_content)?;
             let json_string = serde_json::to_string(&toml_val)?;
             Ok(serde_json::from_str(&json_string)?)
@@ -67,24 +107,457 @@ pub async fn read_agent_config(
     }
 }
 
-/// Write an agent's external config (as serde_json::Value) back to disk in the agent's format (JSON or TOML).
+/// Serialize an agent's external config in the agent's format (JSON or TOML). A TOML config
+/// is rendered as an edit of `current`, the file's present content, see [`edit_toml_config`].
+fn render_agent_config(
+    mcp_config: &McpConfig,
+    config: &Value,
+    current: &str,
+) -> Result<String, ExecutorError> {
+    let config = config_for_platform(mcp_config, config);
+    let config = config.as_ref();
+    if mcp_config.is_toml_config {
+        if current.trim().is_empty() {
+            // Build the TOML tree directly so integers stay integers and nulls are dropped
+            let toml_value =
+                json_to_toml(config)?.unwrap_or_else(|| toml::Value::Table(toml::Table::new()));
+            return Ok(toml::to_string_pretty(&toml_value)?);
+        }
+        Ok(edit_toml_config(current, &mcp_config.servers_path, config)?.to_string())
+    } else {
+        Ok(serde_json::to_string_pretty(config)?)
+    }
+}
+
+/// Apply `config` to the existing TOML document `current`. The table under `servers_path` is
+/// replaced, and other top-level settings only where their value changed, so comments,
+/// ordering and formatting of everything else stay byte-identical.
+fn edit_toml_config(
+    current: &str,
+    servers_path: &[String],
+    config: &Value,
+) -> Result<DocumentMut, ExecutorError> {
+    let current_value = serde_json::to_value(toml::from_str::<toml::Value>(current)?)?;
+    let mut document: DocumentMut = current.parse()?;
+    let Some((final_attr, parents)) = servers_path.split_last() else {
+        return Ok(document);
+    };
+
+    let empty = Map::new();
+    let settings = config.as_object().unwrap_or(&empty);
+    let is_servers_root = |key: &str| servers_path.first().is_some_and(|root| root == key);
+    let removed: Vec<String> = document
+        .iter()
+        .map(|(key, _)| key.to_string())
+        .filter(|key| !is_servers_root(key) && !settings.contains_key(key))
+        .collect();
+    for key in removed {
+        document.remove(&key);
+    }
+    for (key, value) in settings {
+        if is_servers_root(key) || current_value.get(key) == Some(value) {
+            continue;
+        }
+        match json_to_toml_item(value)? {
+            Some(item) => document.insert(key, item),
+            None => document.remove(key),
+        };
+    }
+
+    let mut table: &mut dyn TableLike = document.as_table_mut();
+    for part in parents {
+        if !table.get(part).is_some_and(Item::is_table_like) {
+            let mut parent = Table::new();
+            parent.set_implicit(true);
+            table.insert(part, Item::Table(parent));
+        }
+        table = table
+            .get_mut(part)
+            .and_then(Item::as_table_like_mut)
+            .expect("parent table was just ensured");
+    }
+
+    let mut servers = Table::new();
+    servers.set_implicit(true);
+    for (name, server) in servers_at(config, servers_path).into_iter().flatten() {
+        if let Some(item) = json_to_toml_item(server)? {
+            servers.insert(name, item);
+        }
+    }
+    // Keep the servers where they were in the file, along with any comment above them
+    if let Some(existing) = table.get(final_attr).and_then(Item::as_table) {
+        if let Some(position) = existing.position() {
+            servers.set_position(position);
+        }
+        *servers.decor_mut() = existing.decor().clone();
+    }
+    table.insert(final_attr, Item::Table(servers));
+    Ok(document)
+}
+
+/// Write an agent's external config (as serde_json::Value) back to disk in the agent's format
+/// (JSON or TOML). An existing TOML file keeps its comments and formatting.
 pub async fn write_agent_config(
     config_path: &std::path::Path,
     mcp_config: &McpConfig,
     config: &Value,
 ) -> Result<(), ExecutorError> {
-    if mcp_config.is_toml_config {
-        // Convert JSON Value back to TOML
-        let toml_value: toml::Value = serde_json::from_str(&serde_json::to_string(config)?)?;
-        let toml_content = toml::to_string_pretty(&toml_value)?;
-        fs::write(config_path, toml_content).await?;
-    } else {
-        let json_content = serde_json::to_string_pretty(config)?;
-        fs::write(config_path, json_content).await?;
+    let current = fs::read_to_string(config_path).await.unwrap_or_default();
+    let content = render_agent_config(mcp_config, config, &current)?;
+    fs::write(config_path, content)
+        .await
+        .map_err(|err| ExecutorError::from_write_error(config_path, err))
+}
+
+/// Replace the servers stored under `servers_path` in an agent config, creating any
+/// missing parent objects.
+pub fn set_mcp_servers_in_config(
+    config: &mut Value,
+    servers_path: &[String],
+    servers: &HashMap<String, Value>,
+) -> Result<(), ExecutorError> {
+    let Some((final_attr, parents)) = servers_path.split_last() else {
+        return Ok(());
+    };
+    if !config.is_object() {
+        *config = serde_json::json!({});
     }
+
+    let mut current = config;
+    for part in parents {
+        if !current.get(part).is_some_and(Value::is_object) {
+            current[part.as_str()] = serde_json::json!({});
+        }
+        current = &mut current[part.as_str()];
+    }
+    current[final_attr.as_str()] = serde_json::to_value(servers)?;
     Ok(())
 }
 
+/// Report what setting `servers` in the agent config at `config_path` would change, see
+/// [`validate_agent_config`], so callers can show the change and ask for confirmation.
+pub async fn preview_mcp_servers_update(
+    config_path: &Path,
+    mcp_config: &McpConfig,
+    servers: &HashMap<String, Value>,
+) -> Result<ConfigDiff, ExecutorError> {
+    let mut config = read_agent_config(config_path, mcp_config).await?;
+    set_mcp_servers_in_config(&mut config, &mcp_config.servers_path, servers)?;
+    validate_agent_config(config_path, mcp_config, &config).await
+}
+
+/// What writing a config would change, from [`validate_agent_config`]. Server names are sorted.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, TS)]
+pub struct ConfigDiff {
+    /// Servers that would be added under the agent's servers path
+    pub added: Vec<String>,
+    /// Servers that would be removed
+    pub removed: Vec<String>,
+    /// Servers present before and after whose settings would change
+    pub modified: Vec<String>,
+    /// Whether anything outside the servers path would change as well
+    pub other_settings_changed: bool,
+    /// Whether writing the config would drop any of the current file's comments (TOML only)
+    pub drops_comments: bool,
+    /// Unified diff of the config file, from its current content to the one that would be
+    /// written
+    pub unified_diff: String,
+}
+
+impl ConfigDiff {
+    /// Whether writing the config would change anything the diff tracks.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.modified.is_empty()
+            && !self.other_settings_changed
+    }
+}
+
+/// The servers stored under `servers_path` in an agent config, if it has a servers table.
+fn servers_at<'a>(config: &'a Value, servers_path: &[String]) -> Option<&'a ServerMap> {
+    servers_path
+        .iter()
+        .try_fold(config, |current, part| current.get(part))
+        .and_then(Value::as_object)
+}
+
+/// Dry run of [`write_agent_config`]: parse the file at `config_path` and report which servers
+/// under the agent's `servers_path` writing `config` would add, remove or modify, along with
+/// the file diff, without touching disk. A missing file counts as the agent's config template.
+pub async fn validate_agent_config(
+    config_path: &Path,
+    mcp_config: &McpConfig,
+    config: &Value,
+) -> Result<ConfigDiff, ExecutorError> {
+    let current = read_agent_config(config_path, mcp_config).await?;
+    let empty = ServerMap::new();
+    let before = servers_at(&current, &mcp_config.servers_path).unwrap_or(&empty);
+    let after = servers_at(config, &mcp_config.servers_path).unwrap_or(&empty);
+
+    let mut diff = ConfigDiff::default();
+    for (name, server) in after {
+        match before.get(name) {
+            None => diff.added.push(name.clone()),
+            Some(old) if old != server => diff.modified.push(name.clone()),
+            Some(_) => {}
+        }
+    }
+    diff.removed = before
+        .keys()
+        .filter(|name| !after.contains_key(*name))
+        .cloned()
+        .collect();
+    diff.added.sort();
+    diff.removed.sort();
+    diff.modified.sort();
+
+    // Compare the rest of the config with both server tables set to the same value
+    let (mut current_rest, mut config_rest) = (current.clone(), config.clone());
+    let no_servers = HashMap::new();
+    set_mcp_servers_in_config(&mut current_rest, &mcp_config.servers_path, &no_servers)?;
+    set_mcp_servers_in_config(&mut config_rest, &mcp_config.servers_path, &no_servers)?;
+    diff.other_settings_changed = current_rest != config_rest;
+
+    let content = fs::read_to_string(config_path).await.unwrap_or_default();
+    let rendered = render_agent_config(mcp_config, config, &content)?;
+    if mcp_config.is_toml_config {
+        let kept: HashSet<&str> = toml_comments(&rendered).collect();
+        diff.drops_comments = toml_comments(&content).any(|comment| !kept.contains(comment));
+    }
+    diff.unified_diff = workspace_utils::diff::create_unified_diff(
+        &config_path.to_string_lossy(),
+        &content,
+        &rendered,
+    );
+    Ok(diff)
+}
+
+/// Names of the MCP servers in an agent's config file, sorted. A missing or empty file, or
+/// one without a servers table, yields no names.
+pub async fn list_configured_mcp_servers(
+    agent: &CodingAgent,
+    config_path: &Path,
+) -> Result<Vec<String>, ExecutorError> {
+    let mcp_config = agent.get_mcp_config();
+    let config = read_agent_config(config_path, &mcp_config).await?;
+    let servers = servers_at(&config, &mcp_config.servers_path);
+
+    let mut names: Vec<String> = servers
+        .map(|servers| servers.keys().cloned().collect())
+        .unwrap_or_default();
+    names.sort();
+    Ok(names)
+}
+
+/// The comment lines of a TOML document, trimmed.
+fn toml_comments(content: &str) -> impl Iterator<Item = &str> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| line.starts_with('#'))
+}
+
+/// Like [`json_to_toml`], but as a `toml_edit` item for splicing into an existing document.
+/// Objects become standard tables, and objects nested in arrays inline tables.
+fn json_to_toml_item(value: &Value) -> Result<Option<Item>, ExecutorError> {
+    match value {
+        Value::Object(map) => {
+            let mut table = Table::new();
+            for (key, value) in map {
+                if let Some(item) = json_to_toml_item(value)? {
+                    table.insert(key, item);
+                }
+            }
+            Ok(Some(Item::Table(table)))
+        }
+        other => Ok(json_to_toml_edit_value(other)?.map(Item::Value)),
+    }
+}
+
+fn json_to_toml_edit_value(value: &Value) -> Result<Option<toml_edit::Value>, ExecutorError> {
+    Ok(Some(match value {
+        Value::Null => return Ok(None),
+        Value::Bool(b) => (*b).into(),
+        Value::Number(n) => match toml_number(n)? {
+            TomlNumber::Integer(i) => i.into(),
+            TomlNumber::Float(f) => f.into(),
+        },
+        Value::String(s) => s.as_str().into(),
+        Value::Array(items) => toml_edit::Value::Array(
+            items
+                .iter()
+                .filter_map(|item| json_to_toml_edit_value(item).transpose())
+                .collect::<Result<_, _>>()?,
+        ),
+        Value::Object(map) => toml_edit::Value::InlineTable(
+            map.iter()
+                .filter_map(|(k, v)| {
+                    json_to_toml_edit_value(v)
+                        .map(|v| v.map(|v| (k.as_str(), v)))
+                        .transpose()
+                })
+                .collect::<Result<_, _>>()?,
+        ),
+    }))
+}
+
+/// Convert a JSON value to TOML without a serde round-trip. Codex `mcp_servers` entries carry
+/// integer args and timeouts that must keep their type, and TOML has no `null`, so null
+/// values (and array items) are omitted rather than failing the whole write.
+fn json_to_toml(value: &Value) -> Result<Option<toml::Value>, ExecutorError> {
+    Ok(Some(match value {
+        Value::Null => return Ok(None),
+        Value::Bool(b) => toml::Value::Boolean(*b),
+        Value::Number(n) => match toml_number(n)? {
+            TomlNumber::Integer(i) => toml::Value::Integer(i),
+            TomlNumber::Float(f) => toml::Value::Float(f),
+        },
+        Value::String(s) => toml::Value::String(s.clone()),
+        Value::Array(items) => toml::Value::Array(
+            items
+                .iter()
+                .filter_map(|item| json_to_toml(item).transpose())
+                .collect::<Result<_, _>>()?,
+        ),
+        Value::Object(map) => toml::Value::Table(
+            map.iter()
+                .filter_map(|(k, v)| {
+                    json_to_toml(v)
+                        .map(|v| v.map(|v| (k.clone(), v)))
+                        .transpose()
+                })
+                .collect::<Result<_, _>>()?,
+        ),
+    }))
+}
+
+enum TomlNumber {
+    Integer(i64),
+    Float(f64),
+}
+
+/// A JSON number as TOML, whose integers are signed 64-bit. Integers beyond that range are
+/// rejected rather than silently turned into floats.
+fn toml_number(n: &serde_json::Number) -> Result<TomlNumber, ExecutorError> {
+    if let Some(i) = n.as_i64() {
+        return Ok(TomlNumber::Integer(i));
+    }
+    match n.as_f64() {
+        Some(f) if !n.is_u64() => Ok(TomlNumber::Float(f)),
+        _ => Err(ExecutorError::InvalidConfig(format!(
+            "{n} is too large for a TOML integer"
+        ))),
+    }
+}
+
+/// A throwaway config containing extra MCP servers for a single spawn.
+///
+/// The config is written to its own per-run directory, which is removed when this is dropped.
+#[derive(Debug)]
+pub struct EphemeralMcpConfig {
+    _dir: TempDir,
+    config_path: PathBuf,
+}
+
+impl EphemeralMcpConfig {
+    /// Write `servers` into `mcp_config`'s template under its servers path, as `file_name`.
+    pub async fn write(
+        mcp_config: &McpConfig,
+        servers: &Value,
+        file_name: &str,
+    ) -> Result<Self, ExecutorError> {
+        let dir = tempfile::Builder::new()
+            .prefix("vibe-kanban-mcp-")
+            .tempdir()
+            .map_err(ExecutorError::Io)?;
+        let this = Self {
+            config_path: dir.path().join(file_name),
+            _dir: dir,
+        };
+
+        let mut config = mcp_config.template.clone();
+        let mut target = &mut config;
+        for key in &mcp_config.servers_path {
+            if !target.get(key).is_some_and(Value::is_object) {
+                target[key.as_str()] = Value::Object(Map::new());
+            }
+            target = &mut target[key.as_str()];
+        }
+        if let (Some(target), Some(servers)) = (target.as_object_mut(), servers.as_object()) {
+            target.extend(
+                servers
+                    .iter()
+                    .filter(|(name, _)| name.as_str() != "meta")
+                    .map(|(k, v)| (k.clone(), v.clone())),
+            );
+        }
+
+        write_agent_config(&this.config_path, mcp_config, &config).await?;
+        Ok(this)
+    }
+
+    pub fn config_path(&self) -> &Path {
+        &self.config_path
+    }
+}
+
+/// Key of a server's `meta` entry whose text is appended to the agent's prompt, e.g.
+/// "You have access to the Foo MCP server; use it for X".
+const META_PROMPT_APPEND: &str = "prompt_append";
+
+/// The servers in `agent`'s MCP config file, in canonical form and with their `meta`. `None`
+/// when the agent has no config file or it cannot be read.
+pub async fn persisted_mcp_servers(agent: &CodingAgent) -> Option<Value> {
+    let config_path = agent.default_mcp_config_path()?;
+    let mcp_config = agent.get_mcp_config();
+    let config = read_agent_config(&config_path, &mcp_config).await.ok()?;
+    let servers = servers_at(&config, &mcp_config.servers_path)?;
+    Some(canonicalize_agent_config(
+        agent,
+        &Value::Object(servers.clone()),
+    ))
+}
+
+/// The `prompt_append` instructions of the canonical server maps `layers` combined in order,
+/// so a server in a later map replaces one of the same name, `meta` included.
+pub fn layered_meta_prompt_instructions(layers: &[&Value]) -> Vec<String> {
+    let mut servers = ServerMap::new();
+    let mut meta = ServerMap::new();
+    for layer in layers.iter().filter_map(|layer| layer.as_object()) {
+        for (name, server) in layer.iter().filter(|(name, _)| name.as_str() != "meta") {
+            meta.remove(name);
+            servers.insert(name.clone(), server.clone());
+        }
+        if let Some(layer_meta) = layer.get("meta").and_then(Value::as_object) {
+            meta.extend(layer_meta.clone());
+        }
+    }
+    servers.insert("meta".to_string(), Value::Object(meta));
+    meta_prompt_instructions(&Value::Object(servers))
+}
+
+/// The `prompt_append` instructions in a canonical server map's `meta`, in server name order.
+/// Entries for servers the map does not configure are ignored.
+fn meta_prompt_instructions(canonical: &Value) -> Vec<String> {
+    let Some(meta) = canonical.get("meta").and_then(Value::as_object) else {
+        return Vec::new();
+    };
+    let mut instructions: Vec<(&String, &str)> = meta
+        .iter()
+        .filter(|(server, _)| server.as_str() != "meta" && canonical.get(server).is_some())
+        .filter_map(|(server, entry)| {
+            let text = entry.get(META_PROMPT_APPEND)?.as_str()?.trim();
+            (!text.is_empty()).then_some((server, text))
+        })
+        .collect();
+    instructions.sort_by_key(|(server, _)| *server);
+    instructions
+        .into_iter()
+        .map(|(_, text)| text.to_string())
+        .collect()
+}
+
 type ServerMap = Map<String, Value>;
 
 fn is_http_server(s: &Map<String, Value>) -> bool {
@@ -107,6 +580,9 @@ fn attach_meta(mut obj: ServerMap, meta: Option<Value>) -> Value {
     Value::Object(obj)
 }
 
+/// `Accept` header added to HTTP servers for agents that need it spelled out.
+const HTTP_ACCEPT: &str = "application/json, text/event-stream";
+
 fn ensure_header(headers: &mut Map<String, Value>, key: &str, val: &str) {
     match headers.get_mut(key) {
         Some(Value::String(_)) => {}
@@ -131,6 +607,83 @@ where
     servers
 }
 
+/// Programs run through `cmd /c` on Windows besides `.cmd`/`.bat` paths: Node package
+/// runners, which are installed as `.cmd` shims. `VK_MCP_WINDOWS_SHELL_PROGRAMS` replaces the
+/// list with its comma-separated entries, e.g. for other runners installed the same way.
+const DEFAULT_WINDOWS_SHELL_PROGRAMS: &str = "npx,npm,pnpm,pnpx,yarn";
+
+fn windows_shell_programs() -> Vec<String> {
+    std::env::var("VK_MCP_WINDOWS_SHELL_PROGRAMS")
+        .unwrap_or_else(|_| DEFAULT_WINDOWS_SHELL_PROGRAMS.to_string())
+        .split(',')
+        .map(|program| program.trim().to_ascii_lowercase())
+        .filter(|program| !program.is_empty())
+        .collect()
+}
+
+fn needs_windows_shell(command: &str, shell_programs: &[String]) -> bool {
+    let lower = command.to_ascii_lowercase();
+    let name = lower.rsplit(['/', '\\']).next().unwrap_or(&lower);
+    name.ends_with(".cmd")
+        || name.ends_with(".bat")
+        || shell_programs.iter().any(|program| program == name)
+}
+
+/// Agents spawn stdio MCP servers without a shell, which cannot start `.cmd`/`.bat`
+/// scripts on Windows. Run those through `cmd /c`, as we do for executor binaries. Covers both
+/// `command` + `args` servers and OpenCode's single `command` array; a wrapped server starts
+/// with `cmd`, so wrapping twice changes nothing.
+fn wrap_windows_stdio_servers(servers: &mut ServerMap, shell_programs: &[String]) {
+    for (name, v) in servers.iter_mut() {
+        let Value::Object(s) = v else { continue };
+        if name == "meta" || !is_stdio(s) {
+            continue;
+        }
+
+        match s.get_mut("command") {
+            Some(Value::Array(command)) => {
+                if command
+                    .first()
+                    .and_then(Value::as_str)
+                    .is_some_and(|program| needs_windows_shell(program, shell_programs))
+                {
+                    command.splice(
+                        0..0,
+                        ["cmd", "/c"].map(|arg| Value::String(arg.to_string())),
+                    );
+                }
+            }
+            Some(Value::String(command)) if needs_windows_shell(command, shell_programs) => {
+                let program = std::mem::replace(command, "cmd".to_string());
+                let mut args = vec![Value::String("/c".to_string()), Value::String(program)];
+                if let Some(Value::Array(existing)) = s.remove("args") {
+                    args.extend(existing);
+                }
+                s.insert("args".to_string(), Value::Array(args));
+            }
+            _ => {}
+        }
+    }
+}
+
+/// `config` as it is written on this platform: on Windows, every stdio server under
+/// `servers_path` that needs a shell is wrapped, whether it was preconfigured or added by the
+/// user.
+fn config_for_platform<'a>(mcp_config: &McpConfig, config: &'a Value) -> Cow<'a, Value> {
+    if !cfg!(windows) {
+        return Cow::Borrowed(config);
+    }
+    let mut config = config.clone();
+    let servers = mcp_config
+        .servers_path
+        .iter()
+        .try_fold(&mut config, |value, key| value.get_mut(key));
+    if let Some(Value::Object(servers)) = servers {
+        wrap_windows_stdio_servers(servers, &windows_shell_programs());
+    }
+    Cow::Owned(config)
+}
+
 // --- Adapters ---------------------------------------------------------------
 
 fn adapt_passthrough(servers: ServerMap, meta: Option<Value>) -> Value {
@@ -147,11 +700,7 @@ fn adapt_gemini(servers: ServerMap, meta: Option<Value>) -> Value {
             .and_then(|v| v.as_object().cloned())
             .unwrap_or_default();
 
-        ensure_header(
-            &mut headers,
-            "Accept",
-            "application/json, text/event-stream",
-        );
+        ensure_header(&mut headers, "Accept", HTTP_ACCEPT);
         Map::from_iter([
             ("httpUrl".to_string(), url),
             ("headers".to_string(), Value::Object(headers)),
@@ -182,11 +731,7 @@ fn adapt_opencode(servers: ServerMap, meta: Option<Value>) -> Value {
             .and_then(|v| v.as_object().cloned())
             .unwrap_or_default();
 
-        ensure_header(
-            &mut headers,
-            "Accept",
-            "application/json, text/event-stream",
-        );
+        ensure_header(&mut headers, "Accept", HTTP_ACCEPT);
 
         Map::from_iter([
             ("type".to_string(), Value::String("remote".to_string())),
@@ -236,6 +781,138 @@ fn adapt_opencode(servers: ServerMap, meta: Option<Value>) -> Value {
     attach_meta(servers, meta)
 }
 
+// --- Inverse adapters ---------------------------------------------------------
+
+/// Canonical `headers` for an HTTP server: the `Accept` header the adapters inject is dropped,
+/// and so is the whole map if nothing else is left.
+fn canonical_headers(server: &mut ServerMap, headers: Option<Value>) {
+    let Some(Value::Object(mut headers)) = headers else {
+        return;
+    };
+    if headers.get("Accept").and_then(Value::as_str) == Some(HTTP_ACCEPT) {
+        headers.remove("Accept");
+    }
+    if !headers.is_empty() {
+        server.insert("headers".to_string(), Value::Object(headers));
+    }
+}
+
+fn canonical_http_server(url: Value, headers: Option<Value>) -> ServerMap {
+    let mut server = Map::from_iter([
+        ("type".to_string(), Value::String("http".to_string())),
+        ("url".to_string(), url),
+    ]);
+    canonical_headers(&mut server, headers);
+    server
+}
+
+/// Undo [`wrap_windows_stdio_servers`] for a `cmd /c <shim> ...` server.
+fn unwrap_windows_stdio_server(s: &mut ServerMap) {
+    if s.get("command").and_then(Value::as_str) != Some("cmd") {
+        return;
+    }
+    let Some(Value::Array(args)) = s.get("args") else {
+        return;
+    };
+    let wrapped = matches!(
+        (args.first(), args.get(1)),
+        (Some(Value::String(flag)), Some(Value::String(command)))
+            if flag == "/c" && needs_windows_shell(command, &windows_shell_programs())
+    );
+    if !wrapped {
+        return;
+    }
+    let Some(Value::Array(mut args)) = s.remove("args") else {
+        return;
+    };
+    let command = args.remove(1);
+    args.remove(0);
+    s.insert("command".to_string(), command);
+    if !args.is_empty() {
+        s.insert("args".to_string(), Value::Array(args));
+    }
+}
+
+fn canonicalize_gemini(servers: &mut ServerMap) {
+    for (_k, v) in servers.iter_mut() {
+        if let Value::Object(s) = v
+            && let Some(url) = s.remove("httpUrl")
+        {
+            let headers = s.remove("headers");
+            let mut server = canonical_http_server(url, headers);
+            server.extend(std::mem::take(s));
+            *s = server;
+        }
+    }
+}
+
+fn canonicalize_opencode(servers: &mut ServerMap) {
+    for (_k, v) in servers.iter_mut() {
+        let Value::Object(s) = v else { continue };
+        let kind = s.get("type").and_then(Value::as_str).map(str::to_string);
+        let server = match kind.as_deref() {
+            Some("remote") => {
+                let url = s
+                    .remove("url")
+                    .unwrap_or_else(|| Value::String(String::new()));
+                canonical_http_server(url, s.remove("headers"))
+            }
+            Some("local") => {
+                let mut command = match s.remove("command") {
+                    Some(Value::Array(command)) => command,
+                    Some(command) => vec![command],
+                    None => Vec::new(),
+                };
+                let mut server = Map::new();
+                if !command.is_empty() {
+                    server.insert("command".to_string(), command.remove(0));
+                }
+                if !command.is_empty() {
+                    server.insert("args".to_string(), Value::Array(command));
+                }
+                if let Some(env) = s.remove("environment") {
+                    server.insert("env".to_string(), env);
+                }
+                server
+            }
+            _ => continue,
+        };
+        s.remove("type");
+        // Adapted servers are always enabled; keep a disabled flag so it shows up in diffs
+        if s.get("enabled") == Some(&Value::Bool(true)) {
+            s.remove("enabled");
+        }
+        let rest = std::mem::replace(s, server);
+        s.extend(rest);
+    }
+}
+
+/// Read an agent's MCP server map, as stored under its config's `servers_path`, back into the
+/// canonical form the adapters start from (`command`/`args` for stdio servers, `type: "http"`
+/// with `url`/`headers` otherwise), so what is installed can be compared with what would be
+/// written. Headers the adapters inject are dropped; keys the canonical form has no place for
+/// are kept as they are.
+pub fn canonicalize_agent_config(agent: &CodingAgent, value: &Value) -> Value {
+    let Some(mut servers) = value.as_object().cloned() else {
+        return Value::Object(ServerMap::new());
+    };
+    match agent.mcp_adapter() {
+        Adapter::Gemini => canonicalize_gemini(&mut servers),
+        Adapter::Opencode => canonicalize_opencode(&mut servers),
+        Adapter::Passthrough | Adapter::Codex => {}
+    }
+    if cfg!(windows) {
+        for (name, v) in servers.iter_mut() {
+            if let Value::Object(s) = v
+                && name != "meta"
+            {
+                unwrap_windows_stdio_server(s);
+            }
+        }
+    }
+    Value::Object(servers)
+}
+
 enum Adapter {
     Passthrough,
     Gemini,
@@ -248,6 +925,10 @@ fn apply_adapter(adapter: Adapter, canonical: Value) -> Value {
         Some(map) => extract_meta(map.clone()),
         None => (ServerMap::new(), None),
     };
+    let mut servers_only = servers_only;
+    if cfg!(windows) {
+        wrap_windows_stdio_servers(&mut servers_only, &windows_shell_programs());
+    }
 
     match adapter {
         Adapter::Passthrough => adapt_passthrough(servers_only, meta),
@@ -258,19 +939,471 @@ fn apply_adapter(adapter: Adapter, canonical: Value) -> Value {
 }
 
 impl CodingAgent {
-    pub fn preconfigured_mcp(&self) -> Value {
+    fn mcp_adapter(&self) -> Adapter {
         use Adapter::*;
 
-        let adapter = match self {
+        match self {
             CodingAgent::ClaudeCode(_) | CodingAgent::Droid(_) => Passthrough,
             CodingAgent::Gemini(_) => Gemini,
             CodingAgent::Codex(_) => Codex,
             CodingAgent::Opencode(_) => Opencode,
             #[cfg(feature = "qa-mode")]
             CodingAgent::QaMock(_) => Passthrough, // QA mock doesn't need MCP
-        };
+        }
+    }
 
+    pub fn preconfigured_mcp(&self) -> Value {
         let canonical = PRECONFIGURED_MCP_SERVERS.clone();
-        apply_adapter(adapter, canonical)
+        apply_adapter(self.mcp_adapter(), canonical)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn ephemeral_config_contains_servers_and_is_removed_on_drop() {
+        let mcp_config = McpConfig::new(
+            vec!["mcpServers".to_string()],
+            serde_json::json!({ "mcpServers": {} }),
+            Value::Null,
+            false,
+        );
+        let servers = serde_json::json!({
+            "run_only": { "command": "npx", "args": ["-y", "run-only-mcp"] }
+        });
+
+        let ephemeral = EphemeralMcpConfig::write(&mcp_config, &servers, "mcp.json")
+            .await
+            .unwrap();
+        let path = ephemeral.config_path().to_path_buf();
+        let written = read_agent_config(&path, &mcp_config).await.unwrap();
+        assert_eq!(written["mcpServers"]["run_only"], servers["run_only"]);
+
+        drop(ephemeral);
+        assert!(!path.exists());
+        assert!(!path.parent().unwrap().exists());
+    }
+
+    #[tokio::test]
+    async fn codex_mcp_servers_keep_toml_types() {
+        let codex: CodingAgent =
+            serde_json::from_value(serde_json::json!({ "CODEX": {} })).unwrap();
+        let mcp_config = codex.get_mcp_config();
+        let config = serde_json::json!({
+            "mcp_servers": {
+                "local": {
+                    "command": "node",
+                    "args": ["server.js", "--port", 8080],
+                    "cwd": null,
+                    "startup_timeout_sec": 20,
+                    "tool_timeout_sec": 1.5,
+                    "env": { "API_MODE": "test" }
+                }
+            }
+        });
+
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let path = file.path();
+        write_agent_config(path, &mcp_config, &config)
+            .await
+            .unwrap();
+        let written: toml::Table =
+            toml::from_str(&fs::read_to_string(path).await.unwrap()).unwrap();
+
+        let expected: toml::Table = toml::from_str(
+            r#"
+            [mcp_servers.local]
+            command = "node"
+            args = ["server.js", "--port", 8080]
+            startup_timeout_sec = 20
+            tool_timeout_sec = 1.5
+
+            [mcp_servers.local.env]
+            API_MODE = "test"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(written, expected);
+    }
+
+    #[test]
+    fn integers_too_large_for_toml_are_rejected() {
+        let config = serde_json::json!({ "mcp_servers": { "local": { "port": u64::MAX } } });
+        assert!(matches!(
+            json_to_toml(&config),
+            Err(ExecutorError::InvalidConfig(_))
+        ));
+        assert!(matches!(
+            json_to_toml_item(&config),
+            Err(ExecutorError::InvalidConfig(_))
+        ));
+
+        let config = serde_json::json!({ "args": [i64::MAX, 1.5] });
+        assert_eq!(
+            json_to_toml(&config).unwrap().unwrap()["args"],
+            toml::Value::Array(vec![
+                toml::Value::Integer(i64::MAX),
+                toml::Value::Float(1.5)
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn codex_config_comments_survive_server_insertion() {
+        let codex: CodingAgent =
+            serde_json::from_value(serde_json::json!({ "CODEX": {} })).unwrap();
+        let mcp_config = codex.get_mcp_config();
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let path = file.path();
+        let original = "# Codex settings, tuned by hand\n\
+                        model = \"o3\" # fastest for our repo\n\n\
+                        [profiles.deep]\nmodel_reasoning_effort = \"high\"\n";
+        fs::write(path, original).await.unwrap();
+
+        let mut config = read_agent_config(path, &mcp_config).await.unwrap();
+        let servers = HashMap::from([(
+            "docs".to_string(),
+            serde_json::json!({ "command": "docs-mcp", "args": ["--stdio"] }),
+        )]);
+        set_mcp_servers_in_config(&mut config, &mcp_config.servers_path, &servers).unwrap();
+        write_agent_config(path, &mcp_config, &config)
+            .await
+            .unwrap();
+        let written = fs::read_to_string(path).await.unwrap();
+
+        assert!(written.starts_with(original), "{written}");
+        let written: toml::Table = toml::from_str(&written).unwrap();
+        assert_eq!(
+            written["mcp_servers"]["docs"]["command"].as_str(),
+            Some("docs-mcp")
+        );
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn windows_cmd_stdio_servers_are_shell_wrapped() {
+        let canonical = serde_json::json!({
+            "local": { "command": "C:\\tools\\server.cmd", "args": ["--stdio"] },
+            "runner": { "command": "npx", "args": ["-y", "some-mcp"] },
+            "native": { "command": "server.exe" },
+        });
+
+        let passthrough = apply_adapter(Adapter::Passthrough, canonical.clone());
+        assert_eq!(passthrough["local"]["command"], "cmd");
+        assert_eq!(
+            passthrough["local"]["args"],
+            serde_json::json!(["/c", "C:\\tools\\server.cmd", "--stdio"])
+        );
+        assert_eq!(
+            passthrough["runner"]["args"],
+            serde_json::json!(["/c", "npx", "-y", "some-mcp"])
+        );
+        assert_eq!(passthrough["native"]["command"], "server.exe");
+
+        let opencode = apply_adapter(Adapter::Opencode, canonical);
+        assert_eq!(
+            opencode["local"]["command"],
+            serde_json::json!(["cmd", "/c", "C:\\tools\\server.cmd", "--stdio"])
+        );
+    }
+
+    #[test]
+    fn windows_wrap_covers_command_arrays_and_configured_programs() {
+        let programs = ["npx".to_string(), "uvx".to_string()];
+        let mut servers = serde_json::json!({
+            "local": { "type": "local", "command": ["C:\\tools\\server.bat", "--stdio"] },
+            "python": { "command": "uvx", "args": ["some-mcp"] },
+            "remote": { "type": "remote", "url": "https://example.com/mcp" },
+            "native": { "type": "local", "command": ["server.exe"] },
+        });
+        let Value::Object(map) = &mut servers else {
+            unreachable!()
+        };
+
+        wrap_windows_stdio_servers(map, &programs);
+        assert_eq!(
+            map["local"]["command"],
+            serde_json::json!(["cmd", "/c", "C:\\tools\\server.bat", "--stdio"])
+        );
+        assert_eq!(map["python"]["command"], "cmd");
+        assert_eq!(
+            map["python"]["args"],
+            serde_json::json!(["/c", "uvx", "some-mcp"])
+        );
+        assert!(map["remote"].get("command").is_none());
+        assert_eq!(map["native"]["command"], serde_json::json!(["server.exe"]));
+
+        // Wrapped servers start with `cmd`, so a second pass leaves them alone
+        let wrapped = map.clone();
+        wrap_windows_stdio_servers(map, &programs);
+        assert_eq!(*map, wrapped);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn windows_user_stdio_servers_are_wrapped_when_written() {
+        let mcp_config = McpConfig::new(
+            vec!["mcpServers".to_string()],
+            serde_json::json!({ "mcpServers": {} }),
+            Value::Null,
+            false,
+        );
+        let config = serde_json::json!({
+            "mcpServers": { "docs": { "command": "docs-mcp.cmd", "args": ["--stdio"] } }
+        });
+
+        let written: Value =
+            serde_json::from_str(&render_agent_config(&mcp_config, &config, "").unwrap()).unwrap();
+        assert_eq!(
+            written["mcpServers"]["docs"],
+            serde_json::json!({ "command": "cmd", "args": ["/c", "docs-mcp.cmd", "--stdio"] })
+        );
+    }
+
+    #[tokio::test]
+    async fn preview_shows_added_server_without_writing() {
+        let mcp_config = McpConfig::new(
+            vec!["mcpServers".to_string()],
+            serde_json::json!({ "mcpServers": {} }),
+            Value::Null,
+            false,
+        );
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let path = file.path();
+        let existing = serde_json::json!({
+            "mcpServers": { "docs": { "command": "docs-mcp" } }
+        });
+        write_agent_config(path, &mcp_config, &existing)
+            .await
+            .unwrap();
+        let before = fs::read_to_string(path).await.unwrap();
+
+        let servers = HashMap::from([
+            (
+                "docs".to_string(),
+                serde_json::json!({ "command": "docs-mcp" }),
+            ),
+            (
+                "search".to_string(),
+                serde_json::json!({ "command": "search-mcp" }),
+            ),
+        ]);
+        let preview = preview_mcp_servers_update(path, &mcp_config, &servers)
+            .await
+            .unwrap();
+
+        assert_eq!(preview.added, vec!["search".to_string()]);
+        assert!(preview.removed.is_empty() && preview.modified.is_empty());
+        assert!(
+            preview
+                .unified_diff
+                .lines()
+                .any(|line| line.starts_with('+') && line.contains("search-mcp")),
+            "{}",
+            preview.unified_diff
+        );
+        assert!(
+            !preview
+                .unified_diff
+                .lines()
+                .any(|line| line.starts_with('-') && line.contains("docs-mcp"))
+        );
+        assert_eq!(fs::read_to_string(path).await.unwrap(), before);
+    }
+
+    fn config_with_preconfigured(preconfigured: Value) -> McpConfig {
+        McpConfig::new(
+            vec!["mcpServers".to_string()],
+            serde_json::json!({ "mcpServers": {} }),
+            preconfigured,
+            false,
+        )
+    }
+
+    #[test]
+    fn merge_preconfigured_keeps_user_servers_on_overlap() {
+        let mut mcp_config = config_with_preconfigured(serde_json::json!({
+            "playwright": { "command": "npx", "args": ["@playwright/mcp@latest"] },
+            "context7": { "type": "http", "url": "https://mcp.context7.com/mcp" },
+            "meta": { "playwright": { "name": "Playwright" } },
+        }));
+        let user_playwright = serde_json::json!({ "command": "playwright-mcp", "args": [] });
+        mcp_config.set_servers(HashMap::from([(
+            "playwright".to_string(),
+            user_playwright.clone(),
+        )]));
+
+        let merge = mcp_config.merge_preconfigured_respecting_user();
+        assert_eq!(
+            merge,
+            PreconfiguredMerge {
+                added: vec!["context7".to_string()],
+                skipped: vec!["playwright".to_string()],
+            }
+        );
+        assert_eq!(mcp_config.servers["playwright"], user_playwright);
+        assert_eq!(
+            mcp_config.servers["context7"]["url"],
+            "https://mcp.context7.com/mcp"
+        );
+        assert!(!mcp_config.servers.contains_key("meta"));
+    }
+
+    #[test]
+    fn merge_preconfigured_adds_everything_without_overlap() {
+        let mut mcp_config = config_with_preconfigured(serde_json::json!({
+            "playwright": { "command": "npx", "args": ["@playwright/mcp@latest"] },
+            "exa": { "command": "npx", "args": ["-y", "exa-mcp-server"] },
+        }));
+        mcp_config.set_servers(HashMap::from([(
+            "docs".to_string(),
+            serde_json::json!({ "command": "docs-mcp" }),
+        )]));
+
+        let merge = mcp_config.merge_preconfigured_respecting_user();
+        assert_eq!(
+            merge.added,
+            vec!["exa".to_string(), "playwright".to_string()]
+        );
+        assert!(merge.skipped.is_empty());
+        assert_eq!(mcp_config.servers.len(), 3);
+        assert_eq!(mcp_config.servers["docs"]["command"], "docs-mcp");
+    }
+
+    #[tokio::test]
+    async fn lists_configured_server_names() {
+        let dir = tempfile::tempdir().unwrap();
+        let codex: CodingAgent =
+            serde_json::from_value(serde_json::json!({ "CODEX": {} })).unwrap();
+        let opencode: CodingAgent =
+            serde_json::from_value(serde_json::json!({ "OPENCODE": {} })).unwrap();
+
+        let populated = dir.path().join("config.toml");
+        fs::write(
+            &populated,
+            "model = \"o3\"\n\n[mcp_servers.search]\ncommand = \"search-mcp\"\n\n\
+             [mcp_servers.docs]\ncommand = \"docs-mcp\"\n",
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            list_configured_mcp_servers(&codex, &populated)
+                .await
+                .unwrap(),
+            vec!["docs".to_string(), "search".to_string()]
+        );
+
+        let empty = dir.path().join("opencode.json");
+        fs::write(&empty, "").await.unwrap();
+        assert!(
+            list_configured_mcp_servers(&opencode, &empty)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+
+        let missing = dir.path().join("missing.json");
+        assert!(
+            list_configured_mcp_servers(&opencode, &missing)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn validate_reports_server_changes_without_writing() {
+        let codex: CodingAgent =
+            serde_json::from_value(serde_json::json!({ "CODEX": {} })).unwrap();
+        let mcp_config = codex.get_mcp_config();
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let path = file.path();
+        let original = "# tuned by hand\nmodel = \"o3\"\n\n\
+                        [mcp_servers.docs]\ncommand = \"docs-mcp\"\n\n\
+                        [mcp_servers.old]\ncommand = \"old-mcp\"\n";
+        fs::write(path, original).await.unwrap();
+
+        let config = serde_json::json!({
+            "model": "o3",
+            "mcp_servers": {
+                "docs": { "command": "docs-mcp", "args": ["--verbose"] },
+                "search": { "command": "search-mcp" },
+            },
+        });
+        let diff = validate_agent_config(path, &mcp_config, &config)
+            .await
+            .unwrap();
+        assert_eq!(diff.added, vec!["search".to_string()]);
+        assert_eq!(diff.removed, vec!["old".to_string()]);
+        assert_eq!(diff.modified, vec!["docs".to_string()]);
+        assert!(!diff.other_settings_changed && !diff.drops_comments);
+        assert!(
+            diff.unified_diff
+                .lines()
+                .any(|line| line.starts_with('-') && line.contains("old-mcp")),
+            "{}",
+            diff.unified_diff
+        );
+        assert_eq!(fs::read_to_string(path).await.unwrap(), original);
+
+        let current = read_agent_config(path, &mcp_config).await.unwrap();
+        let mut renamed = current.clone();
+        renamed["model"] = Value::String("o4-mini".to_string());
+        let diff = validate_agent_config(path, &mcp_config, &renamed)
+            .await
+            .unwrap();
+        assert!(diff.other_settings_changed && diff.added.is_empty() && diff.modified.is_empty());
+        assert!(
+            validate_agent_config(path, &mcp_config, &current)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn gemini_servers_canonicalize_back_to_the_preconfigured_map() {
+        let gemini: CodingAgent =
+            serde_json::from_value(serde_json::json!({ "GEMINI": {} })).unwrap();
+        assert_eq!(
+            canonicalize_agent_config(&gemini, &gemini.preconfigured_mcp()),
+            *PRECONFIGURED_MCP_SERVERS
+        );
+    }
+
+    #[test]
+    fn opencode_servers_canonicalize_to_command_and_url() {
+        let opencode: CodingAgent =
+            serde_json::from_value(serde_json::json!({ "OPENCODE": {} })).unwrap();
+        let installed = serde_json::json!({
+            "runner": {
+                "type": "local",
+                "command": ["npx", "-y", "some-mcp"],
+                "environment": { "TOKEN": "x" },
+                "enabled": true,
+            },
+            "native": { "type": "local", "command": ["server"], "enabled": false },
+            "remote": {
+                "type": "remote",
+                "url": "https://mcp.example.com",
+                "headers": { "Accept": HTTP_ACCEPT, "X-Key": "k" },
+                "enabled": true,
+            },
+        });
+
+        assert_eq!(
+            canonicalize_agent_config(&opencode, &installed),
+            serde_json::json!({
+                "runner": { "command": "npx", "args": ["-y", "some-mcp"], "env": { "TOKEN": "x" } },
+                "native": { "command": "server", "enabled": false },
+                "remote": {
+                    "type": "http",
+                    "url": "https://mcp.example.com",
+                    "headers": { "X-Key": "k" },
+                },
+            })
+        );
     }
 }
@@ -444,6 +444,20 @@ impl ExecutorConfigs {
                         last_auth_timestamp: time_b,
                     },
                 ) => time_b.cmp(time_a),
+                // LoginDetected > ApiKeyConfigured
+                (AvailabilityInfo::LoginDetected { .. }, AvailabilityInfo::ApiKeyConfigured) => {
+                    std::cmp::Ordering::Less
+                }
+                (AvailabilityInfo::ApiKeyConfigured, AvailabilityInfo::LoginDetected { .. }) => {
+                    std::cmp::Ordering::Greater
+                }
+                // ApiKeyConfigured > InstallationFound
+                (AvailabilityInfo::ApiKeyConfigured, AvailabilityInfo::InstallationFound) => {
+                    std::cmp::Ordering::Less
+                }
+                (AvailabilityInfo::InstallationFound, AvailabilityInfo::ApiKeyConfigured) => {
+                    std::cmp::Ordering::Greater
+                }
                 // LoginDetected > InstallationFound
                 (AvailabilityInfo::LoginDetected { .. }, AvailabilityInfo::InstallationFound) => {
                     std::cmp::Ordering::Less
@@ -0,0 +1,138 @@
+//! Opt-in automatic restarts for coding agents that crash mid-run.
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+use workspace_utils::{log_msg::LogMsg, msg_store::MsgStore};
+
+use crate::logs::{
+    NormalizedEntry, NormalizedEntryError, NormalizedEntryType,
+    utils::patch::extract_normalized_entry_from_patch,
+};
+
+/// Bounded restart policy for coding agents that exit with a failure.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, TS, PartialEq, Eq)]
+pub struct RestartPolicy {
+    /// Maximum number of times a single run is restarted
+    pub max_restarts: u32,
+    /// Delay before the first restart, doubled for every following one
+    pub backoff_ms: u32,
+}
+
+/// How an agent run ended, as far as restarting it is concerned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AgentExit {
+    Success,
+    /// Exited with a failure that a fresh attempt may get past
+    Crashed,
+    /// Stopped by the user
+    Cancelled,
+    /// Needs login or other setup, which restarting cannot fix
+    AuthFailed,
+    /// Stopped for running past its maximum runtime; a restart would hit the same limit
+    MaxRuntimeExceeded,
+}
+
+impl RestartPolicy {
+    /// Delay before the next restart, or `None` if the run should be left as is.
+    /// Only crashes are restarted, and only `max_restarts` times.
+    pub fn restart_delay(&self, exit: AgentExit, restarts_so_far: u32) -> Option<Duration> {
+        if exit != AgentExit::Crashed || restarts_so_far >= self.max_restarts {
+            return None;
+        }
+        let factor = 1u64 << restarts_so_far.min(16);
+        Some(Duration::from_millis(
+            u64::from(self.backoff_ms).saturating_mul(factor),
+        ))
+    }
+}
+
+/// Whether an agent's normalized logs report that it needs setup (e.g. login) to continue.
+pub fn reported_auth_failure(msg_store: &MsgStore) -> bool {
+    msg_store.get_history().iter().any(|msg| {
+        let LogMsg::JsonPatch(patch) = msg else {
+            return false;
+        };
+        matches!(
+            extract_normalized_entry_from_patch(patch),
+            Some((
+                _,
+                NormalizedEntry {
+                    entry_type: NormalizedEntryType::ErrorMessage {
+                        error_type: NormalizedEntryError::SetupRequired,
+                    },
+                    ..
+                }
+            ))
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logs::utils::ConversationPatch;
+
+    const POLICY: RestartPolicy = RestartPolicy {
+        max_restarts: 2,
+        backoff_ms: 10,
+    };
+
+    #[test]
+    fn restarts_are_bounded_and_back_off() {
+        assert_eq!(
+            POLICY.restart_delay(AgentExit::Crashed, 0),
+            Some(Duration::from_millis(10))
+        );
+        assert_eq!(
+            POLICY.restart_delay(AgentExit::Crashed, 1),
+            Some(Duration::from_millis(20))
+        );
+        assert_eq!(POLICY.restart_delay(AgentExit::Crashed, 2), None);
+    }
+
+    #[test]
+    fn zero_max_restarts_never_restarts() {
+        let policy = RestartPolicy {
+            max_restarts: 0,
+            ..POLICY
+        };
+        assert_eq!(policy.restart_delay(AgentExit::Crashed, 0), None);
+    }
+
+    #[test]
+    fn backoff_is_capped_instead_of_overflowing() {
+        let policy = RestartPolicy {
+            max_restarts: u32::MAX,
+            backoff_ms: u32::MAX,
+        };
+        let capped = Duration::from_millis(u64::from(u32::MAX) << 16);
+        assert_eq!(policy.restart_delay(AgentExit::Crashed, 16), Some(capped));
+        assert_eq!(policy.restart_delay(AgentExit::Crashed, 40), Some(capped));
+    }
+
+    #[test]
+    fn never_restarts_auth_failures_or_cancellation() {
+        assert_eq!(POLICY.restart_delay(AgentExit::AuthFailed, 0), None);
+        assert_eq!(POLICY.restart_delay(AgentExit::Cancelled, 0), None);
+        assert_eq!(POLICY.restart_delay(AgentExit::Success, 0), None);
+        assert_eq!(POLICY.restart_delay(AgentExit::MaxRuntimeExceeded, 0), None);
+
+        let msg_store = MsgStore::new();
+        assert!(!reported_auth_failure(&msg_store));
+        msg_store.push_patch(ConversationPatch::add_normalized_entry(
+            0,
+            NormalizedEntry {
+                timestamp: None,
+                entry_type: NormalizedEntryType::ErrorMessage {
+                    error_type: NormalizedEntryError::SetupRequired,
+                },
+                content: "Please log in".to_string(),
+                metadata: None,
+                agent: None,
+            },
+        ));
+        assert!(reported_auth_failure(&msg_store));
+    }
+}
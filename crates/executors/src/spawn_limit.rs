@@ -0,0 +1,136 @@
+//! Opt-in cap on how many coding agents start up at the same time.
+//!
+//! Fan-out flows (e.g. reviewing many workspaces at once) can otherwise run a burst of npx
+//! downloads and agent servers in parallel. A spawn routed through [`SpawnLimiter`] holds a
+//! permit until the agent reports readiness, so later spawns queue behind it.
+
+use std::{
+    future::Future,
+    sync::{Arc, LazyLock, RwLock},
+};
+
+use tokio::sync::Semaphore;
+
+use crate::executors::{ExecutorError, ReadinessFuture, SpawnedChild};
+
+static SHARED: LazyLock<SpawnLimiter> = LazyLock::new(|| SpawnLimiter::new(None));
+
+#[derive(Debug)]
+pub struct SpawnLimiter {
+    semaphore: RwLock<Arc<Semaphore>>,
+}
+
+impl SpawnLimiter {
+    /// A limiter allowing `limit` concurrent startups, or any number when `None`.
+    pub fn new(limit: Option<usize>) -> Self {
+        Self {
+            semaphore: RwLock::new(Arc::new(Self::semaphore_for(limit))),
+        }
+    }
+
+    /// The process-wide limiter shared by callers that opt in. Unlimited until
+    /// [`SpawnLimiter::set_limit`] is called.
+    pub fn shared() -> &'static SpawnLimiter {
+        &SHARED
+    }
+
+    /// Change the limit. Startups already holding a permit are unaffected; only spawns that
+    /// have not acquired one yet see the new limit.
+    pub fn set_limit(&self, limit: Option<usize>) {
+        *self.semaphore.write().unwrap() = Arc::new(Self::semaphore_for(limit));
+    }
+
+    /// Run `spawn` once a startup slot is free. The slot is released when the returned
+    /// readiness future's underlying agent becomes ready or fails to start, whether or not
+    /// the caller awaits it.
+    pub async fn spawn<F>(&self, spawn: F) -> Result<(SpawnedChild, ReadinessFuture), ExecutorError>
+    where
+        F: Future<Output = Result<(SpawnedChild, ReadinessFuture), ExecutorError>>,
+    {
+        let semaphore = self.semaphore.read().unwrap().clone();
+        let permit = semaphore
+            .acquire_owned()
+            .await
+            .expect("spawn limiter semaphore is never closed");
+        let (child, readiness) = spawn.await?;
+
+        let (mut signal, gated) = ReadinessFuture::channel();
+        tokio::spawn(async move {
+            let result = readiness.await;
+            drop(permit);
+            match result {
+                Ok(()) => signal.ready(),
+                Err(err) => signal.fail(err),
+            }
+        });
+        Ok((child, gated))
+    }
+
+    fn semaphore_for(limit: Option<usize>) -> Semaphore {
+        Semaphore::new(limit.unwrap_or(Semaphore::MAX_PERMITS).max(1))
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::{
+        sync::atomic::{AtomicBool, Ordering},
+        time::Duration,
+    };
+
+    use command_group::AsyncCommandGroup;
+    use tokio::process::Command;
+
+    use super::*;
+
+    fn sleeping_child() -> SpawnedChild {
+        Command::new("sleep")
+            .arg("5")
+            .kill_on_drop(true)
+            .group_spawn()
+            .unwrap()
+            .into()
+    }
+
+    #[tokio::test]
+    async fn second_spawn_waits_for_first_readiness() {
+        let limiter = Arc::new(SpawnLimiter::new(Some(1)));
+        let (mut first_signal, first_readiness) = ReadinessFuture::channel();
+        let (mut first, first_ready) = limiter
+            .spawn(async move { Ok((sleeping_child(), first_readiness)) })
+            .await
+            .unwrap();
+
+        let second_started = Arc::new(AtomicBool::new(false));
+        let second = tokio::spawn({
+            let limiter = limiter.clone();
+            let second_started = second_started.clone();
+            async move {
+                limiter
+                    .spawn(async move {
+                        second_started.store(true, Ordering::SeqCst);
+                        let (mut signal, readiness) = ReadinessFuture::channel();
+                        signal.ready();
+                        Ok((sleeping_child(), readiness))
+                    })
+                    .await
+            }
+        });
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(!second_started.load(Ordering::SeqCst));
+
+        first_signal.ready();
+        first_ready.await.unwrap();
+        let (mut second, second_ready) = tokio::time::timeout(Duration::from_secs(5), second)
+            .await
+            .expect("second spawn never got a permit")
+            .unwrap()
+            .unwrap();
+        assert!(second_started.load(Ordering::SeqCst));
+        second_ready.await.unwrap();
+
+        first.child.kill().await.unwrap();
+        second.child.kill().await.unwrap();
+    }
+}
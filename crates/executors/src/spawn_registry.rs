@@ -0,0 +1,160 @@
+//! Opt-in registry of running agents, so a caller can stop all of them at once (e.g. a
+//! "stop all" button).
+//!
+//! The registry only holds weak references: a child is tracked until its owner drops it, and
+//! registering never keeps a finished agent alive. Nothing is registered unless the owner of
+//! the spawned child calls [`SpawnRegistry::register`].
+
+use std::{
+    sync::{Arc, LazyLock, Mutex, Weak},
+    time::Duration,
+};
+
+use command_group::AsyncGroupChild;
+use tokio::sync::{RwLock, oneshot};
+use tokio_util::sync::CancellationToken;
+
+use crate::executors::InterruptSender;
+
+static SHARED: LazyLock<SpawnRegistry> = LazyLock::new(SpawnRegistry::new);
+
+#[derive(Debug, Default)]
+pub struct SpawnRegistry {
+    spawns: Mutex<Vec<TrackedSpawn>>,
+}
+
+#[derive(Debug)]
+struct TrackedSpawn {
+    child: Weak<RwLock<AsyncGroupChild>>,
+    interrupt: CancellationToken,
+}
+
+impl SpawnRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The process-wide registry shared by callers that opt in.
+    pub fn shared() -> &'static SpawnRegistry {
+        &SHARED
+    }
+
+    /// Track `child` until its owner drops it. The executor's `interrupt` sender is taken over
+    /// so [`SpawnRegistry::cancel_all`] can fire it; the returned sender forwards to it and
+    /// replaces it for the owner.
+    pub fn register(
+        &self,
+        child: &Arc<RwLock<AsyncGroupChild>>,
+        interrupt: Option<InterruptSender>,
+    ) -> Option<InterruptSender> {
+        let token = CancellationToken::new();
+        let forward = interrupt.map(|interrupt| forward_interrupt(interrupt, token.clone()));
+        let mut spawns = self.spawns.lock().unwrap();
+        spawns.retain(|spawn| spawn.child.strong_count() > 0);
+        spawns.push(TrackedSpawn {
+            child: Arc::downgrade(child),
+            interrupt: token,
+        });
+        forward
+    }
+
+    /// Process ids of the tracked children that are still owned.
+    pub fn active(&self) -> Vec<u32> {
+        self.live_children()
+            .iter()
+            .filter_map(|(child, _)| child.try_read().ok()?.id())
+            .collect()
+    }
+
+    /// Interrupt every tracked agent, give them `grace` to exit, then kill whatever is left.
+    /// Returns how many children were stopped.
+    pub async fn cancel_all(&self, grace: Duration) -> usize {
+        let children = self.live_children();
+        for (_, interrupt) in &children {
+            interrupt.cancel();
+        }
+
+        let stops = children.iter().map(|(child, _)| async move {
+            let mut child = child.write().await;
+            if tokio::time::timeout(grace, child.wait()).await.is_err()
+                && let Err(err) = child.kill().await
+            {
+                tracing::warn!("Failed to kill agent process group: {err}");
+            }
+        });
+        futures::future::join_all(stops).await;
+        children.len()
+    }
+
+    fn live_children(&self) -> Vec<(Arc<RwLock<AsyncGroupChild>>, CancellationToken)> {
+        let mut spawns = self.spawns.lock().unwrap();
+        spawns.retain(|spawn| spawn.child.strong_count() > 0);
+        spawns
+            .iter()
+            .filter_map(|spawn| Some((spawn.child.upgrade()?, spawn.interrupt.clone())))
+            .collect()
+    }
+}
+
+/// A sender that passes an interrupt from the owner on to `interrupt`, which also fires when
+/// `token` is cancelled. Finishes without interrupting once the executor stops listening.
+fn forward_interrupt(mut interrupt: InterruptSender, token: CancellationToken) -> InterruptSender {
+    let (forward, owner_interrupt) = oneshot::channel();
+    tokio::spawn(async move {
+        let fire = tokio::select! {
+            sent = owner_interrupt => match sent {
+                Ok(()) => true,
+                // The owner gave up its sender; only the registry can interrupt now
+                Err(_) => tokio::select! {
+                    _ = token.cancelled() => true,
+                    _ = interrupt.closed() => false,
+                },
+            },
+            _ = token.cancelled() => true,
+            _ = interrupt.closed() => false,
+        };
+        if fire {
+            let _ = interrupt.send(());
+        }
+    });
+    forward
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use command_group::AsyncCommandGroup;
+    use tokio::process::Command;
+
+    use super::*;
+
+    fn sleeping_child() -> Arc<RwLock<AsyncGroupChild>> {
+        let child = Command::new("sleep")
+            .arg("30")
+            .kill_on_drop(true)
+            .group_spawn()
+            .unwrap();
+        Arc::new(RwLock::new(child))
+    }
+
+    #[tokio::test]
+    async fn cancel_all_interrupts_then_kills_every_child() {
+        let registry = SpawnRegistry::new();
+        let first = sleeping_child();
+        let second = sleeping_child();
+
+        let (interrupt, mut interrupted) = oneshot::channel();
+        let _forward = registry.register(&first, Some(interrupt));
+        registry.register(&second, None);
+        assert_eq!(registry.active().len(), 2);
+
+        let stopped = registry.cancel_all(Duration::from_millis(100)).await;
+        assert_eq!(stopped, 2);
+        assert!(interrupted.try_recv().is_ok());
+        for child in [&first, &second] {
+            assert!(child.write().await.try_wait().unwrap().is_some());
+        }
+
+        drop((first, second));
+        assert!(registry.active().is_empty());
+    }
+}
@@ -0,0 +1,165 @@
+//! Latency breakdown of an agent spawn, from the moment the spawn starts to the agent's
+//! first output.
+
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use futures::StreamExt;
+use workspace_utils::{log_msg::LogMsg, msg_store::MsgStore};
+
+use crate::logs::{NormalizedEntryType, utils::patch::extract_normalized_entry_from_patch};
+
+/// A milestone of the spawn path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpawnPhase {
+    /// The program was found and its arguments resolved.
+    CommandResolved,
+    /// The agent process is running.
+    ProcessSpawned,
+    /// The agent's server or app-server is ready for work. Agents without one are ready as
+    /// soon as their process is spawned.
+    Ready,
+    /// The agent produced its first conversation entry: a message, thought or tool call.
+    FirstOutput,
+}
+
+/// When each [`SpawnPhase`] was reached, measured from the start of the spawn. Phases the
+/// spawn never got to are `None`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SpawnTimings {
+    pub command_resolution: Option<Duration>,
+    pub process_spawn: Option<Duration>,
+    pub ready: Option<Duration>,
+    pub first_output: Option<Duration>,
+}
+
+impl SpawnTimings {
+    fn slot(&mut self, phase: SpawnPhase) -> &mut Option<Duration> {
+        match phase {
+            SpawnPhase::CommandResolved => &mut self.command_resolution,
+            SpawnPhase::ProcessSpawned => &mut self.process_spawn,
+            SpawnPhase::Ready => &mut self.ready,
+            SpawnPhase::FirstOutput => &mut self.first_output,
+        }
+    }
+
+    /// Emit the timings as one structured log entry, in milliseconds.
+    pub fn log(&self) {
+        let millis = |d: Option<Duration>| d.map(|d| d.as_millis() as u64);
+        tracing::info!(
+            command_resolution_ms = millis(self.command_resolution),
+            process_spawn_ms = millis(self.process_spawn),
+            ready_ms = millis(self.ready),
+            first_output_ms = millis(self.first_output),
+            "Agent spawn timings"
+        );
+    }
+}
+
+/// Records the [`SpawnTimings`] of one spawn. Clones share the same record, so the timer
+/// can be handed to the executor and to whatever watches the agent's output.
+#[derive(Debug, Clone)]
+pub struct SpawnTimer {
+    started: Instant,
+    timings: Arc<Mutex<SpawnTimings>>,
+}
+
+impl Default for SpawnTimer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SpawnTimer {
+    /// Start timing a spawn now.
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            timings: Arc::new(Mutex::new(SpawnTimings::default())),
+        }
+    }
+
+    /// Record that `phase` was reached. Only the first mark of a phase counts.
+    pub fn mark(&self, phase: SpawnPhase) {
+        let elapsed = self.started.elapsed();
+        let mut timings = self.timings.lock().unwrap();
+        timings.slot(phase).get_or_insert(elapsed);
+    }
+
+    pub fn timings(&self) -> SpawnTimings {
+        *self.timings.lock().unwrap()
+    }
+
+    /// Mark [`SpawnPhase::FirstOutput`] when the normalizer adds the agent's first message,
+    /// thought or tool call to `msg_store`, then log the timings. Raw stdout doesn't count:
+    /// agents print startup banners and protocol chatter well before they start working.
+    pub fn watch_first_output(&self, msg_store: &MsgStore) -> tokio::task::JoinHandle<()> {
+        let timer = self.clone();
+        let mut stream = msg_store.history_plus_stream();
+        tokio::spawn(async move {
+            while let Some(Ok(msg)) = stream.next().await {
+                match msg {
+                    LogMsg::JsonPatch(patch) if is_agent_output(&patch) => {
+                        timer.mark(SpawnPhase::FirstOutput);
+                        break;
+                    }
+                    LogMsg::Finished => break,
+                    _ => {}
+                }
+            }
+            timer.timings().log();
+        })
+    }
+}
+
+fn is_agent_output(patch: &json_patch::Patch) -> bool {
+    extract_normalized_entry_from_patch(patch).is_some_and(|(_, entry)| {
+        matches!(
+            entry.entry_type,
+            NormalizedEntryType::AssistantMessage
+                | NormalizedEntryType::Thinking
+                | NormalizedEntryType::ToolUse { .. }
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logs::{NormalizedEntry, utils::patch::ConversationPatch};
+
+    fn entry(entry_type: NormalizedEntryType) -> NormalizedEntry {
+        NormalizedEntry {
+            timestamp: None,
+            entry_type,
+            content: "hello".to_string(),
+            metadata: None,
+            agent: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn first_output_waits_for_an_assistant_entry() {
+        let timer = SpawnTimer::new();
+        let msg_store = MsgStore::new();
+        msg_store.push_stdout("Starting agent...\n");
+        msg_store.push_patch(ConversationPatch::add_normalized_entry(
+            0,
+            entry(NormalizedEntryType::UserMessage),
+        ));
+        msg_store.push_finished();
+        timer.watch_first_output(&msg_store).await.unwrap();
+        assert_eq!(timer.timings().first_output, None);
+
+        let msg_store = MsgStore::new();
+        msg_store.push_stdout("Starting agent...\n");
+        msg_store.push_patch(ConversationPatch::add_normalized_entry(
+            0,
+            entry(NormalizedEntryType::AssistantMessage),
+        ));
+        timer.watch_first_output(&msg_store).await.unwrap();
+        assert!(timer.timings().first_output.is_some());
+    }
+}
@@ -7,12 +7,22 @@
 use std::os::unix::io::{FromRawFd, IntoRawFd, OwnedFd};
 #[cfg(windows)]
 use std::os::windows::io::{FromRawHandle, IntoRawHandle, OwnedHandle};
+use std::{
+    io,
+    pin::Pin,
+    task::{Context, Poll, ready},
+    time::Duration,
+};
 
 use command_group::AsyncGroupChild;
 use futures::{StreamExt, stream::BoxStream};
-use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::{
+    io::{AsyncWrite, AsyncWriteExt},
+    sync::{mpsc, oneshot},
+    task::JoinHandle,
+};
 use tokio_stream::wrappers::UnboundedReceiverStream;
-use tokio_util::io::ReaderStream;
+use tokio_util::{io::ReaderStream, sync::PollSender};
 
 use crate::executors::ExecutorError;
 
@@ -183,6 +193,191 @@ pub fn create_stdout_pipe_writer<'b>(
     wrap_fd_as_tokio_writer(pipe_writer)
 }
 
+/// How writes to a child's replacement stdout pipe are forwarded.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StdoutPipeMode {
+    /// Every write goes straight to the pipe.
+    #[default]
+    Direct,
+    /// Writes are coalesced and forwarded once `max_batch_bytes` have accumulated, no write
+    /// has arrived for `idle_flush`, or the writer is flushed. Useful when the caller emits
+    /// many small writes (e.g. one per log line) and per-write syscalls become the bottleneck.
+    Batched {
+        max_batch_bytes: usize,
+        idle_flush: Duration,
+    },
+}
+
+impl StdoutPipeMode {
+    /// Batched mode with defaults suited to line-oriented agent logs.
+    pub fn batched() -> Self {
+        Self::Batched {
+            max_batch_bytes: 64 * 1024,
+            idle_flush: Duration::from_millis(10),
+        }
+    }
+}
+
+/// Like [`create_stdout_pipe_writer`], forwarding writes according to `mode`.
+pub fn create_stdout_pipe_writer_with_mode(
+    child: &mut AsyncGroupChild,
+    mode: StdoutPipeMode,
+) -> Result<Box<dyn AsyncWrite + Send + Unpin>, ExecutorError> {
+    let writer = create_stdout_pipe_writer(child)?;
+    Ok(match mode {
+        StdoutPipeMode::Direct => Box::new(writer),
+        StdoutPipeMode::Batched {
+            max_batch_bytes,
+            idle_flush,
+        } => Box::new(BatchedWriter::spawn(writer, max_batch_bytes, idle_flush)),
+    })
+}
+
+/// How many writes a [`BatchedWriter`] holds before writers wait for its task to catch up.
+const BATCH_CHANNEL_CAPACITY: usize = 256;
+
+enum BatchCommand {
+    Write(Vec<u8>),
+    /// Forward everything sent before it, then report how that went.
+    Flush(oneshot::Sender<io::Result<()>>),
+}
+
+/// Async writer that hands bytes to a background task, which coalesces them into larger writes
+/// on the inner writer.
+///
+/// Bytes are forwarded once a batch is full, once no write has arrived for a while, and on
+/// `flush`, which waits until the inner writer has them. `shutdown` forwards whatever is left,
+/// shuts down the inner writer and waits for both; dropping the writer does the same in the
+/// background.
+pub struct BatchedWriter {
+    tx: PollSender<BatchCommand>,
+    /// Acknowledgement of the flush in progress
+    flush_ack: Option<oneshot::Receiver<io::Result<()>>>,
+    /// The forwarding task, until shutdown has waited for it
+    task: Option<JoinHandle<()>>,
+}
+
+impl BatchedWriter {
+    pub fn spawn<W>(mut inner: W, max_batch_bytes: usize, idle_flush: Duration) -> Self
+    where
+        W: AsyncWrite + Send + Unpin + 'static,
+    {
+        let (tx, mut rx) = mpsc::channel::<BatchCommand>(BATCH_CHANNEL_CAPACITY);
+        let task = tokio::spawn(async move {
+            let mut batch = Vec::new();
+            loop {
+                let command = if batch.is_empty() {
+                    rx.recv().await
+                } else {
+                    match tokio::time::timeout(idle_flush, rx.recv()).await {
+                        Ok(command) => command,
+                        Err(_) => {
+                            if let Err(err) = write_batch(&mut inner, &mut batch).await {
+                                tracing::error!("Failed to forward batched stdout: {}", err);
+                                return;
+                            }
+                            continue;
+                        }
+                    }
+                };
+                match command {
+                    Some(BatchCommand::Write(chunk)) => {
+                        batch.extend_from_slice(&chunk);
+                        if batch.len() >= max_batch_bytes
+                            && let Err(err) = write_batch(&mut inner, &mut batch).await
+                        {
+                            tracing::error!("Failed to forward batched stdout: {}", err);
+                            return;
+                        }
+                    }
+                    Some(BatchCommand::Flush(ack)) => {
+                        let _ = ack.send(write_batch(&mut inner, &mut batch).await);
+                    }
+                    None => break,
+                }
+            }
+            if let Err(err) = write_batch(&mut inner, &mut batch).await {
+                tracing::error!("Failed to forward batched stdout: {}", err);
+            }
+            let _ = inner.shutdown().await;
+        });
+        Self {
+            tx: PollSender::new(tx),
+            flush_ack: None,
+            task: Some(task),
+        }
+    }
+
+    /// Wait for room in the channel and send `command`.
+    fn poll_send(
+        &mut self,
+        cx: &mut Context<'_>,
+        command: impl FnOnce() -> BatchCommand,
+    ) -> Poll<io::Result<()>> {
+        ready!(self.tx.poll_reserve(cx)).map_err(|_| writer_closed())?;
+        self.tx.send_item(command()).map_err(|_| writer_closed())?;
+        Poll::Ready(Ok(()))
+    }
+}
+
+fn writer_closed() -> io::Error {
+    io::Error::new(io::ErrorKind::BrokenPipe, "batched stdout writer is closed")
+}
+
+async fn write_batch<W: AsyncWrite + Unpin>(inner: &mut W, batch: &mut Vec<u8>) -> io::Result<()> {
+    if batch.is_empty() {
+        return Ok(());
+    }
+    inner.write_all(batch).await?;
+    inner.flush().await?;
+    batch.clear();
+    Ok(())
+}
+
+impl AsyncWrite for BatchedWriter {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        ready!(
+            self.get_mut()
+                .poll_send(cx, || BatchCommand::Write(buf.to_vec()))
+        )?;
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if this.flush_ack.is_none() {
+            let (ack_tx, ack_rx) = oneshot::channel();
+            ready!(this.poll_send(cx, || BatchCommand::Flush(ack_tx)))?;
+            this.flush_ack = Some(ack_rx);
+        }
+        let ack = this.flush_ack.as_mut().expect("a flush was just requested");
+        let result = ready!(Pin::new(ack).poll(cx));
+        this.flush_ack = None;
+        Poll::Ready(result.unwrap_or_else(|_| Err(writer_closed())))
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        if self.task.is_none() {
+            return Poll::Ready(Ok(()));
+        }
+        if !self.tx.is_closed() {
+            let flushed = ready!(self.as_mut().poll_flush(cx));
+            self.tx.close();
+            flushed?;
+        }
+        if let Some(task) = &mut self.task {
+            // The task drains the batch and shuts the inner writer down once the channel closes
+            let _ = ready!(Pin::new(task).poll(cx));
+        }
+        self.task = None;
+        Poll::Ready(Ok(()))
+    }
+}
+
 // =========================================
 // OS file descriptor helper functions
 // =========================================
@@ -232,3 +427,76 @@ fn wrap_fd_as_tokio_writer(
         Ok(tokio::fs::File::from_std(std_file))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    /// Records each write it receives so tests can see how writes were batched.
+    #[derive(Clone, Default)]
+    struct RecordingWriter {
+        writes: Arc<Mutex<Vec<Vec<u8>>>>,
+    }
+
+    impl AsyncWrite for RecordingWriter {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            self.writes.lock().unwrap().push(buf.to_vec());
+            Poll::Ready(Ok(buf.len()))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    #[tokio::test]
+    async fn batched_writer_delivers_every_byte_in_fewer_writes() {
+        let inner = RecordingWriter::default();
+        // Batches only fill up or get flushed; the idle timeout never fires
+        let mut writer = BatchedWriter::spawn(inner.clone(), 1024, Duration::from_secs(3600));
+
+        let mut expected = Vec::new();
+        for i in 0..1000 {
+            let line = format!("{{\"line\":{i}}}\n");
+            writer.write_all(line.as_bytes()).await.unwrap();
+            expected.extend_from_slice(line.as_bytes());
+        }
+        writer.shutdown().await.unwrap();
+
+        let writes = inner.writes.lock().unwrap().clone();
+        assert_eq!(writes.concat(), expected);
+        assert!(
+            writes.len() <= expected.len() / 1024 + 1,
+            "expected batched writes, got {}",
+            writes.len()
+        );
+    }
+
+    #[tokio::test]
+    async fn flush_waits_for_the_inner_writer() {
+        let inner = RecordingWriter::default();
+        let mut writer = BatchedWriter::spawn(inner.clone(), 1024, Duration::from_secs(3600));
+
+        writer.write_all(b"first\n").await.unwrap();
+        writer.flush().await.unwrap();
+        assert_eq!(*inner.writes.lock().unwrap(), vec![b"first\n".to_vec()]);
+
+        writer.write_all(b"second\n").await.unwrap();
+        writer.shutdown().await.unwrap();
+        assert_eq!(
+            inner.writes.lock().unwrap().concat(),
+            b"first\nsecond\n".to_vec()
+        );
+        assert!(writer.write_all(b"late\n").await.is_err());
+    }
+}
@@ -35,8 +35,10 @@ use executors::{
     approvals::{ExecutorApprovalService, NoopExecutorApprovalService},
     env::{ExecutionEnv, RepoContext},
     executors::{BaseCodingAgent, ExecutorExitResult, ExecutorExitSignal, InterruptSender},
-    logs::{NormalizedEntryType, utils::patch::extract_normalized_entry_from_patch},
+    logs::summary::{FinalMessageTransform, SessionSummary},
     profile::ExecutorProfileId,
+    restart::{AgentExit, RestartPolicy, reported_auth_failure},
+    spawn_timing::{SpawnTimer, SpawnTimings},
 };
 use futures::{FutureExt, TryStreamExt, stream::select};
 use serde_json::json;
@@ -78,6 +80,12 @@ pub struct LocalContainerService {
     queued_message_service: QueuedMessageService,
     publisher: Result<SharePublisher, RemoteClientNotConfigured>,
     notification_service: NotificationService,
+    /// Consecutive automatic restarts per session, for the agent restart policy
+    restarts: RestartTracker,
+    /// Spawn latency of running executions, reported in their session summary
+    spawn_timers: Arc<RwLock<HashMap<Uuid, SpawnTimer>>>,
+    /// Applied to the agent's final message before it becomes the turn summary
+    final_message_transform: Arc<RwLock<Option<FinalMessageTransform>>>,
 }
 
 impl LocalContainerService {
@@ -110,6 +118,9 @@ impl LocalContainerService {
             queued_message_service,
             publisher,
             notification_service,
+            restarts: RestartTracker::default(),
+            spawn_timers: Arc::new(RwLock::new(HashMap::new())),
+            final_message_transform: Arc::new(RwLock::new(None)),
         };
 
         container.spawn_workspace_cleanup();
@@ -117,6 +128,12 @@ impl LocalContainerService {
         container
     }
 
+    /// Rewrite the final assistant message of every later session summary with `transform`,
+    /// or stop rewriting it when `None`.
+    pub async fn set_final_message_transform(&self, transform: Option<FinalMessageTransform>) {
+        *self.final_message_transform.write().await = transform;
+    }
+
     pub async fn get_child_from_store(&self, id: &Uuid) -> Option<Arc<RwLock<AsyncGroupChild>>> {
         let map = self.child_store.read().await;
         map.get(id).cloned()
@@ -368,6 +385,20 @@ impl LocalContainerService {
         any_committed
     }
 
+    /// Ask an agent that ran past its maximum runtime to stop, giving it a grace period before
+    /// the exit monitor kills its process group. Output captured so far stays in the store.
+    async fn interrupt_after_max_runtime(&self, exec_id: Uuid) {
+        if let Some(msg_store) = self.msg_stores.read().await.get(&exec_id) {
+            msg_store.push_stderr("Agent exceeded its maximum runtime and was stopped\n");
+        }
+
+        let Some(interrupt_sender) = self.take_interrupt_sender(&exec_id).await else {
+            return;
+        };
+        let child = self.get_child_from_store(&exec_id).await;
+        interrupt_agent(interrupt_sender, child, MAX_RUNTIME_GRACE_PERIOD).await;
+    }
+
     /// Spawn a background task that polls the child process for completion and
     /// cleans up the execution entry when it exits.
     pub fn spawn_exit_monitor(
@@ -392,6 +423,10 @@ impl LocalContainerService {
                 .unwrap_or_else(|| std::future::pending().boxed()); // no signal, stall forever
 
             let status_result: std::io::Result<std::process::ExitStatus>;
+            // The agent process's own exit code, which the exit signal path otherwise replaces
+            let mut agent_exit_code: Option<i32> = None;
+            // Kept apart from the exit code, which the agent may have chosen itself
+            let mut max_runtime_exceeded = false;
 
             // Wait for process to exit, or exit signal from executor
             tokio::select! {
@@ -399,24 +434,30 @@ impl LocalContainerService {
                 // Some coding agent processes do not automatically exit after processing the user request; instead the executor
                 // signals when processing has finished to gracefully kill the process.
                 exit_result = &mut exit_signal_future => {
+                    if matches!(exit_result, Ok(ExecutorExitResult::MaxRuntimeExceeded)) {
+                        max_runtime_exceeded = true;
+                        container.interrupt_after_max_runtime(exec_id).await;
+                    }
+
                     // Executor signaled completion: kill group and use the provided result
                     if let Some(child_lock) = child_store.read().await.get(&exec_id).cloned() {
-                        let mut child = child_lock.write().await ;
-                        if let Err(err) = command::kill_process_group(&mut child).await {
-                            tracing::error!("Failed to kill process group after exit signal: {} {}", exec_id, err);
-                        }
+                        let mut child = child_lock.write().await;
+                        agent_exit_code = stop_signalled_agent(exec_id, &mut child).await;
                     }
 
                     // Map the exit result to appropriate exit status
                     status_result = match exit_result {
                         Ok(ExecutorExitResult::Success) => Ok(success_exit_status()),
-                        Ok(ExecutorExitResult::Failure) => Ok(failure_exit_status()),
+                        Ok(ExecutorExitResult::Failure | ExecutorExitResult::MaxRuntimeExceeded) => {
+                            Ok(failure_exit_status())
+                        }
                         Err(_) => Ok(success_exit_status()), // Channel closed, assume success
                     };
                 }
                 // Process exit
                 exit_status_result = &mut process_exit_rx => {
                     status_result = exit_status_result.unwrap_or_else(|e| Err(std::io::Error::other(e)));
+                    agent_exit_code = status_result.as_ref().ok().and_then(|status| status.code());
                 }
             }
 
@@ -442,10 +483,22 @@ impl LocalContainerService {
 
             if let Ok(ctx) = ExecutionProcess::load_context(&db.pool, exec_id).await {
                 // Update executor session summary if available
-                if let Err(e) = container.update_executor_session_summary(&exec_id).await {
+                let succeeded = matches!(
+                    ctx.execution_process.status,
+                    ExecutionProcessStatus::Completed
+                );
+                if let Err(e) = container
+                    .update_executor_session_summary(&exec_id, succeeded, agent_exit_code)
+                    .await
+                {
                     tracing::warn!("Failed to update executor session summary: {}", e);
                 }
 
+                // A crashed agent that gets restarted is not finished yet
+                let restarted = container
+                    .restart_crashed_agent(&ctx, max_runtime_exceeded)
+                    .await;
+
                 let success = matches!(
                     ctx.execution_process.status,
                     ExecutionProcessStatus::Completed
@@ -500,7 +553,7 @@ impl LocalContainerService {
                     }
                 }
 
-                if container.should_finalize(&ctx) {
+                if !restarted && container.should_finalize(&ctx) {
                     // Only execute queued messages if the execution succeeded
                     // If it failed or was killed, just clear the queue and finalize
                     let should_execute_queued = !matches!(
@@ -593,6 +646,7 @@ impl LocalContainerService {
 
             // Cleanup child handle
             child_store.write().await.remove(&exec_id);
+            container.spawn_timers.write().await.remove(&exec_id);
         })
     }
 
@@ -673,46 +727,69 @@ impl LocalContainerService {
             .map_err(|e| ContainerError::Other(anyhow!("{e}")))
     }
 
-    /// Extract the last assistant message from the MsgStore history
-    fn extract_last_assistant_message(&self, exec_id: &Uuid) -> Option<String> {
-        // Get the MsgStore for this execution
+    /// Summarize what the agent left in the MsgStore for this execution
+    fn session_summary(&self, exec_id: &Uuid, exit_code: Option<i32>) -> Option<SessionSummary> {
         let msg_stores = self.msg_stores.try_read().ok()?;
         let msg_store = msg_stores.get(exec_id)?;
-
-        // Get the history and scan in reverse for the last assistant message
-        let history = msg_store.get_history();
-
-        for msg in history.iter().rev() {
-            if let LogMsg::JsonPatch(patch) = msg {
-                // Try to extract a NormalizedEntry from the patch
-                if let Some((_, entry)) = extract_normalized_entry_from_patch(patch)
-                    && matches!(entry.entry_type, NormalizedEntryType::AssistantMessage)
-                {
-                    let content = entry.content.trim();
-                    if !content.is_empty() {
-                        const MAX_SUMMARY_LENGTH: usize = 4096;
-                        if content.len() > MAX_SUMMARY_LENGTH {
-                            let truncated = truncate_to_char_boundary(content, MAX_SUMMARY_LENGTH);
-                            return Some(format!("{truncated}..."));
-                        }
-                        return Some(content.to_string());
-                    }
-                }
-            }
-        }
-
-        None
+        let spawn_timings = self
+            .spawn_timers
+            .try_read()
+            .ok()
+            .and_then(|timers| timers.get(exec_id).map(SpawnTimer::timings));
+        let transform = self.final_message_transform.try_read().ok()?;
+        Some(summarize_session(
+            msg_store,
+            exit_code,
+            spawn_timings,
+            transform.as_ref(),
+        ))
     }
 
-    /// Update the coding agent turn summary with the final assistant message
-    async fn update_executor_session_summary(&self, exec_id: &Uuid) -> Result<(), anyhow::Error> {
+    /// Update the coding agent turn summary with the final assistant message, flag
+    /// successful runs that produced no output at all and report non-zero exit codes
+    async fn update_executor_session_summary(
+        &self,
+        exec_id: &Uuid,
+        succeeded: bool,
+        exit_code: Option<i32>,
+    ) -> Result<(), anyhow::Error> {
         // Check if there's a coding agent turn for this execution process
         let turn = CodingAgentTurn::find_by_execution_process_id(&self.db.pool, *exec_id).await?;
 
         if let Some(turn) = turn {
+            let Some(session) = self.session_summary(exec_id, exit_code) else {
+                return Ok(());
+            };
+
+            if !succeeded && let Some(diagnostic) = session.exit_diagnostic() {
+                tracing::warn!("Coding agent for execution {}: {}", exec_id, diagnostic);
+                if let Some(msg_store) = self.msg_stores.read().await.get(exec_id) {
+                    msg_store.push_stderr(format!("{diagnostic}\n"));
+                }
+            }
+
+            if succeeded && session.no_assistant_output {
+                tracing::warn!(
+                    "Coding agent for execution {} exited successfully without any output",
+                    exec_id
+                );
+                if let Some(msg_store) = self.msg_stores.read().await.get(exec_id) {
+                    msg_store.push_stderr(
+                        "Agent exited without producing any output; check its configuration\n",
+                    );
+                }
+            }
+
             // Only update if summary is not already set
             if turn.summary.is_none() {
-                if let Some(summary) = self.extract_last_assistant_message(exec_id) {
+                if let Some(message) = session.last_assistant_message {
+                    const MAX_SUMMARY_LENGTH: usize = 4096;
+                    let summary = if message.len() > MAX_SUMMARY_LENGTH {
+                        let truncated = truncate_to_char_boundary(&message, MAX_SUMMARY_LENGTH);
+                        format!("{truncated}...")
+                    } else {
+                        message
+                    };
                     CodingAgentTurn::update_summary(&self.db.pool, *exec_id, &summary).await?;
                 } else {
                     tracing::debug!("No assistant message found for execution {}", exec_id);
@@ -820,6 +897,84 @@ impl LocalContainerService {
         Ok(())
     }
 
+    /// Restart a crashed coding agent if the user opted into a restart policy. The new run
+    /// resumes the agent session when one was recorded, and starts after the policy's backoff
+    /// on a task of its own. Returns whether a restart was scheduled.
+    async fn restart_crashed_agent(
+        &self,
+        ctx: &ExecutionContext,
+        max_runtime_exceeded: bool,
+    ) -> bool {
+        if !matches!(
+            ctx.execution_process.run_reason,
+            ExecutionProcessRunReason::CodingAgent
+        ) {
+            return false;
+        }
+        let Some(policy) = self.config.read().await.agent_restart_policy else {
+            return false;
+        };
+
+        let auth_failed = self
+            .msg_stores
+            .read()
+            .await
+            .get(&ctx.execution_process.id)
+            .is_some_and(|store| reported_auth_failure(store));
+        let Some(exit) = agent_exit(
+            &ctx.execution_process.status,
+            max_runtime_exceeded,
+            auth_failed,
+        ) else {
+            return false;
+        };
+
+        // The restart runs once the backoff is over, so it reloads the crashed execution
+        let container = self.clone();
+        let exec_id = ctx.execution_process.id;
+        let restart = async move {
+            let ctx = ExecutionProcess::load_context(&container.db.pool, exec_id).await?;
+            if let Err(e) = container.start_restart_execution(&ctx).await {
+                // The exit monitor left the task open for the restart
+                container
+                    .finalize_task(container.publisher.as_ref().ok(), &ctx)
+                    .await;
+                return Err(e);
+            }
+            Ok::<_, ContainerError>(())
+        };
+        self.restarts
+            .schedule(ctx.session.id, policy, exit, restart)
+            .await
+            .is_some()
+    }
+
+    /// Re-run a crashed coding agent action with the same prompt, resuming its session if
+    /// the agent reported one.
+    async fn start_restart_execution(
+        &self,
+        ctx: &ExecutionContext,
+    ) -> Result<ExecutionProcess, ContainerError> {
+        let crashed = ctx
+            .execution_process
+            .executor_action()
+            .map_err(ContainerError::Other)?;
+        let latest_agent_session_id = ExecutionProcess::find_latest_coding_agent_turn_session_id(
+            &self.db.pool,
+            ctx.session.id,
+        )
+        .await?;
+        let action = restart_action(crashed, latest_agent_session_id)?;
+
+        self.start_execution(
+            &ctx.workspace,
+            &ctx.session,
+            &action,
+            &ExecutionProcessRunReason::CodingAgent,
+        )
+        .await
+    }
+
     /// Start a follow-up execution from a queued message
     async fn start_queued_follow_up(
         &self,
@@ -899,6 +1054,168 @@ impl LocalContainerService {
     }
 }
 
+/// Send an agent its executor's interrupt, then wait up to `grace` for it to exit on its own.
+async fn interrupt_agent(
+    interrupt_sender: InterruptSender,
+    child: Option<Arc<RwLock<AsyncGroupChild>>>,
+    grace: Duration,
+) {
+    let _ = interrupt_sender.send(());
+    if let Some(child) = child {
+        let mut child = child.write().await;
+        let _ = tokio::time::timeout(grace, child.wait()).await;
+    }
+}
+
+/// Stop an agent whose executor signalled completion, returning the exit code the agent had
+/// already exited with on its own, if any.
+async fn stop_signalled_agent(exec_id: Uuid, child: &mut AsyncGroupChild) -> Option<i32> {
+    let exit_code = match child.try_wait() {
+        Ok(Some(exit_status)) => exit_status.code(),
+        _ => None,
+    };
+    if let Err(err) = command::kill_process_group(child).await {
+        tracing::error!(
+            "Failed to kill process group after exit signal: {} {}",
+            exec_id,
+            err
+        );
+    }
+    exit_code
+}
+
+/// The summary of a finished session, with the final message passed through `transform`.
+fn summarize_session(
+    msg_store: &MsgStore,
+    exit_code: Option<i32>,
+    spawn_timings: Option<SpawnTimings>,
+    transform: Option<&FinalMessageTransform>,
+) -> SessionSummary {
+    let summary = SessionSummary::from_msg_store(msg_store)
+        .with_exit_code(exit_code)
+        .with_spawn_timings(spawn_timings);
+    match transform {
+        Some(transform) => summary.with_final_message_transform(transform),
+        None => summary,
+    }
+}
+
+/// Consecutive automatic restarts per session, counted while restarts keep getting started.
+#[derive(Clone, Default)]
+struct RestartTracker {
+    counts: Arc<RwLock<HashMap<Uuid, u32>>>,
+}
+
+impl RestartTracker {
+    /// Run `restart` after the backoff `policy` gives for the session's next restart following
+    /// `exit`, on a task of its own so the rest of the exit handling isn't held up. The count is
+    /// cleared once the policy stops restarting or a restart fails to start. Returns the task
+    /// running the restart, if one was scheduled.
+    async fn schedule(
+        &self,
+        session_id: Uuid,
+        policy: RestartPolicy,
+        exit: AgentExit,
+        restart: impl Future<Output = Result<(), ContainerError>> + Send + 'static,
+    ) -> Option<JoinHandle<()>> {
+        let restarts = self.restarts(session_id).await;
+        let Some(delay) = policy.restart_delay(exit, restarts) else {
+            self.counts.write().await.remove(&session_id);
+            return None;
+        };
+
+        tracing::warn!(
+            "Coding agent crashed in session {}; restarting in {:?} (restart {} of {})",
+            session_id,
+            delay,
+            restarts + 1,
+            policy.max_restarts
+        );
+
+        let counts = self.counts.clone();
+        Some(tokio::spawn(async move {
+            tokio::time::sleep(delay).await;
+            // Counted up front, so the restarted run already sees it when it exits
+            counts.write().await.insert(session_id, restarts + 1);
+            if let Err(e) = restart.await {
+                tracing::error!("Failed to restart crashed coding agent: {}", e);
+                counts.write().await.remove(&session_id);
+            }
+        }))
+    }
+
+    async fn restarts(&self, session_id: Uuid) -> u32 {
+        self.counts
+            .read()
+            .await
+            .get(&session_id)
+            .copied()
+            .unwrap_or(0)
+    }
+}
+
+/// How a finished coding agent run ended, as far as restarting it is concerned, or `None`
+/// while it is still running.
+fn agent_exit(
+    status: &ExecutionProcessStatus,
+    max_runtime_exceeded: bool,
+    auth_failed: bool,
+) -> Option<AgentExit> {
+    Some(match status {
+        ExecutionProcessStatus::Running => return None,
+        ExecutionProcessStatus::Completed => AgentExit::Success,
+        ExecutionProcessStatus::Killed => AgentExit::Cancelled,
+        ExecutionProcessStatus::Failed if max_runtime_exceeded => AgentExit::MaxRuntimeExceeded,
+        ExecutionProcessStatus::Failed if auth_failed => AgentExit::AuthFailed,
+        ExecutionProcessStatus::Failed => AgentExit::Crashed,
+    })
+}
+
+/// The action that re-runs the crashed coding agent request `crashed` with the same prompt,
+/// as a follow-up of `agent_session_id` when the agent reported a session.
+fn restart_action(
+    crashed: &ExecutorAction,
+    agent_session_id: Option<String>,
+) -> Result<ExecutorAction, ContainerError> {
+    let (prompt, executor_profile_id, working_dir) = match crashed.typ() {
+        ExecutorActionType::CodingAgentInitialRequest(request) => (
+            &request.prompt,
+            &request.executor_profile_id,
+            &request.working_dir,
+        ),
+        ExecutorActionType::CodingAgentFollowUpRequest(request) => (
+            &request.prompt,
+            &request.executor_profile_id,
+            &request.working_dir,
+        ),
+        _ => {
+            return Err(ContainerError::Other(anyhow!(
+                "Only coding agent requests can be restarted"
+            )));
+        }
+    };
+
+    let action_type = match agent_session_id {
+        Some(session_id) => {
+            ExecutorActionType::CodingAgentFollowUpRequest(CodingAgentFollowUpRequest {
+                prompt: prompt.clone(),
+                session_id,
+                executor_profile_id: executor_profile_id.clone(),
+                working_dir: working_dir.clone(),
+            })
+        }
+        None => ExecutorActionType::CodingAgentInitialRequest(CodingAgentInitialRequest {
+            prompt: prompt.clone(),
+            executor_profile_id: executor_profile_id.clone(),
+            working_dir: working_dir.clone(),
+        }),
+    };
+    Ok(ExecutorAction::new(
+        action_type,
+        crashed.next_action.clone(),
+    ))
+}
+
 fn failure_exit_status() -> std::process::ExitStatus {
     #[cfg(unix)]
     {
@@ -1135,20 +1452,34 @@ impl ContainerService for LocalContainerService {
         env.insert("VK_WORKSPACE_ID", workspace.id.to_string());
         env.insert("VK_WORKSPACE_BRANCH", &workspace.branch);
 
-        // Create the child and stream, add to execution tracker with timeout
+        let spawn_timer = SpawnTimer::new();
+        let env = env.with_spawn_timer(spawn_timer.clone());
+
+        // Create the child and stream, add to execution tracker with timeout. The agent's own
+        // spawn timeout is honoured, with a margin for resolving and launching its command
+        let spawn_limit =
+            executor_action.spawn_timeout().unwrap_or_default() + SPAWN_TIMEOUT_MARGIN;
         let mut spawned = tokio::time::timeout(
-            Duration::from_secs(30),
+            spawn_limit,
             executor_action.spawn(&current_dir, approvals_service, &env),
         )
         .await
         .map_err(|_| {
             ContainerError::Other(anyhow!(
-                "Timeout: process took more than 30 seconds to start"
+                "Timeout: process took more than {} seconds to start",
+                spawn_limit.as_secs()
             ))
         })??;
 
         self.track_child_msgs_in_store(execution_process.id, &mut spawned.child)
             .await;
+        if let Some(msg_store) = self.msg_stores.read().await.get(&execution_process.id) {
+            spawn_timer.watch_first_output(msg_store);
+        }
+        self.spawn_timers
+            .write()
+            .await
+            .insert(execution_process.id, spawn_timer);
 
         self.add_child_to_store(execution_process.id, spawned.child)
             .await;
@@ -1414,6 +1745,15 @@ impl ContainerService for LocalContainerService {
         Ok(())
     }
 }
+
+/// How long an agent stopped for exceeding its maximum runtime gets to exit on its own before
+/// its process group is killed.
+const MAX_RUNTIME_GRACE_PERIOD: Duration = Duration::from_secs(5);
+
+/// Time allowed for starting an executor on top of its agent's spawn timeout, and the whole
+/// allowance for agents without one.
+const SPAWN_TIMEOUT_MARGIN: Duration = Duration::from_secs(30);
+
 fn success_exit_status() -> std::process::ExitStatus {
     #[cfg(unix)]
     {
@@ -1426,3 +1766,257 @@ fn success_exit_status() -> std::process::ExitStatus {
         ExitStatusExt::from_raw(0)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use executors::{
+        actions::script::{ScriptContext, ScriptRequest, ScriptRequestLanguage},
+        logs::{NormalizedEntry, NormalizedEntryType, utils::ConversationPatch},
+    };
+
+    use super::*;
+
+    const POLICY: RestartPolicy = RestartPolicy {
+        max_restarts: 2,
+        backoff_ms: 10,
+    };
+
+    fn initial_request(prompt: &str) -> ExecutorAction {
+        ExecutorAction::new(
+            ExecutorActionType::CodingAgentInitialRequest(CodingAgentInitialRequest {
+                prompt: prompt.to_string(),
+                executor_profile_id: ExecutorProfileId::new(BaseCodingAgent::ClaudeCode),
+                working_dir: Some("app".to_string()),
+            }),
+            None,
+        )
+    }
+
+    fn script_action(script: &str) -> ExecutorAction {
+        ExecutorAction::new(
+            ExecutorActionType::ScriptRequest(ScriptRequest {
+                script: script.to_string(),
+                language: ScriptRequestLanguage::Bash,
+                context: ScriptContext::SetupScript,
+                working_dir: None,
+            }),
+            None,
+        )
+    }
+
+    /// Run `agent` in `dir` and hand each exit to `tracker` as the exit monitor does, running
+    /// the agent again whenever a scheduled restart has started. Returns how many times it ran.
+    async fn run_with_restarts(
+        agent: &ExecutorAction,
+        dir: &Path,
+        tracker: &RestartTracker,
+    ) -> u32 {
+        let session_id = Uuid::new_v4();
+        let env = ExecutionEnv::new(RepoContext::default(), false);
+        let mut runs = 0;
+        loop {
+            let mut spawned = agent
+                .spawn(dir, Arc::new(NoopExecutorApprovalService), &env)
+                .await
+                .unwrap();
+            let status = spawned.child.wait().await.unwrap();
+            runs += 1;
+            let status = if status.success() {
+                ExecutionProcessStatus::Completed
+            } else {
+                ExecutionProcessStatus::Failed
+            };
+
+            let exit = agent_exit(&status, false, false).unwrap();
+            let Some(restart) = tracker
+                .schedule(session_id, POLICY, exit, async { Ok(()) })
+                .await
+            else {
+                assert_eq!(tracker.restarts(session_id).await, 0);
+                return runs;
+            };
+            tokio::time::timeout(Duration::from_secs(5), restart)
+                .await
+                .expect("scheduled restart never ran")
+                .unwrap();
+            assert_eq!(tracker.restarts(session_id).await, runs);
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn agent_that_crashes_once_is_restarted_until_it_succeeds() {
+        let dir = tempfile::tempdir().unwrap();
+        let crashes_once = script_action("[ -e crashed ] && exit 0; touch crashed; exit 1");
+
+        let runs = run_with_restarts(&crashes_once, dir.path(), &RestartTracker::default()).await;
+        assert_eq!(runs, 2);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn agent_that_keeps_crashing_is_restarted_max_restarts_times() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let runs = run_with_restarts(
+            &script_action("exit 1"),
+            dir.path(),
+            &RestartTracker::default(),
+        )
+        .await;
+        assert_eq!(runs, 1 + POLICY.max_restarts);
+    }
+
+    #[tokio::test]
+    async fn failed_restart_clears_the_count() {
+        let tracker = RestartTracker::default();
+        let session_id = Uuid::new_v4();
+        let restart = async { Err(ContainerError::Other(anyhow!("worktree is gone"))) };
+
+        tracker
+            .schedule(session_id, POLICY, AgentExit::Crashed, restart)
+            .await
+            .expect("a crash should be restarted")
+            .await
+            .unwrap();
+        assert_eq!(tracker.restarts(session_id).await, 0);
+    }
+
+    #[test]
+    fn only_crashes_are_restarted() {
+        let exit = |status, max_runtime_exceeded, auth_failed| {
+            agent_exit(&status, max_runtime_exceeded, auth_failed)
+        };
+        assert_eq!(exit(ExecutionProcessStatus::Running, false, false), None);
+        assert_eq!(
+            exit(ExecutionProcessStatus::Failed, false, false),
+            Some(AgentExit::Crashed)
+        );
+        assert_eq!(
+            exit(ExecutionProcessStatus::Failed, false, true),
+            Some(AgentExit::AuthFailed)
+        );
+        assert_eq!(
+            exit(ExecutionProcessStatus::Failed, true, false),
+            Some(AgentExit::MaxRuntimeExceeded)
+        );
+        assert_eq!(
+            exit(ExecutionProcessStatus::Killed, false, false),
+            Some(AgentExit::Cancelled)
+        );
+    }
+
+    #[test]
+    fn restart_resumes_the_agent_session() {
+        let action = restart_action(
+            &initial_request("fix the build"),
+            Some("agent-session".to_string()),
+        )
+        .unwrap();
+        let ExecutorActionType::CodingAgentFollowUpRequest(request) = action.typ() else {
+            panic!("restart did not resume the agent session");
+        };
+        assert_eq!(request.prompt, "fix the build");
+        assert_eq!(request.session_id, "agent-session");
+        assert_eq!(request.working_dir.as_deref(), Some("app"));
+    }
+
+    #[test]
+    fn restart_without_an_agent_session_starts_over() {
+        let action = restart_action(&initial_request("fix the build"), None).unwrap();
+        let ExecutorActionType::CodingAgentInitialRequest(request) = action.typ() else {
+            panic!("restart without a session should start a new one");
+        };
+        assert_eq!(request.prompt, "fix the build");
+
+        assert!(restart_action(&script_action("true"), None).is_err());
+    }
+
+    #[test]
+    fn final_message_transform_rewrites_the_turn_summary() {
+        let msg_store = MsgStore::new();
+        msg_store.push_patch(ConversationPatch::add_normalized_entry(
+            0,
+            NormalizedEntry {
+                timestamp: None,
+                entry_type: NormalizedEntryType::AssistantMessage,
+                content: "Done.\n\nLet me know if you need anything else!".to_string(),
+                metadata: None,
+                agent: None,
+            },
+        ));
+        let first_paragraph: FinalMessageTransform = Arc::new(|message: String| {
+            message.split("\n\n").next().unwrap_or_default().to_string()
+        });
+
+        let summary = summarize_session(&msg_store, Some(0), None, Some(&first_paragraph));
+        assert_eq!(summary.last_assistant_message.as_deref(), Some("Done."));
+        assert_eq!(summary.exit_code, Some(0));
+
+        let summary = summarize_session(&msg_store, Some(0), None, None);
+        assert_eq!(
+            summary.last_assistant_message.as_deref(),
+            Some("Done.\n\nLet me know if you need anything else!")
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn agent_exit_code_reaches_the_session_summary_after_an_exit_signal() {
+        use command_group::AsyncCommandGroup;
+
+        let mut child = tokio::process::Command::new("sh")
+            .args(["-c", "exit 3"])
+            .group_spawn()
+            .unwrap();
+        while child.try_wait().unwrap().is_none() {
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+
+        let exit_code = stop_signalled_agent(Uuid::new_v4(), &mut child).await;
+        assert_eq!(exit_code, Some(3));
+
+        let summary = summarize_session(&MsgStore::new(), exit_code, None, None);
+        assert_eq!(summary.exit_code, Some(3));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn agent_past_its_max_runtime_is_interrupted_then_killed() {
+        use command_group::AsyncCommandGroup;
+
+        // An agent that exits once its executor handles the interrupt (here by closing stdin)
+        let mut child = tokio::process::Command::new("sh")
+            .args(["-c", "read line; exit 0"])
+            .stdin(std::process::Stdio::piped())
+            .group_spawn()
+            .unwrap();
+        let stdin = child.inner().stdin.take().unwrap();
+        let (interrupt_tx, interrupt_rx) = tokio::sync::oneshot::channel();
+        tokio::spawn(async move {
+            let _ = interrupt_rx.await;
+            drop(stdin);
+        });
+        let child = Arc::new(RwLock::new(child));
+        interrupt_agent(interrupt_tx, Some(child.clone()), Duration::from_secs(5)).await;
+        let exit_code = stop_signalled_agent(Uuid::new_v4(), &mut *child.write().await).await;
+        assert_eq!(exit_code, Some(0));
+
+        // An agent that ignores the interrupt is killed once the grace period runs out
+        let child = tokio::process::Command::new("sleep")
+            .arg("30")
+            .group_spawn()
+            .unwrap();
+        let (interrupt_tx, _interrupt_rx) = tokio::sync::oneshot::channel();
+        let child = Arc::new(RwLock::new(child));
+        interrupt_agent(
+            interrupt_tx,
+            Some(child.clone()),
+            Duration::from_millis(100),
+        )
+        .await;
+        let mut child = child.write().await;
+        assert_eq!(stop_signalled_agent(Uuid::new_v4(), &mut child).await, None);
+        assert!(child.try_wait().unwrap().is_some());
+    }
+}
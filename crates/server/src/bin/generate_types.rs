@@ -10,7 +10,7 @@ fn generate_types_content() -> String {
 // Do not edit this file manually.\n
 // If you are an AI, and you absolutely have to edit this file, please confirm with the user first.";
 
-    let decls: Vec<String> = vec![
+    let mut decls: Vec<String> = vec![
         remote::routes::tasks::SharedTaskResponse::decl(),
         remote::routes::tasks::AssigneesQuery::decl(),
         remote::db::tasks::SharedTask::decl(),
@@ -170,6 +170,7 @@ fn generate_types_content() -> String {
         services::services::git::ConflictOp::decl(),
         executors::actions::ExecutorAction::decl(),
         executors::mcp_config::McpConfig::decl(),
+        executors::mcp_config::ConfigDiff::decl(),
         executors::actions::ExecutorActionType::decl(),
         executors::actions::script::ScriptContext::decl(),
         executors::actions::script::ScriptRequest::decl(),
@@ -177,28 +178,26 @@ fn generate_types_content() -> String {
         executors::executors::BaseCodingAgent::decl(),
         executors::executors::CodingAgent::decl(),
         executors::executors::AvailabilityInfo::decl(),
+        executors::executors::AvailabilityDetail::decl(),
+        executors::executors::AuthStatus::decl(),
         executors::command::CommandBuilder::decl(),
+        executors::command::ResourceLimits::decl(),
         executors::profile::ExecutorProfileId::decl(),
         executors::profile::ExecutorConfig::decl(),
         executors::profile::ExecutorConfigs::decl(),
         executors::executors::BaseAgentCapability::decl(),
-        executors::executors::claude::ClaudeCode::decl(),
-        executors::executors::gemini::Gemini::decl(),
-        executors::executors::codex::Codex::decl(),
-        executors::executors::codex::SandboxMode::decl(),
-        executors::executors::codex::AskForApproval::decl(),
-        executors::executors::codex::ReasoningEffort::decl(),
-        executors::executors::codex::ReasoningSummary::decl(),
-        executors::executors::codex::ReasoningSummaryFormat::decl(),
-        executors::executors::opencode::Opencode::decl(),
-        executors::executors::droid::Droid::decl(),
-        executors::executors::droid::Autonomy::decl(),
-        executors::executors::droid::ReasoningEffortLevel::decl(),
-        executors::executors::AppendPrompt::decl(),
+        executors::executors::SandboxLevel::decl(),
+        executors::executors::SandboxDescriptor::decl(),
+        executors::restart::RestartPolicy::decl(),
+    ];
+    decls.extend(executors::executors::CodingAgent::serialize_for_ts());
+    decls.extend([
         executors::actions::coding_agent_initial::CodingAgentInitialRequest::decl(),
         executors::actions::coding_agent_follow_up::CodingAgentFollowUpRequest::decl(),
         executors::actions::review::ReviewRequest::decl(),
+        executors::actions::external_agent::ExternalAgentRequest::decl(),
         executors::actions::review::RepoReviewContext::decl(),
+        executors::actions::review::ReviewScope::decl(),
         executors::logs::CommandExitStatus::decl(),
         executors::logs::CommandRunResult::decl(),
         executors::logs::NormalizedEntry::decl(),
@@ -213,7 +212,7 @@ fn generate_types_content() -> String {
         executors::logs::ToolStatus::decl(),
         executors::logs::utils::patch::PatchType::decl(),
         serde_json::Value::decl(),
-    ];
+    ]);
 
     let body = decls
         .into_iter()
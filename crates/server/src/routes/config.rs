@@ -11,9 +11,13 @@ use axum::{
 use deployment::{Deployment, DeploymentError};
 use executors::{
     executors::{
-        AvailabilityInfo, BaseAgentCapability, BaseCodingAgent, StandardCodingAgentExecutor,
+        AuthStatus, AvailabilityDetail, AvailabilityInfo, BaseAgentCapability, BaseCodingAgent,
+        SandboxDescriptor, StandardCodingAgentExecutor,
+    },
+    mcp_config::{
+        McpConfig, preview_mcp_servers_update, read_agent_config, set_mcp_servers_in_config,
+        write_agent_config,
     },
-    mcp_config::{McpConfig, read_agent_config, write_agent_config},
     profile::{ExecutorConfigs, ExecutorProfileId},
 };
 use serde::{Deserialize, Serialize};
@@ -41,6 +45,7 @@ pub fn router() -> Router<DeploymentImpl> {
             get(check_editor_availability),
         )
         .route("/agents/check-availability", get(check_agent_availability))
+        .route("/agents/check-auth", get(check_agent_auth))
 }
 
 #[derive(Debug, Serialize, Deserialize, TS)]
@@ -79,6 +84,8 @@ pub struct UserSystemInfo {
     pub environment: Environment,
     /// Capabilities supported per executor (e.g., { "CLAUDE_CODE": ["SESSION_FORK"] })
     pub capabilities: HashMap<String, Vec<BaseAgentCapability>>,
+    /// Effective default sandbox per executor, for executors that describe one
+    pub default_sandboxes: HashMap<String, SandboxDescriptor>,
 }
 
 // TODO: update frontend, BE schema has changed, this replaces GET /config and /config/constants
@@ -105,6 +112,19 @@ async fn get_user_system_info(
             }
             caps
         },
+        default_sandboxes: {
+            let mut sandboxes: HashMap<String, SandboxDescriptor> = HashMap::new();
+            let profs = ExecutorConfigs::get_cached();
+            for key in profs.executors.keys() {
+                if let Some(sandbox) = profs
+                    .get_coding_agent(&ExecutorProfileId::new(*key))
+                    .and_then(|agent| agent.default_sandbox())
+                {
+                    sandboxes.insert(key.to_string(), sandbox);
+                }
+            }
+            sandboxes
+        },
     };
 
     ResponseJson(ApiResponse::success(user_system_info))
@@ -213,6 +233,9 @@ pub struct GetMcpServerResponse {
 #[derive(TS, Debug, Serialize, Deserialize)]
 pub struct UpdateMcpServersBody {
     servers: HashMap<String, Value>,
+    /// Return a diff of the config file instead of writing it
+    #[ts(optional)]
+    dry_run: Option<bool>,
 }
 
 async fn get_mcp_servers(
@@ -280,6 +303,16 @@ async fn update_mcp_servers(
     };
 
     let mcpc = agent.get_mcp_config();
+    if payload.dry_run.unwrap_or(false) {
+        return match preview_mcp_servers_update(&config_path, &mcpc, &payload.servers).await {
+            Ok(preview) => Ok(ResponseJson(ApiResponse::success(preview.unified_diff))),
+            Err(e) => Ok(ResponseJson(ApiResponse::error(&format!(
+                "Failed to preview MCP servers update: {}",
+                e
+            )))),
+        };
+    }
+
     match update_mcp_servers_in_config(&config_path, &mcpc, payload.servers).await {
         Ok(message) => Ok(ResponseJson(ApiResponse::success(message))),
         Err(e) => Ok(ResponseJson(ApiResponse::error(&format!(
@@ -305,7 +338,7 @@ async fn update_mcp_servers_in_config(
     let old_servers = get_mcp_servers_from_config_path(&config, &mcpc.servers_path).len();
 
     // Set the MCP servers using the correct attribute path
-    set_mcp_servers_in_config_path(&mut config, &mcpc.servers_path, &new_servers)?;
+    set_mcp_servers_in_config(&mut config, &mcpc.servers_path, &new_servers)?;
 
     // Write the updated config back to file (JSON or TOML depending on agent)
     write_agent_config(config_path, mcpc, &config).await?;
@@ -343,42 +376,6 @@ fn get_mcp_servers_from_config_path(raw_config: &Value, path: &[String]) -> Hash
     }
 }
 
-/// Helper function to set MCP servers in config using a path
-fn set_mcp_servers_in_config_path(
-    raw_config: &mut Value,
-    path: &[String],
-    servers: &HashMap<String, Value>,
-) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    // Ensure config is an object
-    if !raw_config.is_object() {
-        *raw_config = serde_json::json!({});
-    }
-
-    let mut current = raw_config;
-    // Navigate/create the nested structure (all parts except the last)
-    for part in &path[..path.len() - 1] {
-        if current.get(part).is_none() {
-            current
-                .as_object_mut()
-                .unwrap()
-                .insert(part.to_string(), serde_json::json!({}));
-        }
-        current = current.get_mut(part).unwrap();
-        if !current.is_object() {
-            *current = serde_json::json!({});
-        }
-    }
-
-    // Set the final attribute
-    let final_attr = path.last().unwrap();
-    current
-        .as_object_mut()
-        .unwrap()
-        .insert(final_attr.to_string(), serde_json::to_value(servers)?);
-
-    Ok(())
-}
-
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ProfilesContent {
     pub content: String,
@@ -474,14 +471,35 @@ pub struct CheckAgentAvailabilityQuery {
 async fn check_agent_availability(
     State(_deployment): State<DeploymentImpl>,
     Query(query): Query<CheckAgentAvailabilityQuery>,
-) -> ResponseJson<ApiResponse<AvailabilityInfo>> {
+) -> ResponseJson<ApiResponse<AvailabilityDetail>> {
     let profiles = ExecutorConfigs::get_cached();
     let profile_id = ExecutorProfileId::new(query.executor);
 
     let info = match profiles.get_coding_agent(&profile_id) {
-        Some(agent) => agent.get_availability_info(),
-        None => AvailabilityInfo::NotFound,
+        Some(agent) => agent.get_availability_detail(),
+        None => AvailabilityDetail {
+            info: AvailabilityInfo::NotFound,
+            configured_mcp_servers: None,
+        },
     };
 
     ResponseJson(ApiResponse::success(info))
 }
+
+async fn check_agent_auth(
+    State(_deployment): State<DeploymentImpl>,
+    Query(query): Query<CheckAgentAvailabilityQuery>,
+) -> ResponseJson<ApiResponse<AuthStatus>> {
+    let profiles = ExecutorConfigs::get_cached();
+    let profile_id = ExecutorProfileId::new(query.executor);
+
+    let status = match profiles.get_coding_agent(&profile_id) {
+        Some(agent) => agent.check_auth().await.unwrap_or_else(|err| {
+            tracing::warn!("Failed to check {} auth: {err}", query.executor);
+            AuthStatus::Unknown
+        }),
+        None => AuthStatus::Unknown,
+    };
+
+    ResponseJson(ApiResponse::success(status))
+}
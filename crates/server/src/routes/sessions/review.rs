@@ -11,9 +11,12 @@ use deployment::Deployment;
 use executors::{
     actions::{
         ExecutorAction, ExecutorActionType,
-        review::{RepoReviewContext as ExecutorRepoReviewContext, ReviewRequest as ReviewAction},
+        review::{
+            RepoReviewContext as ExecutorRepoReviewContext, ReviewRequest as ReviewAction,
+            ReviewScope,
+        },
     },
-    executors::build_review_prompt,
+    executors::{build_review_prompt, build_uncommitted_review_prompt},
     profile::ExecutorProfileId,
 };
 use serde::{Deserialize, Serialize};
@@ -29,6 +32,12 @@ pub struct StartReviewRequest {
     pub additional_prompt: Option<String>,
     #[serde(default)]
     pub use_all_workspace_commits: bool,
+    /// Review the working tree's staged and unstaged changes instead of commits
+    #[serde(default)]
+    pub uncommitted_changes: bool,
+    /// Have the reviewer summarize the commit messages of each reviewed repository
+    #[serde(default)]
+    pub include_commit_log: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize, TS)]
@@ -68,7 +77,9 @@ pub async fn start_review(
     let agent_session_id =
         ExecutionProcess::find_latest_coding_agent_turn_session_id(pool, session.id).await?;
 
-    let context: Option<Vec<ExecutorRepoReviewContext>> = if payload.use_all_workspace_commits {
+    let context: Option<Vec<ExecutorRepoReviewContext>> = if payload.uncommitted_changes {
+        None
+    } else if payload.use_all_workspace_commits {
         let repos =
             WorkspaceRepo::find_repos_with_target_branch_for_workspace(pool, workspace.id).await?;
         let workspace_path = PathBuf::from(container_ref.as_str());
@@ -76,15 +87,18 @@ pub async fn start_review(
         let mut contexts = Vec::new();
         for repo in repos {
             let worktree_path = workspace_path.join(&repo.repo.name);
-            if let Ok(base_commit) = deployment.git().get_fork_point(
-                &worktree_path,
-                &repo.target_branch,
-                &workspace.branch,
-            ) {
+            // Fall back to the default branch when the target branch can't be resolved
+            let base_commit = deployment
+                .git()
+                .get_fork_point(&worktree_path, &repo.target_branch, &workspace.branch)
+                .ok()
+                .or_else(|| utils::git::default_branch_merge_base(&worktree_path).ok());
+            if let Some(base_commit) = base_commit {
                 contexts.push(ExecutorRepoReviewContext {
                     repo_id: repo.repo.id,
                     repo_name: repo.repo.display_name,
                     base_commit,
+                    include_commit_log: payload.include_commit_log,
                 });
             }
         }
@@ -97,7 +111,17 @@ pub async fn start_review(
         None
     };
 
-    let prompt = build_review_prompt(context.as_deref(), payload.additional_prompt.as_deref());
+    let (prompt, scope) = if payload.uncommitted_changes {
+        (
+            build_uncommitted_review_prompt(payload.additional_prompt.as_deref()),
+            ReviewScope::UncommittedChanges,
+        )
+    } else {
+        (
+            build_review_prompt(context.as_deref(), payload.additional_prompt.as_deref()),
+            ReviewScope::Commits,
+        )
+    };
     let resumed_session = agent_session_id.is_some();
 
     let action = ExecutorAction::new(
@@ -105,6 +129,7 @@ pub async fn start_review(
             executor_profile_id: payload.executor_profile_id.clone(),
             context,
             prompt,
+            scope,
             session_id: agent_session_id,
             working_dir: workspace.agent_working_dir.clone(),
         }),
@@ -130,6 +155,7 @@ pub async fn start_review(
                 "executor": payload.executor_profile_id.executor.to_string(),
                 "variant": payload.executor_profile_id.variant,
                 "resumed_session": resumed_session,
+                "uncommitted_changes": payload.uncommitted_changes,
             }),
         )
         .await;
@@ -4,17 +4,7 @@ use db::models::{
     workspace::{Workspace, WorkspaceError},
 };
 use deployment::Deployment;
-use executors::{
-    actions::{
-        ExecutorAction, ExecutorActionType,
-        script::{ScriptContext, ScriptRequest, ScriptRequestLanguage},
-    },
-    command::{CommandBuilder, apply_overrides},
-    executors::{
-        ExecutorError,
-        codex::{Codex, base_command, fallback_command},
-    },
-};
+use executors::executors::{StandardCodingAgentExecutor, codex::Codex};
 use services::services::container::ContainerService;
 use uuid::Uuid;
 
@@ -36,11 +26,12 @@ pub async fn run_codex_setup(
         let latest_action = latest_process
             .executor_action()
             .map_err(|e| ApiError::Workspace(WorkspaceError::ValidationError(e.to_string())))?;
-        get_setup_helper_action(codex)
+        codex
+            .get_setup_helper_action()
             .await?
             .append_action(latest_action.to_owned())
     } else {
-        get_setup_helper_action(codex).await?
+        codex.get_setup_helper_action().await?
     };
 
     deployment
@@ -77,53 +68,3 @@ pub async fn run_codex_setup(
         .await?;
     Ok(execution_process)
 }
-
-async fn get_setup_helper_action(codex: &Codex) -> Result<ExecutorAction, ApiError> {
-    let mut login_command = CommandBuilder::new(base_command());
-    login_command = login_command.extend_params(["login"]);
-    login_command = apply_overrides(login_command, &codex.cmd)?;
-
-    let (program_path, args) = match login_command
-        .build_initial()
-        .map_err(|err| ApiError::Executor(ExecutorError::from(err)))?
-        .into_resolved()
-        .await
-    {
-        Ok(resolved) => resolved,
-        Err(err) => {
-            if matches!(err, ExecutorError::ExecutableNotFound { .. })
-                && codex.cmd.base_command_override.is_none()
-            {
-                if let ExecutorError::ExecutableNotFound { program } = &err {
-                    tracing::info!(
-                        %program,
-                        "Codex executable not found. Falling back to npx."
-                    );
-                }
-                let mut fallback_command = CommandBuilder::new(fallback_command());
-                fallback_command = fallback_command.extend_params(["login"]);
-                fallback_command = apply_overrides(fallback_command, &codex.cmd)?;
-                fallback_command
-                    .build_initial()
-                    .map_err(|err| ApiError::Executor(ExecutorError::from(err)))?
-                    .into_resolved()
-                    .await
-                    .map_err(ApiError::Executor)?
-            } else {
-                return Err(ApiError::Executor(err));
-            }
-        }
-    };
-    let login_script = format!("{} {}", program_path.to_string_lossy(), args.join(" "));
-    let login_request = ScriptRequest {
-        script: login_script,
-        language: ScriptRequestLanguage::Bash,
-        context: ScriptContext::ToolInstallScript,
-        working_dir: None,
-    };
-
-    Ok(ExecutorAction::new(
-        ExecutorActionType::ScriptRequest(login_request),
-        None,
-    ))
-}
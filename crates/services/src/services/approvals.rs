@@ -363,6 +363,7 @@ mod tests {
                 })
                 .unwrap(),
             ),
+            agent: None,
         }
     }
 
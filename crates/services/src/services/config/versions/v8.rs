@@ -1,5 +1,5 @@
 use anyhow::Error;
-use executors::{executors::BaseCodingAgent, profile::ExecutorProfileId};
+use executors::{executors::BaseCodingAgent, profile::ExecutorProfileId, restart::RestartPolicy};
 use serde::{Deserialize, Serialize};
 use ts_rs::TS;
 pub use v7::{
@@ -47,6 +47,9 @@ pub struct Config {
     pub beta_workspaces_invitation_sent: bool,
     #[serde(default)]
     pub commit_reminder: bool,
+    /// Automatically restart coding agents that crash; disabled when unset
+    #[serde(default)]
+    pub agent_restart_policy: Option<RestartPolicy>,
 }
 
 impl Config {
@@ -75,6 +78,7 @@ impl Config {
             beta_workspaces: false,
             beta_workspaces_invitation_sent: false,
             commit_reminder: false,
+            agent_restart_policy: None,
         }
     }
 
@@ -128,6 +132,7 @@ impl Default for Config {
             beta_workspaces: false,
             beta_workspaces_invitation_sent: false,
             commit_reminder: false,
+            agent_restart_policy: None,
         }
     }
 }
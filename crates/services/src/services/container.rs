@@ -35,6 +35,7 @@ use executors::{
         coding_agent_initial::CodingAgentInitialRequest,
         script::{ScriptContext, ScriptRequest, ScriptRequestLanguage},
     },
+    executor_registry::ExecutorRegistry,
     executors::{ExecutorError, StandardCodingAgentExecutor},
     logs::{NormalizedEntry, NormalizedEntryError, NormalizedEntryType, utils::ConversationPatch},
     profile::ExecutorProfileId,
@@ -777,6 +778,13 @@ pub trait ContainerService {
                         .get_coding_agent_or_default(&request.executor_profile_id);
                     executor.normalize_logs(temp_store.clone(), &current_dir);
                 }
+                ExecutorActionType::ExternalAgentRequest(request) => {
+                    let executor = ExecutorRegistry::shared()
+                        .resolve(&request.executor_id)
+                        .ok()?;
+                    executor
+                        .normalize_logs(temp_store.clone(), &request.effective_dir(&current_dir));
+                }
                 _ => {
                     tracing::debug!(
                         "Executor action doesn't support log normalization: {:?}",
@@ -1043,6 +1051,9 @@ pub trait ContainerService {
             ExecutorActionType::ReviewRequest(review_request) => {
                 Some(review_request.prompt.clone())
             }
+            ExecutorActionType::ExternalAgentRequest(external_request) => {
+                Some(external_request.prompt.clone())
+            }
             ExecutorActionType::ScriptRequest(_) => None,
         } {
             let create_coding_agent_turn = CreateCodingAgentTurn {
@@ -1064,11 +1075,19 @@ pub trait ContainerService {
             .start_execution_inner(workspace, &execution_process, executor_action)
             .await
         {
-            // Mark process as failed
+            // A start that was cancelled was stopped on purpose; don't record it as a failure
+            let cancelled = matches!(
+                start_error,
+                ContainerError::ExecutorError(ExecutorError::Cancelled)
+            );
             if let Err(update_error) = ExecutionProcess::update_completion(
                 &self.db().pool,
                 execution_process.id,
-                ExecutionProcessStatus::Failed,
+                if cancelled {
+                    ExecutionProcessStatus::Killed
+                } else {
+                    ExecutionProcessStatus::Failed
+                },
                 None,
             )
             .await
@@ -1080,6 +1099,9 @@ pub trait ContainerService {
                 );
             }
             Task::update_status(&self.db().pool, task.id, TaskStatus::InReview).await?;
+            if cancelled {
+                return Err(start_error);
+            }
 
             // Emit stderr error message
             let log_message = LogMsg::Stderr(format!("Failed to start execution: {start_error}"));
@@ -1104,6 +1126,7 @@ pub trait ContainerService {
                     },
                     content: help_text,
                     metadata: None,
+                    agent: None,
                 };
                 let patch = ConversationPatch::add_normalized_entry(2, error_message);
                 if let Ok(json_line) = serde_json::to_string::<LogMsg>(&LogMsg::JsonPatch(patch)) {
@@ -1158,6 +1181,21 @@ pub trait ContainerService {
             }
         }
 
+        if let ExecutorActionType::ExternalAgentRequest(request) = executor_action.typ()
+            && let Some(msg_store) = self.get_msg_store_by_id(&execution_process.id).await
+        {
+            match ExecutorRegistry::shared().resolve(&request.executor_id) {
+                Ok(executor) => {
+                    executor.normalize_logs(msg_store, &request.effective_dir(&workspace_root))
+                }
+                Err(err) => tracing::error!(
+                    "Failed to resolve executor '{}' for normalization: {}",
+                    request.executor_id,
+                    err
+                ),
+            }
+        }
+
         self.spawn_stream_raw_logs_to_db(&execution_process.id);
         Ok(execution_process)
     }
@@ -1179,14 +1217,16 @@ pub trait ContainerService {
             (
                 ExecutorActionType::CodingAgentInitialRequest(_)
                 | ExecutorActionType::CodingAgentFollowUpRequest(_)
-                | ExecutorActionType::ReviewRequest(_),
+                | ExecutorActionType::ReviewRequest(_)
+                | ExecutorActionType::ExternalAgentRequest(_),
                 ExecutorActionType::ScriptRequest(_),
             ) => ExecutionProcessRunReason::CleanupScript,
             (
                 _,
                 ExecutorActionType::CodingAgentFollowUpRequest(_)
                 | ExecutorActionType::CodingAgentInitialRequest(_)
-                | ExecutorActionType::ReviewRequest(_),
+                | ExecutorActionType::ReviewRequest(_)
+                | ExecutorActionType::ExternalAgentRequest(_),
             ) => ExecutionProcessRunReason::CodingAgent,
         };
 
@@ -206,7 +206,7 @@ fn fix_hunk_headers(hunks: Vec<String>) -> Vec<String> {
                 .iter()
                 .filter(|line| line.starts_with(['+', ' ']))
                 .count();
-            let new_header = format!("@@ -1,{old_count} +1,{new_count} @@");
+            let new_header = format!("@@ -1,{old_count} +1,{new_count} @@\n");
             lines.insert(0, new_header);
             new_hunks.push(lines.join(""));
         } else {
@@ -246,3 +246,33 @@ pub fn normalize_unified_diff(file_path: &str, unified_diff: &str) -> String {
     let hunks = extract_unified_diff_hunks(unified_diff);
     concatenate_diff_hunks(file_path, &hunks)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_hunk_headers_are_rebuilt_on_their_own_line() {
+        let hunks = fix_hunk_headers(vec![
+            "@@\n-old\n+new\n context\n".to_string(),
+            "@@ -10,1 +10,1 @@\n-a\n+b\n".to_string(),
+        ]);
+        assert_eq!(
+            hunks,
+            [
+                "@@ -1,2 +1,2 @@\n-old\n+new\n context\n",
+                "@@ -10,1 +10,1 @@\n-a\n+b\n"
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_hunk_headers_keep_the_first_change_in_the_normalized_diff() {
+        // An apply_patch style diff: bare `@@` markers with no line ranges
+        let diff = "*** Update File: src/lib.rs\n@@\n-old\n+new\n";
+        assert_eq!(
+            normalize_unified_diff("src/lib.rs", diff),
+            "--- a/src/lib.rs\n+++ b/src/lib.rs\n@@ -1,1 +1,1 @@\n-old\n+new\n"
+        );
+    }
+}
@@ -1,3 +1,7 @@
+use std::path::{Path, PathBuf};
+
+use thiserror::Error;
+
 pub fn is_valid_branch_prefix(prefix: &str) -> bool {
     if prefix.is_empty() {
         return true;
@@ -10,10 +14,97 @@ pub fn is_valid_branch_prefix(prefix: &str) -> bool {
     git2::Branch::name_is_valid(&format!("{prefix}/x")).unwrap_or_default()
 }
 
+#[derive(Debug, Error)]
+pub enum ReviewBaseError {
+    #[error("{} is not a git repository", .0.display())]
+    NotARepository(PathBuf),
+    #[error("Could not determine the repository's default branch")]
+    NoDefaultBranch,
+    #[error(transparent)]
+    Git(#[from] git2::Error),
+}
+
+/// Resolve a sensible base commit for reviewing the work in `worktree_path`: the merge-base
+/// of `HEAD` with the default branch (`origin/HEAD`, else `main`, else `master`).
+pub fn default_branch_merge_base(worktree_path: &Path) -> Result<String, ReviewBaseError> {
+    let repo = git2::Repository::open(worktree_path)
+        .map_err(|_| ReviewBaseError::NotARepository(worktree_path.to_path_buf()))?;
+    let head = repo.head()?.peel_to_commit()?.id();
+    let default_branch = default_branch_commit(&repo).ok_or(ReviewBaseError::NoDefaultBranch)?;
+    Ok(repo.merge_base(head, default_branch)?.to_string())
+}
+
+fn default_branch_commit(repo: &git2::Repository) -> Option<git2::Oid> {
+    let origin_head = repo
+        .find_reference("refs/remotes/origin/HEAD")
+        .and_then(|reference| reference.resolve())
+        .ok()
+        .and_then(|reference| reference.target());
+    origin_head.or_else(|| {
+        [
+            "refs/remotes/origin/main",
+            "refs/remotes/origin/master",
+            "refs/heads/main",
+            "refs/heads/master",
+        ]
+        .into_iter()
+        .find_map(|name| repo.refname_to_id(name).ok())
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn commit(repo: &git2::Repository, update_ref: &str, parents: &[&git2::Commit]) -> git2::Oid {
+        let signature = git2::Signature::now("Test", "test@example.com").unwrap();
+        let tree = repo
+            .find_tree(repo.index().unwrap().write_tree().unwrap())
+            .unwrap();
+        repo.commit(
+            Some(update_ref),
+            &signature,
+            &signature,
+            "commit",
+            &tree,
+            parents,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_default_branch_merge_base() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = git2::Repository::init_opts(
+            dir.path(),
+            git2::RepositoryInitOptions::new().initial_head("main"),
+        )
+        .unwrap();
+
+        // main: A - C, feature (checked out): A - B
+        let a = repo.find_commit(commit(&repo, "HEAD", &[])).unwrap();
+        repo.branch("feature", &a, false).unwrap();
+        repo.set_head("refs/heads/feature").unwrap();
+        let b = commit(&repo, "HEAD", &[&a]);
+        let c = commit(&repo, "refs/heads/main", &[&a]);
+        assert_ne!(b, c);
+
+        assert_eq!(
+            default_branch_merge_base(dir.path()).unwrap(),
+            a.id().to_string()
+        );
+    }
+
+    #[test]
+    fn test_default_branch_merge_base_requires_repo() {
+        let dir = tempfile::tempdir().unwrap();
+
+        assert!(matches!(
+            default_branch_merge_base(dir.path()),
+            Err(ReviewBaseError::NotARepository(_))
+        ));
+    }
+
     #[test]
     fn test_valid_prefixes() {
         assert!(is_valid_branch_prefix(""));
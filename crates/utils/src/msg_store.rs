@@ -5,7 +5,10 @@ use std::{
 
 use axum::response::sse::Event;
 use futures::{StreamExt, TryStreamExt, future};
-use tokio::{sync::broadcast, task::JoinHandle};
+use tokio::{
+    sync::{broadcast, watch},
+    task::JoinHandle,
+};
 use tokio_stream::wrappers::BroadcastStream;
 
 use crate::{log_msg::LogMsg, stream_lines::LinesStreamExt};
@@ -24,9 +27,13 @@ struct Inner {
     total_bytes: usize,
 }
 
+type PatchTransform = Arc<dyn Fn(&mut json_patch::Patch) + Send + Sync>;
+
 pub struct MsgStore {
     inner: RwLock<Inner>,
     sender: broadcast::Sender<LogMsg>,
+    normalization_paused: watch::Sender<bool>,
+    patch_transforms: RwLock<Vec<PatchTransform>>,
 }
 
 impl Default for MsgStore {
@@ -44,14 +51,19 @@ impl MsgStore {
                 total_bytes: 0,
             }),
             sender,
+            normalization_paused: watch::Sender::new(false),
+            patch_transforms: RwLock::new(Vec::new()),
         }
     }
 
+    /// Store `msg` and send it to live listeners. Both happen under the history lock, so
+    /// messages pushed concurrently (e.g. by the stdout and stderr normalizers) reach every
+    /// listener in the same order they are stored in.
     pub fn push(&self, msg: LogMsg) {
-        let _ = self.sender.send(msg.clone()); // live listeners
         let bytes = msg.approx_bytes();
 
         let mut inner = self.inner.write().unwrap();
+        let _ = self.sender.send(msg.clone()); // live listeners
         while inner.total_bytes.saturating_add(bytes) > HISTORY_BYTES {
             if let Some(front) = inner.history.pop_front() {
                 inner.total_bytes = inner.total_bytes.saturating_sub(front.bytes);
@@ -71,10 +83,26 @@ impl MsgStore {
     pub fn push_stderr<S: Into<String>>(&self, s: S) {
         self.push(LogMsg::Stderr(s.into()));
     }
-    pub fn push_patch(&self, patch: json_patch::Patch) {
+    pub fn push_patch(&self, mut patch: json_patch::Patch) {
+        let transforms = self.patch_transforms.read().unwrap().clone();
+        for transform in transforms {
+            transform(&mut patch);
+        }
         self.push(LogMsg::JsonPatch(patch));
     }
 
+    /// Rewrite every patch pushed from now on, e.g. to tag entries with their source.
+    /// Transforms run in the order they were added; patches already in history are untouched.
+    pub fn add_patch_transform(
+        &self,
+        transform: impl Fn(&mut json_patch::Patch) + Send + Sync + 'static,
+    ) {
+        self.patch_transforms
+            .write()
+            .unwrap()
+            .push(Arc::new(transform));
+    }
+
     pub fn push_session_id(&self, session_id: String) {
         self.push(LogMsg::SessionId(session_id));
     }
@@ -97,11 +125,17 @@ impl MsgStore {
             .collect()
     }
 
-    /// History then live, as `LogMsg`.
+    /// History then live, as `LogMsg`. Every message is delivered exactly once, in the order
+    /// it was stored.
     pub fn history_plus_stream(
         &self,
     ) -> futures::stream::BoxStream<'static, Result<LogMsg, std::io::Error>> {
-        let (history, rx) = (self.get_history(), self.get_receiver());
+        // Subscribe under the lock, so no message lands between the history and the live feed
+        let (history, rx) = {
+            let inner = self.inner.read().unwrap();
+            let history: Vec<LogMsg> = inner.history.iter().map(|s| s.msg.clone()).collect();
+            (history, self.sender.subscribe())
+        };
 
         let hist = futures::stream::iter(history.into_iter().map(Ok::<_, std::io::Error>));
         let live = BroadcastStream::new(rx)
@@ -110,10 +144,71 @@ impl MsgStore {
         Box::pin(hist.chain(live))
     }
 
+    /// Stop delivering messages to normalizers. Raw output is still stored and streamed to
+    /// clients; normalizers receive the backlog in order on `resume_normalization`.
+    pub fn pause_normalization(&self) {
+        self.normalization_paused.send_replace(true);
+    }
+
+    pub fn resume_normalization(&self) {
+        self.normalization_paused.send_replace(false);
+    }
+
+    pub fn is_normalization_paused(&self) -> bool {
+        *self.normalization_paused.borrow()
+    }
+
+    /// History then live, held back while normalization is paused. Messages that arrive
+    /// during a pause are buffered and delivered in order once it is lifted, or once the
+    /// live stream ends.
+    pub fn normalization_stream(
+        &self,
+    ) -> futures::stream::BoxStream<'static, Result<LogMsg, std::io::Error>> {
+        struct Gate {
+            source: futures::stream::BoxStream<'static, Result<LogMsg, std::io::Error>>,
+            paused: watch::Receiver<bool>,
+            // False once the store (and with it the pause control) has been dropped
+            watching: bool,
+            buffer: VecDeque<Result<LogMsg, std::io::Error>>,
+            source_done: bool,
+        }
+
+        let gate = Gate {
+            source: self.history_plus_stream(),
+            paused: self.normalization_paused.subscribe(),
+            watching: true,
+            buffer: VecDeque::new(),
+            source_done: false,
+        };
+
+        futures::stream::unfold(gate, |mut gate| async move {
+            loop {
+                let paused = gate.watching && !gate.source_done && *gate.paused.borrow_and_update();
+                if !paused && let Some(item) = gate.buffer.pop_front() {
+                    return Some((item, gate));
+                }
+                if gate.source_done {
+                    return None;
+                }
+                tokio::select! {
+                    item = gate.source.next() => match item {
+                        Some(item) if paused => gate.buffer.push_back(item),
+                        Some(item) => return Some((item, gate)),
+                        None => gate.source_done = true,
+                    },
+                    changed = gate.paused.changed(), if gate.watching => {
+                        gate.watching = changed.is_ok();
+                    }
+                }
+            }
+        })
+        .boxed()
+    }
+
     pub fn stdout_chunked_stream(
         &self,
     ) -> futures::stream::BoxStream<'static, Result<String, std::io::Error>> {
-        self.history_plus_stream()
+        self.normalization_stream()
             .take_while(|res| future::ready(!matches!(res, Ok(LogMsg::Finished))))
             .filter_map(|res| async move {
                 match res {
@@ -133,7 +228,7 @@ impl MsgStore {
     pub fn stderr_chunked_stream(
         &self,
     ) -> futures::stream::BoxStream<'static, Result<String, std::io::Error>> {
-        self.history_plus_stream()
+        self.normalization_stream()
             .take_while(|res| future::ready(!matches!(res, Ok(LogMsg::Finished))))
             .filter_map(|res| async move {
                 match res {
@@ -175,3 +270,68 @@ impl MsgStore {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn paused_normalization_buffers_until_resume() {
+        let store = Arc::new(MsgStore::new());
+        store.push_stdout("first\n");
+        store.pause_normalization();
+
+        let mut lines = store.stdout_lines_stream();
+        store.push_stdout("second\n");
+        store.push_stdout("third\n");
+
+        // Nothing is delivered while paused, not even the history
+        assert!(
+            tokio::time::timeout(Duration::from_millis(50), lines.next())
+                .await
+                .is_err()
+        );
+
+        store.resume_normalization();
+        store.push_stdout("fourth\n");
+        store.push_finished();
+
+        let lines: Vec<String> = lines.map(|line| line.unwrap()).collect().await;
+        assert_eq!(lines, ["first", "second", "third", "fourth"]);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn concurrent_pushes_reach_listeners_in_stored_order() {
+        let store = Arc::new(MsgStore::new());
+        store.push_stdout("before\n");
+        let listener = store.history_plus_stream();
+
+        let feeds = (0..4).map(|feed| {
+            let store = store.clone();
+            tokio::spawn(async move {
+                for i in 0..200 {
+                    if feed % 2 == 0 {
+                        store.push_stdout(format!("{feed}-{i}\n"));
+                    } else {
+                        store.push_stderr(format!("{feed}-{i}\n"));
+                    }
+                    tokio::task::yield_now().await;
+                }
+            })
+        });
+        futures::future::join_all(feeds).await;
+        store.push_finished();
+
+        let received: Vec<LogMsg> = listener
+            .map(|msg| msg.unwrap())
+            .take_while(|msg| future::ready(!matches!(msg, LogMsg::Finished)))
+            .collect()
+            .await;
+        let mut stored = store.get_history();
+        stored.pop(); // Finished
+        assert_eq!(received.len(), 801);
+        assert_eq!(format!("{received:?}"), format!("{stored:?}"));
+    }
+}
@@ -16,12 +16,8 @@ pub fn make_path_relative(path: &str, worktree_path: &str) -> String {
         return path.to_string();
     }
 
-    if let Ok(relative_path) = path_obj.strip_prefix(&worktree_path_obj) {
-        let result = relative_path.to_string_lossy().to_string();
+    if let Some(result) = strip_worktree_prefix(&path_obj, &worktree_path_obj) {
         tracing::trace!("Successfully made relative: '{}' -> '{}'", path, result);
-        if result.is_empty() {
-            return ".".to_string();
-        }
         return result;
     }
 
@@ -78,6 +74,27 @@ pub fn make_path_relative(path: &str, worktree_path: &str) -> String {
     }
 }
 
+/// Like [`make_path_relative`], but purely lexical: symlinks are not resolved and the
+/// filesystem is never touched, so it is cheap enough to run on every log entry.
+pub fn make_path_relative_lexically(path: &str, worktree_path: &str) -> String {
+    let path_obj = normalize_macos_private_alias(Path::new(path));
+    let worktree_path_obj = normalize_macos_private_alias(Path::new(worktree_path));
+    if path_obj.is_relative() {
+        return path.to_string();
+    }
+    strip_worktree_prefix(&path_obj, &worktree_path_obj).unwrap_or_else(|| path.to_string())
+}
+
+/// `path` relative to `worktree_path`, with the worktree itself as `.`.
+fn strip_worktree_prefix(path: &Path, worktree_path: &Path) -> Option<String> {
+    let relative = path.strip_prefix(worktree_path).ok()?.to_string_lossy();
+    Some(if relative.is_empty() {
+        ".".to_string()
+    } else {
+        relative.to_string()
+    })
+}
+
 /// Normalize macOS prefix /private/var/ and /private/tmp/ to their public aliases without resolving paths.
 /// This allows prefix normalization to work when the full paths don't exist.
 pub fn normalize_macos_private_alias<P: AsRef<Path>>(p: P) -> PathBuf {
@@ -150,6 +167,25 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_make_path_relative_lexically() {
+        // The worktree does not exist, so only the lexical prefix can match
+        let worktree = "/nonexistent/test-worktree";
+        assert_eq!(
+            make_path_relative_lexically("/nonexistent/test-worktree/src/main.rs", worktree),
+            "src/main.rs"
+        );
+        assert_eq!(make_path_relative_lexically(worktree, worktree), ".");
+        assert_eq!(
+            make_path_relative_lexically("/other/path/file.js", worktree),
+            "/other/path/file.js"
+        );
+        assert_eq!(
+            make_path_relative_lexically("src/main.rs", worktree),
+            "src/main.rs"
+        );
+    }
+
     #[cfg(target_os = "macos")]
     #[test]
     fn test_make_path_relative_macos_private_alias() {
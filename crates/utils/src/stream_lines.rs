@@ -1,5 +1,6 @@
 use bytes::Bytes;
 use futures::{Stream, StreamExt, TryStreamExt};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt};
 use tokio_util::{
     codec::{FramedRead, LinesCodec},
     io::StreamReader,
@@ -20,3 +21,50 @@ pub trait LinesStreamExt: Stream<Item = Result<String, std::io::Error>> + Sized
 }
 
 impl<S> LinesStreamExt for S where S: Stream<Item = Result<String, std::io::Error>> {}
+
+/// Read the next line from `reader`, replacing invalid UTF-8 with U+FFFD instead of erroring.
+///
+/// The trailing `\n` / `\r\n` is stripped. Returns `None` at EOF. Partially read bytes are kept
+/// in `buf` between calls, so this is safe to use inside `tokio::select!` or a timeout.
+pub async fn read_line_lossy<R>(
+    reader: &mut R,
+    buf: &mut Vec<u8>,
+) -> std::io::Result<Option<String>>
+where
+    R: AsyncBufRead + Unpin,
+{
+    reader.read_until(b'\n', buf).await?;
+    if buf.is_empty() {
+        return Ok(None);
+    }
+
+    let mut end = buf.len();
+    if buf[..end].ends_with(b"\n") {
+        end -= 1;
+        if buf[..end].ends_with(b"\r") {
+            end -= 1;
+        }
+    }
+    let line = String::from_utf8_lossy(&buf[..end]).into_owned();
+    buf.clear();
+    Ok(Some(line))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn read_line_lossy_survives_invalid_utf8() {
+        let input: &[u8] = b"first\n\xff\xfebad path\r\nlast";
+        let mut reader = tokio::io::BufReader::new(input);
+        let mut buf = Vec::new();
+
+        let mut lines = Vec::new();
+        while let Some(line) = read_line_lossy(&mut reader, &mut buf).await.unwrap() {
+            lines.push(line);
+        }
+
+        assert_eq!(lines, ["first", "\u{fffd}\u{fffd}bad path", "last"]);
+    }
+}
@@ -49,6 +49,19 @@ export function AgentAvailabilityIndicator({
           </p>
         </>
       )}
+      {availability.status === 'needs_login' && (
+        <>
+          <div className="flex items-center gap-2">
+            <AlertCircle className="h-4 w-4 text-warning" />
+            <span className="text-warning">
+              {t('settings.agents.availability.needsLogin')}
+            </span>
+          </div>
+          <p className="text-xs text-muted-foreground pl-6">
+            {t('settings.agents.availability.needsLoginTooltip')}
+          </p>
+        </>
+      )}
       {availability.status === 'not_found' && (
         <>
           <div className="flex items-center gap-2">
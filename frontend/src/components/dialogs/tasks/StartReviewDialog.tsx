@@ -70,6 +70,8 @@ const StartReviewDialogImpl = NiceModal.create<StartReviewDialogProps>(
       () => !resolvedSessionId
     );
     const [includeGitContext, setIncludeGitContext] = useState(true);
+    const [includeCommitLog, setIncludeCommitLog] = useState(false);
+    const [uncommittedChanges, setUncommittedChanges] = useState(false);
     const [isSubmitting, setIsSubmitting] = useState(false);
     const [error, setError] = useState<string | null>(null);
 
@@ -111,6 +113,8 @@ const StartReviewDialogImpl = NiceModal.create<StartReviewDialogProps>(
           executor_profile_id: effectiveProfile,
           additional_prompt: combinedPrompt || null,
           use_all_workspace_commits: includeGitContext,
+          uncommitted_changes: uncommittedChanges,
+          include_commit_log: includeCommitLog,
         });
 
         queryClient.invalidateQueries({
@@ -138,6 +142,8 @@ const StartReviewDialogImpl = NiceModal.create<StartReviewDialogProps>(
       workspaceId,
       createNewSession,
       includeGitContext,
+      includeCommitLog,
+      uncommittedChanges,
       reviewMarkdown,
       additionalPrompt,
       queryClient,
@@ -214,6 +220,7 @@ const StartReviewDialogImpl = NiceModal.create<StartReviewDialogProps>(
                   onCheckedChange={(checked) =>
                     setIncludeGitContext(checked === true)
                   }
+                  disabled={uncommittedChanges}
                 />
                 <Label
                   htmlFor="include-git-context"
@@ -227,6 +234,49 @@ const StartReviewDialogImpl = NiceModal.create<StartReviewDialogProps>(
               </p>
             </div>
 
+            <div className="space-y-1">
+              <div className="flex items-center space-x-2">
+                <Checkbox
+                  id="include-commit-log"
+                  checked={includeCommitLog}
+                  onCheckedChange={(checked) =>
+                    setIncludeCommitLog(checked === true)
+                  }
+                  disabled={!includeGitContext || uncommittedChanges}
+                />
+                <Label
+                  htmlFor="include-commit-log"
+                  className="cursor-pointer text-sm"
+                >
+                  {t('startReviewDialog.includeCommitLog')}
+                </Label>
+              </div>
+              <p className="text-xs text-muted-foreground ml-6">
+                {t('startReviewDialog.includeCommitLogDescription')}
+              </p>
+            </div>
+
+            <div className="space-y-1">
+              <div className="flex items-center space-x-2">
+                <Checkbox
+                  id="review-uncommitted-changes"
+                  checked={uncommittedChanges}
+                  onCheckedChange={(checked) =>
+                    setUncommittedChanges(checked === true)
+                  }
+                />
+                <Label
+                  htmlFor="review-uncommitted-changes"
+                  className="cursor-pointer text-sm"
+                >
+                  {t('startReviewDialog.reviewUncommittedChanges')}
+                </Label>
+              </div>
+              <p className="text-xs text-muted-foreground ml-6">
+                {t('startReviewDialog.reviewUncommittedChangesDescription')}
+              </p>
+            </div>
+
             {profiles && (
               <div className="flex gap-3 flex-col sm:flex-row">
                 <AgentSelector
@@ -6,6 +6,7 @@ export type AgentAvailabilityState =
   | { status: 'checking' }
   | { status: 'login_detected' }
   | { status: 'installation_found' }
+  | { status: 'needs_login' }
   | { status: 'not_found' }
   | null;
 
@@ -24,16 +25,37 @@ export function useAgentAvailability(
     const checkAvailability = async () => {
       setAvailability({ status: 'checking' });
       try {
-        const info = await configApi.checkAgentAvailability(agent);
+        const [{ info }, auth] = await Promise.all([
+          configApi.checkAgentAvailability(agent),
+          configApi.checkAgentAuth(agent).catch((error) => {
+            console.error('Failed to check agent auth:', error);
+            return null;
+          }),
+        ]);
+
+        // The agent's own auth check is more reliable than the credential
+        // file heuristics behind `info`, so it wins whenever it is conclusive
+        if (auth?.type === 'AUTHENTICATED') {
+          setAvailability({ status: 'login_detected' });
+          return;
+        }
+        if (auth?.type === 'NOT_AUTHENTICATED' && info.type !== 'NOT_FOUND') {
+          setAvailability({ status: 'needs_login' });
+          return;
+        }
 
         // Map backend enum to frontend state
         switch (info.type) {
           case 'LOGIN_DETECTED':
+          case 'API_KEY_CONFIGURED':
             setAvailability({ status: 'login_detected' });
             break;
           case 'INSTALLATION_FOUND':
             setAvailability({ status: 'installation_found' });
             break;
+          case 'INSTALLED_NEEDS_LOGIN':
+            setAvailability({ status: 'needs_login' });
+            break;
           case 'NOT_FOUND':
             setAvailability({ status: 'not_found' });
             break;
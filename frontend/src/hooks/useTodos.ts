@@ -10,8 +10,8 @@ interface UseTodosResult {
 
 /**
  * Hook that extracts and maintains the latest TODO state from normalized conversation entries.
- * Filters for TodoManagement ActionType entries and returns the most recent todo list,
- * along with the currently in-progress todo item.
+ * Filters for TodoManagement ActionType entries, and presented plans with parsed steps, and
+ * returns the most recent todo list, along with the currently in-progress todo item.
  */
 export const useTodos = (entries: PatchTypeWithKey[]): UseTodosResult => {
   return useMemo(() => {
@@ -22,12 +22,22 @@ export const useTodos = (entries: PatchTypeWithKey[]): UseTodosResult => {
       if (entry.type === 'NORMALIZED_ENTRY' && entry.content) {
         const normalizedEntry = entry.content as NormalizedEntry;
 
-        if (
-          normalizedEntry.entry_type?.type === 'tool_use' &&
-          normalizedEntry.entry_type?.action_type?.action === 'todo_management'
-        ) {
-          const actionType = normalizedEntry.entry_type.action_type;
-          const partialTodos = actionType.todos || [];
+        const actionType =
+          normalizedEntry.entry_type?.type === 'tool_use'
+            ? normalizedEntry.entry_type.action_type
+            : null;
+        // A presented plan only counts when steps could be parsed from it
+        const stepTodos =
+          actionType?.action === 'plan_presentation' &&
+          actionType.steps?.length > 0
+            ? actionType.steps
+            : null;
+
+        if (actionType?.action === 'todo_management' || stepTodos) {
+          const partialTodos =
+            (actionType?.action === 'todo_management'
+              ? actionType.todos
+              : stepTodos) || [];
           const currentTimestamp =
             normalizedEntry.timestamp || new Date().toISOString();
 
@@ -46,7 +46,8 @@ import {
   RenameBranchRequest,
   RenameBranchResponse,
   CheckEditorAvailabilityResponse,
-  AvailabilityInfo,
+  AuthStatus,
+  AvailabilityDetail,
   BaseCodingAgent,
   RunAgentSetupRequest,
   RunAgentSetupResponse,
@@ -953,11 +954,17 @@ export const configApi = {
   },
   checkAgentAvailability: async (
     agent: BaseCodingAgent
-  ): Promise<AvailabilityInfo> => {
+  ): Promise<AvailabilityDetail> => {
     const response = await makeRequest(
       `/api/agents/check-availability?executor=${encodeURIComponent(agent)}`
     );
-    return handleApiResponse<AvailabilityInfo>(response);
+    return handleApiResponse<AvailabilityDetail>(response);
+  },
+  checkAgentAuth: async (agent: BaseCodingAgent): Promise<AuthStatus> => {
+    const response = await makeRequest(
+      `/api/agents/check-auth?executor=${encodeURIComponent(agent)}`
+    );
+    return handleApiResponse<AuthStatus>(response);
   },
 };
 
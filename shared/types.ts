@@ -202,13 +202,21 @@ export type UserSystemInfo = { config: Config, analytics_user_id: string, login_
 /**
  * Capabilities supported per executor (e.g., { "CLAUDE_CODE": ["SESSION_FORK"] })
  */
-capabilities: { [key in string]?: Array<BaseAgentCapability> }, executors: { [key in BaseCodingAgent]?: ExecutorConfig }, };
+capabilities: { [key in string]?: Array<BaseAgentCapability> }, 
+/**
+ * Effective default sandbox per executor, for executors that describe one
+ */
+default_sandboxes: { [key in string]?: SandboxDescriptor }, executors: { [key in BaseCodingAgent]?: ExecutorConfig }, };
 
 export type Environment = { os_type: string, os_version: string, os_architecture: string, bitness: string, };
 
 export type McpServerQuery = { executor: BaseCodingAgent, };
 
-export type UpdateMcpServersBody = { servers: { [key in string]?: JsonValue }, };
+export type UpdateMcpServersBody = { servers: { [key in string]?: JsonValue }, 
+/**
+ * Return a diff of the config file instead of writing it
+ */
+dry_run?: boolean, };
 
 export type GetMcpServerResponse = { mcp_config: McpConfig, config_path: string, };
 
@@ -234,7 +242,15 @@ export type RenameBranchRequest = { new_branch_name: string, };
 
 export type RenameBranchResponse = { branch: string, };
 
-export type StartReviewRequest = { executor_profile_id: ExecutorProfileId, additional_prompt: string | null, use_all_workspace_commits: boolean, };
+export type StartReviewRequest = { executor_profile_id: ExecutorProfileId, additional_prompt: string | null, use_all_workspace_commits: boolean, 
+/**
+ * Review the working tree's staged and unstaged changes instead of commits
+ */
+uncommitted_changes: boolean, 
+/**
+ * Have the reviewer summarize the commit messages of each reviewed repository
+ */
+include_commit_log: boolean, };
 
 export type ReviewError = { "type": "process_already_running" };
 
@@ -374,7 +390,11 @@ export type DirectoryListResponse = { entries: Array<DirectoryEntry>, current_pa
 
 export type SearchMode = "taskform" | "settings";
 
-export type Config = { config_version: string, theme: ThemeMode, executor_profile: ExecutorProfileId, disclaimer_acknowledged: boolean, onboarding_acknowledged: boolean, notifications: NotificationConfig, editor: EditorConfig, github: GitHubConfig, analytics_enabled: boolean, workspace_dir: string | null, last_app_version: string | null, show_release_notes: boolean, language: UiLanguage, git_branch_prefix: string, showcases: ShowcaseState, pr_auto_description_enabled: boolean, pr_auto_description_prompt: string | null, beta_workspaces: boolean, beta_workspaces_invitation_sent: boolean, commit_reminder: boolean, };
+export type Config = { config_version: string, theme: ThemeMode, executor_profile: ExecutorProfileId, disclaimer_acknowledged: boolean, onboarding_acknowledged: boolean, notifications: NotificationConfig, editor: EditorConfig, github: GitHubConfig, analytics_enabled: boolean, workspace_dir: string | null, last_app_version: string | null, show_release_notes: boolean, language: UiLanguage, git_branch_prefix: string, showcases: ShowcaseState, pr_auto_description_enabled: boolean, pr_auto_description_prompt: string | null, beta_workspaces: boolean, beta_workspaces_invitation_sent: boolean, commit_reminder: boolean, 
+/**
+ * Automatically restart coding agents that crash; disabled when unset
+ */
+agent_restart_policy: RestartPolicy | null, };
 
 export type NotificationConfig = { sound_enabled: boolean, push_enabled: boolean, sound_file: SoundFile, };
 
@@ -420,7 +440,34 @@ export type ExecutorAction = { typ: ExecutorActionType, next_action: ExecutorAct
 
 export type McpConfig = { servers: { [key in string]?: JsonValue }, servers_path: Array<string>, template: JsonValue, preconfigured: JsonValue, is_toml_config: boolean, };
 
-export type ExecutorActionType = { "type": "CodingAgentInitialRequest" } & CodingAgentInitialRequest | { "type": "CodingAgentFollowUpRequest" } & CodingAgentFollowUpRequest | { "type": "ScriptRequest" } & ScriptRequest | { "type": "ReviewRequest" } & ReviewRequest;
+export type ConfigDiff = { 
+/**
+ * Servers that would be added under the agent's servers path
+ */
+added: Array<string>, 
+/**
+ * Servers that would be removed
+ */
+removed: Array<string>, 
+/**
+ * Servers present before and after whose settings would change
+ */
+modified: Array<string>, 
+/**
+ * Whether anything outside the servers path would change as well
+ */
+other_settings_changed: boolean, 
+/**
+ * Whether writing the config would drop any of the current file's comments (TOML only)
+ */
+drops_comments: boolean, 
+/**
+ * Unified diff of the config file, from its current content to the one that would be
+ * written
+ */
+unified_diff: string, };
+
+export type ExecutorActionType = { "type": "CodingAgentInitialRequest" } & CodingAgentInitialRequest | { "type": "CodingAgentFollowUpRequest" } & CodingAgentFollowUpRequest | { "type": "ScriptRequest" } & ScriptRequest | { "type": "ReviewRequest" } & ReviewRequest | { "type": "ExternalAgentRequest" } & ExternalAgentRequest;
 
 export type ScriptContext = "SetupScript" | "CleanupScript" | "DevServer" | "ToolInstallScript";
 
@@ -437,7 +484,23 @@ export enum BaseCodingAgent { CLAUDE_CODE = "CLAUDE_CODE", GEMINI = "GEMINI", CO
 
 export type CodingAgent = { "CLAUDE_CODE": ClaudeCode } | { "GEMINI": Gemini } | { "CODEX": Codex } | { "OPENCODE": Opencode } | { "DROID": Droid };
 
-export type AvailabilityInfo = { "type": "LOGIN_DETECTED", last_auth_timestamp: bigint, } | { "type": "INSTALLATION_FOUND" } | { "type": "NOT_FOUND" };
+export type AvailabilityInfo = { "type": "LOGIN_DETECTED", last_auth_timestamp: bigint, } | { "type": "API_KEY_CONFIGURED" } | { "type": "INSTALLATION_FOUND" } | { "type": "INSTALLED_NEEDS_LOGIN" } | { "type": "NOT_FOUND" };
+
+export type AvailabilityDetail = { info: AvailabilityInfo, 
+/**
+ * MCP servers already configured in the agent's own config file, when it could be read
+ */
+configured_mcp_servers: number | null, };
+
+export type AuthStatus = { "type": "AUTHENTICATED", 
+/**
+ * How the agent is logged in (e.g. `apikey`, `chatgpt`), when it reports it
+ */
+method: string | null, 
+/**
+ * Unix timestamp at which the credentials expire, when known
+ */
+expires_at: bigint | null, } | { "type": "NOT_AUTHENTICATED" } | { "type": "UNKNOWN" };
 
 export type CommandBuilder = { 
 /**
@@ -449,6 +512,16 @@ base: string,
  */
 params: Array<string> | null, };
 
+export type ResourceLimits = { 
+/**
+ * Maximum virtual memory in megabytes (`RLIMIT_AS`)
+ */
+memory_mb?: number | null, 
+/**
+ * Maximum CPU time in seconds (`RLIMIT_CPU`)
+ */
+cpu_time_secs?: number | null, };
+
 export type ExecutorProfileId = { 
 /**
  * The executor type (e.g., "CLAUDE_CODE")
@@ -463,13 +536,110 @@ export type ExecutorConfig = { [key in string]?: { "CLAUDE_CODE": ClaudeCode } |
 
 export type ExecutorConfigs = { executors: { [key in BaseCodingAgent]?: ExecutorConfig }, };
 
-export enum BaseAgentCapability { SESSION_FORK = "SESSION_FORK", SETUP_HELPER = "SETUP_HELPER" }
+export enum BaseAgentCapability { SESSION_FORK = "SESSION_FORK", SETUP_HELPER = "SETUP_HELPER", IMAGE_INPUT = "IMAGE_INPUT" }
+
+export enum SandboxLevel { READ_ONLY = "READ_ONLY", WORKSPACE_WRITE = "WORKSPACE_WRITE", FULL_ACCESS = "FULL_ACCESS" }
 
-export type ClaudeCode = { append_prompt: AppendPrompt, claude_code_router?: boolean | null, plan?: boolean | null, approvals?: boolean | null, model?: string | null, dangerously_skip_permissions?: boolean | null, disable_api_key?: boolean | null, base_command_override?: string | null, additional_params?: Array<string> | null, env?: { [key in string]?: string } | null, };
+export type SandboxDescriptor = { level: SandboxLevel, 
+/**
+ * Whether actions outside the sandbox are gated on user approval
+ */
+requires_approval: boolean, };
+
+export type RestartPolicy = { 
+/**
+ * Maximum number of times a single run is restarted
+ */
+max_restarts: number, 
+/**
+ * Delay before the first restart, doubled for every following one
+ */
+backoff_ms: number, };
+
+export type ClaudeCode = { append_prompt: AppendPrompt, claude_code_router?: boolean | null, plan?: boolean | null, approvals?: boolean | null, model?: string | null, dangerously_skip_permissions?: boolean | null, disable_api_key?: boolean | null, base_command_override?: string | null, additional_params?: Array<string> | null, prepend_args?: Array<string> | null, env?: { [key in string]?: string } | null, isolated_home?: string | null, max_runtime_secs?: number | null, spawn_timeout_secs?: number | null, include_git_status?: boolean | null, include_git_diff?: boolean | null, resource_limits?: ResourceLimits | null, };
+
+export type Gemini = { append_prompt: AppendPrompt, model?: string | null, yolo?: boolean | null, 
+/**
+ * Which tools run without asking for approval. Takes precedence over `yolo`; `yolo: true`
+ * alone is treated as `danger-full-access`.
+ *
+ * - `read-only`: reading and searching files and the web (`read_file`, `read_many_files`,
+ * `list_directory`, `glob`, `search_file_content`, `web_fetch`, `google_web_search`).
+ * Edits and shell commands ask for approval.
+ * - `workspace-write`: the read-only tools plus file edits (`write_file`, `replace`).
+ * Shell commands ask for approval.
+ * - `danger-full-access`: yolo mode; every tool runs without approval.
+ */
+sandbox?: GeminiSandboxMode | null, 
+/**
+ * Ask before editing files outside the working directory in `workspace-write` and
+ * `danger-full-access`, where edits otherwise run without asking
+ */
+guard_external_paths?: boolean | null, base_command_override?: string | null, additional_params?: Array<string> | null, prepend_args?: Array<string> | null, env?: { [key in string]?: string } | null, isolated_home?: string | null, max_runtime_secs?: number | null, spawn_timeout_secs?: number | null, include_git_status?: boolean | null, include_git_diff?: boolean | null, resource_limits?: ResourceLimits | null, };
 
-export type Gemini = { append_prompt: AppendPrompt, model?: string | null, yolo?: boolean | null, base_command_override?: string | null, additional_params?: Array<string> | null, env?: { [key in string]?: string } | null, };
+export type GeminiSandboxMode = "read-only" | "workspace-write" | "danger-full-access";
 
-export type Codex = { append_prompt: AppendPrompt, sandbox?: SandboxMode | null, ask_for_approval?: AskForApproval | null, oss?: boolean | null, model?: string | null, model_reasoning_effort?: ReasoningEffort | null, model_reasoning_summary?: ReasoningSummary | null, model_reasoning_summary_format?: ReasoningSummaryFormat | null, profile?: string | null, base_instructions?: string | null, include_apply_patch_tool?: boolean | null, model_provider?: string | null, compact_prompt?: string | null, developer_instructions?: string | null, base_command_override?: string | null, additional_params?: Array<string> | null, env?: { [key in string]?: string } | null, };
+export type Codex = { append_prompt: AppendPrompt, sandbox?: SandboxMode | null, ask_for_approval?: AskForApproval | null, 
+/**
+ * Shortcut for Codex's `--full-auto`: the `workspace-write` sandbox with `never` approval.
+ * An explicit `sandbox` or `ask_for_approval` takes precedence over the preset
+ */
+full_auto?: boolean | null, 
+/**
+ * Commands approved without asking, e.g. `git status` or `cargo test *`. An entry covers
+ * exactly the command it names; a trailing `*` also covers any further arguments. Everything
+ * else goes through approvals
+ */
+pre_approved_commands?: Array<string> | null, 
+/**
+ * Commands always denied, as a safety floor for unattended runs. `*` matches anything;
+ * otherwise an entry also covers the command with extra arguments. Codex can only be
+ * stopped from running a command it asks about, so without `ask_for_approval` it is made
+ * to ask about every command, and only `unless-trusted` can be combined with this: any
+ * other policy (or `full_auto`) is rejected
+ */
+forbidden_commands?: Array<string> | null, oss?: boolean | null, 
+/**
+ * Let Codex run outside a git repository, e.g. in a scratch directory. Passed to Codex as the
+ * `skip_git_repo_check` config key
+ */
+skip_git_repo_check?: boolean | null, model?: string | null, model_reasoning_effort?: ReasoningEffort | null, model_reasoning_summary?: ReasoningSummary | null, model_reasoning_summary_format?: ReasoningSummaryFormat | null, profile?: string | null, base_instructions?: string | null, include_apply_patch_tool?: boolean | null, 
+/**
+ * Enable or disable Codex's web search tool
+ */
+web_search?: boolean | null, 
+/**
+ * Codex `model_providers` id to use. When unset (and no preset is chosen), Codex's
+ * built-in OpenAI provider is used, which honors `OPENAI_BASE_URL` and `OPENAI_API_KEY`
+ * from the environment; a provider set here takes precedence over those variables.
+ */
+model_provider?: string | null, provider_preset?: ProviderPreset | null, compact_prompt?: string | null, 
+/**
+ * Once the conversation reaches this many tokens, Codex compacts it (with
+ * `compact_prompt` when set) and carries on. Unset leaves automatic compaction off
+ */
+auto_compact_token_limit?: number | null, developer_instructions?: string | null, 
+/**
+ * Extra directories Codex may write to, relative to the workspace unless absolute
+ */
+additional_dirs?: Array<string> | null, 
+/**
+ * Subcommand that starts the Codex app server, for wrappers or releases that rename it.
+ * Defaults to `app-server`
+ */
+app_server_subcommand?: string | null, 
+/**
+ * How many times to respawn an app server that fails to initialize, e.g. one that exits
+ * before answering the handshake, waiting twice as long before each attempt. While retries
+ * remain, spawning waits for the app server to initialize. Defaults to 3; set 0 to return as
+ * soon as the app server is launched
+ */
+connect_retries?: number | null, 
+/**
+ * Directory that rollout files forked for follow-ups are written to, instead of the
+ * `sessions` directory in the Codex home. Relative to the Codex home unless absolute
+ */
+rollout_dir?: string | null, base_command_override?: string | null, additional_params?: Array<string> | null, prepend_args?: Array<string> | null, env?: { [key in string]?: string } | null, isolated_home?: string | null, max_runtime_secs?: number | null, spawn_timeout_secs?: number | null, include_git_status?: boolean | null, include_git_diff?: boolean | null, resource_limits?: ResourceLimits | null, };
 
 export type SandboxMode = "auto" | "read-only" | "workspace-write" | "danger-full-access";
 
@@ -481,19 +651,35 @@ export type ReasoningSummary = "auto" | "concise" | "detailed" | "none";
 
 export type ReasoningSummaryFormat = "none" | "experimental";
 
+export type ProviderPreset = "openrouter" | "azure-openai";
+
 export type Opencode = { append_prompt: AppendPrompt, model?: string | null, mode?: string | null, 
+/**
+ * Reasoning effort for models that support it (`minimal`, `low`, `medium` or `high`)
+ */
+reasoning_effort?: string | null, 
 /**
  * Auto-approve agent actions
  */
-auto_approve: boolean, base_command_override?: string | null, additional_params?: Array<string> | null, env?: { [key in string]?: string } | null, };
+auto_approve: boolean, 
+/**
+ * Ask before editing files outside the working directory, even when auto-approving
+ */
+guard_external_paths?: boolean | null, 
+/**
+ * Directory OpenCode loads its config, including MCP servers, from instead of the default
+ */
+config_dir_override?: string | null, base_command_override?: string | null, additional_params?: Array<string> | null, prepend_args?: Array<string> | null, env?: { [key in string]?: string } | null, isolated_home?: string | null, max_runtime_secs?: number | null, spawn_timeout_secs?: number | null, include_git_status?: boolean | null, include_git_diff?: boolean | null, resource_limits?: ResourceLimits | null, };
 
-export type Droid = { append_prompt: AppendPrompt, autonomy: Autonomy, model?: string | null, reasoning_effort?: DroidReasoningEffort | null, base_command_override?: string | null, additional_params?: Array<string> | null, env?: { [key in string]?: string } | null, };
+export type Droid = { append_prompt: AppendPrompt, autonomy: Autonomy, model?: string | null, reasoning_effort?: DroidReasoningEffort | null, base_command_override?: string | null, additional_params?: Array<string> | null, prepend_args?: Array<string> | null, env?: { [key in string]?: string } | null, isolated_home?: string | null, max_runtime_secs?: number | null, spawn_timeout_secs?: number | null, include_git_status?: boolean | null, include_git_diff?: boolean | null, resource_limits?: ResourceLimits | null, };
 
 export type Autonomy = "normal" | "low" | "medium" | "high" | "skip-permissions-unsafe";
 
 export type DroidReasoningEffort = "none" | "dynamic" | "off" | "low" | "medium" | "high";
 
-export type AppendPrompt = string | null;
+export type AppendPrompt = AppendPromptValue | null;
+
+export type AppendPromptValue = string | Array<string>;
 
 export type CodingAgentInitialRequest = { prompt: string, 
 /**
@@ -517,7 +703,7 @@ executor_profile_id: ExecutorProfileId,
  */
 working_dir: string | null, };
 
-export type ReviewRequest = { executor_profile_id: ExecutorProfileId, context: Array<RepoReviewContext> | null, prompt: string, 
+export type ReviewRequest = { executor_profile_id: ExecutorProfileId, context: Array<RepoReviewContext> | null, prompt: string, scope: ReviewScope, 
 /**
  * Optional session ID to resume an existing session
  */
@@ -527,13 +713,38 @@ session_id: string | null,
  */
 working_dir: string | null, };
 
-export type RepoReviewContext = { repo_id: string, repo_name: string, base_commit: string, };
+export type ExternalAgentRequest = { 
+/**
+ * Id the executor was registered under
+ */
+executor_id: string, prompt: string, 
+/**
+ * Session to continue; a new session is started when None
+ */
+session_id: string | null, 
+/**
+ * Optional relative path to execute the agent in (relative to container_ref).
+ * If None, uses the container_ref directory directly.
+ */
+working_dir: string | null, };
+
+export type RepoReviewContext = { repo_id: string, repo_name: string, base_commit: string, 
+/**
+ * Ask the reviewer to read and summarize the commit messages in the reviewed range
+ */
+include_commit_log: boolean, };
+
+export type ReviewScope = "Commits" | "UncommittedChanges";
 
 export type CommandExitStatus = { "type": "exit_code", code: number, } | { "type": "success", success: boolean, };
 
 export type CommandRunResult = { exit_status: CommandExitStatus | null, output: string | null, };
 
-export type NormalizedEntry = { timestamp: string | null, entry_type: NormalizedEntryType, content: string, };
+export type NormalizedEntry = { timestamp: string | null, entry_type: NormalizedEntryType, content: string, 
+/**
+ * The agent that produced this entry, set when its normalizer tags the log store.
+ */
+agent?: BaseCodingAgent, };
 
 export type NormalizedEntryType = { "type": "user_message" } | { "type": "user_feedback", denied_tool: string, } | { "type": "assistant_message" } | { "type": "tool_use", tool_name: string, action_type: ActionType, status: ToolStatus, } | { "type": "system_message" } | { "type": "error_message", error_type: NormalizedEntryError, } | { "type": "thinking" } | { "type": "loading" } | { "type": "next_action", failed: boolean, execution_processes: number, needs_setup: boolean, } | { "type": "token_usage_info" } & TokenUsageInfo;
 
@@ -549,11 +760,15 @@ unified_diff: string,
  */
 has_line_numbers: boolean, };
 
-export type ActionType = { "action": "file_read", path: string, } | { "action": "file_edit", path: string, changes: Array<FileChange>, } | { "action": "command_run", command: string, result: CommandRunResult | null, } | { "action": "search", query: string, } | { "action": "web_fetch", url: string, } | { "action": "tool", tool_name: string, arguments: JsonValue | null, result: ToolResult | null, } | { "action": "task_create", description: string, } | { "action": "plan_presentation", plan: string, } | { "action": "todo_management", todos: Array<TodoItem>, operation: string, } | { "action": "other", description: string, };
+export type ActionType = { "action": "file_read", path: string, } | { "action": "file_edit", path: string, changes: Array<FileChange>, } | { "action": "command_run", command: string, result: CommandRunResult | null, } | { "action": "search", query: string, } | { "action": "web_fetch", url: string, } | { "action": "tool", tool_name: string, arguments: JsonValue | null, result: ToolResult | null, } | { "action": "task_create", description: string, } | { "action": "plan_presentation", plan: string, 
+/**
+ * Steps parsed from the plan's checklist or numbered list, for rendering as a checklist
+ */
+steps: Array<TodoItem>, } | { "action": "todo_management", todos: Array<TodoItem>, operation: string, } | { "action": "other", description: string, };
 
 export type TodoItem = { content: string, status: string, priority: string | null, };
 
-export type NormalizedEntryError = { "type": "setup_required" } | { "type": "other" };
+export type NormalizedEntryError = { "type": "setup_required" } | { "type": "context_window_exceeded" } | { "type": "mcp_server_failed", server: string, } | { "type": "other" };
 
 export type ToolResult = { type: ToolResultValueType, 
 /**